@@ -0,0 +1,2 @@
+-- Record how each fill occurred ('MARKET', 'LIMIT' or 'STOP')
+ALTER TABLE trades ADD COLUMN order_type TEXT NOT NULL DEFAULT 'MARKET';
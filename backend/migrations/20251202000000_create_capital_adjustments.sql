@@ -0,0 +1,12 @@
+-- Deposits and withdrawals applied to a session outside of trading PnL
+CREATE TABLE capital_adjustments (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    session_id UUID NOT NULL REFERENCES sessions(id),
+    kind TEXT NOT NULL,
+    amount DOUBLE PRECISION NOT NULL,
+    equity_before DOUBLE PRECISION NOT NULL,
+    equity_after DOUBLE PRECISION NOT NULL,
+    timestamp TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_capital_adjustments_session ON capital_adjustments(session_id, timestamp);
@@ -0,0 +1,2 @@
+-- 'single' sessions trade one symbol; 'basket' sessions list comma-separated constituents in `symbol`
+ALTER TABLE sessions ADD COLUMN session_type TEXT NOT NULL DEFAULT 'single';
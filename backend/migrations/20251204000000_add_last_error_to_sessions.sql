@@ -0,0 +1,2 @@
+-- Surface runtime misconfiguration (e.g. unknown strategy type) on the session itself
+ALTER TABLE sessions ADD COLUMN last_error TEXT;
@@ -0,0 +1,6 @@
+-- Link closing trades to the trade that opened the position
+ALTER TABLE trades ADD COLUMN entry_trade_id UUID REFERENCES trades(id);
+ALTER TABLE trades ADD COLUMN holding_seconds BIGINT;
+
+-- The currently open position's entry trade
+ALTER TABLE sessions ADD COLUMN open_trade_id UUID;
@@ -0,0 +1,2 @@
+-- Activation condition for 'pending' sessions, referencing another session's state
+ALTER TABLE sessions ADD COLUMN trigger JSONB;
@@ -0,0 +1,2 @@
+-- Fee charged on each fill; `pnl` stays net of fees
+ALTER TABLE trades ADD COLUMN fee_paid DOUBLE PRECISION NOT NULL DEFAULT 0.0;
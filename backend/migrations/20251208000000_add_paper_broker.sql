@@ -0,0 +1,16 @@
+-- Route a session's orders through the simulated broker (random latency and rejections)
+ALTER TABLE sessions ADD COLUMN paper_broker BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Orders that never produced a fill, e.g. rejected by the simulated broker
+CREATE TABLE order_events (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    session_id UUID NOT NULL REFERENCES sessions(id),
+    symbol TEXT NOT NULL,
+    side TEXT NOT NULL,
+    price DOUBLE PRECISION NOT NULL,
+    event TEXT NOT NULL,
+    reason TEXT,
+    timestamp TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_order_events_session ON order_events(session_id, timestamp);
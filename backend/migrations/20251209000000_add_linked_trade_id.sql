@@ -0,0 +1,2 @@
+-- On a flip, the opening trade points at the trade that closed the previous position
+ALTER TABLE trades ADD COLUMN linked_trade_id UUID REFERENCES trades(id);
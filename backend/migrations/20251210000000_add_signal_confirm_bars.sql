@@ -0,0 +1,2 @@
+-- Number of consecutive bars a signal direction must persist before it is acted on (1 = act immediately)
+ALTER TABLE sessions ADD COLUMN signal_confirm_bars INTEGER NOT NULL DEFAULT 1;
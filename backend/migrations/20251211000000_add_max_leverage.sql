@@ -0,0 +1,2 @@
+-- Notional multiple of equity a full-strength signal may take (1 = unlevered)
+ALTER TABLE sessions ADD COLUMN max_leverage DOUBLE PRECISION NOT NULL DEFAULT 1.0;
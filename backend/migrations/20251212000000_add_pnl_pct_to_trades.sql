@@ -0,0 +1,2 @@
+-- Closing trade return as a percentage of the equity committed at entry
+ALTER TABLE trades ADD COLUMN pnl_pct DOUBLE PRECISION;
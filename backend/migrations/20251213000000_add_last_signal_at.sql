@@ -0,0 +1,2 @@
+-- When the engine last evaluated the session's strategy on a closed bar
+ALTER TABLE sessions ADD COLUMN last_signal_at TIMESTAMPTZ;
@@ -0,0 +1,2 @@
+-- Edge mode: act only when the signal direction changes after the prior direction held this many bars (NULL = act on every bar's level)
+ALTER TABLE sessions ADD COLUMN edge_lookback INTEGER;
@@ -0,0 +1,3 @@
+-- Per-session protective exits as fractions of the entry price (NULL or 0 = disabled), checked on every tick
+ALTER TABLE sessions ADD COLUMN stop_loss_pct DOUBLE PRECISION;
+ALTER TABLE sessions ADD COLUMN take_profit_pct DOUBLE PRECISION;
@@ -0,0 +1,2 @@
+-- Notional multiple of equity held by the open position, fixed at entry (NULL when flat or opened before this was recorded)
+ALTER TABLE sessions ADD COLUMN position_exposure DOUBLE PRECISION;
@@ -0,0 +1,2 @@
+-- Trailing-stop distance from the position's best price as a fraction (NULL = engine default of 0.05, 0 = disabled)
+ALTER TABLE sessions ADD COLUMN trail_pct DOUBLE PRECISION;
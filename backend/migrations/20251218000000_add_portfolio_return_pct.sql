@@ -0,0 +1,2 @@
+-- Allocation-weighted percentage return of the portfolio, comparable across session sizes
+ALTER TABLE portfolio_cache ADD COLUMN return_pct DOUBLE PRECISION;
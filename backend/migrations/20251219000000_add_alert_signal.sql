@@ -0,0 +1,2 @@
+-- Latest strategy signal of an alert-only session, which never takes a position
+ALTER TABLE sessions ADD COLUMN alert_signal DOUBLE PRECISION;
@@ -0,0 +1,2 @@
+-- Per-trade entries and exits of a generated strategy's backtest, when requested
+ALTER TABLE strategies ADD COLUMN backtest_trades JSONB;
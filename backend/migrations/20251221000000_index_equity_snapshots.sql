@@ -0,0 +1,3 @@
+-- Lets the portfolio cache read only the snapshots after its last settled minute, and each session's latest one before it
+CREATE INDEX idx_equity_snapshots_timestamp ON equity_snapshots(timestamp);
+CREATE INDEX idx_equity_snapshots_session ON equity_snapshots(session_id, timestamp);
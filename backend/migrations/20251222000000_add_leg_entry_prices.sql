@@ -0,0 +1,2 @@
+-- Constituent prices at the entry of a basket session's open position, in basket order
+ALTER TABLE sessions ADD COLUMN leg_entry_prices DOUBLE PRECISION[];
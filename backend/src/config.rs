@@ -1,7 +1,109 @@
 //! Application configuration loaded from environment variables.
 
+use std::collections::HashMap;
 use std::env;
 
+use uuid::Uuid;
+
+use crate::services::interval;
+
+/// Intervals `PORTFOLIO_CACHE_RESOLUTION` may be set to.
+const PORTFOLIO_CACHE_RESOLUTIONS: &[&str] = &["1m", "5m", "15m", "30m", "1h"];
+
+/// What the engine does when a strategy's signal series does not line up
+/// with the candle frame it was computed from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LengthMismatchPolicy {
+    /// Record the problem on the session's `last_error` and skip the bar.
+    Error,
+    /// Assume the series are aligned on their latest bar and carry on.
+    Align,
+}
+
+impl LengthMismatchPolicy {
+    fn parse(raw: &str) -> Self {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "align" => LengthMismatchPolicy::Align,
+            _ => LengthMismatchPolicy::Error,
+        }
+    }
+}
+
+/// How a new position's notional is derived from session equity.
+///
+/// Every mode starts from the signal leverage `L = max(1, min(|signal|, 1) ×
+/// max_leverage)`; see [`Config::position_sizing`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PositionSizing {
+    /// `equity × L × allocated_weight`.
+    Weighted,
+    /// `equity × L × allocated_weight × kelly_fraction` of the strategy,
+    /// with the fraction clamped to `0..=kelly_cap` and 1 when unset.
+    Kelly,
+    /// `equity × L`; `allocated_weight` and `kelly_fraction` are ignored.
+    Unweighted,
+}
+
+impl PositionSizing {
+    fn parse(raw: &str) -> Result<Self, String> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "weighted" => Ok(PositionSizing::Weighted),
+            "kelly" => Ok(PositionSizing::Kelly),
+            "unweighted" => Ok(PositionSizing::Unweighted),
+            other => Err(format!(
+                "POSITION_SIZING must be weighted, kelly or unweighted, got {other:?}"
+            )),
+        }
+    }
+}
+
+/// A way an open position can be exited on a closed bar.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExitKind {
+    /// Levered position lost its maintenance margin.
+    Liquidation,
+    /// Price retraced from the position's best level by the trailing distance.
+    TrailingStop,
+    /// The strategy signal closed or flipped the position.
+    Signal,
+}
+
+impl ExitKind {
+    /// Default precedence: stops before the strategy signal.
+    pub const DEFAULT_PRECEDENCE: [ExitKind; 3] = [
+        ExitKind::Liquidation,
+        ExitKind::TrailingStop,
+        ExitKind::Signal,
+    ];
+
+    fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "liquidation" => Some(ExitKind::Liquidation),
+            "trailing_stop" => Some(ExitKind::TrailingStop),
+            "signal" => Some(ExitKind::Signal),
+            _ => None,
+        }
+    }
+}
+
+/// Order in which exits are evaluated on a closed bar.  Unknown names are
+/// ignored and kinds left out keep their default relative order after the
+/// listed ones.
+fn parse_exit_precedence(raw: &str) -> Vec<ExitKind> {
+    let mut order: Vec<ExitKind> = Vec::new();
+    for kind in raw.split(',').filter_map(ExitKind::parse) {
+        if !order.contains(&kind) {
+            order.push(kind);
+        }
+    }
+    for kind in ExitKind::DEFAULT_PRECEDENCE {
+        if !order.contains(&kind) {
+            order.push(kind);
+        }
+    }
+    order
+}
+
 /// Application configuration.
 ///
 /// Loaded from environment variables at startup.
@@ -15,6 +117,86 @@ pub struct Config {
     pub binance_api_key: Option<String>,
     /// Binance secret key (optional, for live trading)
     pub binance_secret_key: Option<String>,
+    /// Strategies whose sessions are started automatically on engine boot
+    pub watchlist_strategy_ids: Vec<Uuid>,
+    /// Initial capital for sessions started from the watchlist (default: 10000)
+    pub watchlist_initial_capital: f64,
+    /// Handling of signal/candle length mismatches (default: error)
+    pub signal_length_mismatch: LengthMismatchPolicy,
+    /// Per-session cap on stored equity snapshots (default: unlimited)
+    pub max_snapshots_per_session: Option<i64>,
+    /// Fraction of paper-broker orders rejected (default: 0.02)
+    pub broker_reject_rate: f64,
+    /// Upper bound of paper-broker fill latency in ms (default: 500)
+    pub broker_max_latency_ms: u64,
+    /// Seed for the paper-broker RNG (default: `engine_seed`, else random)
+    pub broker_seed: Option<u64>,
+    /// Seed for all simulated randomness in the engine (default: random)
+    pub engine_seed: Option<u64>,
+    /// Friendly symbol names mapped to exchange tickers (e.g. `BTC` -> `BTCUSDT`)
+    pub symbol_aliases: HashMap<String, String>,
+    /// Quote asset appended to bare base assets (default: USDT, or USD with `BINANCE_US`)
+    pub quote_asset: String,
+    /// Minimum seconds between portfolio cache rebuilds (default: 10)
+    pub portfolio_rebuild_min_interval_secs: u64,
+    /// Spacing in seconds of `portfolio_cache` points (default: 60)
+    pub portfolio_cache_resolution_secs: i64,
+    /// Seconds between background portfolio cache updates (default: 60)
+    pub portfolio_sync_interval_secs: u64,
+    /// Currency portfolio totals are converted to (default: USDT)
+    pub reporting_currency: String,
+    /// Sessions of one symbol evaluated concurrently per kline event (default: 4)
+    pub engine_session_concurrency: usize,
+    /// Markets fetched and optimised concurrently during strategy generation (default: 4)
+    pub generation_concurrency: usize,
+    /// Strategy types that may be created or generated (default: all)
+    pub allowed_strategy_types: Option<Vec<String>>,
+    /// Strategy types that may not be created or generated (default: none)
+    pub denied_strategy_types: Vec<String>,
+    /// Equity below which a session is closed out and marked `liquidated` (default: 1)
+    pub min_session_equity: f64,
+    /// Order in which exits are evaluated on a closed bar (default: liquidation,
+    /// trailing stop, signal)
+    pub exit_precedence: Vec<ExitKind>,
+    /// Kline age in ms beyond which the engine drops its stream backlog (default: 5000)
+    pub stream_max_lag_ms: i64,
+    /// Overlap in ms of the old and new streams on a subscription change;
+    /// 0 reconnects instead (default: 2000)
+    pub stream_handover_ms: u64,
+    /// Longest wait in seconds between stream reconnect attempts
+    /// (default: 60)
+    pub stream_reconnect_max_secs: u64,
+    /// Routine equity snapshot writes per second across all sessions;
+    /// 0 disables the cap (default: 50)
+    pub max_snapshot_writes_per_sec: f64,
+    /// Lifetime in ms of cached latest-candle fetches; 0 disables the cache
+    /// (default: 30000)
+    pub candle_cache_ttl_ms: u64,
+    /// Attempts per klines request before giving up on a rate-limited or
+    /// transient failure (default: 4)
+    pub binance_max_attempts: u32,
+    /// First retry delay in ms, doubled on each further attempt (default: 500)
+    pub binance_retry_base_ms: u64,
+    /// URL alert sessions POST signal changes to (default: none)
+    pub alert_webhook_url: Option<String>,
+    /// File to append received stream klines to (default: none)
+    pub record_stream_path: Option<String>,
+    /// Capture file to replay through the engine instead of streaming
+    /// (default: none)
+    pub replay_stream_path: Option<String>,
+    /// Intervals after which the latest candle is too old to trade on (default: 3)
+    pub max_candle_age_intervals: f64,
+    /// Notional sizing of new positions (default: kelly)
+    pub position_sizing: PositionSizing,
+    /// Upper bound on a strategy's Kelly fraction in sizing (default: 1)
+    pub kelly_cap: f64,
+    /// Whether the engine acts on signals at startup (default: true)
+    pub trading_enabled: bool,
+    /// Round opening quantities down to the symbol's lot size (default: false)
+    pub enforce_lot_size: bool,
+    /// Lifetime in seconds of cached exchange-info lot sizes; 0 disables the
+    /// cache (default: 3600)
+    pub lot_size_ttl_secs: u64,
 }
 
 impl Config {
@@ -29,20 +211,265 @@ impl Config {
     /// - `SERVER_ADDR` - Server bind address (default: 0.0.0.0:8080)
     /// - `BINANCE_API_KEY` - Binance API key for live trading
     /// - `BINANCE_SECRET_KEY` - Binance secret key for live trading
+    /// - `WATCHLIST_STRATEGY_IDS` - Comma-separated strategy ids to start a
+    ///   session for if they have never had one (invalid ids are ignored)
+    /// - `WATCHLIST_INITIAL_CAPITAL` - Capital for auto-started sessions (default: 10000)
+    /// - `SIGNAL_LENGTH_MISMATCH` - `error` or `align` (default: error)
+    /// - `MAX_SNAPSHOTS_PER_SESSION` - Thin a session's equity snapshots once
+    ///   it has more than this many, keeping one per hour and then one per
+    ///   day beyond the newest half (default: unlimited)
+    /// - `BROKER_REJECT_RATE` - Paper-broker rejection probability (default: 0.02)
+    /// - `BROKER_MAX_LATENCY_MS` - Paper-broker maximum fill latency (default: 500)
+    /// - `ENGINE_SEED` - Fixed seed for every RNG used in simulation, so the
+    ///   same market data produces identical trades and curves
+    /// - `BROKER_SEED` - Paper-broker RNG seed, overriding `ENGINE_SEED`
+    /// - `SYMBOL_ALIASES` - Comma-separated `ALIAS=TICKER` pairs
+    ///   (malformed entries are ignored)
+    /// - `QUOTE_ASSET` - Quote for bare symbols like `BTC` (default: USDT, or
+    ///   USD when `BINANCE_US` is set)
+    /// - `PORTFOLIO_REBUILD_MIN_INTERVAL_SECS` - Rebuild requests within this
+    ///   many seconds of the last rebuild are coalesced (default: 10)
+    /// - `PORTFOLIO_CACHE_RESOLUTION` - Interval between cached portfolio
+    ///   points: `1m`, `5m`, `15m`, `30m` or `1h` (default: 1m)
+    /// - `PORTFOLIO_SYNC_INTERVAL_SECS` - Seconds between background
+    ///   portfolio cache updates (default: 60)
+    /// - `REPORTING_CURRENCY` - Currency session equities are converted to
+    ///   before summing into the portfolio total (default: USDT)
+    /// - `ENGINE_SESSION_CONCURRENCY` - Sessions of one symbol evaluated
+    ///   concurrently per kline event; 1 is sequential (default: 4)
+    /// - `GENERATION_CONCURRENCY` - Symbol/interval markets strategy generation
+    ///   fetches and optimises at once; 1 is sequential (default: 4)
+    /// - `ALLOWED_STRATEGY_TYPES` - Comma-separated strategy types that may be
+    ///   created or generated (default: all)
+    /// - `DENIED_STRATEGY_TYPES` - Comma-separated strategy types refused even
+    ///   if allowed (default: none)
+    /// - `MIN_SESSION_EQUITY` - Sessions whose equity falls below this are
+    ///   closed out and marked `liquidated` (default: 1)
+    /// - `EXIT_PRECEDENCE` - Comma-separated order of `liquidation`,
+    ///   `trailing_stop` and `signal` exits on a closed bar
+    ///   (default: liquidation,trailing_stop,signal)
+    /// - `STREAM_MAX_LAG_MS` - When a kline is processed this long after its
+    ///   event time, buffered klines are dropped except the latest per market
+    ///   (default: 5000)
+    /// - `STREAM_HANDOVER_MS` - When the active markets change, the new stream
+    ///   is opened first and the old one closed this many ms later; 0 tears
+    ///   the stream down and reconnects, missing klines meanwhile (default: 2000)
+    /// - `STREAM_RECONNECT_MAX_SECS` - Cap on the delay before reconnecting a
+    ///   dropped stream, which doubles from 1 s on each consecutive failure
+    ///   (default: 60)
+    /// - `MAX_SNAPSHOT_WRITES_PER_SEC` - Cap on mark-to-market equity snapshot
+    ///   inserts per second across all sessions; over budget, equity is
+    ///   updated without a snapshot. 0 disables the cap (default: 50)
+    /// - `CANDLE_CACHE_TTL_MS` - Reuse a latest-candle fetch for this long, and
+    ///   never past the close of the bar it was fetched in; 0 disables the
+    ///   cache (default: 30000)
+    /// - `BINANCE_MAX_ATTEMPTS` - Attempts per klines request when Binance
+    ///   rate limits or fails transiently; 1 disables retries (default: 4)
+    /// - `BINANCE_RETRY_BASE_MS` - Delay before the first retry, doubled on
+    ///   each further attempt, with jitter (default: 500)
+    /// - `ALERT_WEBHOOK_URL` - Where `alert` sessions POST each change of
+    ///   signal direction as JSON; unset, changes are only logged
+    /// - `RECORD_STREAM_PATH` - Append every kline received from the websocket
+    ///   to this JSON Lines file (default: none)
+    /// - `REPLAY_STREAM_PATH` - Instead of streaming, process the klines of a
+    ///   file written via `RECORD_STREAM_PATH` in recorded order, then leave
+    ///   the engine idle (default: none)
+    /// - `MAX_CANDLE_AGE_INTERVALS` - Skip trading, flagging `STALE_DATA`, when
+    ///   the latest candle opened more than this many intervals ago (default: 3)
+    /// - `POSITION_SIZING` - `weighted`, `kelly` or `unweighted` (default:
+    ///   kelly)
+    /// - `KELLY_CAP` - Largest Kelly fraction applied to sizing, in (0, 1]
+    ///   (default: 1)
+    /// - `TRADING_ENABLED` - `false` starts the engine paused: equity is still
+    ///   marked to market but signals are not traded until `POST
+    ///   /engine/resume` (default: true)
+    /// - `ENFORCE_LOT_SIZE` - Round each opening quantity down to the symbol's
+    ///   `LOT_SIZE` step, skipping opens below its minimum quantity (default:
+    ///   false)
+    /// - `LOT_SIZE_TTL_SECS` - Reuse the lot sizes read from exchange info for
+    ///   this long; 0 disables the cache (default: 3600)
     ///
     /// # Panics
     ///
-    /// Panics if `DATABASE_URL` is not set.
+    /// Panics if `DATABASE_URL` is not set, or if `POSITION_SIZING` is set
+    /// to an unknown mode.
     pub fn from_env() -> Self {
+        let engine_seed = env::var("ENGINE_SEED").ok().and_then(|v| v.parse().ok());
         Self {
             database_url: env::var("DATABASE_URL").expect("DATABASE_URL must be set"),
             server_addr: env::var("SERVER_ADDR").unwrap_or_else(|_| "0.0.0.0:8080".to_string()),
             binance_api_key: env::var("BINANCE_API_KEY").ok(),
             binance_secret_key: env::var("BINANCE_SECRET_KEY").ok(),
+            watchlist_strategy_ids: env::var("WATCHLIST_STRATEGY_IDS")
+                .map(|v| parse_uuid_list(&v))
+                .unwrap_or_default(),
+            watchlist_initial_capital: env::var("WATCHLIST_INITIAL_CAPITAL")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(10_000.0),
+            signal_length_mismatch: env::var("SIGNAL_LENGTH_MISMATCH")
+                .map(|v| LengthMismatchPolicy::parse(&v))
+                .unwrap_or(LengthMismatchPolicy::Error),
+            max_snapshots_per_session: env::var("MAX_SNAPSHOTS_PER_SESSION")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|n| *n > 0),
+            broker_reject_rate: env::var("BROKER_REJECT_RATE")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0.02),
+            broker_max_latency_ms: env::var("BROKER_MAX_LATENCY_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(500),
+            alert_webhook_url: env::var("ALERT_WEBHOOK_URL").ok().filter(|v| !v.is_empty()),
+            broker_seed: env::var("BROKER_SEED")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .or(engine_seed),
+            engine_seed,
+            symbol_aliases: env::var("SYMBOL_ALIASES")
+                .map(|v| parse_alias_map(&v))
+                .unwrap_or_default(),
+            quote_asset: env::var("QUOTE_ASSET").unwrap_or_else(|_| {
+                if env::var("BINANCE_US").is_ok() {
+                    "USD".to_string()
+                } else {
+                    "USDT".to_string()
+                }
+            }),
+            portfolio_rebuild_min_interval_secs: env::var("PORTFOLIO_REBUILD_MIN_INTERVAL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(10),
+            portfolio_cache_resolution_secs: env::var("PORTFOLIO_CACHE_RESOLUTION")
+                .ok()
+                .filter(|v| PORTFOLIO_CACHE_RESOLUTIONS.contains(&v.trim()))
+                .and_then(|v| interval::seconds(v.trim()))
+                .unwrap_or(60),
+            portfolio_sync_interval_secs: env::var("PORTFOLIO_SYNC_INTERVAL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|secs| *secs > 0)
+                .unwrap_or(60),
+            reporting_currency: env::var("REPORTING_CURRENCY")
+                .map(|v| v.trim().to_uppercase())
+                .unwrap_or_else(|_| "USDT".to_string()),
+            engine_session_concurrency: env::var("ENGINE_SESSION_CONCURRENCY")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|n| *n > 0)
+                .unwrap_or(4),
+            generation_concurrency: env::var("GENERATION_CONCURRENCY")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|n| *n > 0)
+                .unwrap_or(4),
+            allowed_strategy_types: env::var("ALLOWED_STRATEGY_TYPES")
+                .ok()
+                .map(|v| parse_name_list(&v)),
+            denied_strategy_types: env::var("DENIED_STRATEGY_TYPES")
+                .map(|v| parse_name_list(&v))
+                .unwrap_or_default(),
+            min_session_equity: env::var("MIN_SESSION_EQUITY")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(1.0),
+            exit_precedence: env::var("EXIT_PRECEDENCE")
+                .map(|v| parse_exit_precedence(&v))
+                .unwrap_or_else(|_| ExitKind::DEFAULT_PRECEDENCE.to_vec()),
+            stream_max_lag_ms: env::var("STREAM_MAX_LAG_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|ms| *ms > 0)
+                .unwrap_or(5000),
+            stream_handover_ms: env::var("STREAM_HANDOVER_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(2000),
+            stream_reconnect_max_secs: env::var("STREAM_RECONNECT_MAX_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|secs| *secs > 0)
+                .unwrap_or(60),
+            max_snapshot_writes_per_sec: env::var("MAX_SNAPSHOT_WRITES_PER_SEC")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|n: &f64| *n >= 0.0)
+                .unwrap_or(50.0),
+            candle_cache_ttl_ms: env::var("CANDLE_CACHE_TTL_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(30_000),
+            binance_max_attempts: env::var("BINANCE_MAX_ATTEMPTS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|n| *n >= 1)
+                .unwrap_or(4),
+            binance_retry_base_ms: env::var("BINANCE_RETRY_BASE_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(500),
+            record_stream_path: env::var("RECORD_STREAM_PATH")
+                .ok()
+                .filter(|v| !v.is_empty()),
+            replay_stream_path: env::var("REPLAY_STREAM_PATH")
+                .ok()
+                .filter(|v| !v.is_empty()),
+            max_candle_age_intervals: env::var("MAX_CANDLE_AGE_INTERVALS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|n: &f64| *n >= 1.0)
+                .unwrap_or(3.0),
+            position_sizing: env::var("POSITION_SIZING")
+                .map(|v| PositionSizing::parse(&v).unwrap_or_else(|e| panic!("{e}")))
+                .unwrap_or(PositionSizing::Kelly),
+            kelly_cap: env::var("KELLY_CAP")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|cap: &f64| *cap > 0.0 && *cap <= 1.0)
+                .unwrap_or(1.0),
+            trading_enabled: env::var("TRADING_ENABLED")
+                .ok()
+                .and_then(|v| v.trim().to_ascii_lowercase().parse().ok())
+                .unwrap_or(true),
+            enforce_lot_size: env::var("ENFORCE_LOT_SIZE")
+                .ok()
+                .and_then(|v| v.trim().to_ascii_lowercase().parse().ok())
+                .unwrap_or(false),
+            lot_size_ttl_secs: env::var("LOT_SIZE_TTL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(3600),
         }
     }
 }
 
+fn parse_name_list(raw: &str) -> Vec<String> {
+    raw.split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn parse_uuid_list(raw: &str) -> Vec<Uuid> {
+    raw.split(',')
+        .filter_map(|id| Uuid::parse_str(id.trim()).ok())
+        .collect()
+}
+
+fn parse_alias_map(raw: &str) -> HashMap<String, String> {
+    raw.split(',')
+        .filter_map(|pair| {
+            let (alias, ticker) = pair.split_once('=')?;
+            let (alias, ticker) = (alias.trim(), ticker.trim());
+            (!alias.is_empty() && !ticker.is_empty())
+                .then(|| (alias.to_uppercase(), ticker.to_uppercase()))
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -61,6 +488,40 @@ mod tests {
         assert_eq!(config.server_addr, "0.0.0.0:8080");
         assert!(config.binance_api_key.is_none());
         assert!(config.binance_secret_key.is_none());
+        assert!(config.watchlist_strategy_ids.is_empty());
+        assert!((config.watchlist_initial_capital - 10_000.0).abs() < f64::EPSILON);
+        assert!(config.max_snapshots_per_session.is_none());
+        assert!((config.broker_reject_rate - 0.02).abs() < f64::EPSILON);
+        assert_eq!(config.broker_max_latency_ms, 500);
+        assert!(config.broker_seed.is_none());
+        assert!(config.engine_seed.is_none());
+        assert!(config.symbol_aliases.is_empty());
+        assert_eq!(config.portfolio_rebuild_min_interval_secs, 10);
+        assert_eq!(config.portfolio_cache_resolution_secs, 60);
+        assert_eq!(config.portfolio_sync_interval_secs, 60);
+        assert_eq!(config.reporting_currency, "USDT");
+        assert_eq!(config.engine_session_concurrency, 4);
+        assert_eq!(config.generation_concurrency, 4);
+        assert!(config.allowed_strategy_types.is_none());
+        assert!(config.denied_strategy_types.is_empty());
+        assert!((config.min_session_equity - 1.0).abs() < f64::EPSILON);
+        assert_eq!(config.exit_precedence, ExitKind::DEFAULT_PRECEDENCE);
+        assert_eq!(config.stream_max_lag_ms, 5000);
+        assert_eq!(config.stream_handover_ms, 2000);
+        assert_eq!(config.stream_reconnect_max_secs, 60);
+        assert!((config.max_snapshot_writes_per_sec - 50.0).abs() < f64::EPSILON);
+        assert_eq!(config.candle_cache_ttl_ms, 30_000);
+        assert_eq!(config.binance_max_attempts, 4);
+        assert_eq!(config.binance_retry_base_ms, 500);
+        assert!(config.alert_webhook_url.is_none());
+        assert!(config.record_stream_path.is_none());
+        assert!(config.replay_stream_path.is_none());
+        assert!((config.max_candle_age_intervals - 3.0).abs() < f64::EPSILON);
+        assert_eq!(config.position_sizing, PositionSizing::Kelly);
+        assert!((config.kelly_cap - 1.0).abs() < f64::EPSILON);
+        assert!(config.trading_enabled);
+        assert!(!config.enforce_lot_size);
+        assert_eq!(config.lot_size_ttl_secs, 3600);
 
         env::remove_var("DATABASE_URL");
     }
@@ -85,6 +546,76 @@ mod tests {
         env::remove_var("BINANCE_SECRET_KEY");
     }
 
+    #[test]
+    fn test_parse_exit_precedence_fills_in_missing_kinds() {
+        assert_eq!(
+            parse_exit_precedence("signal, bogus ,trailing_stop,signal"),
+            vec![
+                ExitKind::Signal,
+                ExitKind::TrailingStop,
+                ExitKind::Liquidation
+            ]
+        );
+        assert_eq!(parse_exit_precedence(""), ExitKind::DEFAULT_PRECEDENCE);
+    }
+
+    #[test]
+    fn test_parse_uuid_list_skips_invalid() {
+        let ids = parse_uuid_list(
+            "550e8400-e29b-41d4-a716-446655440000, not-a-uuid,,6ba7b810-9dad-11d1-80b4-00c04fd430c8",
+        );
+
+        assert_eq!(ids.len(), 2);
+        assert_eq!(
+            ids[0],
+            Uuid::parse_str("550e8400-e29b-41d4-a716-446655440000").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_alias_map_skips_malformed() {
+        let aliases = parse_alias_map("btc=BTCUSDT, ETH = ethusdt,bogus,=XRPUSDT,SOL=");
+
+        assert_eq!(aliases.len(), 2);
+        assert_eq!(aliases["BTC"], "BTCUSDT");
+        assert_eq!(aliases["ETH"], "ETHUSDT");
+    }
+
+    #[test]
+    fn test_length_mismatch_policy_parse() {
+        assert_eq!(
+            LengthMismatchPolicy::parse("align"),
+            LengthMismatchPolicy::Align
+        );
+        assert_eq!(
+            LengthMismatchPolicy::parse(" ALIGN "),
+            LengthMismatchPolicy::Align
+        );
+        assert_eq!(
+            LengthMismatchPolicy::parse("error"),
+            LengthMismatchPolicy::Error
+        );
+        assert_eq!(
+            LengthMismatchPolicy::parse("bogus"),
+            LengthMismatchPolicy::Error
+        );
+    }
+
+    #[test]
+    fn test_position_sizing_parse_rejects_unknown_modes() {
+        assert_eq!(
+            PositionSizing::parse(" Weighted "),
+            Ok(PositionSizing::Weighted)
+        );
+        assert_eq!(PositionSizing::parse("kelly"), Ok(PositionSizing::Kelly));
+        assert_eq!(
+            PositionSizing::parse("unweighted"),
+            Ok(PositionSizing::Unweighted)
+        );
+        assert!(PositionSizing::parse("kely").is_err());
+        assert!(PositionSizing::parse("").is_err());
+    }
+
     #[test]
     fn test_config_clone() {
         env::set_var("DATABASE_URL", "postgres://test@localhost/db");
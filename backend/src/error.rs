@@ -16,12 +16,24 @@ pub enum AppError {
     /// Binance API or WebSocket errors.
     #[error("Binance API Error: {0}")]
     Binance(String),
+    /// Binance kept rate limiting after retries (returns 429).
+    #[error("Rate Limited: {0}")]
+    RateLimited(String),
     /// Strategy generation / optimisation errors.
     #[error("Strategy Error: {0}")]
     Strategy(String),
     /// Data processing / feature engineering errors.
     #[error("Data Processing Error: {0}")]
     Data(String),
+    /// Invalid client input (returns 400).
+    #[error("Bad Request: {0}")]
+    BadRequest(String),
+    /// Action disabled by deployment policy (returns 403).
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+    /// Unexpected server-side failure, e.g. a caught handler panic.
+    #[error("Internal Error: {0}")]
+    Internal(String),
     /// Resource not found (returns 404).
     #[allow(dead_code)]
     #[error("Not Found: {0}")]
@@ -36,7 +48,10 @@ struct ErrorResponse {
 impl ResponseError for AppError {
     fn status_code(&self) -> StatusCode {
         match self {
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
             AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
@@ -66,14 +81,45 @@ mod tests {
 
         let err = AppError::NotFound("session 123".to_string());
         assert_eq!(err.to_string(), "Not Found: session 123");
+
+        let err = AppError::BadRequest("amount must be non-zero".to_string());
+        assert_eq!(err.to_string(), "Bad Request: amount must be non-zero");
     }
 
     #[test]
     fn test_status_codes() {
-        assert_eq!(AppError::NotFound("test".to_string()).status_code(), StatusCode::NOT_FOUND);
-        assert_eq!(AppError::Binance("test".to_string()).status_code(), StatusCode::INTERNAL_SERVER_ERROR);
-        assert_eq!(AppError::Strategy("test".to_string()).status_code(), StatusCode::INTERNAL_SERVER_ERROR);
-        assert_eq!(AppError::Data("test".to_string()).status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(
+            AppError::NotFound("test".to_string()).status_code(),
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            AppError::BadRequest("test".to_string()).status_code(),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            AppError::Forbidden("test".to_string()).status_code(),
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            AppError::RateLimited("test".to_string()).status_code(),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+        assert_eq!(
+            AppError::Binance("test".to_string()).status_code(),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+        assert_eq!(
+            AppError::Strategy("test".to_string()).status_code(),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+        assert_eq!(
+            AppError::Data("test".to_string()).status_code(),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+        assert_eq!(
+            AppError::Internal("test".to_string()).status_code(),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
     }
 
     #[test]
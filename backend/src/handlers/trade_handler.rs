@@ -1,42 +1,299 @@
+use std::collections::BTreeMap;
 use std::sync::Arc;
 
-use actix_web::{delete, get, post, web, HttpResponse, Responder};
+use actix_web::{delete, get, patch, post, web, HttpRequest, HttpResponse, Responder};
 use chrono::{DateTime, Utc};
+use futures::StreamExt;
 use sqlx::PgPool;
+use tokio::sync::{broadcast, mpsc};
 use uuid::Uuid;
 
 use crate::error::AppError;
 use crate::models::strategy::{
-    CreateSessionRequest, CreateStrategyRequest, GenerateStrategiesRequest, Session, Strategy,
-    Trade,
+    AdjustCapitalRequest, BacktestRequest, CapitalAdjustment, CompareParamsRequest,
+    CreateSessionRequest, CreateStrategyRequest, GenerateStrategiesRequest, ImportTradesRequest,
+    OrderEvent, Session, Strategy, Trade, UpdateStrategyRequest,
 };
-use crate::services::market_data::MarketDataService;
-use crate::services::strategy_generator::StrategyGenerator;
+use crate::services::backfill;
+use crate::services::backtest::{self, CandleWindow};
+use crate::services::bar_explain;
+use crate::services::capital_flows;
+use crate::services::correlation;
+use crate::services::diagnostics::{DiagnosticKind, Diagnostics};
+use crate::services::drawdown;
+use crate::services::engine_status::{EngineStatus, EngineStatusReport};
+use crate::services::generation_jobs::GenerationJobs;
+use crate::services::interval;
+use crate::services::market_data::{CandleCacheStats, MarketDataService};
+use crate::services::metrics::Metrics;
+use crate::services::portfolio_manager::PortfolioManager;
+use crate::services::replay;
+use crate::services::risk_metrics;
+use crate::services::session_events::SessionEvents;
+use crate::services::strategy_diff::{self, StrategyDiff};
+use crate::services::strategy_generator::{self, GenerationEvent, StrategyGenerator};
+use crate::services::strategy_registry;
+use crate::services::strategy_schema::{self, StrategyTypePolicy};
+use crate::services::trade_import;
+use crate::services::trading_engine;
 
 #[post("/strategies/generate")]
 async fn generate_strategies(
     generator: web::Data<Arc<StrategyGenerator>>,
+    policy: web::Data<StrategyTypePolicy>,
     body: web::Json<GenerateStrategiesRequest>,
 ) -> Result<impl Responder, AppError> {
     let req = body.into_inner();
     let top_n = req.top_n.unwrap_or(10);
     let limit = req.limit.unwrap_or(1000);
     let iterations = req.iterations.unwrap_or(50);
+    let strategy_types = validate_generation_request(&policy, &req)?;
+    let objective = req.objective.unwrap_or_default();
 
-    let count = generator
-        .generate_and_save(req.symbols, req.intervals, top_n, limit, iterations)
+    let report = generator
+        .generate_and_save(
+            req.symbols,
+            req.intervals,
+            top_n,
+            limit,
+            iterations,
+            &strategy_types,
+            objective,
+            req.rank_by.unwrap_or_else(|| objective.rank_metric()),
+            req.train_ratio,
+            req.store_trades.unwrap_or(false),
+            None,
+        )
         .await
         .map_err(|e| AppError::Strategy(e.to_string()))?;
 
     Ok(HttpResponse::Ok().json(serde_json::json!({
         "message": "Generation complete",
-        "strategies_created": count
+        "strategies_created": report.strategies_created,
+        "convergence": report.convergence
     })))
 }
 
+/// Run a generation request, streaming progress as server-sent events:
+/// `market_started` / `market_done` around each market, a `candidate` event
+/// for each viable candidate as its market finishes, then one `done` event
+/// with the saved count and convergence traces, or an `error` event.
+#[post("/strategies/generate/stream")]
+async fn generate_strategies_stream(
+    generator: web::Data<Arc<StrategyGenerator>>,
+    policy: web::Data<StrategyTypePolicy>,
+    body: web::Json<GenerateStrategiesRequest>,
+) -> Result<impl Responder, AppError> {
+    let req = body.into_inner();
+    let strategy_types = validate_generation_request(&policy, &req)?;
+    let rx = spawn_generation(generator.get_ref().clone(), req, strategy_types);
+
+    let events = futures::stream::unfold(rx, |mut rx| async move {
+        let event = rx.recv().await?;
+        Some((Ok::<_, actix_web::Error>(sse_frame(&event)), rx))
+    });
+
+    Ok(event_stream(events))
+}
+
+/// Start a generation request as a background job and return its id at
+/// once; progress is read from `GET /strategies/generate/progress`.
+#[post("/strategies/generate/jobs")]
+async fn start_generation_job(
+    generator: web::Data<Arc<StrategyGenerator>>,
+    jobs: web::Data<Arc<GenerationJobs>>,
+    policy: web::Data<StrategyTypePolicy>,
+    body: web::Json<GenerateStrategiesRequest>,
+) -> Result<impl Responder, AppError> {
+    let req = body.into_inner();
+    let strategy_types = validate_generation_request(&policy, &req)?;
+    let markets = req.symbols.len() * req.intervals.len();
+    let (job_id, job) = jobs.create();
+    let mut rx = spawn_generation(generator.get_ref().clone(), req, strategy_types);
+
+    actix_web::rt::spawn(async move {
+        while let Some(event) = rx.recv().await {
+            job.publish(event);
+        }
+    });
+
+    Ok(HttpResponse::Accepted().json(serde_json::json!({
+        "job_id": job_id,
+        "markets": markets,
+    })))
+}
+
+#[derive(serde::Deserialize)]
+struct GenerationProgressQuery {
+    job_id: Uuid,
+}
+
+/// Server-sent events of a generation job, as on `/strategies/generate/stream`.
+/// Events already published are replayed first, and the stream ends after the
+/// job's `done` or `error` event.  Disconnecting does not stop the job.
+#[get("/strategies/generate/progress")]
+async fn get_generation_progress(
+    jobs: web::Data<Arc<GenerationJobs>>,
+    query: web::Query<GenerationProgressQuery>,
+) -> Result<impl Responder, AppError> {
+    let job = jobs
+        .get(query.job_id)
+        .ok_or_else(|| AppError::NotFound(format!("Generation job {} not found", query.job_id)))?;
+    let (history, rx) = job.subscribe();
+    let finished = history.iter().any(GenerationEvent::is_terminal);
+
+    let live = futures::stream::unfold((rx, finished), |(mut rx, finished)| async move {
+        if finished {
+            return None;
+        }
+        loop {
+            match rx.recv().await {
+                Ok(event) => {
+                    let done = event.is_terminal();
+                    return Some((event, (rx, done)));
+                }
+                // A subscriber that fell behind skips what it missed.
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    });
+    let events = futures::stream::iter(history)
+        .chain(live)
+        .map(|event| Ok::<_, actix_web::Error>(sse_frame(&event)));
+
+    Ok(event_stream(events))
+}
+
+/// Run `req` on this worker, returning its progress events followed by one
+/// `done` or `error` event.  The run completes even if the receiver is
+/// dropped.
+fn spawn_generation(
+    generator: Arc<StrategyGenerator>,
+    req: GenerateStrategiesRequest,
+    strategy_types: Vec<String>,
+) -> mpsc::UnboundedReceiver<GenerationEvent> {
+    let (tx, rx) = mpsc::unbounded_channel::<GenerationEvent>();
+
+    // The optimiser's future is not required to be `Send`, so run it on this
+    // worker's local task set.
+    actix_web::rt::spawn(async move {
+        let objective = req.objective.unwrap_or_default();
+        let result = generator
+            .generate_and_save(
+                req.symbols,
+                req.intervals,
+                req.top_n.unwrap_or(10),
+                req.limit.unwrap_or(1000),
+                req.iterations.unwrap_or(50),
+                &strategy_types,
+                objective,
+                req.rank_by.unwrap_or_else(|| objective.rank_metric()),
+                req.train_ratio,
+                req.store_trades.unwrap_or(false),
+                Some(&tx),
+            )
+            .await;
+        let _ = match result {
+            Ok(report) => tx.send(GenerationEvent::Done(report)),
+            Err(e) => tx.send(GenerationEvent::Error {
+                message: e.to_string(),
+            }),
+        };
+    });
+
+    rx
+}
+
+/// One server-sent event frame for `event`.
+fn sse_frame(event: &GenerationEvent) -> web::Bytes {
+    let data = serde_json::to_string(event).unwrap_or_else(|_| "{}".into());
+    web::Bytes::from(format!("event: {}\ndata: {data}\n\n", event.name()))
+}
+
+fn event_stream<S>(frames: S) -> HttpResponse
+where
+    S: futures::Stream<Item = Result<web::Bytes, actix_web::Error>> + 'static,
+{
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(frames)
+}
+
+/// Check a generation request's intervals and strategy types, returning the
+/// types to optimise.
+fn validate_generation_request(
+    policy: &StrategyTypePolicy,
+    req: &GenerateStrategiesRequest,
+) -> Result<Vec<String>, AppError> {
+    let invalid = interval::unsupported(&req.intervals);
+    if !invalid.is_empty() {
+        return Err(AppError::BadRequest(format!(
+            "Unsupported interval(s): {}. Supported: {}",
+            invalid.join(", "),
+            interval::SUPPORTED_INTERVALS.join(", ")
+        )));
+    }
+
+    let strategy_types = match &req.strategy_types {
+        Some(types) => {
+            let unknown: Vec<&str> = types
+                .iter()
+                .map(String::as_str)
+                .filter(|t| strategy_registry::lookup(t).is_none())
+                .collect();
+            if !unknown.is_empty() {
+                return Err(AppError::BadRequest(format!(
+                    "Unknown strategy type(s): {}",
+                    unknown.join(", ")
+                )));
+            }
+            ensure_permitted(policy, types)?;
+            types.clone()
+        }
+        None => policy.effective().into_iter().map(str::to_string).collect(),
+    };
+    if strategy_types.is_empty() {
+        return Err(AppError::Forbidden(
+            "No strategy types are enabled on this deployment".into(),
+        ));
+    }
+    if let Some(ratio) = req.train_ratio {
+        let limit = req.limit.unwrap_or(1000) as usize;
+        if strategy_generator::split_point(limit, ratio).is_none() {
+            return Err(AppError::BadRequest(format!(
+                "train_ratio {ratio} must be between 0 and 1 and leave at least {} of the {limit} bars on each side",
+                strategy_generator::MIN_SPLIT_BARS
+            )));
+        }
+    }
+
+    Ok(strategy_types)
+}
+
+#[post("/strategies/generate/estimate")]
+async fn estimate_generation(
+    generator: web::Data<Arc<StrategyGenerator>>,
+    policy: web::Data<StrategyTypePolicy>,
+    body: web::Json<GenerateStrategiesRequest>,
+) -> Result<impl Responder, AppError> {
+    let req = body.into_inner();
+    let strategy_types = validate_generation_request(&policy, &req)?;
+
+    Ok(HttpResponse::Ok().json(generator.estimate(
+        req.symbols.len(),
+        req.intervals.len(),
+        strategy_types.len(),
+        req.limit.unwrap_or(1000),
+        req.iterations.unwrap_or(50),
+    )))
+}
+
 #[post("/strategies")]
 async fn create_strategy(
     pool: web::Data<PgPool>,
+    policy: web::Data<StrategyTypePolicy>,
     body: web::Json<CreateStrategyRequest>,
 ) -> Result<impl Responder, AppError> {
     let CreateStrategyRequest {
@@ -48,6 +305,8 @@ async fn create_strategy(
         performance_metrics,
         backtest_curve,
     } = body.into_inner();
+    ensure_permitted(&policy, std::slice::from_ref(&strategy_type))?;
+    ensure_valid_parameters(&strategy_type, &parameters)?;
 
     let rec = sqlx::query_as::<_, Strategy>("INSERT INTO strategies (name, strategy_type, symbol, interval, parameters, performance_metrics, backtest_curve) VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING *")
         .bind(name)
@@ -63,17 +322,270 @@ async fn create_strategy(
     Ok(HttpResponse::Ok().json(rec))
 }
 
+#[post("/backtest")]
+async fn run_backtest(
+    market: web::Data<Arc<MarketDataService>>,
+    policy: web::Data<StrategyTypePolicy>,
+    body: web::Json<BacktestRequest>,
+) -> Result<impl Responder, AppError> {
+    let req = body.into_inner();
+    ensure_permitted(&policy, std::slice::from_ref(&req.strategy_type))?;
+    ensure_valid_parameters(&req.strategy_type, &req.parameters)?;
+    if !interval::is_supported(&req.interval) {
+        return Err(AppError::BadRequest(format!(
+            "Unsupported interval: {}. Supported: {}",
+            req.interval,
+            interval::SUPPORTED_INTERVALS.join(", ")
+        )));
+    }
+    let window = match (req.start_ms, req.end_ms) {
+        (Some(start_ms), Some(end_ms)) => CandleWindow::range(start_ms, end_ms, &req.interval)?,
+        (None, None) => {
+            let limit = req.limit.unwrap_or(1000);
+            if !(1..=1000).contains(&limit) {
+                return Err(AppError::BadRequest(
+                    "limit must be between 1 and 1000".into(),
+                ));
+            }
+            CandleWindow::Latest(limit)
+        }
+        _ => {
+            return Err(AppError::BadRequest(
+                "start_ms and end_ms must be given together".into(),
+            ))
+        }
+    };
+    let initial_capital = req.initial_capital.unwrap_or(10_000.0);
+    if !(initial_capital.is_finite() && initial_capital > 0.0) {
+        return Err(AppError::BadRequest(
+            "initial_capital must be positive".into(),
+        ));
+    }
+    let ticker = market
+        .validate_symbols(std::slice::from_ref(&req.symbol))
+        .await?
+        .remove(0);
+
+    let report = backtest::run_backtest(
+        market.get_ref(),
+        &req.strategy_type,
+        &req.parameters,
+        &ticker,
+        &req.interval,
+        window,
+        initial_capital,
+    )
+    .await?;
+    Ok(HttpResponse::Ok().json(report))
+}
+
+/// Page size of list endpoints when `limit` is not given.
+const DEFAULT_PAGE_LIMIT: i64 = 50;
+/// Largest `limit` a list endpoint accepts.
+const MAX_PAGE_LIMIT: i64 = 500;
+
+#[derive(serde::Deserialize)]
+struct PageQuery {
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+/// Resolve optional `limit`/`offset` query params, rejecting rather than
+/// clamping values out of range.
+fn page_bounds(limit: Option<i64>, offset: Option<i64>) -> Result<(i64, i64), AppError> {
+    let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+    if !(1..=MAX_PAGE_LIMIT).contains(&limit) {
+        return Err(AppError::BadRequest(format!(
+            "limit must be between 1 and {MAX_PAGE_LIMIT}"
+        )));
+    }
+    let offset = offset.unwrap_or(0);
+    if offset < 0 {
+        return Err(AppError::BadRequest("offset must not be negative".into()));
+    }
+    Ok((limit, offset))
+}
+
+/// A page of `items` with the unpaginated row count in `X-Total-Count`.
+fn paged_response<T: serde::Serialize>(items: &[T], total: i64) -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header(("X-Total-Count", total.to_string()))
+        .json(items)
+}
+
 #[get("/strategies")]
-async fn list_strategies(pool: web::Data<PgPool>) -> Result<impl Responder, AppError> {
-    let recs = sqlx::query_as::<_, Strategy>("SELECT * FROM strategies ORDER BY created_at DESC")
-        .fetch_all(pool.get_ref())
+async fn list_strategies(
+    pool: web::Data<PgPool>,
+    query: web::Query<PageQuery>,
+) -> Result<impl Responder, AppError> {
+    let (limit, offset) = page_bounds(query.limit, query.offset)?;
+    let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM strategies")
+        .fetch_one(pool.get_ref())
         .await?;
-    Ok(HttpResponse::Ok().json(recs))
+    let recs = sqlx::query_as::<_, Strategy>(
+        "SELECT * FROM strategies ORDER BY created_at DESC LIMIT $1 OFFSET $2",
+    )
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool.get_ref())
+    .await?;
+    Ok(paged_response(&recs, total))
+}
+
+/// Reject with 400, naming each offending field, if `parameters` fall outside
+/// the bounds of `strategy_type`'s schema or do not configure the strategy.
+fn ensure_valid_parameters(
+    strategy_type: &str,
+    parameters: &serde_json::Value,
+) -> Result<(), AppError> {
+    let schemas = strategy_schema::all_schemas().map_err(|e| AppError::Strategy(e.to_string()))?;
+    let schema = schemas
+        .iter()
+        .find(|s| s.strategy_type == strategy_type)
+        .ok_or_else(|| AppError::BadRequest(format!("Unknown strategy type: {strategy_type}")))?;
+
+    let errors = strategy_schema::validate_parameters(schema, parameters);
+    if !errors.is_empty() {
+        let fields: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        return Err(AppError::BadRequest(format!(
+            "Invalid parameters: {}",
+            fields.join("; ")
+        )));
+    }
+    match strategy_registry::lookup(strategy_type) {
+        Some(entry) => entry.check_parameters(parameters),
+        None => Err(AppError::BadRequest(format!(
+            "Unknown strategy type: {strategy_type}"
+        ))),
+    }
+}
+
+/// Reject with 403 if any of `strategy_types` is disabled by `policy`.
+fn ensure_permitted(
+    policy: &StrategyTypePolicy,
+    strategy_types: &[String],
+) -> Result<(), AppError> {
+    let denied: Vec<&str> = strategy_types
+        .iter()
+        .map(String::as_str)
+        .filter(|t| !policy.permits(t))
+        .collect();
+    if denied.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::Forbidden(format!(
+            "Strategy type(s) disabled on this deployment: {}",
+            denied.join(", ")
+        )))
+    }
+}
+
+#[get("/strategies/types")]
+async fn list_strategy_types(policy: web::Data<StrategyTypePolicy>) -> impl Responder {
+    HttpResponse::Ok().json(policy.effective())
+}
+
+#[get("/strategies/schema")]
+async fn get_strategy_schema() -> Result<impl Responder, AppError> {
+    let schemas = strategy_schema::all_schemas().map_err(|e| AppError::Strategy(e.to_string()))?;
+    Ok(HttpResponse::Ok().json(schemas))
+}
+
+#[derive(serde::Deserialize)]
+struct StrategyDiffQuery {
+    a: Uuid,
+    b: Uuid,
+}
+
+#[get("/strategies/diff")]
+async fn diff_strategies(
+    pool: web::Data<PgPool>,
+    query: web::Query<StrategyDiffQuery>,
+) -> Result<impl Responder, AppError> {
+    let StrategyDiffQuery { a, b } = query.into_inner();
+    let pool = pool.get_ref();
+    let fetch = |id: Uuid| async move {
+        sqlx::query_as::<_, Strategy>("SELECT * FROM strategies WHERE id = $1")
+            .bind(id)
+            .fetch_optional(pool)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("Strategy {id} not found")))
+    };
+    let (strategy_a, strategy_b) = (fetch(a).await?, fetch(b).await?);
+
+    Ok(HttpResponse::Ok().json(StrategyDiff {
+        a,
+        b,
+        parameters: strategy_diff::diff_parameters(&strategy_a.parameters, &strategy_b.parameters),
+        metrics: strategy_diff::diff_metrics(
+            strategy_a.performance_metrics.as_ref(),
+            strategy_b.performance_metrics.as_ref(),
+        ),
+    }))
+}
+
+#[get("/strategies/{id}")]
+async fn get_strategy(
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+) -> Result<impl Responder, AppError> {
+    let rec = sqlx::query_as::<_, Strategy>("SELECT * FROM strategies WHERE id = $1")
+        .bind(path.into_inner())
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| AppError::NotFound("Strategy not found".into()))?;
+    Ok(HttpResponse::Ok().json(rec))
+}
+
+/// Rename a strategy or change its parameters.  Sessions pick up new
+/// parameters on their next bar.
+#[patch("/strategies/{id}")]
+async fn update_strategy(
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+    body: web::Json<UpdateStrategyRequest>,
+) -> Result<impl Responder, AppError> {
+    let strategy_id = path.into_inner();
+    let req = body.into_inner();
+    let fixed = req.fixed_fields();
+    if !fixed.is_empty() {
+        return Err(AppError::BadRequest(format!(
+            "{} cannot be changed because sessions depend on it; create a new strategy instead",
+            fixed.join(", ")
+        )));
+    }
+    let name = req.name.map(|n| n.trim().to_string());
+    if name.as_deref().is_some_and(str::is_empty) {
+        return Err(AppError::BadRequest("name must not be empty".into()));
+    }
+
+    let strategy_type: String =
+        sqlx::query_scalar("SELECT strategy_type FROM strategies WHERE id = $1")
+            .bind(strategy_id)
+            .fetch_optional(pool.get_ref())
+            .await?
+            .ok_or_else(|| AppError::NotFound("Strategy not found".into()))?;
+    if let Some(parameters) = &req.parameters {
+        ensure_valid_parameters(&strategy_type, parameters)?;
+    }
+
+    let rec = sqlx::query_as::<_, Strategy>(
+        "UPDATE strategies SET name = COALESCE($2, name), parameters = COALESCE($3, parameters) WHERE id = $1 RETURNING *",
+    )
+    .bind(strategy_id)
+    .bind(name)
+    .bind(req.parameters)
+    .fetch_optional(pool.get_ref())
+    .await?
+    .ok_or_else(|| AppError::NotFound("Strategy not found".into()))?;
+
+    Ok(HttpResponse::Ok().json(rec))
 }
 
 #[delete("/strategies/{id}")]
 async fn delete_strategy(
     pool: web::Data<PgPool>,
+    portfolio: web::Data<Arc<PortfolioManager>>,
     path: web::Path<Uuid>,
 ) -> Result<impl Responder, AppError> {
     let strategy_id = path.into_inner();
@@ -91,6 +603,16 @@ async fn delete_strategy(
         .execute(&mut *tx)
         .await?;
 
+    sqlx::query("DELETE FROM capital_adjustments WHERE session_id IN (SELECT id FROM sessions WHERE strategy_id = $1)")
+        .bind(strategy_id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("DELETE FROM order_events WHERE session_id IN (SELECT id FROM sessions WHERE strategy_id = $1)")
+        .bind(strategy_id)
+        .execute(&mut *tx)
+        .await?;
+
     sqlx::query("DELETE FROM sessions WHERE strategy_id = $1")
         .bind(strategy_id)
         .execute(&mut *tx)
@@ -107,17 +629,28 @@ async fn delete_strategy(
         return Err(AppError::NotFound("Strategy not found".into()));
     }
 
+    schedule_cache_rebuild(portfolio.get_ref());
+
     Ok(HttpResponse::Ok().json(serde_json::json!({ "message": "Deleted" })))
 }
 
 #[delete("/strategies")]
-async fn delete_all_strategies(pool: web::Data<PgPool>) -> Result<impl Responder, AppError> {
+async fn delete_all_strategies(
+    pool: web::Data<PgPool>,
+    portfolio: web::Data<Arc<PortfolioManager>>,
+) -> Result<impl Responder, AppError> {
     let mut tx = pool.begin().await?;
 
     sqlx::query("DELETE FROM trades").execute(&mut *tx).await?;
     sqlx::query("DELETE FROM equity_snapshots")
         .execute(&mut *tx)
         .await?;
+    sqlx::query("DELETE FROM capital_adjustments")
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM order_events")
+        .execute(&mut *tx)
+        .await?;
     sqlx::query("DELETE FROM sessions")
         .execute(&mut *tx)
         .await?;
@@ -127,12 +660,15 @@ async fn delete_all_strategies(pool: web::Data<PgPool>) -> Result<impl Responder
 
     tx.commit().await?;
 
+    schedule_cache_rebuild(portfolio.get_ref());
+
     Ok(HttpResponse::Ok().json(serde_json::json!({ "message": "All strategies deleted" })))
 }
 
 #[post("/sessions")]
 async fn start_session(
     pool: web::Data<PgPool>,
+    market: web::Data<Arc<MarketDataService>>,
     body: web::Json<CreateSessionRequest>,
 ) -> Result<impl Responder, AppError> {
     let req = body.into_inner();
@@ -142,27 +678,121 @@ async fn start_session(
         .await?;
 
     let initial_capital = req.initial_capital;
-    let execution_mode = req.execution_mode.unwrap_or_else(|| "sync".to_string());
+    let (execution_mode, edge_lookback) = req.execution().map_err(AppError::BadRequest)?;
+    let signal_confirm_bars = req.signal_confirm_bars.unwrap_or(1);
+    if signal_confirm_bars < 1 {
+        return Err(AppError::BadRequest(
+            "signal_confirm_bars must be at least 1".into(),
+        ));
+    }
+    if edge_lookback.is_some_and(|n| n < 1) {
+        return Err(AppError::BadRequest(
+            "edge_lookback must be at least 1".into(),
+        ));
+    }
+    let max_leverage = req.max_leverage.unwrap_or(1.0);
+    if max_leverage < 1.0 || !max_leverage.is_finite() {
+        return Err(AppError::BadRequest(
+            "max_leverage must be at least 1.0".into(),
+        ));
+    }
+    if req
+        .trail_pct
+        .is_some_and(|v| !(v.is_finite() && (0.0..1.0).contains(&v)))
+    {
+        return Err(AppError::BadRequest(
+            "trail_pct must be a fraction in [0, 1)".into(),
+        ));
+    }
+    for (field, value) in [
+        ("stop_loss_pct", req.stop_loss_pct),
+        ("take_profit_pct", req.take_profit_pct),
+    ] {
+        if value.is_some_and(|v| !(v.is_finite() && v >= 0.0)) {
+            return Err(AppError::BadRequest(format!(
+                "{field} must be a non-negative fraction"
+            )));
+        }
+    }
+
+    let (symbol, session_type) = match req.basket_symbols {
+        Some(symbols) => {
+            let mut constituents: Vec<String> = Vec::with_capacity(symbols.len());
+            for symbol in symbols {
+                let symbol = symbol.trim().to_uppercase();
+                if !symbol.is_empty() && !constituents.contains(&symbol) {
+                    constituents.push(symbol);
+                }
+            }
+            if constituents.len() < 2 {
+                return Err(AppError::BadRequest(
+                    "basket_symbols must list at least two symbols".into(),
+                ));
+            }
+            (constituents.join(","), "basket")
+        }
+        None => (strategy.symbol, "single"),
+    };
+    let constituents: Vec<String> = symbol.split(',').map(String::from).collect();
+    market.validate_symbols(&constituents).await?;
+
+    if let Some(trigger) = &req.trigger {
+        let watched_exists: bool =
+            sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM sessions WHERE id = $1)")
+                .bind(trigger.watched_session())
+                .fetch_one(pool.get_ref())
+                .await?;
+        if !watched_exists {
+            return Err(AppError::BadRequest(format!(
+                "trigger references unknown session {}",
+                trigger.watched_session()
+            )));
+        }
+    }
+    let status = if req.trigger.is_some() {
+        "pending"
+    } else {
+        "active"
+    };
+    let trigger = req
+        .trigger
+        .map(serde_json::to_value)
+        .transpose()
+        .map_err(|e| AppError::BadRequest(e.to_string()))?;
 
     let rec = sqlx::query_as::<_, Session>(
-        "INSERT INTO sessions (strategy_id, symbol, interval, initial_capital, current_equity, execution_mode) VALUES ($1, $2, $3, $4, $5, $6) RETURNING *",
+        "INSERT INTO sessions (strategy_id, symbol, interval, initial_capital, current_equity, execution_mode, session_type, status, trigger, paper_broker, signal_confirm_bars, edge_lookback, max_leverage, trail_pct, stop_loss_pct, take_profit_pct) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16) RETURNING *",
     )
     .bind(strategy.id)
-    .bind(strategy.symbol)
+    .bind(symbol)
     .bind(strategy.interval)
     .bind(initial_capital)
     .bind(initial_capital)
     .bind(execution_mode)
+    .bind(session_type)
+    .bind(status)
+    .bind(trigger)
+    .bind(req.paper_broker.unwrap_or(false))
+    .bind(signal_confirm_bars)
+    .bind(edge_lookback)
+    .bind(max_leverage)
+    .bind(req.trail_pct)
+    .bind(req.stop_loss_pct)
+    .bind(req.take_profit_pct)
     .fetch_one(pool.get_ref())
     .await?;
 
-    sqlx::query(
-        "INSERT INTO equity_snapshots (session_id, equity, timestamp) VALUES ($1, $2, NOW())",
-    )
-    .bind(rec.id)
-    .bind(rec.initial_capital)
-    .execute(pool.get_ref())
-    .await?;
+    // Pending sessions get their first snapshot on activation, so they do not
+    // count towards portfolio equity before they trade.
+    if rec.status == "active" {
+        sqlx::query(
+            "INSERT INTO equity_snapshots (session_id, equity, timestamp) VALUES ($1, $2, NOW())",
+        )
+        .bind(rec.id)
+        .bind(rec.initial_capital)
+        .execute(pool.get_ref())
+        .await?;
+    }
 
     Ok(HttpResponse::Ok().json(rec))
 }
@@ -175,6 +805,7 @@ struct BulkSessionRequest {
 #[post("/sessions/bulk")]
 async fn bulk_start_session(
     pool: web::Data<PgPool>,
+    portfolio: web::Data<Arc<PortfolioManager>>,
     body: web::Json<BulkSessionRequest>,
 ) -> Result<impl Responder, AppError> {
     let ids = body.into_inner().strategy_ids;
@@ -200,6 +831,8 @@ async fn bulk_start_session(
         }
     }
 
+    schedule_cache_rebuild(portfolio.get_ref());
+
     Ok(HttpResponse::Ok().json(serde_json::json!({
         "message": "Bulk sessions started",
         "count": created_count
@@ -207,15 +840,42 @@ async fn bulk_start_session(
 }
 
 #[get("/sessions")]
-async fn list_sessions(pool: web::Data<PgPool>) -> Result<impl Responder, AppError> {
-    let recs = sqlx::query_as::<_, Session>("SELECT * FROM sessions ORDER BY created_at DESC")
-        .fetch_all(pool.get_ref())
+async fn list_sessions(
+    pool: web::Data<PgPool>,
+    query: web::Query<PageQuery>,
+) -> Result<impl Responder, AppError> {
+    let (limit, offset) = page_bounds(query.limit, query.offset)?;
+    let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM sessions")
+        .fetch_one(pool.get_ref())
         .await?;
-    Ok(HttpResponse::Ok().json(recs))
+    let recs = sqlx::query_as::<_, Session>(
+        "SELECT * FROM sessions ORDER BY created_at DESC LIMIT $1 OFFSET $2",
+    )
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool.get_ref())
+    .await?;
+    Ok(paged_response(&recs, total))
+}
+
+#[get("/sessions/{id}")]
+async fn get_session(
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+) -> Result<impl Responder, AppError> {
+    let rec = sqlx::query_as::<_, Session>("SELECT * FROM sessions WHERE id = $1")
+        .bind(path.into_inner())
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| AppError::NotFound("Session not found".into()))?;
+    Ok(HttpResponse::Ok().json(rec))
 }
 
 #[post("/sessions/reset")]
-async fn reset_sessions(pool: web::Data<PgPool>) -> Result<impl Responder, AppError> {
+async fn reset_sessions(
+    pool: web::Data<PgPool>,
+    portfolio: web::Data<Arc<PortfolioManager>>,
+) -> Result<impl Responder, AppError> {
     let mut tx = pool.begin().await?;
 
     sqlx::query("DELETE FROM trades WHERE session_id IN (SELECT id FROM sessions)")
@@ -224,155 +884,1450 @@ async fn reset_sessions(pool: web::Data<PgPool>) -> Result<impl Responder, AppEr
     sqlx::query("DELETE FROM equity_snapshots WHERE session_id IN (SELECT id FROM sessions)")
         .execute(&mut *tx)
         .await?;
+    sqlx::query("DELETE FROM capital_adjustments WHERE session_id IN (SELECT id FROM sessions)")
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM order_events WHERE session_id IN (SELECT id FROM sessions)")
+        .execute(&mut *tx)
+        .await?;
     sqlx::query("DELETE FROM sessions")
         .execute(&mut *tx)
         .await?;
 
     tx.commit().await?;
 
+    schedule_cache_rebuild(portfolio.get_ref());
+
     Ok(HttpResponse::Ok().json(serde_json::json!({ "message": "Sessions reset" })))
 }
 
-#[get("/sessions/{id}/trades")]
-async fn get_trades(
+#[post("/sessions/{id}/stop")]
+async fn stop_session(
     pool: web::Data<PgPool>,
+    market: web::Data<Arc<MarketDataService>>,
+    portfolio: web::Data<Arc<PortfolioManager>>,
+    events: web::Data<Arc<SessionEvents>>,
     path: web::Path<Uuid>,
 ) -> Result<impl Responder, AppError> {
-    let id = path.into_inner();
-    let recs = sqlx::query_as::<_, Trade>(
-        "SELECT * FROM trades WHERE session_id = $1 ORDER BY timestamp DESC",
+    let session = trading_engine::stop_session(
+        pool.get_ref(),
+        events.get_ref(),
+        market.get_ref(),
+        path.into_inner(),
     )
-    .bind(id)
-    .fetch_all(pool.get_ref())
     .await?;
-    Ok(HttpResponse::Ok().json(recs))
+    schedule_cache_rebuild(portfolio.get_ref());
+    Ok(HttpResponse::Ok().json(session))
 }
 
-#[derive(serde::Serialize, sqlx::FromRow)]
-struct Snapshot {
-    equity: f64,
-    timestamp: chrono::DateTime<chrono::Utc>,
+#[post("/sessions/{id}/resume")]
+async fn resume_session(
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+) -> Result<impl Responder, AppError> {
+    let session = trading_engine::resume_session(pool.get_ref(), path.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(session))
 }
 
-#[get("/sessions/{id}/equity")]
-async fn get_equity_curve(
+#[post("/sessions/{id}/adjust-capital")]
+async fn adjust_capital(
     pool: web::Data<PgPool>,
+    portfolio: web::Data<Arc<PortfolioManager>>,
     path: web::Path<Uuid>,
+    body: web::Json<AdjustCapitalRequest>,
 ) -> Result<impl Responder, AppError> {
     let id = path.into_inner();
-    let recs = sqlx::query_as::<_, Snapshot>(
-        "SELECT equity, timestamp FROM equity_snapshots WHERE session_id = $1 ORDER BY timestamp ASC",
+    let amount = body.into_inner().amount;
+    if !amount.is_finite() || amount == 0.0 {
+        return Err(AppError::BadRequest(
+            "amount must be a non-zero number".into(),
+        ));
+    }
+
+    let mut tx = pool.begin().await?;
+    let session = sqlx::query_as::<_, Session>("SELECT * FROM sessions WHERE id = $1 FOR UPDATE")
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Session not found".into()))?;
+
+    let equity_before = session.current_equity;
+    if equity_before <= 0.0 {
+        // Nothing to rescale the entry basis or the portfolio returns from.
+        return Err(AppError::BadRequest(format!(
+            "session equity {equity_before:.2} is not positive; capital cannot be adjusted"
+        )));
+    }
+    let equity_after = equity_before + amount;
+    if equity_after <= 0.0 {
+        return Err(AppError::BadRequest(format!(
+            "withdrawal of {} exceeds session equity {:.2}",
+            -amount, equity_before
+        )));
+    }
+
+    // Rescale the entry basis so the open position's MTM maps to the new equity
+    // at the current price, keeping future PnL percentages unchanged.
+    let entry_equity = session
+        .entry_equity
+        .map(|basis| basis * equity_after / equity_before);
+    let kind = if amount > 0.0 {
+        "DEPOSIT"
+    } else {
+        "WITHDRAWAL"
+    };
+    let now = Utc::now();
+
+    let rec = sqlx::query_as::<_, Session>(
+        "UPDATE sessions SET current_equity = $1, entry_equity = $2, initial_capital = initial_capital + $3, last_update = $4 WHERE id = $5 RETURNING *",
     )
+    .bind(equity_after)
+    .bind(entry_equity)
+    .bind(amount)
+    .bind(now)
     .bind(id)
-    .fetch_all(pool.get_ref())
+    .fetch_one(&mut *tx)
     .await?;
-    Ok(HttpResponse::Ok().json(recs))
-}
 
-#[derive(serde::Serialize, sqlx::FromRow)]
-struct PortfolioPoint {
-    timestamp: DateTime<Utc>,
-    total_equity: f64,
-}
+    sqlx::query(
+        "INSERT INTO capital_adjustments (session_id, kind, amount, equity_before, equity_after, timestamp) VALUES ($1, $2, $3, $4, $5, $6)",
+    )
+    .bind(id)
+    .bind(kind)
+    .bind(amount)
+    .bind(equity_before)
+    .bind(equity_after)
+    .bind(now)
+    .execute(&mut *tx)
+    .await?;
 
-#[derive(serde::Serialize, sqlx::FromRow)]
-struct PortfolioCandle {
-    #[sqlx(rename = "bucket_time")]
-    time: DateTime<Utc>,
-    open: f64,
-    high: f64,
-    low: f64,
-    close: f64,
-}
+    sqlx::query("INSERT INTO equity_snapshots (session_id, equity, timestamp) VALUES ($1, $2, $3)")
+        .bind(id)
+        .bind(equity_after)
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
 
-#[derive(serde::Deserialize)]
-struct PortfolioQuery {
-    range_days: Option<i64>,
-    interval: Option<String>,
-    style: Option<String>,
+    tx.commit().await?;
+    // Cached returns were taken on the capital before the adjustment.
+    portfolio.invalidate_cache();
+    schedule_cache_rebuild(portfolio.get_ref());
+
+    Ok(HttpResponse::Ok().json(rec))
 }
 
-#[derive(serde::Serialize)]
-struct CandleBar {
-    time: String,
-    open: f64,
-    high: f64,
-    low: f64,
+#[post("/sessions/{id}/backfill")]
+async fn backfill_session_equity(
+    pool: web::Data<PgPool>,
+    market: web::Data<Arc<MarketDataService>>,
+    portfolio: web::Data<Arc<PortfolioManager>>,
+    path: web::Path<Uuid>,
+) -> Result<impl Responder, AppError> {
+    let id = path.into_inner();
+    let session = sqlx::query_as::<_, Session>("SELECT * FROM sessions WHERE id = $1")
+        .bind(id)
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| AppError::NotFound("Session not found".into()))?;
+
+    let report = backfill::backfill_session(pool.get_ref(), market.get_ref(), &session).await?;
+    if report.inserted > 0 {
+        portfolio.invalidate_cache();
+        schedule_cache_rebuild(portfolio.get_ref());
+    }
+    Ok(HttpResponse::Ok().json(report))
+}
+
+#[post("/sessions/{id}/import-trades")]
+async fn import_session_trades(
+    pool: web::Data<PgPool>,
+    portfolio: web::Data<Arc<PortfolioManager>>,
+    path: web::Path<Uuid>,
+    body: web::Json<ImportTradesRequest>,
+) -> Result<impl Responder, AppError> {
+    let id = path.into_inner();
+    let req = body.into_inner();
+
+    let mut tx = pool.begin().await?;
+    let session = sqlx::query_as::<_, Session>("SELECT * FROM sessions WHERE id = $1 FOR UPDATE")
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Session not found".into()))?;
+
+    let already_imported: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM trades WHERE session_id = $1 AND timestamp < $2)",
+    )
+    .bind(id)
+    .bind(session.created_at)
+    .fetch_one(&mut *tx)
+    .await?;
+    if already_imported {
+        return Err(AppError::BadRequest(
+            "Session already has imported history".into(),
+        ));
+    }
+
+    let plan = trade_import::plan_import(&req.trades, session.created_at, session.initial_capital)
+        .map_err(AppError::BadRequest)?;
+
+    let mut entry_id: Option<Uuid> = None;
+    for t in &plan.trades {
+        let trade_id: Uuid = sqlx::query_scalar(
+            "INSERT INTO trades (session_id, symbol, side, price, quantity, pnl, pnl_pct, fee_paid, reason, order_type, entry_trade_id, holding_seconds, timestamp) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13) RETURNING id",
+        )
+        .bind(id)
+        .bind(&session.symbol)
+        .bind(t.side)
+        .bind(t.price)
+        .bind(0.0_f64)
+        .bind(t.pnl)
+        .bind(t.pnl_pct)
+        .bind(t.fee)
+        .bind("Imported")
+        .bind("MARKET")
+        .bind(if t.is_close { entry_id } else { None })
+        .bind(t.holding_seconds)
+        .bind(t.timestamp)
+        .fetch_one(&mut *tx)
+        .await?;
+        entry_id = (!t.is_close).then_some(trade_id);
+    }
+
+    for (ts, equity) in &plan.snapshots {
+        sqlx::query(
+            "INSERT INTO equity_snapshots (session_id, equity, timestamp) VALUES ($1, $2, $3)",
+        )
+        .bind(id)
+        .bind(equity)
+        .bind(ts)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+    portfolio.invalidate_cache();
+    schedule_cache_rebuild(portfolio.get_ref());
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "trades_imported": plan.trades.len(),
+        "snapshots_created": plan.snapshots.len(),
+    })))
+}
+
+#[get("/sessions/{id}/capital-adjustments")]
+async fn get_capital_adjustments(
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+) -> Result<impl Responder, AppError> {
+    let id = path.into_inner();
+    let recs = sqlx::query_as::<_, CapitalAdjustment>(
+        "SELECT * FROM capital_adjustments WHERE session_id = $1 ORDER BY timestamp DESC",
+    )
+    .bind(id)
+    .fetch_all(pool.get_ref())
+    .await?;
+    Ok(HttpResponse::Ok().json(recs))
+}
+
+#[get("/sessions/{id}/order-events")]
+async fn get_order_events(
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+) -> Result<impl Responder, AppError> {
+    let id = path.into_inner();
+    let recs = sqlx::query_as::<_, OrderEvent>(
+        "SELECT * FROM order_events WHERE session_id = $1 ORDER BY timestamp DESC",
+    )
+    .bind(id)
+    .fetch_all(pool.get_ref())
+    .await?;
+    Ok(HttpResponse::Ok().json(recs))
+}
+
+#[derive(serde::Deserialize)]
+struct TradesQuery {
+    min_price: Option<f64>,
+    max_price: Option<f64>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+#[get("/sessions/{id}/trades")]
+async fn get_trades(
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+    query: web::Query<TradesQuery>,
+) -> Result<impl Responder, AppError> {
+    let id = path.into_inner();
+    let TradesQuery {
+        min_price,
+        max_price,
+        limit,
+        offset,
+    } = query.into_inner();
+    let (limit, offset) = page_bounds(limit, offset)?;
+    if let (Some(min), Some(max)) = (min_price, max_price) {
+        if min > max {
+            return Err(AppError::BadRequest(
+                "min_price must not exceed max_price".into(),
+            ));
+        }
+    }
+
+    let total: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM trades WHERE session_id = $1 AND ($2::float8 IS NULL OR price >= $2) AND ($3::float8 IS NULL OR price <= $3)",
+    )
+    .bind(id)
+    .bind(min_price)
+    .bind(max_price)
+    .fetch_one(pool.get_ref())
+    .await?;
+    let recs = sqlx::query_as::<_, Trade>(
+        "SELECT * FROM trades WHERE session_id = $1 AND ($2::float8 IS NULL OR price >= $2) AND ($3::float8 IS NULL OR price <= $3) ORDER BY timestamp DESC LIMIT $4 OFFSET $5",
+    )
+    .bind(id)
+    .bind(min_price)
+    .bind(max_price)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool.get_ref())
+    .await?;
+    Ok(paged_response(&recs, total))
+}
+
+#[derive(serde::Serialize, sqlx::FromRow)]
+struct TradePerformance {
+    total_trades: i64,
+    closed_trades: i64,
+    /// Share of closed trades with a positive PnL, in percent; `None` before
+    /// the first close.
+    win_rate_pct: Option<f64>,
+    gross_pnl: f64,
+    total_fees: f64,
+    /// PnL of every trade, entry fees included.
+    net_pnl: f64,
+    /// PnL of the closing trades alone; entry fees are in `total_fees` and
+    /// `net_pnl`.
+    realized_pnl: f64,
+    /// Fees as a fraction of absolute gross PnL; `None` when gross PnL is zero.
+    fee_to_pnl_ratio: Option<f64>,
+    avg_holding_seconds: Option<f64>,
+    min_holding_seconds: Option<i64>,
+    max_holding_seconds: Option<i64>,
+    /// Mean and median closing-trade return, in percent of entry equity.
+    avg_trade_return_pct: Option<f64>,
+    median_trade_return_pct: Option<f64>,
+    /// Realized PnL per closed trade (win rate × average win − loss rate ×
+    /// average loss); `None` before the first close.
+    expectancy: Option<f64>,
+}
+
+/// Aggregates over `trades`; callers append the `WHERE` clause binding `$1`.
+const TRADE_PERFORMANCE_SELECT: &str = r#"
+    SELECT
+        COUNT(*) AS total_trades,
+        COUNT(entry_trade_id) AS closed_trades,
+        100.0 * (COUNT(*) FILTER (WHERE entry_trade_id IS NOT NULL AND pnl > 0))::FLOAT8 / NULLIF(COUNT(entry_trade_id), 0) AS win_rate_pct,
+        COALESCE(SUM(pnl), 0) + COALESCE(SUM(fee_paid), 0) AS gross_pnl,
+        COALESCE(SUM(fee_paid), 0) AS total_fees,
+        COALESCE(SUM(pnl), 0) AS net_pnl,
+        COALESCE(SUM(pnl) FILTER (WHERE entry_trade_id IS NOT NULL), 0) AS realized_pnl,
+        COALESCE(SUM(fee_paid), 0) / NULLIF(ABS(COALESCE(SUM(pnl), 0) + COALESCE(SUM(fee_paid), 0)), 0) AS fee_to_pnl_ratio,
+        AVG(holding_seconds)::FLOAT8 AS avg_holding_seconds,
+        MIN(holding_seconds) AS min_holding_seconds,
+        MAX(holding_seconds) AS max_holding_seconds,
+        AVG(pnl_pct) AS avg_trade_return_pct,
+        percentile_cont(0.5) WITHIN GROUP (ORDER BY pnl_pct) AS median_trade_return_pct,
+        SUM(pnl) FILTER (WHERE entry_trade_id IS NOT NULL) / NULLIF(COUNT(entry_trade_id), 0) AS expectancy
+    FROM trades
+"#;
+
+#[get("/strategies/{id}/performance")]
+async fn get_strategy_performance(
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+) -> Result<impl Responder, AppError> {
+    let id = path.into_inner();
+    sqlx::query_as::<_, Strategy>("SELECT * FROM strategies WHERE id = $1")
+        .bind(id)
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| AppError::NotFound("Strategy not found".into()))?;
+
+    let sql = format!(
+        "{TRADE_PERFORMANCE_SELECT} WHERE session_id IN (SELECT id FROM sessions WHERE strategy_id = $1)"
+    );
+    let rec = sqlx::query_as::<_, TradePerformance>(&sql)
+        .bind(id)
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(rec))
+}
+
+/// In-sample trades saved when the strategy was generated with
+/// `store_trades`; `null` when none were stored.
+#[get("/strategies/{id}/backtest-trades")]
+async fn get_strategy_backtest_trades(
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+) -> Result<impl Responder, AppError> {
+    let trades: Option<serde_json::Value> =
+        sqlx::query_scalar("SELECT backtest_trades FROM strategies WHERE id = $1")
+            .bind(path.into_inner())
+            .fetch_optional(pool.get_ref())
+            .await?
+            .ok_or_else(|| AppError::NotFound("Strategy not found".into()))?;
+    Ok(HttpResponse::Ok().json(trades))
+}
+
+#[derive(serde::Serialize, sqlx::FromRow)]
+struct SymbolStrategyCell {
+    strategy_type: String,
+    symbol: String,
+    sessions: i64,
+    closed_trades: i64,
+    /// Fraction of closed trades with positive net PnL.
+    win_rate: f64,
+    net_pnl: f64,
+    avg_trade_return_pct: Option<f64>,
+}
+
+#[derive(serde::Serialize)]
+struct SymbolStrategyMatrix {
+    strategy_types: Vec<String>,
+    symbols: Vec<String>,
+    /// One cell per strategy type and symbol with at least one closed trade.
+    cells: Vec<SymbolStrategyCell>,
+}
+
+#[get("/reports/symbol-strategy-matrix")]
+async fn get_symbol_strategy_matrix(pool: web::Data<PgPool>) -> Result<impl Responder, AppError> {
+    let cells = sqlx::query_as::<_, SymbolStrategyCell>(
+        r#"
+        SELECT
+            st.strategy_type,
+            s.symbol,
+            COUNT(DISTINCT s.id) AS sessions,
+            COUNT(*) AS closed_trades,
+            (COUNT(*) FILTER (WHERE t.pnl > 0))::FLOAT8 / COUNT(*) AS win_rate,
+            COALESCE(SUM(t.pnl), 0) AS net_pnl,
+            AVG(t.pnl_pct) AS avg_trade_return_pct
+        FROM trades t
+        JOIN sessions s ON s.id = t.session_id
+        JOIN strategies st ON st.id = s.strategy_id
+        WHERE t.entry_trade_id IS NOT NULL
+        GROUP BY st.strategy_type, s.symbol
+        ORDER BY st.strategy_type, s.symbol
+        "#,
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    let mut strategy_types: Vec<String> = cells.iter().map(|c| c.strategy_type.clone()).collect();
+    strategy_types.dedup();
+    let mut symbols: Vec<String> = cells.iter().map(|c| c.symbol.clone()).collect();
+    symbols.sort();
+    symbols.dedup();
+
+    Ok(HttpResponse::Ok().json(SymbolStrategyMatrix {
+        strategy_types,
+        symbols,
+        cells,
+    }))
+}
+
+/// Seconds since creation and seconds holding a position: closed trades'
+/// `holding_seconds` plus the age of any open position.  Callers append the
+/// `WHERE` clause on `s`.
+const EXPOSURE_SELECT: &str = r#"
+    SELECT
+        s.id AS session_id,
+        EXTRACT(EPOCH FROM (NOW() - s.created_at))::FLOAT8 AS lifetime_seconds,
+        (
+            COALESCE((SELECT SUM(t.holding_seconds) FROM trades t
+                      WHERE t.session_id = s.id AND t.entry_trade_id IS NOT NULL), 0)
+            + COALESCE((SELECT EXTRACT(EPOCH FROM (NOW() - o.timestamp)) FROM trades o
+                        WHERE o.id = s.open_trade_id), 0)
+        )::FLOAT8 AS exposed_seconds
+    FROM sessions s
+"#;
+
+#[derive(sqlx::FromRow)]
+struct ExposureRow {
+    session_id: Uuid,
+    lifetime_seconds: f64,
+    exposed_seconds: f64,
+}
+
+#[derive(serde::Serialize)]
+struct TimeInMarket {
+    session_id: Uuid,
+    lifetime_seconds: f64,
+    exposed_seconds: f64,
+    /// Share of the session's lifetime spent holding a position, in percent.
+    time_in_market_pct: Option<f64>,
+}
+
+/// `exposed` as a percentage of `lifetime`, capped at 100; `None` for a
+/// session with no lifetime yet.
+fn time_in_market_pct(exposed: f64, lifetime: f64) -> Option<f64> {
+    (lifetime > 0.0).then(|| (exposed / lifetime * 100.0).clamp(0.0, 100.0))
+}
+
+impl From<ExposureRow> for TimeInMarket {
+    fn from(row: ExposureRow) -> Self {
+        Self {
+            session_id: row.session_id,
+            lifetime_seconds: row.lifetime_seconds,
+            exposed_seconds: row.exposed_seconds,
+            time_in_market_pct: time_in_market_pct(row.exposed_seconds, row.lifetime_seconds),
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct SessionStatsRow {
+    current_equity: f64,
+    current_position: f64,
+    entry_price: Option<f64>,
+    unrealized_pnl: Option<f64>,
+    lifetime_seconds: f64,
+    exposed_seconds: f64,
+}
+
+/// The session's position, with its exposure from [`EXPOSURE_SELECT`];
+/// trade aggregates come from [`TRADE_PERFORMANCE_SELECT`] and the drawdown
+/// from the equity curve, with the same math as `/portfolio/drawdowns`.
+fn session_stats_select() -> String {
+    format!(
+        r#"
+    SELECT
+        s.current_equity,
+        s.current_position,
+        s.entry_price,
+        CASE WHEN s.open_trade_id IS NOT NULL THEN s.current_equity - s.entry_equity END AS unrealized_pnl,
+        e.lifetime_seconds,
+        e.exposed_seconds
+    FROM sessions s
+    JOIN ({EXPOSURE_SELECT}) e ON e.session_id = s.id
+    WHERE s.id = $1
+"#
+    )
+}
+
+#[derive(serde::Serialize)]
+struct SessionStats {
+    session_id: Uuid,
+    current_equity: f64,
+    #[serde(flatten)]
+    performance: TradePerformance,
+    /// Deepest peak-to-trough decline of the equity snapshots, in percent.
+    max_drawdown_pct: f64,
+    current_position: f64,
+    entry_price: Option<f64>,
+    /// Marked-to-market PnL of the open position; `None` when flat.
+    unrealized_pnl: Option<f64>,
+    lifetime_seconds: f64,
+    /// Closed trades' holding times plus the age of any open position.
+    exposed_seconds: f64,
+    /// Share of the session's lifetime spent holding a position, in percent.
+    time_in_market_pct: Option<f64>,
+}
+
+/// Realised results, trade statistics, exposure and the open position of
+/// one session.
+#[get("/sessions/{id}/stats")]
+async fn get_session_stats(
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+) -> Result<impl Responder, AppError> {
+    let id = path.into_inner();
+    let row = sqlx::query_as::<_, SessionStatsRow>(&session_stats_select())
+        .bind(id)
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| AppError::NotFound("Session not found".into()))?;
+    let sql = format!("{TRADE_PERFORMANCE_SELECT} WHERE session_id = $1");
+    let performance = sqlx::query_as::<_, TradePerformance>(&sql)
+        .bind(id)
+        .fetch_one(pool.get_ref())
+        .await?;
+    let equity: Vec<f64> = sqlx::query_scalar(
+        "SELECT equity FROM equity_snapshots WHERE session_id = $1 ORDER BY timestamp ASC",
+    )
+    .bind(id)
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    Ok(HttpResponse::Ok().json(SessionStats {
+        session_id: id,
+        current_equity: row.current_equity,
+        performance,
+        max_drawdown_pct: drawdown::max_drawdown_pct(equity),
+        current_position: row.current_position,
+        entry_price: row.entry_price,
+        unrealized_pnl: row.unrealized_pnl,
+        lifetime_seconds: row.lifetime_seconds,
+        exposed_seconds: row.exposed_seconds,
+        time_in_market_pct: time_in_market_pct(row.exposed_seconds, row.lifetime_seconds),
+    }))
+}
+
+/// Time in market of every active session, and for the portfolio as the
+/// lifetime-weighted share across them.
+#[get("/portfolio/time-in-market")]
+async fn get_portfolio_time_in_market(pool: web::Data<PgPool>) -> Result<impl Responder, AppError> {
+    let sql = format!("{EXPOSURE_SELECT} WHERE s.status = 'active' ORDER BY s.created_at");
+    let sessions: Vec<TimeInMarket> = sqlx::query_as::<_, ExposureRow>(&sql)
+        .fetch_all(pool.get_ref())
+        .await?
+        .into_iter()
+        .map(TimeInMarket::from)
+        .collect();
+
+    let lifetime: f64 = sessions.iter().map(|s| s.lifetime_seconds).sum();
+    let exposed: f64 = sessions.iter().map(|s| s.exposed_seconds).sum();
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "time_in_market_pct": time_in_market_pct(exposed, lifetime),
+        "sessions": sessions,
+    })))
+}
+
+#[derive(serde::Serialize, sqlx::FromRow)]
+struct Snapshot {
+    equity: f64,
+    timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+#[get("/sessions/{id}/equity")]
+async fn get_equity_curve(
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+) -> Result<impl Responder, AppError> {
+    let id = path.into_inner();
+    let recs = sqlx::query_as::<_, Snapshot>(
+        "SELECT equity, timestamp FROM equity_snapshots WHERE session_id = $1 ORDER BY timestamp ASC",
+    )
+    .bind(id)
+    .fetch_all(pool.get_ref())
+    .await?;
+    Ok(HttpResponse::Ok().json(recs))
+}
+
+/// Push the session's equity and trade updates as JSON text messages
+/// (`session_id`, `equity`, `position`, `last_trade`) as the engine writes
+/// them.  A client too slow to keep up skips the updates it missed.
+#[get("/ws/sessions/{id}")]
+async fn session_socket(
+    req: HttpRequest,
+    body: web::Payload,
+    pool: web::Data<PgPool>,
+    events: web::Data<Arc<SessionEvents>>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let id = path.into_inner();
+    let exists: Option<Uuid> = sqlx::query_scalar("SELECT id FROM sessions WHERE id = $1")
+        .bind(id)
+        .fetch_optional(pool.get_ref())
+        .await?;
+    if exists.is_none() {
+        return Err(AppError::NotFound("Session not found".into()));
+    }
+
+    let (response, mut socket, mut incoming) =
+        actix_ws::handle(&req, body).map_err(|e| AppError::BadRequest(e.to_string()))?;
+    let mut updates = events.subscribe();
+    actix_web::rt::spawn(async move {
+        loop {
+            tokio::select! {
+                msg = incoming.next() => match msg {
+                    Some(Ok(actix_ws::Message::Ping(bytes))) => {
+                        if socket.pong(&bytes).await.is_err() {
+                            return;
+                        }
+                    }
+                    Some(Ok(actix_ws::Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => {}
+                },
+                update = updates.recv() => match update {
+                    Ok(event) if event.session_id == id => {
+                        let Ok(text) = serde_json::to_string(&event) else {
+                            continue;
+                        };
+                        if socket.text(text).await.is_err() {
+                            return;
+                        }
+                    }
+                    Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+            }
+        }
+        let _ = socket.close(None).await;
+    });
+    Ok(response)
+}
+
+#[derive(serde::Serialize)]
+struct PortfolioPoint {
+    timestamp: DateTime<Utc>,
+    total_equity: f64,
+}
+
+#[derive(serde::Serialize)]
+struct PortfolioReturnPoint {
+    timestamp: DateTime<Utc>,
+    return_pct: f64,
+}
+
+/// What a portfolio history charts: summed equity in the reporting currency,
+/// or the allocation-weighted return of the sessions in percent.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PortfolioBasis {
+    Equity,
+    Return,
+}
+
+impl PortfolioBasis {
+    fn parse(basis: Option<&str>) -> Result<Self, AppError> {
+        match basis.unwrap_or("equity") {
+            "equity" => Ok(Self::Equity),
+            "return" => Ok(Self::Return),
+            other => Err(AppError::BadRequest(format!(
+                "Unknown basis: {other}. Expected equity or return"
+            ))),
+        }
+    }
+
+    /// `portfolio_cache` column holding the value.
+    fn column(self) -> &'static str {
+        match self {
+            Self::Equity => "total_equity",
+            Self::Return => "return_pct",
+        }
+    }
+}
+
+/// One bucket of the candle aggregation.  OHLC values are nullable because
+/// `array_agg(...)[1]`, `MIN` and `MAX` yield NULL for a bucket without
+/// equity values.
+#[derive(sqlx::FromRow)]
+struct PortfolioCandleRow {
+    bucket_time: DateTime<Utc>,
+    open: Option<f64>,
+    high: Option<f64>,
+    low: Option<f64>,
+    close: Option<f64>,
+}
+
+struct PortfolioCandle {
+    time: DateTime<Utc>,
+    open: f64,
+    high: f64,
+    low: f64,
+    close: f64,
+}
+
+/// Drop buckets with any missing OHLC value rather than failing the request.
+fn complete_candles(rows: Vec<PortfolioCandleRow>) -> Vec<PortfolioCandle> {
+    rows.into_iter()
+        .filter_map(|r| {
+            Some(PortfolioCandle {
+                time: r.bucket_time,
+                open: r.open?,
+                high: r.high?,
+                low: r.low?,
+                close: r.close?,
+            })
+        })
+        .collect()
+}
+
+#[derive(serde::Deserialize)]
+struct PortfolioQuery {
+    range_days: Option<i64>,
+    interval: Option<String>,
+    style: Option<String>,
+    basis: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+struct CandleBar {
+    time: String,
+    open: f64,
+    high: f64,
+    low: f64,
     close: f64,
 }
 
-#[get("/portfolio/history")]
-async fn get_portfolio_history(
+/// Bucket width in seconds for a portfolio history interval; unknown values
+/// fall back to 15m.
+fn portfolio_step_seconds(interval: Option<&str>) -> i64 {
+    match interval.unwrap_or("15m") {
+        "1m" => 60,
+        "3m" => 180,
+        "5m" => 300,
+        "15m" => 900,
+        "30m" => 1800,
+        "1h" => 3600,
+        "4h" => 14400,
+        "12h" => 43200,
+        "1d" => 86400,
+        _ => 900,
+    }
+}
+
+/// Bucket width in seconds for a `portfolio_cache` query at `interval`.
+/// Without one, the finer of 15m and the cache resolution is used; an
+/// interval finer than the cache resolution is rejected.
+fn cache_step_seconds(interval: Option<&str>, resolution_secs: i64) -> Result<i64, AppError> {
+    let Some(interval) = interval else {
+        return Ok(portfolio_step_seconds(None).max(resolution_secs));
+    };
+    let step = portfolio_step_seconds(Some(interval));
+    if step < resolution_secs {
+        return Err(AppError::BadRequest(format!(
+            "interval {interval} is finer than the portfolio cache resolution of {resolution_secs}s"
+        )));
+    }
+    Ok(step)
+}
+
+/// Candles of the `basis` column per step from `start_ts`, up to `end_ts`
+/// (exclusive) when given.
+async fn fetch_portfolio_candles(
+    pool: &PgPool,
+    start_ts: DateTime<Utc>,
+    end_ts: Option<DateTime<Utc>>,
+    step_seconds: i64,
+    basis: PortfolioBasis,
+) -> Result<Vec<PortfolioCandle>, AppError> {
+    let col = basis.column();
+    let sql = format!(
+        r#"
+        SELECT
+            to_timestamp(floor(extract(epoch from timestamp) / $2) * $2) as bucket_time,
+            (array_agg({col} ORDER BY timestamp ASC))[1] as open,
+            MAX({col}) as high,
+            MIN({col}) as low,
+            (array_agg({col} ORDER BY timestamp DESC))[1] as close
+        FROM portfolio_cache
+        WHERE timestamp >= $1 AND ($3::timestamptz IS NULL OR timestamp < $3) AND {col} IS NOT NULL
+        GROUP BY 1
+        ORDER BY 1 ASC
+    "#
+    );
+
+    let rows = sqlx::query_as::<_, PortfolioCandleRow>(&sql)
+        .bind(start_ts)
+        .bind(step_seconds as f64)
+        .bind(end_ts)
+        .fetch_all(pool)
+        .await?;
+    Ok(complete_candles(rows))
+}
+
+/// `(timestamp, value)` of the `basis` column per step from `start_ts`, up
+/// to `end_ts` (exclusive) when given.
+async fn fetch_portfolio_line(
+    pool: &PgPool,
+    start_ts: DateTime<Utc>,
+    end_ts: Option<DateTime<Utc>>,
+    step_seconds: i64,
+    basis: PortfolioBasis,
+) -> Result<Vec<(DateTime<Utc>, f64)>, AppError> {
+    // One point per `floor(epoch / step) * step` bucket holding the last
+    // cached value before the bucket ends, as the candle close does.  Empty
+    // buckets carry the previous value forward so the line stays continuous.
+    let col = basis.column();
+    let sql = format!(
+        r#"
+        SELECT g.ts AS timestamp, c.{col}
+        FROM generate_series(
+            to_timestamp(floor(extract(epoch from $1::timestamptz) / $2) * $2),
+            NOW(),
+            make_interval(secs => $2)
+        ) AS g(ts)
+        CROSS JOIN LATERAL (
+            SELECT {col}
+            FROM portfolio_cache
+            WHERE timestamp < g.ts + make_interval(secs => $2) AND {col} IS NOT NULL
+            ORDER BY timestamp DESC
+            LIMIT 1
+        ) c
+        WHERE $3::timestamptz IS NULL OR g.ts < $3
+        ORDER BY g.ts ASC
+    "#
+    );
+
+    Ok(sqlx::query_as::<_, (DateTime<Utc>, f64)>(&sql)
+        .bind(start_ts)
+        .bind(step_seconds as f64)
+        .bind(end_ts)
+        .fetch_all(pool)
+        .await?)
+}
+
+#[get("/portfolio/history")]
+async fn get_portfolio_history(
+    pool: web::Data<PgPool>,
+    portfolio: web::Data<Arc<PortfolioManager>>,
+    query: web::Query<PortfolioQuery>,
+) -> Result<impl Responder, AppError> {
+    let range_days = query.range_days.unwrap_or(7).max(1);
+    let style = query.style.as_deref().unwrap_or("line");
+    let basis = PortfolioBasis::parse(query.basis.as_deref())?;
+    let step_seconds = cache_step_seconds(query.interval.as_deref(), portfolio.resolution_secs())?;
+    let start_ts = Utc::now() - chrono::Duration::days(range_days);
+
+    if style == "candle" {
+        let candles: Vec<CandleBar> =
+            fetch_portfolio_candles(pool.get_ref(), start_ts, None, step_seconds, basis)
+                .await?
+                .into_iter()
+                .map(|c| CandleBar {
+                    time: c.time.to_rfc3339(),
+                    open: c.open,
+                    high: c.high,
+                    low: c.low,
+                    close: c.close,
+                })
+                .collect();
+
+        return Ok(HttpResponse::Ok().json(candles));
+    }
+
+    let points = fetch_portfolio_line(pool.get_ref(), start_ts, None, step_seconds, basis).await?;
+    Ok(match basis {
+        PortfolioBasis::Equity => HttpResponse::Ok().json(
+            points
+                .into_iter()
+                .map(|(timestamp, total_equity)| PortfolioPoint {
+                    timestamp,
+                    total_equity,
+                })
+                .collect::<Vec<_>>(),
+        ),
+        PortfolioBasis::Return => HttpResponse::Ok().json(
+            points
+                .into_iter()
+                .map(|(timestamp, return_pct)| PortfolioReturnPoint {
+                    timestamp,
+                    return_pct,
+                })
+                .collect::<Vec<_>>(),
+        ),
+    })
+}
+
+#[derive(serde::Deserialize)]
+struct CorrelationQuery {
+    range_days: Option<i64>,
+    /// Bucket width, e.g. `1m` for the minute grid.
+    resolution: Option<String>,
+    /// Former name of `resolution`.
+    interval: Option<String>,
+}
+
+/// Buckets a correlation window may span: a week of minutes.
+const MAX_CORRELATION_BUCKETS: i64 = 10_080;
+
+#[derive(sqlx::FromRow)]
+struct BucketedEquity {
+    session_id: Uuid,
+    bucket: i64,
+    equity: f64,
+    taken_at: DateTime<Utc>,
+}
+
+/// Each active session's last flow-neutral equity per `step_seconds` bucket
+/// since `start_ts`, by session, so deposits and withdrawals do not count as
+/// returns.  Sessions without snapshots in the window are left out.
+async fn active_bucketed_equity(
+    pool: &PgPool,
+    start_ts: DateTime<Utc>,
+    step_seconds: i64,
+) -> Result<BTreeMap<Uuid, BTreeMap<i64, f64>>, AppError> {
+    let rows = sqlx::query_as::<_, BucketedEquity>(
+        r#"
+        SELECT
+            e.session_id,
+            (floor(extract(epoch from e.timestamp) / $2) * $2)::BIGINT AS bucket,
+            (array_agg(e.equity ORDER BY e.timestamp DESC))[1] AS equity,
+            MAX(e.timestamp) AS taken_at
+        FROM equity_snapshots e
+        JOIN sessions s ON s.id = e.session_id
+        WHERE s.status = 'active' AND e.timestamp >= $1
+        GROUP BY 1, 2
+        ORDER BY 1, 2
+        "#,
+    )
+    .bind(start_ts)
+    .bind(step_seconds as f64)
+    .fetch_all(pool)
+    .await?;
+
+    let flows = capital_flows::load_all(pool).await?;
+    let mut by_session: BTreeMap<Uuid, BTreeMap<i64, f64>> = BTreeMap::new();
+    for row in rows {
+        let equity = match flows.get(&row.session_id) {
+            Some(flows) => flows.neutral_equity(row.equity, row.taken_at),
+            None => row.equity,
+        };
+        by_session
+            .entry(row.session_id)
+            .or_default()
+            .insert(row.bucket, equity);
+    }
+    Ok(by_session)
+}
+
+/// Correlation of every pair of active sessions' equity returns over
+/// `range_days`, bucketed at `resolution` (default `1h`).  Every bucket of
+/// the window is on the grid, so sessions are compared over the same
+/// wall-clock steps whenever either snapshots.  The matrix is returned in
+/// session order and keyed by session id.
+#[get("/portfolio/correlation")]
+async fn get_portfolio_correlation(
+    pool: web::Data<PgPool>,
+    query: web::Query<CorrelationQuery>,
+) -> Result<impl Responder, AppError> {
+    let range_days = query.range_days.unwrap_or(7).max(1);
+    let resolution = query
+        .resolution
+        .as_deref()
+        .or(query.interval.as_deref())
+        .unwrap_or("1h");
+    let step_seconds = portfolio_step_seconds(Some(resolution));
+    let buckets = range_days * 86_400 / step_seconds;
+    if buckets > MAX_CORRELATION_BUCKETS {
+        return Err(AppError::BadRequest(format!(
+            "range_days {range_days} spans {buckets} {resolution} buckets; at most {MAX_CORRELATION_BUCKETS} are allowed"
+        )));
+    }
+    let now = Utc::now();
+    let start_ts = now - chrono::Duration::days(range_days);
+
+    let mut by_session = active_bucketed_equity(pool.get_ref(), start_ts, step_seconds).await?;
+    let active: Vec<Uuid> = sqlx::query_scalar("SELECT id FROM sessions WHERE status = 'active'")
+        .fetch_all(pool.get_ref())
+        .await?;
+    for id in active {
+        by_session.entry(id).or_default();
+    }
+
+    let grid = correlation::regular_grid(start_ts.timestamp(), now.timestamp(), step_seconds);
+    let returns: Vec<Vec<Option<f64>>> = by_session
+        .values()
+        .map(|points| correlation::bucket_returns(points, &grid))
+        .collect();
+    let ids: Vec<Uuid> = by_session.into_keys().collect();
+    let matrix = correlation::correlation_matrix(&returns);
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "by_session": correlation::keyed_matrix(&ids, matrix.clone()),
+        "sessions": ids,
+        "matrix": matrix,
+    })))
+}
+
+#[derive(serde::Deserialize)]
+struct DrawdownQuery {
+    range_days: Option<i64>,
+    min_depth_pct: Option<f64>,
+}
+
+#[get("/portfolio/drawdowns")]
+async fn get_portfolio_drawdowns(
     pool: web::Data<PgPool>,
-    query: web::Query<PortfolioQuery>,
+    query: web::Query<DrawdownQuery>,
 ) -> Result<impl Responder, AppError> {
-    let range_days = query.range_days.unwrap_or(7).max(1);
-    let style = query.style.as_deref().unwrap_or("line");
+    let min_depth_pct = query.min_depth_pct.unwrap_or(1.0).max(0.0);
+    let start_ts: Option<DateTime<Utc>> = query
+        .range_days
+        .map(|days| Utc::now() - chrono::Duration::days(days.max(1)));
 
-    let step_seconds = match query.interval.as_deref().unwrap_or("15m") {
-        "1m" => 60,
-        "3m" => 180,
-        "5m" => 300,
-        "15m" => 900,
-        "30m" => 1800,
-        "1h" => 3600,
-        "4h" => 14400,
-        "12h" => 43200,
-        "1d" => 86400,
-        _ => 900,
-    };
+    // Growth of 100 at the cached weighted return, which unlike total
+    // equity does not fall on a withdrawal.
+    let curve: Vec<(DateTime<Utc>, f64)> = sqlx::query_as(
+        "SELECT timestamp, 100 + return_pct FROM portfolio_cache WHERE ($1::timestamptz IS NULL OR timestamp >= $1) AND return_pct IS NOT NULL ORDER BY timestamp ASC",
+    )
+    .bind(start_ts)
+    .fetch_all(pool.get_ref())
+    .await?;
 
-    let start_ts = Utc::now() - chrono::Duration::days(range_days);
+    Ok(HttpResponse::Ok().json(drawdown::drawdown_episodes(&curve, min_depth_pct)))
+}
 
-    if style == "candle" {
-        let sql = r#"
-            SELECT
-                to_timestamp(floor(extract(epoch from timestamp) / $2) * $2) as bucket_time,
-                (array_agg(total_equity ORDER BY timestamp ASC))[1] as open,
-                MAX(total_equity) as high,
-                MIN(total_equity) as low,
-                (array_agg(total_equity ORDER BY timestamp DESC))[1] as close
-            FROM portfolio_cache
-            WHERE timestamp >= $1
-            GROUP BY 1
-            ORDER BY 1 ASC
-        "#;
-
-        let recs = sqlx::query_as::<_, PortfolioCandle>(sql)
-            .bind(start_ts)
-            .bind(step_seconds as f64)
-            .fetch_all(pool.get_ref())
-            .await?;
-
-        let candles: Vec<CandleBar> = recs
-            .into_iter()
-            .map(|c| CandleBar {
-                time: c.time.to_rfc3339(),
-                open: c.open,
-                high: c.high,
-                low: c.low,
-                close: c.close,
-            })
-            .collect();
+#[derive(serde::Deserialize)]
+struct PortfolioStatsQuery {
+    range_days: Option<i64>,
+    interval: Option<String>,
+}
 
-        return Ok(HttpResponse::Ok().json(candles));
+#[derive(serde::Serialize)]
+struct PortfolioStats {
+    current_equity: Option<f64>,
+    /// Highest total equity in the whole cache, whatever the window.
+    all_time_high: Option<f64>,
+    /// Decline of the return index below its all-time high, in percent.
+    current_drawdown_pct: Option<f64>,
+    /// Deepest peak-to-trough decline of the return index within the
+    /// window, in percent.
+    max_drawdown_pct: f64,
+    /// Annualised standard deviation of per-interval returns, in percent.
+    volatility_pct: Option<f64>,
+    sharpe_ratio: Option<f64>,
+    /// Interval points the window figures were computed from.
+    points: usize,
+}
+
+/// Growth of 1 at a cached `return_pct`.
+fn return_index(return_pct: f64) -> f64 {
+    1.0 + return_pct / 100.0
+}
+
+/// Account-level figures from the window's bucketed `returns`, the latest
+/// cached equity and return and the cache's all-time highs of both.
+///
+/// Drawdowns and per-interval returns are taken from the return index, the
+/// growth of 1 at each cached `return_pct`, rather than from total equity,
+/// so deposits and withdrawals do not count as gains or losses.  The window
+/// figures use the last point of each interval; the current drawdown uses
+/// the newest cached row against the highest in the whole cache.
+fn portfolio_stats(
+    returns: &[f64],
+    current_equity: Option<f64>,
+    all_time_high: Option<f64>,
+    current_return_pct: Option<f64>,
+    peak_return_pct: Option<f64>,
+    periods_per_year: f64,
+) -> PortfolioStats {
+    let index: Vec<f64> = returns.iter().map(|r| return_index(*r)).collect();
+    let current_drawdown_pct = match (current_return_pct, peak_return_pct) {
+        (Some(current), Some(peak)) if return_index(peak) > 0.0 => {
+            Some(((1.0 - return_index(current) / return_index(peak)) * 100.0).max(0.0))
+        }
+        _ => None,
+    };
+    PortfolioStats {
+        current_equity,
+        all_time_high,
+        current_drawdown_pct,
+        max_drawdown_pct: drawdown::max_drawdown_pct(index.iter().copied()),
+        volatility_pct: risk_metrics::annualized_volatility(&index, periods_per_year)
+            .map(|vol| vol * 100.0),
+        sharpe_ratio: risk_metrics::sharpe_ratio(&index, periods_per_year),
+        points: index.len(),
     }
+}
 
-    let sql = r#"
-        SELECT timestamp, total_equity
+/// Drawdown and risk summary of the portfolio equity curve, over the same
+/// `range_days` and `interval` buckets as `/portfolio/history`.
+#[get("/portfolio/stats")]
+async fn get_portfolio_stats(
+    pool: web::Data<PgPool>,
+    portfolio: web::Data<Arc<PortfolioManager>>,
+    query: web::Query<PortfolioStatsQuery>,
+) -> Result<impl Responder, AppError> {
+    let range_days = query.range_days.unwrap_or(7).max(1);
+    let step_seconds = cache_step_seconds(query.interval.as_deref(), portfolio.resolution_secs())?;
+    let start_ts = Utc::now() - chrono::Duration::days(range_days);
+
+    let returns: Vec<f64> = fetch_portfolio_line(
+        pool.get_ref(),
+        start_ts,
+        None,
+        step_seconds,
+        PortfolioBasis::Return,
+    )
+    .await?
+    .into_iter()
+    .map(|(_, return_pct)| return_pct)
+    .collect();
+    let (current_equity, all_time_high, current_return_pct, peak_return_pct): (
+        Option<f64>,
+        Option<f64>,
+        Option<f64>,
+        Option<f64>,
+    ) = sqlx::query_as(
+        r#"
+        SELECT
+            (SELECT total_equity FROM portfolio_cache
+             WHERE total_equity IS NOT NULL ORDER BY timestamp DESC LIMIT 1),
+            MAX(total_equity),
+            (SELECT return_pct FROM portfolio_cache
+             WHERE return_pct IS NOT NULL ORDER BY timestamp DESC LIMIT 1),
+            MAX(return_pct)
         FROM portfolio_cache
-        WHERE timestamp >= $1
-        AND CAST(EXTRACT(EPOCH FROM timestamp) AS INTEGER) % $2 = 0
-        ORDER BY timestamp ASC
-    "#;
+        "#,
+    )
+    .fetch_one(pool.get_ref())
+    .await?;
 
-    let recs = sqlx::query_as::<_, PortfolioPoint>(sql)
-        .bind(start_ts)
-        .bind(step_seconds)
-        .fetch_all(pool.get_ref())
+    let periods_per_year = 365.0 * 86_400.0 / step_seconds as f64;
+    Ok(HttpResponse::Ok().json(portfolio_stats(
+        &returns,
+        current_equity,
+        all_time_high,
+        current_return_pct,
+        peak_return_pct,
+        periods_per_year,
+    )))
+}
+
+/// Rows read per query while streaming a CSV export.
+const CSV_PAGE: i64 = 1000;
+
+/// CSV download of `header` followed by `rows`, streamed so a long history
+/// is never held in memory whole.  Times are written as Unix seconds, which
+/// both TradingView and spreadsheet imports accept without a format hint.
+fn csv_response<S>(filename: &str, header: &'static str, rows: S) -> HttpResponse
+where
+    S: futures::Stream<Item = Result<web::Bytes, actix_web::Error>> + 'static,
+{
+    let header =
+        futures::stream::once(async move { Ok(web::Bytes::from_static(header.as_bytes())) });
+    HttpResponse::Ok()
+        .content_type("text/csv; charset=utf-8")
+        .insert_header((
+            "Content-Disposition",
+            format!("attachment; filename=\"{filename}\""),
+        ))
+        .streaming(header.chain(rows))
+}
+
+/// `Session not found` unless session `id` exists.
+async fn ensure_session_exists(pool: &PgPool, id: Uuid) -> Result<(), AppError> {
+    let exists: Option<Uuid> = sqlx::query_scalar("SELECT id FROM sessions WHERE id = $1")
+        .bind(id)
+        .fetch_optional(pool)
         .await?;
+    match exists {
+        Some(_) => Ok(()),
+        None => Err(AppError::NotFound("Session not found".into())),
+    }
+}
 
-    Ok(HttpResponse::Ok().json(recs))
+/// A session's equity snapshots as CSV, oldest first.
+#[get("/sessions/{id}/equity.csv")]
+async fn export_equity_csv(
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+) -> Result<impl Responder, AppError> {
+    let id = path.into_inner();
+    ensure_session_exists(pool.get_ref(), id).await?;
+
+    let pool = pool.get_ref().clone();
+    // State is the last row written, or `None` once the final page is out.
+    let pages = futures::stream::try_unfold(Some(None), move |cursor| {
+        let pool = pool.clone();
+        async move {
+            let Some(after) = cursor else {
+                return Ok::<_, actix_web::Error>(None);
+            };
+            let page = equity_page(&pool, id, after).await?;
+            let Some(&(last_id, last_ts, _)) = page.last() else {
+                return Ok(None);
+            };
+            let next = (page.len() as i64 == CSV_PAGE).then_some(Some((last_ts, last_id)));
+            let body: String = page
+                .iter()
+                .map(|(_, ts, equity)| format!("{},{}\n", ts.timestamp(), equity))
+                .collect();
+            Ok(Some((web::Bytes::from(body), next)))
+        }
+    });
+    Ok(csv_response(
+        &format!("equity-{id}.csv"),
+        "time,value\n",
+        pages,
+    ))
+}
+
+/// Up to [`CSV_PAGE`] of the session's `(id, timestamp, equity)` snapshots
+/// following `after` (timestamp and id of the last row read) in time order.
+async fn equity_page(
+    pool: &PgPool,
+    session_id: Uuid,
+    after: Option<(DateTime<Utc>, Uuid)>,
+) -> Result<Vec<(Uuid, DateTime<Utc>, f64)>, AppError> {
+    Ok(sqlx::query_as(
+        "SELECT id, timestamp, equity FROM equity_snapshots WHERE session_id = $1 AND ($2::timestamptz IS NULL OR (timestamp, id) > ($2, $3)) ORDER BY timestamp, id LIMIT $4",
+    )
+    .bind(session_id)
+    .bind(after.map(|(ts, _)| ts))
+    .bind(after.map(|(_, id)| id))
+    .bind(CSV_PAGE)
+    .fetch_all(pool)
+    .await?)
+}
+
+/// A session's trades as CSV, oldest first.
+#[get("/sessions/{id}/trades.csv")]
+async fn export_trades_csv(
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+) -> Result<impl Responder, AppError> {
+    let id = path.into_inner();
+    ensure_session_exists(pool.get_ref(), id).await?;
+
+    let pool = pool.get_ref().clone();
+    // State is the last row written, or `None` once the final page is out.
+    let pages = futures::stream::try_unfold(Some(None), move |cursor| {
+        let pool = pool.clone();
+        async move {
+            let Some(after) = cursor else {
+                return Ok::<_, actix_web::Error>(None);
+            };
+            let page = trades_page(&pool, id, after).await?;
+            let Some(last) = page.last() else {
+                return Ok(None);
+            };
+            let next = (page.len() as i64 == CSV_PAGE).then_some(Some((last.timestamp, last.id)));
+            let body: String = page.iter().map(trade_csv_row).collect();
+            Ok(Some((web::Bytes::from(body), next)))
+        }
+    });
+    Ok(csv_response(
+        &format!("trades-{id}.csv"),
+        TRADES_CSV_HEADER,
+        pages,
+    ))
+}
+
+/// Up to [`CSV_PAGE`] of the session's trades following `after`
+/// (timestamp and id of the last row read) in time order.
+async fn trades_page(
+    pool: &PgPool,
+    session_id: Uuid,
+    after: Option<(DateTime<Utc>, Uuid)>,
+) -> Result<Vec<Trade>, AppError> {
+    Ok(sqlx::query_as::<_, Trade>(
+        "SELECT * FROM trades WHERE session_id = $1 AND ($2::timestamptz IS NULL OR (timestamp, id) > ($2, $3)) ORDER BY timestamp, id LIMIT $4",
+    )
+    .bind(session_id)
+    .bind(after.map(|(ts, _)| ts))
+    .bind(after.map(|(_, id)| id))
+    .bind(CSV_PAGE)
+    .fetch_all(pool)
+    .await?)
+}
+
+const TRADES_CSV_HEADER: &str = "id,timestamp,symbol,side,price,quantity,pnl,reason\n";
+
+/// One `trades.csv` line.  The timestamp is Unix seconds like the other CSV
+/// exports; an absent `pnl` or `reason` is an empty field.
+fn trade_csv_row(t: &Trade) -> String {
+    format!(
+        "{},{},{},{},{},{},{},{}\n",
+        t.id,
+        t.timestamp.timestamp(),
+        csv_field(&t.symbol),
+        csv_field(&t.side),
+        t.price,
+        t.quantity,
+        t.pnl.map(|p| p.to_string()).unwrap_or_default(),
+        csv_field(t.reason.as_deref().unwrap_or("")),
+    )
+}
+
+/// `value` quoted when it holds a comma, quote or line break (basket
+/// symbols, free-text reasons).
+fn csv_field(value: &str) -> std::borrow::Cow<'_, str> {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\"")).into()
+    } else {
+        value.into()
+    }
+}
+
+/// Portfolio history as CSV, read [`CSV_PAGE`] buckets at a time.
+#[get("/portfolio/history.csv")]
+async fn export_portfolio_history_csv(
+    pool: web::Data<PgPool>,
+    portfolio: web::Data<Arc<PortfolioManager>>,
+    query: web::Query<PortfolioQuery>,
+) -> Result<impl Responder, AppError> {
+    let range_days = query.range_days.unwrap_or(7).max(1);
+    let basis = PortfolioBasis::parse(query.basis.as_deref())?;
+    let step_seconds = cache_step_seconds(query.interval.as_deref(), portfolio.resolution_secs())?;
+    let start_ts = Utc::now() - chrono::Duration::days(range_days);
+    let candles = query.style.as_deref() == Some("candle");
+
+    // Pages end on bucket boundaries, so no bucket is split across two.
+    let first_bucket = start_ts.timestamp().div_euclid(step_seconds) * step_seconds;
+    let page_span = chrono::Duration::seconds(step_seconds * CSV_PAGE);
+    let first_end = DateTime::from_timestamp(first_bucket, 0).unwrap_or(start_ts) + page_span;
+
+    let pool = pool.get_ref().clone();
+    // State is the page's `[start, end)`, or `None` once past the present.
+    let pages = futures::stream::try_unfold(Some((start_ts, first_end)), move |page| {
+        let pool = pool.clone();
+        async move {
+            let Some((start, end)) = page.filter(|(start, _)| *start <= Utc::now()) else {
+                return Ok::<_, actix_web::Error>(None);
+            };
+            let body: String = if candles {
+                fetch_portfolio_candles(&pool, start, Some(end), step_seconds, basis)
+                    .await?
+                    .iter()
+                    .map(|c| {
+                        format!(
+                            "{},{},{},{},{}\n",
+                            c.time.timestamp(),
+                            c.open,
+                            c.high,
+                            c.low,
+                            c.close
+                        )
+                    })
+                    .collect()
+            } else {
+                fetch_portfolio_line(&pool, start, Some(end), step_seconds, basis)
+                    .await?
+                    .iter()
+                    .map(|(timestamp, value)| format!("{},{}\n", timestamp.timestamp(), value))
+                    .collect()
+            };
+            Ok(Some((web::Bytes::from(body), Some((end, end + page_span)))))
+        }
+    });
+    let header = if candles {
+        "time,open,high,low,close\n"
+    } else {
+        "time,value\n"
+    };
+    Ok(csv_response("portfolio-equity.csv", header, pages))
+}
+
+#[post("/sessions/{id}/compare-params")]
+async fn compare_session_params(
+    pool: web::Data<PgPool>,
+    market: web::Data<Arc<MarketDataService>>,
+    path: web::Path<Uuid>,
+    body: web::Json<CompareParamsRequest>,
+) -> Result<impl Responder, AppError> {
+    let id = path.into_inner();
+    let req = body.into_inner();
+
+    let session = sqlx::query_as::<_, Session>("SELECT * FROM sessions WHERE id = $1")
+        .bind(id)
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| AppError::NotFound("Session not found".into()))?;
+    let strategy_type: String =
+        sqlx::query_scalar("SELECT strategy_type FROM strategies WHERE id = $1")
+            .bind(session.strategy_id)
+            .fetch_optional(pool.get_ref())
+            .await?
+            .ok_or_else(|| AppError::NotFound("Strategy not found".into()))?;
+
+    let comparison =
+        replay::compare_params(market.get_ref(), &session, &strategy_type, &req.a, &req.b).await?;
+    Ok(HttpResponse::Ok().json(comparison))
 }
 
 #[get("/sessions/{id}/candles")]
@@ -387,9 +2342,15 @@ async fn get_session_candles(
         .fetch_one(pool.get_ref())
         .await?;
 
-    let raw = market
-        .fetch_candles_vec(&session.symbol, &session.interval, 300)
-        .await?;
+    let raw = if session.is_basket() {
+        market
+            .fetch_basket_candles_vec(&session.basket_symbols(), &session.interval, 300)
+            .await?
+    } else {
+        market
+            .fetch_candles_vec(&session.symbol, &session.interval, 300)
+            .await?
+    };
 
     let candles: Vec<CandleBar> = raw
         .into_iter()
@@ -407,18 +2368,540 @@ async fn get_session_candles(
     Ok(HttpResponse::Ok().json(candles))
 }
 
+/// Bars each point of `/sessions/{id}/beta`'s rolling series covers.
+const ROLLING_BETA_BARS: usize = 30;
+
+/// Most benchmark candles one beta request may fetch.
+const MAX_BETA_BARS: i64 = 5000;
+
+/// Longest lookback [`parse_window`] accepts.
+const MAX_WINDOW_SECS: i64 = 10 * 365 * 86_400;
+
+#[derive(serde::Deserialize)]
+struct BetaQuery {
+    benchmark: Option<String>,
+    window: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+struct BetaPoint {
+    time: DateTime<Utc>,
+    beta: Option<f64>,
+}
+
+/// Beta of a session's flow-neutral equity returns (see
+/// [`capital_flows`]) on a benchmark's price returns over
+/// the trailing `window`, on the session's bar interval, with the beta over
+/// every trailing [`ROLLING_BETA_BARS`] bars.  A beta near 1 with a high
+/// correlation means the session mostly holds levered benchmark exposure.
+#[get("/sessions/{id}/beta")]
+async fn get_session_beta(
+    pool: web::Data<PgPool>,
+    market: web::Data<Arc<MarketDataService>>,
+    path: web::Path<Uuid>,
+    query: web::Query<BetaQuery>,
+) -> Result<impl Responder, AppError> {
+    let id = path.into_inner();
+    let window_arg = query.window.as_deref().unwrap_or("30d");
+    let window = parse_window(window_arg).ok_or_else(|| {
+        AppError::BadRequest(format!(
+            "Invalid window {window_arg}; use a count of hours, days or weeks like 12h, 30d or 8w"
+        ))
+    })?;
+    let session = sqlx::query_as::<_, Session>("SELECT * FROM sessions WHERE id = $1")
+        .bind(id)
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| AppError::NotFound("Session not found".into()))?;
+    let step = interval::seconds(&session.interval)
+        .ok_or_else(|| AppError::Data(format!("Unknown interval {}", session.interval)))?;
+    if window.num_seconds() / step > MAX_BETA_BARS {
+        return Err(AppError::BadRequest(format!(
+            "Window {window_arg} spans more than {MAX_BETA_BARS} {} bars",
+            session.interval
+        )));
+    }
+
+    let benchmark = market.resolve_symbol(query.benchmark.as_deref().unwrap_or("BTCUSDT"));
+    let end = Utc::now();
+    let start = end - window;
+    let candles = market
+        .fetch_candles_range_vec(
+            &benchmark,
+            &session.interval,
+            start.timestamp_millis(),
+            end.timestamp_millis(),
+        )
+        .await?;
+    let grid: Vec<i64> = candles.iter().map(|c| c.time / 1000).collect();
+    let closes: BTreeMap<i64, f64> = candles.iter().map(|c| (c.time / 1000, c.close)).collect();
+
+    let snapshots = sqlx::query_as::<_, Snapshot>(
+        "SELECT equity, timestamp FROM equity_snapshots WHERE session_id = $1 AND timestamp >= $2 ORDER BY timestamp ASC",
+    )
+    .bind(id)
+    .bind(start)
+    .fetch_all(pool.get_ref())
+    .await?;
+    let flows = capital_flows::load(pool.get_ref(), id).await?;
+    // Last flow-neutral equity within each benchmark bar.
+    let mut equity: BTreeMap<i64, f64> = BTreeMap::new();
+    for snap in snapshots {
+        let bar = grid.partition_point(|t| *t <= snap.timestamp.timestamp());
+        if bar > 0 {
+            equity.insert(
+                grid[bar - 1],
+                flows.neutral_equity(snap.equity, snap.timestamp),
+            );
+        }
+    }
+
+    let session_returns = correlation::bucket_returns(&equity, &grid);
+    let benchmark_returns = correlation::bucket_returns(&closes, &grid);
+    let rolling: Vec<BetaPoint> = grid
+        .iter()
+        .skip(ROLLING_BETA_BARS - 1)
+        .zip(correlation::rolling_beta(
+            &session_returns,
+            &benchmark_returns,
+            ROLLING_BETA_BARS,
+        ))
+        .map(|(t, beta)| BetaPoint {
+            time: DateTime::from_timestamp(*t, 0).unwrap_or(end),
+            beta,
+        })
+        .collect();
+    let observations = session_returns
+        .iter()
+        .zip(&benchmark_returns)
+        .filter(|(a, b)| a.is_some() && b.is_some())
+        .count();
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "session_id": id,
+        "benchmark": benchmark,
+        "interval": session.interval,
+        "start": start,
+        "observations": observations,
+        "beta": correlation::beta(&session_returns, &benchmark_returns),
+        "correlation": correlation::pearson(&session_returns, &benchmark_returns),
+        "rolling": rolling,
+    })))
+}
+
+#[derive(serde::Deserialize)]
+struct ExplainBarQuery {
+    /// Any instant within the bar to trace.
+    time: DateTime<Utc>,
+}
+
+/// Recompute the session's decision on the bar open at `time`: candles,
+/// signals, confirmation and edge windows, prior position and planned trades.
+#[get("/sessions/{id}/explain-bar")]
+async fn explain_session_bar(
+    pool: web::Data<PgPool>,
+    market: web::Data<Arc<MarketDataService>>,
+    path: web::Path<Uuid>,
+    query: web::Query<ExplainBarQuery>,
+) -> Result<impl Responder, AppError> {
+    let session = sqlx::query_as::<_, Session>("SELECT * FROM sessions WHERE id = $1")
+        .bind(path.into_inner())
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| AppError::NotFound("Session not found".into()))?;
+    let trace = bar_explain::explain_bar(pool.get_ref(), &market, &session, query.time).await?;
+    Ok(HttpResponse::Ok().json(trace))
+}
+
+/// Parse a lookback like `12h`, `30d` or `8w`.
+fn parse_window(window: &str) -> Option<chrono::Duration> {
+    let unit = window.chars().last()?;
+    let count: i64 = window[..window.len() - unit.len_utf8()].parse().ok()?;
+    let unit_secs = match unit {
+        'h' => 3600,
+        'd' => 86_400,
+        'w' => 604_800,
+        _ => return None,
+    };
+    count
+        .checked_mul(unit_secs)
+        .filter(|secs| (1..=MAX_WINDOW_SECS).contains(secs))
+        .map(chrono::Duration::seconds)
+}
+
+#[derive(serde::Serialize, sqlx::FromRow)]
+struct SnapshotCount {
+    session_id: Uuid,
+    snapshots: i64,
+    oldest: DateTime<Utc>,
+    newest: DateTime<Utc>,
+}
+
+#[get("/admin/snapshot-counts")]
+async fn get_snapshot_counts(pool: web::Data<PgPool>) -> Result<impl Responder, AppError> {
+    let recs = sqlx::query_as::<_, SnapshotCount>(
+        r#"
+        SELECT session_id, COUNT(*) AS snapshots, MIN(timestamp) AS oldest, MAX(timestamp) AS newest
+        FROM equity_snapshots
+        GROUP BY session_id
+        ORDER BY snapshots DESC
+        "#,
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+    Ok(HttpResponse::Ok().json(recs))
+}
+
+/// Rebuild the portfolio cache in the background after a bulk change.  Bursts
+/// of requests are coalesced by [`PortfolioManager::request_rebuild`].
+fn schedule_cache_rebuild(portfolio: &Arc<PortfolioManager>) {
+    let portfolio = portfolio.clone();
+    tokio::spawn(async move {
+        if let Err(e) = portfolio.request_rebuild().await {
+            tracing::error!("Portfolio cache rebuild failed: {}", e);
+        }
+    });
+}
+
+#[get("/portfolio/cache-status")]
+async fn get_portfolio_cache_status(portfolio: web::Data<Arc<PortfolioManager>>) -> impl Responder {
+    HttpResponse::Ok().json(portfolio.cache_status())
+}
+
+#[derive(serde::Serialize)]
+struct EngineStatusResponse {
+    #[serde(flatten)]
+    engine: EngineStatusReport,
+    candle_cache: CandleCacheStats,
+}
+
+/// Liveness: answers as long as the process serves requests.
+#[get("/health")]
+async fn health() -> impl Responder {
+    HttpResponse::Ok().json(serde_json::json!({ "status": "ok" }))
+}
+
+/// Readiness: Postgres answers, and the engine's stream is connected
+/// whenever there are active sessions to stream for.  Otherwise `503`,
+/// with `failing` naming each dependency that is down.
+#[get("/ready")]
+async fn ready(pool: web::Data<PgPool>, status: web::Data<Arc<EngineStatus>>) -> impl Responder {
+    let stream = status.stream_health();
+    // Doubles as the database ping.
+    let streaming: Result<bool, sqlx::Error> =
+        sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM sessions WHERE status = 'active')")
+            .fetch_one(pool.get_ref())
+            .await;
+
+    let mut failing = Vec::new();
+    let database = match &streaming {
+        Ok(_) => "ok".to_string(),
+        Err(e) => {
+            failing.push("database");
+            e.to_string()
+        }
+    };
+    if matches!(streaming, Ok(true)) && !stream.connected {
+        failing.push("stream");
+    }
+
+    let body = serde_json::json!({
+        "status": if failing.is_empty() { "ready" } else { "not_ready" },
+        "failing": failing,
+        "database": database,
+        "stream": stream,
+    });
+    if failing.is_empty() {
+        HttpResponse::Ok().json(body)
+    } else {
+        HttpResponse::ServiceUnavailable().json(body)
+    }
+}
+
+#[get("/engine/status")]
+async fn get_engine_status(
+    status: web::Data<Arc<EngineStatus>>,
+    market: web::Data<Arc<MarketDataService>>,
+) -> impl Responder {
+    HttpResponse::Ok().json(EngineStatusResponse {
+        engine: status.report(),
+        candle_cache: market.candle_cache_stats(),
+    })
+}
+
+#[post("/engine/pause")]
+async fn pause_engine(status: web::Data<Arc<EngineStatus>>) -> impl Responder {
+    if status.pause_trading() {
+        tracing::warn!("Trading paused");
+    }
+    HttpResponse::Ok().json(status.report())
+}
+
+#[post("/engine/resume")]
+async fn resume_engine(status: web::Data<Arc<EngineStatus>>) -> impl Responder {
+    if status.resume_trading() {
+        tracing::info!("Trading resumed");
+    }
+    HttpResponse::Ok().json(status.report())
+}
+
+#[derive(serde::Deserialize)]
+struct DiagnosticsQuery {
+    kind: Option<DiagnosticKind>,
+}
+
+#[derive(serde::Serialize, sqlx::FromRow)]
+struct SessionErrorRow {
+    session_id: Uuid,
+    status: String,
+    last_error: String,
+    last_update: DateTime<Utc>,
+}
+
+#[get("/diagnostics")]
+async fn get_diagnostics(
+    pool: web::Data<PgPool>,
+    diagnostics: web::Data<Arc<Diagnostics>>,
+    query: web::Query<DiagnosticsQuery>,
+) -> Result<impl Responder, AppError> {
+    let session_errors = sqlx::query_as::<_, SessionErrorRow>(
+        "SELECT id AS session_id, status, last_error, last_update FROM sessions WHERE last_error IS NOT NULL ORDER BY last_update DESC",
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "events": diagnostics.recent(query.kind),
+        "session_errors": session_errors,
+    })))
+}
+
+/// Prometheus scrape target.  Gauges read from the database or the cache
+/// state are refreshed here; counters are kept by the services.
+#[get("/metrics")]
+async fn get_metrics(
+    pool: web::Data<PgPool>,
+    metrics: web::Data<Arc<Metrics>>,
+    portfolio: web::Data<Arc<PortfolioManager>>,
+) -> Result<impl Responder, AppError> {
+    let active: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM sessions WHERE status = 'active'")
+        .fetch_one(pool.get_ref())
+        .await?;
+    metrics.active_sessions.set(active);
+    if let Some(at) = portfolio.cache_status().last_rebuild_at {
+        let age = Utc::now().signed_duration_since(at);
+        metrics
+            .portfolio_cache_age_seconds
+            .set(age.num_milliseconds() as f64 / 1000.0);
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4; charset=utf-8")
+        .body(metrics.encode()?))
+}
+
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(generate_strategies)
+        .service(generate_strategies_stream)
+        .service(start_generation_job)
+        .service(get_generation_progress)
+        .service(estimate_generation)
         .service(create_strategy)
+        .service(run_backtest)
         .service(list_strategies)
+        .service(get_strategy_schema)
+        .service(list_strategy_types)
+        .service(diff_strategies)
+        // After the fixed `/strategies/...` paths, which are not ids.
+        .service(get_strategy)
+        .service(update_strategy)
         .service(delete_strategy)
         .service(delete_all_strategies)
         .service(start_session)
         .service(bulk_start_session)
         .service(list_sessions)
+        .service(get_session)
         .service(reset_sessions)
+        .service(stop_session)
+        .service(resume_session)
+        .service(adjust_capital)
+        .service(backfill_session_equity)
+        .service(import_session_trades)
+        .service(get_capital_adjustments)
         .service(get_trades)
+        .service(get_order_events)
+        .service(get_strategy_performance)
+        .service(get_strategy_backtest_trades)
+        .service(get_symbol_strategy_matrix)
+        .service(get_session_stats)
+        .service(get_portfolio_time_in_market)
         .service(get_equity_curve)
+        .service(session_socket)
+        .service(export_equity_csv)
+        .service(export_trades_csv)
+        .service(compare_session_params)
         .service(get_session_candles)
-        .service(get_portfolio_history);
+        .service(get_session_beta)
+        .service(explain_session_bar)
+        .service(get_portfolio_history)
+        .service(export_portfolio_history_csv)
+        .service(get_portfolio_correlation)
+        .service(get_portfolio_drawdowns)
+        .service(get_portfolio_stats)
+        .service(get_portfolio_cache_status)
+        .service(get_engine_status)
+        .service(pause_engine)
+        .service(resume_engine)
+        .service(get_diagnostics)
+        .service(get_metrics)
+        .service(health)
+        .service(ready)
+        .service(get_snapshot_counts);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(minute: i64, ohlc: Option<f64>) -> PortfolioCandleRow {
+        PortfolioCandleRow {
+            bucket_time: DateTime::from_timestamp(minute * 60, 0).unwrap(),
+            open: ohlc,
+            high: ohlc,
+            low: ohlc,
+            close: ohlc,
+        }
+    }
+
+    #[test]
+    fn test_page_bounds_defaults_and_rejects_out_of_range() {
+        assert_eq!(page_bounds(None, None).unwrap(), (DEFAULT_PAGE_LIMIT, 0));
+        assert_eq!(page_bounds(Some(500), Some(1000)).unwrap(), (500, 1000));
+        assert!(page_bounds(Some(501), None).is_err());
+        assert!(page_bounds(Some(0), None).is_err());
+        assert!(page_bounds(None, Some(-1)).is_err());
+    }
+
+    #[test]
+    fn test_portfolio_basis_parse() {
+        assert_eq!(PortfolioBasis::parse(None).unwrap(), PortfolioBasis::Equity);
+        assert_eq!(
+            PortfolioBasis::parse(Some("return")).unwrap().column(),
+            "return_pct"
+        );
+        assert!(PortfolioBasis::parse(Some("usd")).is_err());
+    }
+
+    #[test]
+    fn test_cache_step_respects_resolution() {
+        assert_eq!(cache_step_seconds(None, 60).unwrap(), 900);
+        assert_eq!(cache_step_seconds(None, 3600).unwrap(), 3600);
+        assert_eq!(cache_step_seconds(Some("1m"), 60).unwrap(), 60);
+        assert_eq!(cache_step_seconds(Some("1h"), 300).unwrap(), 3600);
+        assert!(matches!(
+            cache_step_seconds(Some("1m"), 300),
+            Err(AppError::BadRequest(_))
+        ));
+    }
+
+    #[test]
+    fn test_portfolio_stats_from_curve() {
+        // Returns of 0, 20, -10 and 8% are an index of 1, 1.2, 0.9, 1.08,
+        // however much capital was deposited or withdrawn along the way.
+        let stats = portfolio_stats(
+            &[0.0, 20.0, -10.0, 8.0],
+            Some(5_000.0),
+            Some(20_000.0),
+            Some(8.0),
+            Some(35.0),
+            1.0,
+        );
+        assert_eq!(stats.points, 4);
+        assert_eq!(stats.all_time_high, Some(20_000.0));
+        assert!((stats.current_drawdown_pct.unwrap() - 20.0).abs() < 1e-9);
+        assert!((stats.max_drawdown_pct - 25.0).abs() < 1e-9);
+        assert!(stats.volatility_pct.unwrap() > 0.0);
+        assert!(stats.sharpe_ratio.is_some());
+
+        let empty = portfolio_stats(&[], None, None, None, None, 1.0);
+        assert!(empty.current_drawdown_pct.is_none());
+        assert_eq!(empty.max_drawdown_pct, 0.0);
+        assert!(empty.volatility_pct.is_none());
+    }
+
+    #[test]
+    fn test_complete_candles_skips_empty_buckets() {
+        let mut partial = row(3, Some(101.0));
+        partial.close = None;
+        let rows = vec![
+            row(0, Some(100.0)),
+            row(1, None),
+            row(2, Some(102.0)),
+            partial,
+        ];
+
+        let candles = complete_candles(rows);
+
+        assert_eq!(candles.len(), 2);
+        assert_eq!(candles[0].time.timestamp(), 0);
+        assert_eq!(candles[1].time.timestamp(), 120);
+        assert_eq!(candles[1].close, 102.0);
+    }
+
+    #[test]
+    fn test_trade_csv_row_quotes_fields() {
+        let trade = Trade {
+            id: Uuid::nil(),
+            session_id: Uuid::nil(),
+            symbol: "BTCUSDT,ETHUSDT".to_string(),
+            side: "SELL".to_string(),
+            price: 101.5,
+            quantity: 2.0,
+            pnl: Some(-3.25),
+            pnl_pct: None,
+            fee_paid: 0.2,
+            reason: Some("Stop \"loss\"".to_string()),
+            order_type: "MARKET".to_string(),
+            entry_trade_id: None,
+            holding_seconds: None,
+            linked_trade_id: None,
+            timestamp: DateTime::from_timestamp(60, 0).unwrap(),
+        };
+        assert_eq!(
+            trade_csv_row(&trade),
+            format!(
+                "{},60,\"BTCUSDT,ETHUSDT\",SELL,101.5,2,-3.25,\"Stop \"\"loss\"\"\"\n",
+                Uuid::nil()
+            )
+        );
+
+        let open = Trade {
+            pnl: None,
+            reason: None,
+            symbol: "BTCUSDT".to_string(),
+            ..trade
+        };
+        assert!(trade_csv_row(&open).ends_with(",BTCUSDT,SELL,101.5,2,,\n"));
+    }
+
+    #[test]
+    fn test_time_in_market_pct() {
+        assert_eq!(time_in_market_pct(0.0, 0.0), None);
+        assert_eq!(time_in_market_pct(360.0, 3600.0), Some(10.0));
+        // Whole-second holding times may sum past the lifetime.
+        assert_eq!(time_in_market_pct(3700.0, 3600.0), Some(100.0));
+    }
+
+    #[test]
+    fn test_parse_window() {
+        assert_eq!(parse_window("30d"), Some(chrono::Duration::days(30)));
+        assert_eq!(parse_window("12h"), Some(chrono::Duration::hours(12)));
+        assert_eq!(parse_window("8w"), Some(chrono::Duration::weeks(8)));
+        assert_eq!(parse_window("0d"), None);
+        assert_eq!(parse_window("-1d"), None);
+        assert_eq!(parse_window("30"), None);
+        assert_eq!(parse_window("d"), None);
+        assert_eq!(parse_window(""), None);
+        assert_eq!(parse_window("99999999999999w"), None);
+    }
 }
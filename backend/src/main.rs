@@ -3,16 +3,27 @@ mod db;
 mod error;
 mod handlers;
 mod models;
+mod panic_guard;
 mod services;
 
 use actix_cors::Cors;
 use actix_web::{middleware::Logger, web, App, HttpServer};
 use config::Config;
-use services::market_data::MarketDataService;
+use services::diagnostics::Diagnostics;
+use services::engine_status::EngineStatus;
+use services::generation_jobs::GenerationJobs;
+use services::market_data::{MarketDataService, RetryPolicy};
+use services::metrics::Metrics;
+use services::notifier::Notifier;
 use services::portfolio_manager::PortfolioManager;
+use services::session_events::SessionEvents;
 use services::strategy_generator::StrategyGenerator;
+use services::strategy_schema::StrategyTypePolicy;
+use services::symbols::SymbolResolver;
 use std::sync::Arc;
-use tracing::info;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info};
 use tracing_subscriber::EnvFilter;
 
 #[tokio::main]
@@ -35,36 +46,110 @@ async fn main() -> std::io::Result<()> {
         .await
         .expect("Failed to run migrations");
 
-    let market_service = Arc::new(MarketDataService::new(
-        config.binance_api_key.clone(),
-        config.binance_secret_key.clone(),
-    ));
-    let generator_service = Arc::new(StrategyGenerator::new(pool.clone(), market_service.clone()));
-    let portfolio_manager = Arc::new(PortfolioManager::new(pool.clone()));
+    let diagnostics = Arc::new(Diagnostics::default());
+    let notifier = Notifier::new(config.alert_webhook_url.clone(), diagnostics.clone())
+        .map_err(std::io::Error::other)?;
+    let metrics = Arc::new(Metrics::default());
+    let market_service = Arc::new(
+        MarketDataService::new(
+            config.binance_api_key.clone(),
+            config.binance_secret_key.clone(),
+            SymbolResolver::new(config.symbol_aliases.clone(), &config.quote_asset),
+            diagnostics.clone(),
+        )
+        .with_candle_cache_ttl(Duration::from_millis(config.candle_cache_ttl_ms))
+        .with_lot_size_ttl(Duration::from_secs(config.lot_size_ttl_secs))
+        .with_retry_policy(RetryPolicy {
+            max_attempts: config.binance_max_attempts,
+            base_delay: Duration::from_millis(config.binance_retry_base_ms),
+        })
+        .with_metrics(metrics.clone()),
+    );
+    let generator_service = Arc::new(
+        StrategyGenerator::new(pool.clone(), market_service.clone())
+            .with_concurrency(config.generation_concurrency),
+    );
+    let generation_jobs = Arc::new(GenerationJobs::default());
+    let portfolio_manager = Arc::new(
+        PortfolioManager::new(pool.clone(), config.clone(), market_service.clone())
+            .with_metrics(metrics.clone()),
+    );
+    let session_events = Arc::new(SessionEvents::default().with_metrics(metrics.clone()));
+    let engine_status = Arc::new(
+        EngineStatus::with_diagnostics(diagnostics.clone())
+            .with_session_events(session_events.clone())
+            .with_metrics(metrics.clone())
+            .with_snapshot_budget(config.max_snapshot_writes_per_sec)
+            .with_notifier(notifier),
+    );
+    if !config.trading_enabled {
+        engine_status.pause_trading();
+    }
+    let strategy_policy = StrategyTypePolicy::new(
+        config.allowed_strategy_types.clone(),
+        config.denied_strategy_types.clone(),
+    );
 
+    let shutdown = CancellationToken::new();
     let engine_pool = pool.clone();
     let engine_market = market_service.clone();
-    tokio::spawn(async move {
-        services::trading_engine::start_engine(engine_pool, engine_market).await;
+    let engine_status_clone = engine_status.clone();
+    let engine_config = config.clone();
+    let engine_shutdown = shutdown.clone();
+    let engine_task = tokio::spawn(async move {
+        services::trading_engine::start_engine(
+            engine_pool,
+            engine_market,
+            engine_status_clone,
+            engine_config,
+            engine_shutdown,
+        )
+        .await;
     });
 
+    // The portfolio manager stops on its own token, cancelled only once the
+    // engine has finished, so its final flush follows the engine's last bar.
+    let pm_shutdown = CancellationToken::new();
     let pm_clone = portfolio_manager.clone();
-    tokio::spawn(async move {
-        pm_clone.start_background_task().await;
+    let pm_task_shutdown = pm_shutdown.clone();
+    let pm_task = tokio::spawn(async move {
+        pm_clone.start_background_task(pm_task_shutdown).await;
     });
 
     info!("Server starting at {}", config.server_addr);
 
     HttpServer::new(move || {
         App::new()
+            .wrap(actix_web::middleware::from_fn(panic_guard::catch_panic))
             .wrap(Logger::default())
             .wrap(Cors::permissive())
             .app_data(web::Data::new(pool.clone()))
             .app_data(web::Data::new(market_service.clone()))
             .app_data(web::Data::new(generator_service.clone()))
+            .app_data(web::Data::new(generation_jobs.clone()))
+            .app_data(web::Data::new(engine_status.clone()))
+            .app_data(web::Data::new(session_events.clone()))
+            .app_data(web::Data::new(diagnostics.clone()))
+            .app_data(web::Data::new(metrics.clone()))
+            .app_data(web::Data::new(portfolio_manager.clone()))
+            .app_data(web::Data::new(strategy_policy.clone()))
             .configure(handlers::trade_handler::config)
     })
     .bind(&config.server_addr)?
     .run()
-    .await
+    .await?;
+
+    // The server returns once a signal arrived and in-flight requests have
+    // drained.  The engine is stopped first and the portfolio manager only
+    // after it returns, so the final flush sees the engine's last writes.
+    info!("Server stopped; shutting down the engine");
+    shutdown.cancel();
+    if let Err(e) = engine_task.await {
+        error!("Trading engine task failed: {}", e);
+    }
+    pm_shutdown.cancel();
+    if let Err(e) = pm_task.await {
+        error!("Portfolio manager task failed: {}", e);
+    }
+    Ok(())
 }
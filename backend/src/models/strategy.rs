@@ -28,6 +28,33 @@ pub struct CreateStrategyRequest {
     pub backtest_curve: Option<serde_json::Value>,
 }
 
+/// Partial update of a strategy.  Only `name` and `parameters` can change;
+/// the other fields are accepted so an attempt to change them is refused
+/// rather than silently ignored.
+#[derive(Debug, Deserialize)]
+pub struct UpdateStrategyRequest {
+    pub name: Option<String>,
+    pub parameters: Option<serde_json::Value>,
+    pub strategy_type: Option<serde_json::Value>,
+    pub symbol: Option<serde_json::Value>,
+    pub interval: Option<serde_json::Value>,
+}
+
+impl UpdateStrategyRequest {
+    /// Fields in the body that sessions depend on and cannot change.
+    pub fn fixed_fields(&self) -> Vec<&'static str> {
+        [
+            ("strategy_type", &self.strategy_type),
+            ("symbol", &self.symbol),
+            ("interval", &self.interval),
+        ]
+        .into_iter()
+        .filter(|(_, value)| value.is_some())
+        .map(|(field, _)| field)
+        .collect()
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct GenerateStrategiesRequest {
     pub symbols: Vec<String>,
@@ -35,6 +62,58 @@ pub struct GenerateStrategiesRequest {
     pub top_n: Option<usize>,
     pub limit: Option<u16>,
     pub iterations: Option<usize>,
+    /// Strategy types to optimise (default: every type the deployment permits).
+    pub strategy_types: Option<Vec<String>>,
+    /// Metric the saved top-N are ranked by (default: the objective's).
+    pub rank_by: Option<RankMetric>,
+    /// What the optimiser maximises (default: Sharpe).
+    pub objective: Option<Objective>,
+    /// Share of each market's bars, oldest first, to optimise on; the rest
+    /// are held out for an out-of-sample backtest (default: no split).
+    pub train_ratio: Option<f64>,
+    /// Save each strategy's in-sample trades to `backtest_trades`
+    /// (default: false, as the list can be large).
+    pub store_trades: Option<bool>,
+}
+
+/// Metric used to rank generated candidates.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RankMetric {
+    #[default]
+    Sharpe,
+    Sortino,
+    Calmar,
+    /// Mean return per trade.
+    Expectancy,
+    ProfitFactor,
+    TotalReturn,
+}
+
+/// Fitness the optimiser searches parameters by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Objective {
+    #[default]
+    Sharpe,
+    /// Annualised growth over maximum drawdown, as in
+    /// [`risk_metrics::calmar_ratio`](crate::services::risk_metrics::calmar_ratio).
+    Calmar,
+    ProfitFactor,
+    TotalReturn,
+}
+
+impl Objective {
+    /// The ranking that matches this objective, used when no `rank_by` is
+    /// given.
+    pub fn rank_metric(self) -> RankMetric {
+        match self {
+            Self::Sharpe => RankMetric::Sharpe,
+            Self::Calmar => RankMetric::Calmar,
+            Self::ProfitFactor => RankMetric::ProfitFactor,
+            Self::TotalReturn => RankMetric::TotalReturn,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
@@ -53,18 +132,171 @@ pub struct Session {
     pub status: String,
     pub execution_mode: String,
     pub allocated_weight: f64,
+    pub session_type: String,
+    pub last_error: Option<String>,
+    pub open_trade_id: Option<Uuid>,
+    /// Activation condition for a `pending` session, see [`SessionTrigger`].
+    pub trigger: Option<serde_json::Value>,
+    /// Route orders through the simulated broker (latency and rejections).
+    pub paper_broker: bool,
+    /// Consecutive bars a signal direction must persist before it is acted
+    /// on; 1 disables smoothing.
+    pub signal_confirm_bars: i32,
+    /// Edge mode: trade only when the signal direction changes, and only if
+    /// the previous direction held for this many bars.  `None` trades the
+    /// signal level on every bar.
+    pub edge_lookback: Option<i32>,
+    /// Notional multiple of equity taken by a full-strength signal.
+    pub max_leverage: f64,
+    /// Notional multiple of equity held by the open position, fixed at entry
+    /// from leverage, `allocated_weight` and the sizing mode.
+    pub position_exposure: Option<f64>,
+    /// Trailing-stop distance from the best price since entry, as a
+    /// fraction; `None` uses the engine default and 0 disables.
+    pub trail_pct: Option<f64>,
+    /// Close once price moves this fraction against the entry; `None` or 0
+    /// disables.
+    pub stop_loss_pct: Option<f64>,
+    /// Close once price moves this fraction in favour of the entry; `None` or
+    /// 0 disables.
+    pub take_profit_pct: Option<f64>,
+    /// Last closed-bar strategy evaluation.  Unlike `last_update`, which moves
+    /// on every mark-to-market tick, this goes stale when bars stop arriving.
+    pub last_signal_at: Option<DateTime<Utc>>,
+    /// Latest signal of an `alert` session, which reports signal changes
+    /// instead of trading them.
+    pub alert_signal: Option<f64>,
+    /// Constituent prices at the open position's entry, in
+    /// [`basket_symbols`](Self::basket_symbols) order; the position is split
+    /// equally across them.  `None` for single-symbol sessions and while flat.
+    pub leg_entry_prices: Option<Vec<f64>>,
     pub created_at: DateTime<Utc>,
     pub last_update: DateTime<Utc>,
 }
 
+impl Session {
+    /// Whether this session trades an equal-weight basket rather than one symbol.
+    pub fn is_basket(&self) -> bool {
+        self.session_type == "basket"
+    }
+
+    /// Constituent symbols; a single-symbol session yields just its own symbol.
+    pub fn basket_symbols(&self) -> Vec<String> {
+        self.symbol
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect()
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct CreateSessionRequest {
     pub strategy_id: Uuid,
     pub initial_capital: f64,
     pub execution_mode: Option<String>,
+    /// When set, trade an equal-weight composite of these symbols instead of
+    /// the strategy's own symbol.
+    pub basket_symbols: Option<Vec<String>>,
+    /// When set, the session is created `pending` and activated once the
+    /// condition is met.
+    pub trigger: Option<SessionTrigger>,
+    /// Simulate broker latency and rejections for this session.
+    pub paper_broker: Option<bool>,
+    /// Require the signal direction to hold for this many bars (default 1).
+    pub signal_confirm_bars: Option<i32>,
+    /// Enable edge mode, requiring the prior direction to have held this
+    /// many bars (1 = any change is an edge).
+    pub edge_lookback: Option<i32>,
+    /// Maximum leverage, at least 1.0 (default, unlevered).
+    pub max_leverage: Option<f64>,
+    /// Trailing-stop distance as a fraction of price (default 0.05, 0
+    /// disables).
+    pub trail_pct: Option<f64>,
+    /// Stop-loss distance from entry as a fraction of price (e.g. 0.03).
+    pub stop_loss_pct: Option<f64>,
+    /// Take-profit distance from entry as a fraction of price.
+    pub take_profit_pct: Option<f64>,
 }
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+/// Execution modes the engine implements: `sync` acts on the signal level
+/// every bar, `edge` only on a change of direction (see `edge_lookback`),
+/// and `alert` never trades but notifies on each change of direction.
+pub const EXECUTION_MODES: &[&str] = &["sync", "edge", "alert"];
+
+impl CreateSessionRequest {
+    /// Normalise `execution_mode` (trimmed, lower-case) and reconcile it with
+    /// `edge_lookback`: an `edge` session defaults to a lookback of 1, a
+    /// lookback without a mode implies `edge`, and a lookback on a `sync` or
+    /// `alert` session is refused.
+    pub fn execution(&self) -> Result<(String, Option<i32>), String> {
+        let mode = match self.execution_mode.as_deref().map(str::trim) {
+            Some(raw) => {
+                let mode = raw.to_ascii_lowercase();
+                if !EXECUTION_MODES.contains(&mode.as_str()) {
+                    return Err(format!(
+                        "Unknown execution_mode {raw:?}; expected one of: {}",
+                        EXECUTION_MODES.join(", ")
+                    ));
+                }
+                mode
+            }
+            None if self.edge_lookback.is_some() => "edge".to_string(),
+            None => "sync".to_string(),
+        };
+
+        match (mode.as_str(), self.edge_lookback) {
+            ("sync" | "alert", Some(_)) => {
+                Err("edge_lookback requires execution_mode \"edge\"".into())
+            }
+            ("edge", lookback) => Ok((mode, Some(lookback.unwrap_or(1)))),
+            (_, lookback) => Ok((mode, lookback)),
+        }
+    }
+}
+
+/// Condition on another session's state that activates a pending session.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SessionTrigger {
+    /// The watched session's equity reaches `equity`.
+    EquityAbove { session_id: Uuid, equity: f64 },
+    /// The watched session's equity reaches `multiple` × its initial capital.
+    EquityMultiple { session_id: Uuid, multiple: f64 },
+    /// The watched session falls `pct` (0.0–1.0) below its peak equity.
+    Drawdown { session_id: Uuid, pct: f64 },
+    /// The watched session is closed out by a stop.
+    StoppedOut { session_id: Uuid },
+}
+
+impl SessionTrigger {
+    /// The session whose state this trigger watches.
+    pub fn watched_session(&self) -> Uuid {
+        match self {
+            SessionTrigger::EquityAbove { session_id, .. }
+            | SessionTrigger::EquityMultiple { session_id, .. }
+            | SessionTrigger::Drawdown { session_id, .. }
+            | SessionTrigger::StoppedOut { session_id } => *session_id,
+        }
+    }
+
+    /// Evaluate against the watched session, its peak equity and whether a
+    /// stop has closed one of its positions.
+    pub fn is_met(&self, watched: &Session, peak_equity: f64, stopped_out: bool) -> bool {
+        match self {
+            SessionTrigger::EquityAbove { equity, .. } => watched.current_equity >= *equity,
+            SessionTrigger::EquityMultiple { multiple, .. } => {
+                watched.current_equity >= watched.initial_capital * multiple
+            }
+            SessionTrigger::Drawdown { pct, .. } => {
+                peak_equity > 0.0 && (peak_equity - watched.current_equity) / peak_equity >= *pct
+            }
+            SessionTrigger::StoppedOut { .. } => stopped_out,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Trade {
     pub id: Uuid,
     pub session_id: Uuid,
@@ -73,10 +305,116 @@ pub struct Trade {
     pub price: f64,
     pub quantity: f64,
     pub pnl: Option<f64>,
+    /// For closing trades, `pnl` as a percentage of the equity at entry.
+    pub pnl_pct: Option<f64>,
+    /// Fee charged on this fill; `pnl` is already net of it.
+    pub fee_paid: f64,
+    pub reason: Option<String>,
+    pub order_type: String,
+    /// For closing trades, the trade that opened the position.
+    pub entry_trade_id: Option<Uuid>,
+    /// For closing trades, seconds between entry and exit.
+    pub holding_seconds: Option<i64>,
+    /// For opening trades on a flip or resize, the trade that closed the
+    /// previous position.
+    pub linked_trade_id: Option<Uuid>,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// A trade recorded outside the app, see `POST /sessions/{id}/import-trades`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ImportedTrade {
+    pub timestamp: DateTime<Utc>,
+    /// `BUY` or `SELL`.
+    pub side: String,
+    pub price: f64,
+    #[serde(default)]
+    pub fee: f64,
+    /// Realised PnL net of fees; present only on closing trades.
+    pub pnl: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportTradesRequest {
+    pub trades: Vec<ImportedTrade>,
+}
+
+/// Two parameter sets to replay over a session's market.
+#[derive(Debug, Deserialize)]
+pub struct CompareParamsRequest {
+    pub a: serde_json::Value,
+    pub b: serde_json::Value,
+}
+
+/// An ad-hoc backtest of explicit parameters; nothing is saved.
+#[derive(Debug, Deserialize)]
+pub struct BacktestRequest {
+    pub strategy_type: String,
+    pub parameters: serde_json::Value,
+    pub symbol: String,
+    pub interval: String,
+    /// Candles to fetch, including feature warm-up (default: 1000).
+    /// Ignored when a `start_ms`/`end_ms` window is given.
+    pub limit: Option<u16>,
+    /// Open time (Unix millis) of the first candle of a historical window.
+    pub start_ms: Option<i64>,
+    /// Open time (Unix millis) of the last candle of a historical window.
+    pub end_ms: Option<i64>,
+    /// Starting equity of the simulation (default: 10000).
+    pub initial_capital: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AdjustCapitalRequest {
+    /// Positive to deposit, negative to withdraw.
+    pub amount: f64,
+}
+
+/// A deposit or withdrawal applied to a session, kept apart from trade PnL.
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct CapitalAdjustment {
+    pub id: Uuid,
+    pub session_id: Uuid,
+    pub kind: String,
+    pub amount: f64,
+    pub equity_before: f64,
+    pub equity_after: f64,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// An order that did not result in a fill.
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct OrderEvent {
+    pub id: Uuid,
+    pub session_id: Uuid,
+    pub symbol: String,
+    pub side: String,
+    pub price: f64,
+    /// e.g. `REJECTED`
+    pub event: String,
     pub reason: Option<String>,
     pub timestamp: DateTime<Utc>,
 }
 
+/// How a trade was filled.  Persisted on `trades.order_type`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum OrderType {
+    Market,
+    Limit,
+    Stop,
+}
+
+impl OrderType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderType::Market => "MARKET",
+            OrderType::Limit => "LIMIT",
+            OrderType::Stop => "STOP",
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -89,7 +427,10 @@ mod tests {
             "intervals": ["1h", "4h"],
             "top_n": 10,
             "limit": 1000,
-            "iterations": 50
+            "iterations": 50,
+            "rank_by": "expectancy",
+            "objective": "profit_factor",
+            "store_trades": true
         }"#;
 
         let req: GenerateStrategiesRequest =
@@ -100,6 +441,20 @@ mod tests {
         assert_eq!(req.top_n, Some(10));
         assert_eq!(req.limit, Some(1000));
         assert_eq!(req.iterations, Some(50));
+        assert_eq!(req.rank_by, Some(RankMetric::Expectancy));
+        assert_eq!(req.objective, Some(Objective::ProfitFactor));
+        assert_eq!(req.store_trades, Some(true));
+    }
+
+    #[test]
+    fn test_generate_strategies_request_rejects_unknown_objective() {
+        let json = r#"{
+            "symbols": ["BTCUSDT"],
+            "intervals": ["1h"],
+            "objective": "sortino"
+        }"#;
+
+        assert!(serde_json::from_str::<GenerateStrategiesRequest>(json).is_err());
     }
 
     #[test]
@@ -115,6 +470,7 @@ mod tests {
         assert_eq!(req.symbols, vec!["BTCUSDT"]);
         assert_eq!(req.intervals, vec!["1h"]);
         assert_eq!(req.top_n, None);
+        assert_eq!(req.store_trades, None);
     }
 
     #[test]
@@ -129,8 +485,7 @@ mod tests {
             uuid_str
         );
 
-        let req: CreateSessionRequest =
-            serde_json::from_str(&json).expect("Failed to deserialize");
+        let req: CreateSessionRequest = serde_json::from_str(&json).expect("Failed to deserialize");
 
         assert_eq!(req.strategy_id, Uuid::parse_str(uuid_str).unwrap());
         assert!((req.initial_capital - 10000.0).abs() < f64::EPSILON);
@@ -148,12 +503,32 @@ mod tests {
             uuid_str
         );
 
-        let req: CreateSessionRequest =
-            serde_json::from_str(&json).expect("Failed to deserialize");
+        let req: CreateSessionRequest = serde_json::from_str(&json).expect("Failed to deserialize");
 
         assert_eq!(req.strategy_id, Uuid::parse_str(uuid_str).unwrap());
         assert!((req.initial_capital - 5000.0).abs() < f64::EPSILON);
         assert_eq!(req.execution_mode, None);
+        assert_eq!(req.basket_symbols, None);
+    }
+
+    #[test]
+    fn test_create_session_request_basket() {
+        let json = r#"{
+            "strategy_id": "550e8400-e29b-41d4-a716-446655440000",
+            "initial_capital": 5000.0,
+            "basket_symbols": ["BTCUSDT", "ETHUSDT", "SOLUSDT"]
+        }"#;
+
+        let req: CreateSessionRequest = serde_json::from_str(json).expect("Failed to deserialize");
+
+        assert_eq!(
+            req.basket_symbols,
+            Some(vec![
+                "BTCUSDT".to_string(),
+                "ETHUSDT".to_string(),
+                "SOLUSDT".to_string()
+            ])
+        );
     }
 
     #[test]
@@ -168,8 +543,7 @@ mod tests {
             "backtest_curve": [[1.0, 10000.0], [2.0, 10200.0]]
         }"#;
 
-        let req: CreateStrategyRequest =
-            serde_json::from_str(json).expect("Failed to deserialize");
+        let req: CreateStrategyRequest = serde_json::from_str(json).expect("Failed to deserialize");
 
         assert_eq!(req.name, "Bollinger Reversion");
         assert_eq!(req.strategy_type, "bollinger_reversion");
@@ -178,4 +552,134 @@ mod tests {
         assert!(req.performance_metrics.is_some());
         assert!(req.backtest_curve.is_some());
     }
+
+    #[test]
+    fn test_update_strategy_request_partial() {
+        let req: UpdateStrategyRequest =
+            serde_json::from_str(r#"{"name": "Renamed"}"#).expect("Failed to deserialize");
+        assert_eq!(req.name.as_deref(), Some("Renamed"));
+        assert!(req.parameters.is_none());
+        assert!(req.fixed_fields().is_empty());
+
+        let req: UpdateStrategyRequest = serde_json::from_str(
+            r#"{"parameters": {"period": 14}, "symbol": "ETHUSDT", "interval": "1m"}"#,
+        )
+        .expect("Failed to deserialize");
+        assert!(req.parameters.is_some());
+        assert_eq!(req.fixed_fields(), vec!["symbol", "interval"]);
+    }
+
+    fn sample_session(initial_capital: f64, current_equity: f64) -> Session {
+        Session {
+            id: Uuid::nil(),
+            strategy_id: Uuid::nil(),
+            symbol: "BTCUSDT".to_string(),
+            interval: "1h".to_string(),
+            initial_capital,
+            current_equity,
+            entry_equity: None,
+            current_position: 0.0,
+            entry_price: None,
+            highest_high: None,
+            lowest_low: None,
+            status: "active".to_string(),
+            execution_mode: "sync".to_string(),
+            allocated_weight: 1.0,
+            session_type: "single".to_string(),
+            last_error: None,
+            open_trade_id: None,
+            trigger: None,
+            paper_broker: false,
+            signal_confirm_bars: 1,
+            edge_lookback: None,
+            max_leverage: 1.0,
+            position_exposure: None,
+            trail_pct: None,
+            stop_loss_pct: None,
+            take_profit_pct: None,
+            last_signal_at: None,
+            alert_signal: None,
+            leg_entry_prices: None,
+            created_at: Utc::now(),
+            last_update: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_execution_mode_normalised_and_validated() {
+        let req = |mode: Option<&str>, lookback: Option<i32>| {
+            let mut req: CreateSessionRequest = serde_json::from_str(
+                r#"{"strategy_id": "550e8400-e29b-41d4-a716-446655440000", "initial_capital": 1000.0}"#,
+            )
+            .unwrap();
+            req.execution_mode = mode.map(String::from);
+            req.edge_lookback = lookback;
+            req.execution()
+        };
+
+        assert_eq!(req(None, None), Ok(("sync".into(), None)));
+        assert_eq!(req(Some(" SYNC "), None), Ok(("sync".into(), None)));
+        assert_eq!(req(Some("Edge"), None), Ok(("edge".into(), Some(1))));
+        assert_eq!(req(None, Some(3)), Ok(("edge".into(), Some(3))));
+        assert!(req(Some("sync"), Some(3)).is_err());
+        assert_eq!(req(Some("alert"), None), Ok(("alert".into(), None)));
+        assert!(req(Some("alert"), Some(2)).is_err());
+        assert!(req(Some("paper"), None).is_err());
+        assert!(req(Some("live"), None).is_err());
+    }
+
+    #[test]
+    fn test_session_trigger_deserialize() {
+        let json = r#"{"type": "equity_multiple", "session_id": "550e8400-e29b-41d4-a716-446655440000", "multiple": 2.0}"#;
+        let trigger: SessionTrigger = serde_json::from_str(json).expect("Failed to deserialize");
+
+        assert_eq!(
+            trigger.watched_session(),
+            Uuid::parse_str("550e8400-e29b-41d4-a716-446655440000").unwrap()
+        );
+        assert!(
+            matches!(trigger, SessionTrigger::EquityMultiple { multiple, .. } if multiple == 2.0)
+        );
+    }
+
+    #[test]
+    fn test_session_trigger_is_met() {
+        let id = Uuid::nil();
+        let watched = sample_session(10_000.0, 20_500.0);
+
+        assert!(SessionTrigger::EquityMultiple {
+            session_id: id,
+            multiple: 2.0
+        }
+        .is_met(&watched, 20_500.0, false));
+        assert!(!SessionTrigger::EquityAbove {
+            session_id: id,
+            equity: 25_000.0
+        }
+        .is_met(&watched, 20_500.0, false));
+        assert!(SessionTrigger::Drawdown {
+            session_id: id,
+            pct: 0.1
+        }
+        .is_met(&watched, 25_000.0, false));
+        assert!(!SessionTrigger::Drawdown {
+            session_id: id,
+            pct: 0.1
+        }
+        .is_met(&watched, 21_000.0, false));
+        assert!(SessionTrigger::StoppedOut { session_id: id }.is_met(&watched, 0.0, true));
+    }
+
+    #[test]
+    fn test_order_type_serialization() {
+        assert_eq!(
+            serde_json::to_string(&OrderType::Market).unwrap(),
+            "\"MARKET\""
+        );
+        assert_eq!(serde_json::to_string(&OrderType::Stop).unwrap(), "\"STOP\"");
+
+        let parsed: OrderType = serde_json::from_str("\"LIMIT\"").unwrap();
+        assert_eq!(parsed, OrderType::Limit);
+        assert_eq!(parsed.as_str(), "LIMIT");
+    }
 }
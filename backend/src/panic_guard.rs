@@ -0,0 +1,78 @@
+//! Middleware that turns handler panics into JSON 500 responses.
+//!
+//! Without it a panic inside a handler drops the connection and the client
+//! sees no response at all.
+
+use std::panic::AssertUnwindSafe;
+
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{Error, ResponseError};
+use futures::FutureExt;
+use tracing::error;
+
+use crate::error::AppError;
+
+/// Use with [`actix_web::middleware::from_fn`].
+pub async fn catch_panic(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let http_req = req.request().clone();
+
+    match AssertUnwindSafe(next.call(req)).catch_unwind().await {
+        Ok(res) => res.map(ServiceResponse::map_into_left_body),
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            error!(
+                "Handler panicked on {} {}: {}",
+                http_req.method(),
+                http_req.path(),
+                message
+            );
+
+            let res = AppError::Internal("Request handler panicked".to_string()).error_response();
+            Ok(ServiceResponse::new(http_req, res).map_into_right_body())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use actix_web::middleware::from_fn;
+    use actix_web::{test, web, App, HttpResponse};
+
+    async fn boom() -> HttpResponse {
+        panic!("unexpected data")
+    }
+
+    #[actix_web::test]
+    async fn test_panicking_handler_returns_json_500() {
+        let app = test::init_service(
+            App::new()
+                .wrap(from_fn(catch_panic))
+                .route(
+                    "/ok",
+                    web::get().to(|| async { HttpResponse::Ok().finish() }),
+                )
+                .route("/boom", web::get().to(boom)),
+        )
+        .await;
+
+        let res = test::call_service(&app, test::TestRequest::get().uri("/ok").to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res =
+            test::call_service(&app, test::TestRequest::get().uri("/boom").to_request()).await;
+        assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let body: serde_json::Value = test::read_body_json(res).await;
+        assert_eq!(body["error"], "Internal Error: Request handler panicked");
+    }
+}
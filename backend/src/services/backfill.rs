@@ -0,0 +1,141 @@
+//! Reconstruction of equity snapshots missed while the engine was down.
+//!
+//! Between a session's last snapshot and now, the position is marked to
+//! market at the close of each bar that closed in the gap, exactly as the
+//! live engine would have.  This is only sound if nothing changed the
+//! position, entry price or capital during the gap, so a gap containing a
+//! trade or capital adjustment is refused.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use sqlx::{PgPool, QueryBuilder};
+use uuid::Uuid;
+
+use crate::error::AppError;
+use crate::models::strategy::Session;
+use crate::services::interval;
+use crate::services::market_data::{CandleBar, MarketDataService};
+use crate::services::trading_engine::{self, mark_to_market};
+
+/// Most bars a single klines request returns.
+const MAX_BACKFILL_BARS: i64 = 1000;
+
+#[derive(Debug, Serialize)]
+pub struct BackfillReport {
+    pub session_id: Uuid,
+    pub gap_start: DateTime<Utc>,
+    pub gap_end: DateTime<Utc>,
+    pub inserted: usize,
+}
+
+/// Fill the gap after `session`'s last equity snapshot with one snapshot per
+/// bar closed since.
+pub async fn backfill_session(
+    pool: &PgPool,
+    market: &MarketDataService,
+    session: &Session,
+) -> Result<BackfillReport, AppError> {
+    let bar_secs = interval::seconds(&session.interval).ok_or_else(|| {
+        AppError::BadRequest(format!("Unsupported interval: {}", session.interval))
+    })?;
+
+    let gap_start: DateTime<Utc> = sqlx::query_scalar::<_, Option<DateTime<Utc>>>(
+        "SELECT MAX(timestamp) FROM equity_snapshots WHERE session_id = $1",
+    )
+    .bind(session.id)
+    .fetch_one(pool)
+    .await?
+    .ok_or_else(|| AppError::BadRequest("Session has no equity snapshots".into()))?;
+    let gap_end = Utc::now();
+
+    let changed: bool = sqlx::query_scalar(
+        r#"
+        SELECT EXISTS (SELECT 1 FROM trades WHERE session_id = $1 AND timestamp > $2)
+            OR EXISTS (SELECT 1 FROM capital_adjustments WHERE session_id = $1 AND timestamp > $2)
+        "#,
+    )
+    .bind(session.id)
+    .bind(gap_start)
+    .fetch_one(pool)
+    .await?;
+    if changed {
+        return Err(AppError::BadRequest(
+            "Position or capital changed during the gap; cannot reconstruct equity".into(),
+        ));
+    }
+    if session.current_position.abs() >= f64::EPSILON && session.entry_price.is_none() {
+        return Err(AppError::BadRequest(
+            "Open position has no entry price".into(),
+        ));
+    }
+
+    let gap_bars = (gap_end - gap_start).num_seconds() / bar_secs;
+    if gap_bars < 1 {
+        return Ok(BackfillReport {
+            session_id: session.id,
+            gap_start,
+            gap_end,
+            inserted: 0,
+        });
+    }
+    if gap_bars >= MAX_BACKFILL_BARS {
+        return Err(AppError::BadRequest(format!(
+            "Gap spans {gap_bars} bars, more than the {MAX_BACKFILL_BARS} that can be fetched"
+        )));
+    }
+
+    // One extra bar so the bar in progress at `gap_start` is covered.
+    let limit = (gap_bars + 2) as u16;
+    let candles = if session.is_basket() {
+        trading_engine::basket_position_bars(market, session, limit).await?
+    } else {
+        market
+            .fetch_candles_vec(&session.symbol, &session.interval, limit)
+            .await?
+    };
+
+    let points = gap_points(
+        session,
+        &candles,
+        Duration::seconds(bar_secs),
+        gap_start,
+        gap_end,
+    );
+    if !points.is_empty() {
+        let mut query_builder =
+            QueryBuilder::new("INSERT INTO equity_snapshots (session_id, equity, timestamp) ");
+        query_builder.push_values(&points, |mut b, (ts, equity)| {
+            b.push_bind(session.id).push_bind(equity).push_bind(ts);
+        });
+        query_builder.build().execute(pool).await?;
+    }
+
+    Ok(BackfillReport {
+        session_id: session.id,
+        gap_start,
+        gap_end,
+        inserted: points.len(),
+    })
+}
+
+/// Equity at the close of each bar that closed strictly inside the gap.  A
+/// flat session's equity is constant.
+fn gap_points(
+    session: &Session,
+    candles: &[CandleBar],
+    bar: Duration,
+    gap_start: DateTime<Utc>,
+    gap_end: DateTime<Utc>,
+) -> Vec<(DateTime<Utc>, f64)> {
+    candles
+        .iter()
+        .filter_map(|c| {
+            let close_time = DateTime::<Utc>::from_timestamp_millis(c.time)? + bar;
+            if close_time <= gap_start || close_time >= gap_end {
+                return None;
+            }
+            let equity = mark_to_market(session, c.close).unwrap_or(session.current_equity);
+            Some((close_time, equity))
+        })
+        .collect()
+}
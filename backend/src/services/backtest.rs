@@ -0,0 +1,123 @@
+//! Ad-hoc backtest of explicit strategy parameters.
+//!
+//! Unlike generation, nothing is optimised or saved: the market is fetched
+//! and featurised once, the strategy is run through the strategy registry
+//! and the signals are simulated with the replay fill model, so a
+//! hand-tuned configuration can be previewed on the terms the live engine
+//! would trade it.
+
+use krypto::features::indicators::FeatureEngine;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::error::AppError;
+use crate::services::interval;
+use crate::services::market_data::{self, MarketDataService};
+use crate::services::replay::{self, ReplayMetrics, ReplayPoint, ReplayTrade};
+use crate::services::risk_metrics;
+
+/// Most candles a [`CandleWindow::Range`] may span: 50 pages of klines.
+pub const MAX_RANGE_BARS: i64 = 50_000;
+
+/// Candles a backtest runs over.
+#[derive(Debug, Clone, Copy)]
+pub enum CandleWindow {
+    /// The most recent `n` candles.
+    Latest(u16),
+    /// Every candle opening within `[start_ms, end_ms]`.
+    Range { start_ms: i64, end_ms: i64 },
+}
+
+impl CandleWindow {
+    /// A [`Range`](Self::Range) of `interval` candles, rejected with
+    /// [`AppError::BadRequest`] if it spans more than [`MAX_RANGE_BARS`].
+    pub fn range(start_ms: i64, end_ms: i64, interval: &str) -> Result<Self, AppError> {
+        let interval_ms = interval::seconds(interval)
+            .ok_or_else(|| AppError::BadRequest(format!("Unsupported interval: {interval}")))?
+            * 1000;
+        let bars = end_ms.saturating_sub(start_ms) / interval_ms + 1;
+        if bars > MAX_RANGE_BARS {
+            return Err(AppError::BadRequest(format!(
+                "Candle range spans {bars} {interval} candles; at most {MAX_RANGE_BARS} are allowed"
+            )));
+        }
+        Ok(Self::Range { start_ms, end_ms })
+    }
+}
+
+/// Outcome of one backtest.
+#[derive(Debug, Serialize)]
+pub struct BacktestReport {
+    pub strategy_type: String,
+    pub symbol: String,
+    pub interval: String,
+    pub bars: usize,
+    pub metrics: ReplayMetrics,
+    pub sharpe: Option<f64>,
+    pub sortino: Option<f64>,
+    pub calmar: Option<f64>,
+    pub equity_curve: Vec<ReplayPoint>,
+    pub trades: Vec<ReplayTrade>,
+}
+
+/// Backtest `strategy_type` with `parameters` over `window` of `ticker`
+/// (already resolved) on `interval`.
+pub async fn run_backtest(
+    market: &MarketDataService,
+    strategy_type: &str,
+    parameters: &Value,
+    ticker: &str,
+    interval: &str,
+    window: CandleWindow,
+    initial_capital: f64,
+) -> Result<BacktestReport, AppError> {
+    let raw = match window {
+        CandleWindow::Latest(limit) => market.fetch_candles(ticker, interval, limit).await?,
+        CandleWindow::Range { start_ms, end_ms } => {
+            market
+                .fetch_candles_range(ticker, interval, start_ms, end_ms)
+                .await?
+        }
+    };
+    market_data::validate_candle_frame(&raw)?;
+    let df =
+        FeatureEngine::add_technicals(&raw, None).map_err(|e| AppError::Data(e.to_string()))?;
+
+    let times = replay::frame_times(&df)?;
+    let closes = replay::frame_closes(&df)?;
+    let signals = replay::frame_signals(strategy_type, parameters, &df)?;
+    let run = replay::simulate(&times, &closes, &signals, initial_capital);
+
+    let curve: Vec<f64> = run.equity_curve.iter().map(|p| p.equity).collect();
+    let periods_per_year = interval::periods_per_year(interval);
+
+    Ok(BacktestReport {
+        strategy_type: strategy_type.to_string(),
+        symbol: ticker.to_string(),
+        interval: interval.to_string(),
+        bars: times.len(),
+        metrics: run.metrics,
+        sharpe: risk_metrics::sharpe_ratio(&curve, periods_per_year),
+        sortino: risk_metrics::sortino_ratio(&curve, periods_per_year),
+        calmar: risk_metrics::calmar_ratio(&curve, periods_per_year),
+        equity_curve: run.equity_curve,
+        trades: run.trades,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_range_caps_its_candle_count() {
+        let hour_ms = 3_600_000;
+        let last_allowed = (MAX_RANGE_BARS - 1) * hour_ms;
+        assert!(CandleWindow::range(0, last_allowed, "1h").is_ok());
+        assert!(matches!(
+            CandleWindow::range(0, last_allowed + hour_ms, "1h"),
+            Err(AppError::BadRequest(_))
+        ));
+        assert!(CandleWindow::range(0, last_allowed + hour_ms, "1d").is_ok());
+    }
+}
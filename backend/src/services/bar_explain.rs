@@ -0,0 +1,274 @@
+//! Per-bar decision trace, for `GET /sessions/{id}/explain-bar`.
+//!
+//! Recomputes what the engine saw and decided on one closed bar of a
+//! session: the candles ending at that bar, the strategy's signals, the
+//! confirmation and edge windows, the position going in and the trades the
+//! decision plans.  Everything goes through the engine's own
+//! [`trading_engine::decide_bar`] and [`trading_engine::plan_trades`], so the
+//! trace cannot drift from live behaviour.  The session's current strategy
+//! parameters are used, and the prior position is rebuilt from the trade log
+//! as a direction only, since the log does not keep signal strength.
+
+use chrono::{DateTime, Utc};
+use krypto::features::indicators::FeatureEngine;
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::error::AppError;
+use crate::models::strategy::Session;
+use crate::services::interval;
+use crate::services::market_data::{self, CandleBar, MarketDataService};
+use crate::services::strategy_registry;
+use crate::services::trading_engine::{self, PlannedTrade, SignalDecision, Transition};
+
+/// Candles the strategy runs over, as the engine fetches.
+const ENGINE_BARS: i64 = 1000;
+
+/// Most recent candles echoed back in the trace.
+const TRACE_BARS: usize = 20;
+
+/// One candle of the traced window with the strategy's signal on it.
+#[derive(Debug, Serialize)]
+pub struct TracedCandle {
+    pub time: DateTime<Utc>,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: f64,
+    pub signal: Option<f64>,
+}
+
+/// Everything that went into a session's decision on one bar.
+#[derive(Debug, Serialize)]
+pub struct BarExplanation {
+    pub session_id: uuid::Uuid,
+    pub strategy_type: String,
+    pub parameters: serde_json::Value,
+    pub interval: String,
+    pub execution_mode: String,
+    /// Open time of the traced bar.
+    pub bar_time: DateTime<Utc>,
+    /// Candles the strategy ran over, of which `candles` are the last.
+    pub bars_used: usize,
+    pub candles: Vec<TracedCandle>,
+    pub signal: Option<f64>,
+    /// Edge-mode lookback signals before the confirmation window.
+    pub edge_window: Vec<Option<f64>>,
+    /// Confirmation window, ending with the traced bar.
+    pub confirm_window: Vec<Option<f64>>,
+    pub decision: SignalDecision,
+    /// Direction held going into the bar: 1 long, -1 short, 0 flat.
+    pub prior_position: f64,
+    /// How the position changes, when the decision acts.
+    pub transition: Option<Transition>,
+    pub planned_trades: Vec<PlannedTrade>,
+    pub explanation: String,
+}
+
+#[derive(sqlx::FromRow)]
+struct StrategyRow {
+    strategy_type: String,
+    parameters: serde_json::Value,
+}
+
+/// Trace `session`'s decision on the bar open at `time`.
+pub async fn explain_bar(
+    pool: &PgPool,
+    market: &MarketDataService,
+    session: &Session,
+    time: DateTime<Utc>,
+) -> Result<BarExplanation, AppError> {
+    if time > Utc::now() {
+        return Err(AppError::BadRequest(format!("{time} is in the future")));
+    }
+    let step = interval::seconds(&session.interval)
+        .ok_or_else(|| AppError::Data(format!("Unknown interval {}", session.interval)))?;
+    let strategy = sqlx::query_as::<_, StrategyRow>(
+        "SELECT strategy_type, parameters FROM strategies WHERE id = $1",
+    )
+    .bind(session.strategy_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Session strategy not found".into()))?;
+
+    let end_ms = time.timestamp_millis();
+    let mut bars = if session.is_basket() {
+        // Composites are only built over the latest bars.
+        market
+            .fetch_basket_candles_vec(
+                &session.basket_symbols(),
+                &session.interval,
+                ENGINE_BARS as u16,
+            )
+            .await?
+    } else {
+        let start_ms = (end_ms - (ENGINE_BARS - 1) * step * 1000).max(0);
+        market
+            .fetch_candles_range_vec(&session.symbol, &session.interval, start_ms, end_ms)
+            .await?
+    };
+    let bar = bar_index(&bars, step, end_ms).ok_or_else(|| {
+        AppError::BadRequest(format!(
+            "No {} candle available at {time} for this session",
+            session.interval
+        ))
+    })?;
+    bars.truncate(bar + 1);
+
+    let raw = market_data::bars_to_frame(&bars)?;
+    market_data::validate_candle_frame(&raw)?;
+    let df =
+        FeatureEngine::add_technicals(&raw, None).map_err(|e| AppError::Data(e.to_string()))?;
+    let signals: Vec<Option<f64>> =
+        strategy_registry::predict(&strategy.strategy_type, &strategy.parameters, &df)?
+            .f64()
+            .map_err(|e| AppError::Data(e.to_string()))?
+            .into_iter()
+            .collect();
+    if signals.len() != bars.len() {
+        return Err(AppError::Strategy(format!(
+            "Signal series has {} rows but candle frame has {}",
+            signals.len(),
+            bars.len()
+        )));
+    }
+
+    let bar_open = DateTime::from_timestamp_millis(bars[bar].time).unwrap_or(time);
+    let prior_position =
+        prior_position(pool, session, bar_open + chrono::Duration::seconds(step)).await?;
+    let decided = trading_engine::decide_bar(session, &signals);
+    let (transition, planned_trades) = match decided.decision {
+        SignalDecision::Act(signal) => (
+            Some(Transition::classify(prior_position, signal)),
+            trading_engine::plan_trades(prior_position, signal),
+        ),
+        _ => (None, Vec::new()),
+    };
+
+    let candles = bars
+        .iter()
+        .zip(&signals)
+        .skip(bars.len().saturating_sub(TRACE_BARS))
+        .map(|(b, signal)| TracedCandle {
+            time: DateTime::from_timestamp_millis(b.time).unwrap_or(bar_open),
+            open: b.open,
+            high: b.high,
+            low: b.low,
+            close: b.close,
+            volume: b.volume,
+            signal: *signal,
+        })
+        .collect();
+
+    Ok(BarExplanation {
+        session_id: session.id,
+        explanation: explain(decided.decision, transition, &session.execution_mode),
+        strategy_type: strategy.strategy_type,
+        parameters: strategy.parameters,
+        interval: session.interval.clone(),
+        execution_mode: session.execution_mode.clone(),
+        bar_time: bar_open,
+        bars_used: bars.len(),
+        candles,
+        signal: signals[bar],
+        edge_window: decided.prior,
+        confirm_window: decided.recent,
+        decision: decided.decision,
+        prior_position,
+        transition,
+        planned_trades,
+    })
+}
+
+/// Index of the bar open at `time_ms` in `bars` (oldest first), if any.
+fn bar_index(bars: &[CandleBar], step_secs: i64, time_ms: i64) -> Option<usize> {
+    let bar = bars.partition_point(|b| b.time <= time_ms).checked_sub(1)?;
+    (time_ms < bars[bar].time + step_secs * 1000).then_some(bar)
+}
+
+/// Direction the session held just before `at`, from its latest trade: an
+/// opening trade leaves it long or short, a closing one flat.
+async fn prior_position(
+    pool: &PgPool,
+    session: &Session,
+    at: DateTime<Utc>,
+) -> Result<f64, AppError> {
+    let last: Option<(String, bool)> = sqlx::query_as(
+        "SELECT side, entry_trade_id IS NULL FROM trades WHERE session_id = $1 AND timestamp < $2 ORDER BY timestamp DESC LIMIT 1",
+    )
+    .bind(session.id)
+    .bind(at)
+    .fetch_optional(pool)
+    .await?;
+    Ok(last.map_or(0.0, |(side, opens)| position_after(&side, opens)))
+}
+
+fn position_after(side: &str, opens: bool) -> f64 {
+    match (opens, side) {
+        (false, _) => 0.0,
+        (true, "BUY") => 1.0,
+        (true, _) => -1.0,
+    }
+}
+
+/// One-line account of the decision.
+fn explain(decision: SignalDecision, transition: Option<Transition>, mode: &str) -> String {
+    match decision {
+        SignalDecision::HoldNoSignal => {
+            "The strategy produced no usable signal on this bar, so the position was held."
+                .to_string()
+        }
+        SignalDecision::HoldUnconfirmed => {
+            "The signal direction had not persisted for the confirmation window, so the position was held."
+                .to_string()
+        }
+        SignalDecision::NoEdge(signal) => format!(
+            "Signal {signal:.3} was confirmed but did not start a new regime, so edge mode held the position."
+        ),
+        SignalDecision::Act(signal) if mode == "alert" => {
+            format!("Signal {signal:.3} was confirmed; alert mode reports it instead of trading.")
+        }
+        SignalDecision::Act(signal) => match transition {
+            Some(Transition::Hold) | None => format!(
+                "Signal {signal:.3} was confirmed but matches the position, so nothing was traded."
+            ),
+            Some(transition) => format!("Signal {signal:.3} was confirmed: {transition:?}."),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bar(time: i64) -> CandleBar {
+        CandleBar {
+            time,
+            open: 1.0,
+            high: 1.0,
+            low: 1.0,
+            close: 1.0,
+            volume: 1.0,
+        }
+    }
+
+    #[test]
+    fn test_bar_index_matches_the_open_bar() {
+        let bars = vec![bar(0), bar(60_000), bar(120_000)];
+        assert_eq!(bar_index(&bars, 60, 0), Some(0));
+        assert_eq!(bar_index(&bars, 60, 90_000), Some(1));
+        assert_eq!(bar_index(&bars, 60, 179_999), Some(2));
+        assert_eq!(bar_index(&bars, 60, 180_000), None);
+        assert_eq!(bar_index(&bars, 60, -1), None);
+        assert_eq!(bar_index(&[], 60, 0), None);
+    }
+
+    #[test]
+    fn test_position_after_trade() {
+        assert_eq!(position_after("BUY", true), 1.0);
+        assert_eq!(position_after("SELL", true), -1.0);
+        assert_eq!(position_after("SELL", false), 0.0);
+        assert_eq!(position_after("BUY", false), 0.0);
+    }
+}
@@ -0,0 +1,129 @@
+//! Deposits and withdrawals from `capital_adjustments`, and the equity a
+//! session would have had without them.
+//!
+//! Each adjustment rescales the session's equity by `equity_after /
+//! equity_before` at its timestamp.  Dividing equity by the product of the
+//! scales up to that time gives *flow-neutral* equity, in units of the
+//! opening capital: a deposit leaves it unchanged, so returns, drawdowns and
+//! correlations computed from it reflect trading alone.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// One session's capital adjustments.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CapitalFlows {
+    /// `(timestamp, equity_after / equity_before)` of each adjustment,
+    /// oldest first.
+    scales: Vec<(DateTime<Utc>, f64)>,
+    /// Sum of every adjustment's amount.
+    net_amount: f64,
+}
+
+impl CapitalFlows {
+    /// Record an adjustment made at `timestamp`.  Adjustments oldest first.
+    /// One made with no equity has no scale and only counts towards
+    /// [`opening_capital`](Self::opening_capital).
+    pub fn push(
+        &mut self,
+        timestamp: DateTime<Utc>,
+        amount: f64,
+        equity_before: f64,
+        equity_after: f64,
+    ) {
+        if equity_before > 0.0 && equity_after > 0.0 {
+            self.scales.push((timestamp, equity_after / equity_before));
+        }
+        self.net_amount += amount;
+    }
+
+    /// Product of the scales of adjustments at or before `at`.
+    pub fn scale_at(&self, at: DateTime<Utc>) -> f64 {
+        self.scales
+            .iter()
+            .take_while(|(ts, _)| *ts <= at)
+            .map(|(_, scale)| scale)
+            .product()
+    }
+
+    /// `equity` at `at` with the adjustments up to then taken out.
+    pub fn neutral_equity(&self, equity: f64, at: DateTime<Utc>) -> f64 {
+        equity / self.scale_at(at)
+    }
+
+    /// The session's capital before any adjustment, given its current
+    /// `initial_capital`, which every adjustment adds its amount to.
+    pub fn opening_capital(&self, initial_capital: f64) -> f64 {
+        initial_capital - self.net_amount
+    }
+}
+
+/// Every session's capital adjustments, by session.  Sessions without any
+/// are left out.
+pub async fn load_all(pool: &PgPool) -> Result<HashMap<Uuid, CapitalFlows>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, (Uuid, DateTime<Utc>, f64, f64, f64)>(
+        "SELECT session_id, timestamp, amount, equity_before, equity_after FROM capital_adjustments ORDER BY timestamp ASC",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut flows: HashMap<Uuid, CapitalFlows> = HashMap::new();
+    for (session_id, timestamp, amount, before, after) in rows {
+        flows
+            .entry(session_id)
+            .or_default()
+            .push(timestamp, amount, before, after);
+    }
+    Ok(flows)
+}
+
+/// One session's capital adjustments.
+pub async fn load(pool: &PgPool, session_id: Uuid) -> Result<CapitalFlows, sqlx::Error> {
+    let rows = sqlx::query_as::<_, (DateTime<Utc>, f64, f64, f64)>(
+        "SELECT timestamp, amount, equity_before, equity_after FROM capital_adjustments WHERE session_id = $1 ORDER BY timestamp ASC",
+    )
+    .bind(session_id)
+    .fetch_all(pool)
+    .await?;
+
+    let mut flows = CapitalFlows::default();
+    for (timestamp, amount, before, after) in rows {
+        flows.push(timestamp, amount, before, after);
+    }
+    Ok(flows)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_deposit_leaves_neutral_equity_unchanged() {
+        let t0 = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let mut flows = CapitalFlows::default();
+        // 100 grew to 120, then 60 was deposited and it grew to 198.
+        flows.push(t0, 60.0, 120.0, 180.0);
+
+        let before = flows.neutral_equity(120.0, t0 - Duration::seconds(1));
+        let after = flows.neutral_equity(180.0, t0);
+        assert!((before - 120.0).abs() < 1e-9);
+        assert!((after - 120.0).abs() < 1e-9);
+        assert!((flows.neutral_equity(198.0, t0 + Duration::hours(1)) - 132.0).abs() < 1e-9);
+        assert_eq!(flows.opening_capital(160.0), 100.0);
+    }
+
+    #[test]
+    fn test_adjustment_without_equity_has_no_scale() {
+        let t0 = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let mut flows = CapitalFlows::default();
+        flows.push(t0, 50.0, 0.0, 50.0);
+        assert_eq!(flows.scale_at(t0), 1.0);
+        assert_eq!(flows.opening_capital(150.0), 100.0);
+        assert_eq!(CapitalFlows::default().scale_at(t0), 1.0);
+    }
+}
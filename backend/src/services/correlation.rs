@@ -0,0 +1,202 @@
+//! Pairwise correlation of session returns, and beta against a benchmark.
+//!
+//! Sessions snapshot equity at irregular times, so each session's snapshots
+//! are first bucketed onto a common grid (last equity per bucket, carried
+//! forward across empty buckets) and correlated on bucket-to-bucket returns.
+//! A benchmark's candle closes go through the same bucketing, so its returns
+//! line up with a session's for [`beta`].
+
+use std::collections::BTreeMap;
+
+/// Fewest overlapping returns for which a correlation is reported.
+pub const MIN_OVERLAP: usize = 3;
+
+/// Per-bucket returns for one session on the `grid` of bucket start times.
+///
+/// `points` maps bucket to the session's last equity in it.  Buckets before
+/// the session's first snapshot have no return.
+pub fn bucket_returns(points: &BTreeMap<i64, f64>, grid: &[i64]) -> Vec<Option<f64>> {
+    let mut last: Option<f64> = None;
+    grid.iter()
+        .map(|bucket| {
+            let prev = last;
+            if let Some(&equity) = points.get(bucket) {
+                last = Some(equity);
+            }
+            match (prev, last) {
+                (Some(p), Some(c)) if p > 0.0 => Some(c / p - 1.0),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Pearson correlation over the positions where both series have a value.
+pub fn pearson(a: &[Option<f64>], b: &[Option<f64>]) -> Option<f64> {
+    let pairs = overlap(a, b);
+    if pairs.len() < MIN_OVERLAP {
+        return None;
+    }
+
+    let n = pairs.len() as f64;
+    let mean_a = pairs.iter().map(|p| p.0).sum::<f64>() / n;
+    let mean_b = pairs.iter().map(|p| p.1).sum::<f64>() / n;
+    let (mut cov, mut var_a, mut var_b) = (0.0, 0.0, 0.0);
+    for (x, y) in &pairs {
+        cov += (x - mean_a) * (y - mean_b);
+        var_a += (x - mean_a).powi(2);
+        var_b += (y - mean_b).powi(2);
+    }
+    if var_a <= 0.0 || var_b <= 0.0 {
+        return None;
+    }
+    Some(cov / (var_a.sqrt() * var_b.sqrt()))
+}
+
+/// Paired values of `a` and `b` where both have one.
+fn overlap(a: &[Option<f64>], b: &[Option<f64>]) -> Vec<(f64, f64)> {
+    a.iter()
+        .zip(b)
+        .filter_map(|(x, y)| Some(((*x)?, (*y)?)))
+        .collect()
+}
+
+/// Beta of returns `a` on benchmark returns `b`: their covariance over the
+/// benchmark's variance, over the positions where both have a value.  A
+/// beta of 1 moves one-for-one with the benchmark.
+pub fn beta(a: &[Option<f64>], b: &[Option<f64>]) -> Option<f64> {
+    let pairs = overlap(a, b);
+    if pairs.len() < MIN_OVERLAP {
+        return None;
+    }
+
+    let n = pairs.len() as f64;
+    let mean_a = pairs.iter().map(|p| p.0).sum::<f64>() / n;
+    let mean_b = pairs.iter().map(|p| p.1).sum::<f64>() / n;
+    let (mut cov, mut var_b) = (0.0, 0.0);
+    for (x, y) in &pairs {
+        cov += (x - mean_a) * (y - mean_b);
+        var_b += (y - mean_b).powi(2);
+    }
+    (var_b > 0.0).then(|| cov / var_b)
+}
+
+/// [`beta`] over each trailing run of `span` positions, one value per
+/// position from the `span`-th on.
+pub fn rolling_beta(a: &[Option<f64>], b: &[Option<f64>], span: usize) -> Vec<Option<f64>> {
+    let span = span.max(1);
+    (span..=a.len().min(b.len()))
+        .map(|end| beta(&a[end - span..end], &b[end - span..end]))
+        .collect()
+}
+
+/// Bucket start times every `step` seconds from the bucket holding `start`
+/// through `end`.
+pub fn regular_grid(start: i64, end: i64, step: i64) -> Vec<i64> {
+    let step = step.max(1);
+    let first = start.div_euclid(step) * step;
+    (first..=end).step_by(step as usize).collect()
+}
+
+/// `matrix` (rows and columns in `keys` order) as nested maps by key.
+pub fn keyed_matrix<K: Ord + Clone>(
+    keys: &[K],
+    matrix: Vec<Vec<Option<f64>>>,
+) -> BTreeMap<K, BTreeMap<K, Option<f64>>> {
+    keys.iter()
+        .cloned()
+        .zip(matrix)
+        .map(|(key, row)| (key, keys.iter().cloned().zip(row).collect()))
+        .collect()
+}
+
+/// Symmetric correlation matrix of `returns`, with 1.0 on the diagonal.
+pub fn correlation_matrix(returns: &[Vec<Option<f64>>]) -> Vec<Vec<Option<f64>>> {
+    returns
+        .iter()
+        .enumerate()
+        .map(|(i, a)| {
+            returns
+                .iter()
+                .enumerate()
+                .map(|(j, b)| if i == j { Some(1.0) } else { pearson(a, b) })
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_returns_carries_equity_forward() {
+        let points = BTreeMap::from([(1, 100.0), (2, 110.0), (4, 99.0)]);
+        let returns = bucket_returns(&points, &[0, 1, 2, 3, 4]);
+
+        assert_eq!(returns[0], None);
+        assert_eq!(returns[1], None);
+        assert!((returns[2].unwrap() - 0.1).abs() < 1e-9);
+        assert_eq!(returns[3], Some(0.0));
+        assert!((returns[4].unwrap() + 0.1).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_pearson_extremes() {
+        let a = vec![Some(0.01), Some(-0.02), Some(0.03), Some(0.0)];
+        let inverse: Vec<_> = a.iter().map(|v| v.map(|x| -x)).collect();
+
+        assert!((pearson(&a, &a).unwrap() - 1.0).abs() < 1e-9);
+        assert!((pearson(&a, &inverse).unwrap() + 1.0).abs() < 1e-9);
+        assert_eq!(pearson(&a[..2], &a[..2]), None);
+        assert_eq!(pearson(&a, &[Some(0.0); 4]), None);
+    }
+
+    #[test]
+    fn test_correlation_matrix_is_symmetric() {
+        let returns = vec![
+            vec![Some(0.01), Some(0.02), Some(-0.01), Some(0.03)],
+            vec![Some(0.02), Some(0.01), Some(-0.02), Some(0.02)],
+        ];
+        let matrix = correlation_matrix(&returns);
+
+        assert_eq!(matrix[0][0], Some(1.0));
+        assert_eq!(matrix[0][1], matrix[1][0]);
+        assert!(matrix[0][1].unwrap() > 0.0);
+    }
+
+    #[test]
+    fn test_regular_grid_starts_on_a_bucket() {
+        assert_eq!(regular_grid(130, 300, 60), vec![120, 180, 240, 300]);
+        assert_eq!(regular_grid(120, 120, 60), vec![120]);
+        assert!(regular_grid(200, 100, 60).is_empty());
+    }
+
+    #[test]
+    fn test_keyed_matrix_pairs_keys() {
+        let keyed = keyed_matrix(
+            &["a", "b"],
+            vec![vec![Some(1.0), None], vec![None, Some(1.0)]],
+        );
+        assert_eq!(keyed["a"]["a"], Some(1.0));
+        assert_eq!(keyed["a"]["b"], None);
+        assert_eq!(keyed["b"]["a"], None);
+    }
+
+    #[test]
+    fn test_beta_scales_with_leverage() {
+        let bench = vec![Some(0.01), Some(-0.02), Some(0.03), Some(0.0), None];
+        let levered: Vec<_> = bench.iter().map(|v| v.map(|x| 2.0 * x)).collect();
+
+        assert!((beta(&levered, &bench).unwrap() - 2.0).abs() < 1e-9);
+        assert!((beta(&bench, &bench).unwrap() - 1.0).abs() < 1e-9);
+        // A flat benchmark has no variance to regress on.
+        assert_eq!(beta(&levered, &[Some(0.0); 5]), None);
+
+        let rolling = rolling_beta(&levered, &bench, 3);
+        assert_eq!(rolling.len(), 3);
+        assert!((rolling[0].unwrap() - 2.0).abs() < 1e-9);
+        // The last window has only two paired returns.
+        assert_eq!(rolling[2], None);
+    }
+}
@@ -0,0 +1,117 @@
+//! Recent operational problems, for `GET /diagnostics`.
+//!
+//! Error sites across the services record into one bounded ring buffer held
+//! in app state, so a headless deployment can be checked for what is going
+//! wrong without log access.  Only the newest [`DIAGNOSTICS_CAPACITY`] events
+//! are kept; nothing is persisted.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Events kept before the oldest is evicted.
+pub const DIAGNOSTICS_CAPACITY: usize = 200;
+
+/// Where a diagnostic came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiagnosticKind {
+    /// Engine cycle or per-event processing failure.
+    Engine,
+    /// Error recorded against a session (`sessions.last_error`).
+    Session,
+    /// Websocket stream restarted, handed over or failed.
+    Stream,
+    /// Failed Binance REST call.
+    Binance,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiagnosticEvent {
+    pub at: DateTime<Utc>,
+    pub kind: DiagnosticKind,
+    pub message: String,
+    pub session_id: Option<Uuid>,
+}
+
+/// Bounded log of recent events.  Cheap to share behind an `Arc`.
+#[derive(Default)]
+pub struct Diagnostics {
+    events: Mutex<VecDeque<DiagnosticEvent>>,
+}
+
+impl Diagnostics {
+    pub fn record(&self, kind: DiagnosticKind, message: impl Into<String>) {
+        self.push(DiagnosticEvent {
+            at: Utc::now(),
+            kind,
+            message: message.into(),
+            session_id: None,
+        });
+    }
+
+    pub fn record_session(&self, session_id: Uuid, message: impl Into<String>) {
+        self.push(DiagnosticEvent {
+            at: Utc::now(),
+            kind: DiagnosticKind::Session,
+            message: message.into(),
+            session_id: Some(session_id),
+        });
+    }
+
+    fn push(&self, event: DiagnosticEvent) {
+        let mut events = self.events.lock().unwrap_or_else(|e| e.into_inner());
+        if events.len() == DIAGNOSTICS_CAPACITY {
+            events.pop_front();
+        }
+        events.push_back(event);
+    }
+
+    /// Events newest first, optionally only those of `kind`.
+    pub fn recent(&self, kind: Option<DiagnosticKind>) -> Vec<DiagnosticEvent> {
+        let events = self.events.lock().unwrap_or_else(|e| e.into_inner());
+        events
+            .iter()
+            .rev()
+            .filter(|e| kind.is_none_or(|k| e.kind == k))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recent_is_newest_first_and_filters() {
+        let diagnostics = Diagnostics::default();
+        diagnostics.record(DiagnosticKind::Binance, "timeout");
+        diagnostics.record_session(Uuid::nil(), "STALE_DATA");
+        diagnostics.record(DiagnosticKind::Stream, "restarted");
+
+        let all = diagnostics.recent(None);
+        let messages: Vec<&str> = all.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(messages, vec!["restarted", "STALE_DATA", "timeout"]);
+        assert_eq!(all[1].session_id, Some(Uuid::nil()));
+
+        let sessions = diagnostics.recent(Some(DiagnosticKind::Session));
+        assert_eq!(sessions.len(), 1);
+    }
+
+    #[test]
+    fn test_ring_buffer_evicts_oldest() {
+        let diagnostics = Diagnostics::default();
+        for i in 0..(DIAGNOSTICS_CAPACITY + 3) {
+            diagnostics.record(DiagnosticKind::Engine, i.to_string());
+        }
+
+        let recent = diagnostics.recent(None);
+        assert_eq!(recent.len(), DIAGNOSTICS_CAPACITY);
+        assert_eq!(recent[0].message, (DIAGNOSTICS_CAPACITY + 2).to_string());
+        assert_eq!(recent.last().unwrap().message, "3");
+    }
+}
@@ -0,0 +1,146 @@
+//! Drawdown episodes of an equity curve.
+//!
+//! An episode starts when equity falls below its running peak and ends when
+//! equity regains that peak.  An episode still open at the end of the curve
+//! has no recovery.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DrawdownEpisode {
+    pub peak_at: DateTime<Utc>,
+    pub peak_equity: f64,
+    pub trough_at: DateTime<Utc>,
+    pub trough_equity: f64,
+    /// When equity first regained the peak; `None` while still under water.
+    pub recovered_at: Option<DateTime<Utc>>,
+    /// Peak-to-trough decline in percent of the peak.
+    pub depth_pct: f64,
+    /// Seconds from peak to recovery, or to the last point if unrecovered.
+    pub duration_seconds: i64,
+}
+
+/// Episodes in `curve` (ascending by time) at least `min_depth_pct` deep,
+/// deepest first.
+pub fn drawdown_episodes(
+    curve: &[(DateTime<Utc>, f64)],
+    min_depth_pct: f64,
+) -> Vec<DrawdownEpisode> {
+    let mut episodes = Vec::new();
+    let Some(&(first_ts, first_eq)) = curve.first() else {
+        return episodes;
+    };
+
+    let (mut peak_at, mut peak) = (first_ts, first_eq);
+    let mut trough: Option<(DateTime<Utc>, f64)> = None;
+
+    for &(ts, equity) in &curve[1..] {
+        if equity >= peak {
+            if let Some(t) = trough.take() {
+                episodes.extend(episode((peak_at, peak), t, ts, true, min_depth_pct));
+            }
+            peak_at = ts;
+            peak = equity;
+        } else if trough.is_none_or(|(_, low)| equity < low) {
+            trough = Some((ts, equity));
+        }
+    }
+    if let (Some(t), Some(&(last_ts, _))) = (trough, curve.last()) {
+        episodes.extend(episode((peak_at, peak), t, last_ts, false, min_depth_pct));
+    }
+
+    episodes.sort_by(|a, b| b.depth_pct.total_cmp(&a.depth_pct));
+    episodes
+}
+
+/// Deepest decline of `equity` (in time order) below its running peak, in
+/// percent of the peak: the depth of the deepest episode, or 0 without one.
+pub fn max_drawdown_pct(equity: impl IntoIterator<Item = f64>) -> f64 {
+    let mut peak = f64::NEG_INFINITY;
+    let mut max_depth: f64 = 0.0;
+    for value in equity {
+        peak = peak.max(value);
+        if peak > 0.0 {
+            max_depth = max_depth.max((peak - value) / peak * 100.0);
+        }
+    }
+    max_depth
+}
+
+/// The episode from `peak` to `trough` ending at `end`, if deep enough.
+fn episode(
+    (peak_at, peak): (DateTime<Utc>, f64),
+    (trough_at, trough): (DateTime<Utc>, f64),
+    end: DateTime<Utc>,
+    recovered: bool,
+    min_depth_pct: f64,
+) -> Option<DrawdownEpisode> {
+    let depth_pct = if peak > 0.0 {
+        (peak - trough) / peak * 100.0
+    } else {
+        0.0
+    };
+    (depth_pct >= min_depth_pct).then(|| DrawdownEpisode {
+        peak_at,
+        peak_equity: peak,
+        trough_at,
+        trough_equity: trough,
+        recovered_at: recovered.then_some(end),
+        depth_pct,
+        duration_seconds: (end - peak_at).num_seconds(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn curve(values: &[f64]) -> Vec<(DateTime<Utc>, f64)> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (DateTime::from_timestamp(i as i64 * 60, 0).unwrap(), *v))
+            .collect()
+    }
+
+    #[test]
+    fn test_drawdown_episodes_sorted_by_depth() {
+        let points = curve(&[100.0, 95.0, 100.0, 110.0, 88.0, 99.0, 111.0, 105.0]);
+
+        let episodes = drawdown_episodes(&points, 0.0);
+
+        assert_eq!(episodes.len(), 3);
+        // 110 -> 88, recovered at 111.
+        assert!((episodes[0].depth_pct - 20.0).abs() < 1e-9);
+        assert_eq!(episodes[0].peak_at.timestamp(), 180);
+        assert_eq!(episodes[0].trough_at.timestamp(), 240);
+        assert_eq!(episodes[0].recovered_at.map(|t| t.timestamp()), Some(360));
+        assert_eq!(episodes[0].duration_seconds, 180);
+        // 111 -> 105 is still open.
+        assert!(episodes
+            .iter()
+            .any(|e| e.recovered_at.is_none() && e.peak_equity == 111.0));
+    }
+
+    #[test]
+    fn test_drawdown_episodes_filters_shallow() {
+        let points = curve(&[100.0, 99.5, 100.0, 90.0]);
+
+        let episodes = drawdown_episodes(&points, 1.0);
+
+        assert_eq!(episodes.len(), 1);
+        assert_eq!(episodes[0].trough_equity, 90.0);
+        assert!(drawdown_episodes(&[], 0.0).is_empty());
+    }
+
+    #[test]
+    fn test_max_drawdown_matches_deepest_episode() {
+        let values = [100.0, 95.0, 100.0, 110.0, 88.0, 99.0, 111.0, 105.0];
+        let deepest = drawdown_episodes(&curve(&values), 0.0)[0].depth_pct;
+
+        assert!((max_drawdown_pct(values) - deepest).abs() < 1e-9);
+        assert_eq!(max_drawdown_pct([100.0, 101.0, 102.0]), 0.0);
+        assert_eq!(max_drawdown_pct([]), 0.0);
+    }
+}
@@ -0,0 +1,355 @@
+//! Runtime state shared between the trading engine and the HTTP API.
+//!
+//! The engine records per-phase timings of every bar it processes; the
+//! `/engine/status` endpoint reports rolling averages over the most recent
+//! samples so slow ticks can be attributed to Binance, feature computation
+//! or Postgres.  It also holds the global trading switch toggled by
+//! `/engine/pause` and `/engine/resume`, the engine's handle on the
+//! shared [`Diagnostics`] log, the global [`SnapshotBudget`], the
+//! [`Notifier`] for alert sessions, the [`SessionEvents`] hub live
+//! clients subscribe to and the Prometheus [`Metrics`].  The state of the
+//! engine's websocket stream is kept here for `/ready`.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::services::diagnostics::Diagnostics;
+use crate::services::metrics::Metrics;
+use crate::services::notifier::Notifier;
+use crate::services::session_events::SessionEvents;
+use crate::services::snapshot_budget::SnapshotBudget;
+
+/// Number of samples kept per phase for the rolling statistics.
+const LATENCY_WINDOW: usize = 200;
+
+/// A timed stage of engine tick processing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Whole kline event, across all sessions on the symbol.
+    Tick,
+    /// REST candle fetch from Binance.
+    FetchCandles,
+    /// `FeatureEngine::add_technicals`.
+    Features,
+    /// Strategy `predict`.
+    Predict,
+    /// Signal execution, including the DB transaction.
+    Execute,
+}
+
+impl Phase {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Phase::Tick => "tick",
+            Phase::FetchCandles => "fetch_candles",
+            Phase::Features => "features",
+            Phase::Predict => "predict",
+            Phase::Execute => "execute",
+        }
+    }
+}
+
+/// Rolling latency summary for one phase, in milliseconds.
+#[derive(Debug, Serialize)]
+pub struct PhaseLatency {
+    pub samples: usize,
+    pub avg_ms: f64,
+    pub max_ms: f64,
+    pub last_ms: f64,
+}
+
+/// Feature-frame reuse across sessions sharing a market.
+#[derive(Debug, Serialize)]
+pub struct FeatureCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// The engine's websocket stream, as reported by `/ready`.
+#[derive(Debug, Serialize)]
+pub struct StreamHealth {
+    /// Whether the current stream has delivered an event since it opened.
+    pub connected: bool,
+    /// When the engine last processed a kline.
+    pub last_kline_at: Option<DateTime<Utc>>,
+}
+
+/// Snapshot of engine state returned by `/engine/status`.
+#[derive(Debug, Serialize)]
+pub struct EngineStatusReport {
+    pub started_at: DateTime<Utc>,
+    /// Whether the engine acts on strategy signals.
+    pub trading_enabled: bool,
+    /// When trading was paused; `None` while enabled.
+    pub trading_paused_since: Option<DateTime<Utc>>,
+    pub latency: BTreeMap<&'static str, PhaseLatency>,
+    pub feature_cache: FeatureCacheStats,
+    /// Mark-to-market snapshots not written for lack of write budget.
+    pub snapshots_skipped: u64,
+}
+
+#[derive(Default)]
+struct RollingWindow {
+    samples: VecDeque<f64>,
+}
+
+impl RollingWindow {
+    fn record(&mut self, ms: f64) {
+        if self.samples.len() == LATENCY_WINDOW {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(ms);
+    }
+
+    fn summary(&self) -> PhaseLatency {
+        let n = self.samples.len();
+        let sum: f64 = self.samples.iter().sum();
+        PhaseLatency {
+            samples: n,
+            avg_ms: if n > 0 { sum / n as f64 } else { 0.0 },
+            max_ms: self.samples.iter().copied().fold(0.0, f64::max),
+            last_ms: self.samples.back().copied().unwrap_or(0.0),
+        }
+    }
+}
+
+/// Engine runtime state.  Cheap to share behind an `Arc`.
+pub struct EngineStatus {
+    started_at: DateTime<Utc>,
+    /// Set while trading is paused.
+    paused_since: Mutex<Option<DateTime<Utc>>>,
+    latency: Mutex<BTreeMap<&'static str, RollingWindow>>,
+    feature_cache_hits: AtomicU64,
+    feature_cache_misses: AtomicU64,
+    diagnostics: Arc<Diagnostics>,
+    snapshot_budget: SnapshotBudget,
+    notifier: Notifier,
+    session_events: Arc<SessionEvents>,
+    metrics: Arc<Metrics>,
+    stream_connected: AtomicBool,
+    last_kline_at: Mutex<Option<DateTime<Utc>>>,
+}
+
+impl EngineStatus {
+    pub fn new() -> Self {
+        Self::with_diagnostics(Arc::default())
+    }
+
+    /// A status recording problems into the shared `diagnostics` log.
+    pub fn with_diagnostics(diagnostics: Arc<Diagnostics>) -> Self {
+        Self {
+            started_at: Utc::now(),
+            paused_since: Mutex::new(None),
+            latency: Mutex::new(BTreeMap::new()),
+            feature_cache_hits: AtomicU64::new(0),
+            feature_cache_misses: AtomicU64::new(0),
+            diagnostics,
+            snapshot_budget: SnapshotBudget::default(),
+            notifier: Notifier::default(),
+            session_events: Arc::default(),
+            metrics: Arc::default(),
+            stream_connected: AtomicBool::new(false),
+            last_kline_at: Mutex::new(None),
+        }
+    }
+
+    /// Deliver alert-session notifications through `notifier`.
+    pub fn with_notifier(mut self, notifier: Notifier) -> Self {
+        self.notifier = notifier;
+        self
+    }
+
+    /// Publish session equity and trade updates to `events`.
+    pub fn with_session_events(mut self, events: Arc<SessionEvents>) -> Self {
+        self.session_events = events;
+        self
+    }
+
+    /// Count klines and stream reconnects in `metrics`.
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Cap routine equity snapshot writes at `per_sec` across all sessions.
+    pub fn with_snapshot_budget(mut self, per_sec: f64) -> Self {
+        self.snapshot_budget = SnapshotBudget::new(per_sec);
+        self
+    }
+
+    pub fn diagnostics(&self) -> &Arc<Diagnostics> {
+        &self.diagnostics
+    }
+
+    pub fn snapshot_budget(&self) -> &SnapshotBudget {
+        &self.snapshot_budget
+    }
+
+    pub fn notifier(&self) -> &Notifier {
+        &self.notifier
+    }
+
+    pub fn session_events(&self) -> &SessionEvents {
+        &self.session_events
+    }
+
+    pub fn metrics(&self) -> &Arc<Metrics> {
+        &self.metrics
+    }
+
+    /// Stop acting on strategy signals.  Mark-to-market, protective exits
+    /// and the API keep running.  Returns `false` if already paused.
+    pub fn pause_trading(&self) -> bool {
+        let mut paused = self.paused_since.lock().unwrap_or_else(|e| e.into_inner());
+        if paused.is_some() {
+            return false;
+        }
+        *paused = Some(Utc::now());
+        true
+    }
+
+    /// Resume acting on strategy signals.  Returns `false` if not paused.
+    pub fn resume_trading(&self) -> bool {
+        let mut paused = self.paused_since.lock().unwrap_or_else(|e| e.into_inner());
+        paused.take().is_some()
+    }
+
+    pub fn trading_enabled(&self) -> bool {
+        self.paused_since
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_none()
+    }
+
+    /// Record how long `phase` took.
+    pub fn record(&self, phase: Phase, elapsed: Duration) {
+        let ms = elapsed.as_secs_f64() * 1000.0;
+        let mut latency = self.latency.lock().unwrap_or_else(|e| e.into_inner());
+        latency.entry(phase.as_str()).or_default().record(ms);
+    }
+
+    /// Count a feature-frame lookup; each hit is one skipped fetch and
+    /// `add_technicals` pass.
+    pub fn record_feature_cache(&self, hit: bool) {
+        let counter = if hit {
+            &self.feature_cache_hits
+        } else {
+            &self.feature_cache_misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The stream delivered an event, so it is connected.
+    pub fn record_stream_event(&self) {
+        self.stream_connected.store(true, Ordering::Relaxed);
+    }
+
+    /// The stream closed; it counts as down until the next one delivers.
+    pub fn record_stream_down(&self) {
+        self.stream_connected.store(false, Ordering::Relaxed);
+    }
+
+    pub fn record_kline(&self) {
+        *self.last_kline_at.lock().unwrap_or_else(|e| e.into_inner()) = Some(Utc::now());
+    }
+
+    pub fn stream_health(&self) -> StreamHealth {
+        StreamHealth {
+            connected: self.stream_connected.load(Ordering::Relaxed),
+            last_kline_at: *self.last_kline_at.lock().unwrap_or_else(|e| e.into_inner()),
+        }
+    }
+
+    pub fn report(&self) -> EngineStatusReport {
+        let paused_since = *self.paused_since.lock().unwrap_or_else(|e| e.into_inner());
+        let latency = self.latency.lock().unwrap_or_else(|e| e.into_inner());
+        EngineStatusReport {
+            started_at: self.started_at,
+            trading_enabled: paused_since.is_none(),
+            trading_paused_since: paused_since,
+            latency: latency.iter().map(|(k, v)| (*k, v.summary())).collect(),
+            feature_cache: FeatureCacheStats {
+                hits: self.feature_cache_hits.load(Ordering::Relaxed),
+                misses: self.feature_cache_misses.load(Ordering::Relaxed),
+            },
+            snapshots_skipped: self.snapshot_budget.skipped(),
+        }
+    }
+}
+
+impl Default for EngineStatus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rolling_latency_summary() {
+        let status = EngineStatus::new();
+        status.record(Phase::Features, Duration::from_millis(10));
+        status.record(Phase::Features, Duration::from_millis(30));
+
+        let report = status.report();
+        let features = &report.latency["features"];
+
+        assert_eq!(features.samples, 2);
+        assert!((features.avg_ms - 20.0).abs() < 1e-9);
+        assert!((features.max_ms - 30.0).abs() < 1e-9);
+        assert!((features.last_ms - 30.0).abs() < 1e-9);
+        assert!(!report.latency.contains_key("predict"));
+    }
+
+    #[test]
+    fn test_stream_health_follows_events() {
+        let status = EngineStatus::new();
+        assert!(!status.stream_health().connected);
+
+        status.record_stream_event();
+        status.record_kline();
+        let health = status.stream_health();
+        assert!(health.connected);
+        assert!(health.last_kline_at.is_some());
+
+        status.record_stream_down();
+        let health = status.stream_health();
+        assert!(!health.connected);
+        assert!(health.last_kline_at.is_some());
+    }
+
+    #[test]
+    fn test_pause_and_resume_trading() {
+        let status = EngineStatus::new();
+        assert!(status.trading_enabled());
+
+        assert!(status.pause_trading());
+        assert!(!status.pause_trading());
+        let report = status.report();
+        assert!(!report.trading_enabled);
+        assert!(report.trading_paused_since.is_some());
+
+        assert!(status.resume_trading());
+        assert!(!status.resume_trading());
+        assert!(status.report().trading_paused_since.is_none());
+    }
+
+    #[test]
+    fn test_rolling_window_evicts_oldest() {
+        let mut window = RollingWindow::default();
+        for i in 0..(LATENCY_WINDOW + 5) {
+            window.record(i as f64);
+        }
+
+        let summary = window.summary();
+        assert_eq!(summary.samples, LATENCY_WINDOW);
+        assert!((summary.last_ms - (LATENCY_WINDOW + 4) as f64).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,168 @@
+//! Runtime and REST-weight estimates for strategy generation requests.
+//!
+//! Generation cost is one candle fetch and feature pass per market
+//! (`symbols × intervals`) plus one optimiser run per market and strategy
+//! type, whose time grows with `iterations × bars`.  Real runs record both so
+//! estimates track the host's actual speed; until a run has been measured,
+//! conservative defaults are used.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Serialize;
+
+/// Number of samples kept for each rolling average.
+const TIMING_WINDOW: usize = 50;
+
+/// Assumed seconds to fetch and featurise one market before any is measured.
+const DEFAULT_MARKET_SECS: f64 = 1.0;
+
+/// Assumed optimiser seconds per iteration per bar before any is measured
+/// (50 iterations over 1000 bars take about 2.5 s).
+const DEFAULT_OPTIMISE_SECS_PER_ITERATION_BAR: f64 = 5e-5;
+
+/// Estimated cost of a generation request.
+#[derive(Debug, Serialize)]
+pub struct GenerationEstimate {
+    pub markets: usize,
+    pub optimiser_runs: usize,
+    pub estimated_seconds: f64,
+    /// Binance request weight of the candle fetches.
+    pub rest_weight: u32,
+    /// Whether the timings come from real runs rather than defaults.
+    pub measured: bool,
+    pub market_samples: usize,
+    pub optimiser_samples: usize,
+}
+
+/// Rolling timings collected from real generation runs.
+#[derive(Default)]
+pub struct GenerationTimings {
+    inner: Mutex<Samples>,
+}
+
+#[derive(Default)]
+struct Samples {
+    market_secs: VecDeque<f64>,
+    optimise_secs_per_iteration_bar: VecDeque<f64>,
+}
+
+fn push(window: &mut VecDeque<f64>, value: f64) {
+    if window.len() == TIMING_WINDOW {
+        window.pop_front();
+    }
+    window.push_back(value);
+}
+
+fn mean(window: &VecDeque<f64>) -> Option<f64> {
+    (!window.is_empty()).then(|| window.iter().sum::<f64>() / window.len() as f64)
+}
+
+impl GenerationTimings {
+    /// Record the candle fetch and feature pass of one market.
+    pub fn record_market(&self, elapsed: Duration) {
+        let mut samples = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        push(&mut samples.market_secs, elapsed.as_secs_f64());
+    }
+
+    /// Record one strategy type's optimisation of `iterations` over `bars`.
+    pub fn record_optimisation(&self, elapsed: Duration, iterations: usize, bars: usize) {
+        let work = (iterations * bars) as f64;
+        if work == 0.0 {
+            return;
+        }
+        let mut samples = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        push(
+            &mut samples.optimise_secs_per_iteration_bar,
+            elapsed.as_secs_f64() / work,
+        );
+    }
+
+    /// Estimate a request over `markets` markets and `strategy_types` types,
+    /// evaluated `concurrency` markets at a time.
+    pub fn estimate(
+        &self,
+        markets: usize,
+        strategy_types: usize,
+        limit: u16,
+        iterations: usize,
+        concurrency: usize,
+    ) -> GenerationEstimate {
+        let samples = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let market_secs = mean(&samples.market_secs);
+        let optimise_secs = mean(&samples.optimise_secs_per_iteration_bar);
+
+        let optimiser_runs = markets * strategy_types;
+        let serial_seconds = markets as f64 * market_secs.unwrap_or(DEFAULT_MARKET_SECS)
+            + (optimiser_runs * iterations * limit as usize) as f64
+                * optimise_secs.unwrap_or(DEFAULT_OPTIMISE_SECS_PER_ITERATION_BAR);
+        // Samples are per market, so concurrent markets divide the total;
+        // never by more markets than the request has.
+        let parallel = concurrency.clamp(1, markets.max(1));
+        let estimated_seconds = serial_seconds / parallel as f64;
+
+        GenerationEstimate {
+            markets,
+            optimiser_runs,
+            estimated_seconds,
+            rest_weight: markets as u32 * klines_weight(limit),
+            measured: market_secs.is_some() && optimise_secs.is_some(),
+            market_samples: samples.market_secs.len(),
+            optimiser_samples: samples.optimise_secs_per_iteration_bar.len(),
+        }
+    }
+}
+
+/// Binance request weight of one klines call returning `limit` bars.
+pub fn klines_weight(limit: u16) -> u32 {
+    match limit {
+        0..=99 => 1,
+        100..=499 => 2,
+        500..=1000 => 5,
+        _ => 10,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_uses_defaults_until_measured() {
+        let timings = GenerationTimings::default();
+
+        let est = timings.estimate(4, 9, 1000, 50, 1);
+        assert!(!est.measured);
+        assert_eq!(est.optimiser_runs, 36);
+        assert_eq!(est.rest_weight, 20);
+        assert!((est.estimated_seconds - (4.0 + 36.0 * 2.5)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_estimate_scales_with_measured_timings() {
+        let timings = GenerationTimings::default();
+        timings.record_market(Duration::from_secs(2));
+        // 100 iterations over 500 bars in 1 s.
+        timings.record_optimisation(Duration::from_secs(1), 100, 500);
+
+        let est = timings.estimate(2, 3, 500, 200, 1);
+        assert!(est.measured);
+        // 2 markets × 2 s, plus 6 runs at twice the measured work.
+        assert!((est.estimated_seconds - (4.0 + 6.0 * 2.0)).abs() < 1e-9);
+        assert_eq!(est.rest_weight, 10);
+    }
+
+    #[test]
+    fn test_estimate_divides_by_concurrent_markets() {
+        let timings = GenerationTimings::default();
+
+        let serial = timings.estimate(4, 9, 1000, 50, 1);
+        let parallel = timings.estimate(4, 9, 1000, 50, 2);
+        assert!((parallel.estimated_seconds * 2.0 - serial.estimated_seconds).abs() < 1e-9);
+        // A limit above the market count only runs every market at once.
+        let capped = timings.estimate(4, 9, 1000, 50, 16);
+        assert!((capped.estimated_seconds * 4.0 - serial.estimated_seconds).abs() < 1e-9);
+        assert_eq!(capped.rest_weight, serial.rest_weight);
+    }
+}
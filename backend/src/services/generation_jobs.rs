@@ -0,0 +1,140 @@
+//! Background strategy-generation jobs, for `GET /strategies/generate/progress`.
+//!
+//! A job started by `POST /strategies/generate/jobs` runs detached from any
+//! request; its [`GenerationEvent`]s are kept in order and broadcast to
+//! whoever is subscribed.  A subscriber first receives the events published
+//! so far, so a client that connects late or reconnects still sees the whole
+//! run.  Nothing is persisted: jobs are lost on restart, and finished jobs
+//! are dropped [`JOB_RETENTION`] after they end.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use crate::services::strategy_generator::GenerationEvent;
+
+/// How long a finished job's events stay available.
+pub const JOB_RETENTION: Duration = Duration::from_secs(3600);
+
+/// Events buffered per live subscriber before it starts missing some.
+const SUBSCRIBER_BUFFER: usize = 256;
+
+/// One generation run's events.
+pub struct GenerationJob {
+    inner: Mutex<JobState>,
+    tx: broadcast::Sender<GenerationEvent>,
+}
+
+#[derive(Default)]
+struct JobState {
+    events: Vec<GenerationEvent>,
+    finished_at: Option<Instant>,
+}
+
+impl GenerationJob {
+    fn new() -> Self {
+        Self {
+            inner: Mutex::default(),
+            tx: broadcast::channel(SUBSCRIBER_BUFFER).0,
+        }
+    }
+
+    /// Record `event` and send it to current subscribers.
+    pub fn publish(&self, event: GenerationEvent) {
+        let mut state = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        if event.is_terminal() {
+            state.finished_at = Some(Instant::now());
+        }
+        state.events.push(event.clone());
+        // Sent under the lock so a concurrent `subscribe` sees each event
+        // exactly once, in either its history or its receiver.
+        let _ = self.tx.send(event);
+    }
+
+    /// Events published so far, and a receiver for the ones that follow.
+    pub fn subscribe(&self) -> (Vec<GenerationEvent>, broadcast::Receiver<GenerationEvent>) {
+        let state = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        (state.events.clone(), self.tx.subscribe())
+    }
+
+    fn expired(&self, now: Instant) -> bool {
+        let state = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        state
+            .finished_at
+            .is_some_and(|at| now.duration_since(at) >= JOB_RETENTION)
+    }
+}
+
+/// Jobs by id.  Cheap to share behind an `Arc`.
+#[derive(Default)]
+pub struct GenerationJobs {
+    jobs: Mutex<HashMap<Uuid, Arc<GenerationJob>>>,
+}
+
+impl GenerationJobs {
+    /// Register a new job, dropping any that finished over
+    /// [`JOB_RETENTION`] ago.
+    pub fn create(&self) -> (Uuid, Arc<GenerationJob>) {
+        let id = Uuid::new_v4();
+        let job = Arc::new(GenerationJob::new());
+        let now = Instant::now();
+
+        let mut jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        jobs.retain(|_, j| !j.expired(now));
+        jobs.insert(id, job.clone());
+        (id, job)
+    }
+
+    pub fn get(&self, id: Uuid) -> Option<Arc<GenerationJob>> {
+        let jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        jobs.get(&id).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::strategy_generator::GenerationReport;
+
+    fn started(symbol: &str) -> GenerationEvent {
+        GenerationEvent::MarketStarted {
+            symbol: symbol.to_string(),
+            interval: "1h".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_subscriber_gets_history_then_live_events() {
+        let jobs = GenerationJobs::default();
+        let (id, job) = jobs.create();
+        job.publish(started("BTCUSDT"));
+
+        let (history, mut rx) = jobs.get(id).unwrap().subscribe();
+        assert_eq!(history.len(), 1);
+        assert!(rx.try_recv().is_err());
+
+        job.publish(GenerationEvent::Done(GenerationReport {
+            strategies_created: 0,
+            convergence: Vec::new(),
+        }));
+        assert!(rx.try_recv().unwrap().is_terminal());
+        assert!(jobs.get(Uuid::nil()).is_none());
+    }
+
+    #[test]
+    fn test_only_finished_jobs_expire() {
+        let job = GenerationJob::new();
+        job.publish(started("ETHUSDT"));
+        let later = Instant::now() + JOB_RETENTION;
+        assert!(!job.expired(later));
+
+        job.publish(GenerationEvent::Error {
+            message: "boom".to_string(),
+        });
+        assert!(!job.expired(Instant::now()));
+        assert!(job.expired(later + Duration::from_secs(1)));
+    }
+}
@@ -0,0 +1,84 @@
+//! Binance kline interval strings.
+//!
+//! Requests carry intervals as free-form strings that are passed straight to
+//! the Binance API, so they are checked here first rather than surfacing as
+//! one exchange error per symbol.
+
+/// Intervals accepted by the Binance klines endpoint, shortest first.
+pub const SUPPORTED_INTERVALS: &[&str] = &[
+    "1m", "3m", "5m", "15m", "30m", "1h", "2h", "4h", "6h", "8h", "12h", "1d", "3d", "1w", "1M",
+];
+
+pub fn is_supported(interval: &str) -> bool {
+    SUPPORTED_INTERVALS.contains(&interval)
+}
+
+/// Length of one bar of `interval` in seconds; a month is taken as 30 days.
+pub fn seconds(interval: &str) -> Option<i64> {
+    let secs = match interval {
+        "1m" => 60,
+        "3m" => 180,
+        "5m" => 300,
+        "15m" => 900,
+        "30m" => 1800,
+        "1h" => 3600,
+        "2h" => 7200,
+        "4h" => 14400,
+        "6h" => 21600,
+        "8h" => 28800,
+        "12h" => 43200,
+        "1d" => 86400,
+        "3d" => 259_200,
+        "1w" => 604_800,
+        "1M" => 2_592_000,
+        _ => return None,
+    };
+    Some(secs)
+}
+
+/// Bars of `interval` in a 365-day year, for annualising per-bar ratios;
+/// daily bars are assumed for an unknown interval.
+pub fn periods_per_year(interval: &str) -> f64 {
+    seconds(interval)
+        .map(|secs| 365.0 * 86_400.0 / secs as f64)
+        .unwrap_or(365.0)
+}
+
+/// The entries of `intervals` Binance would reject, in request order.
+pub fn unsupported(intervals: &[String]) -> Vec<&str> {
+    intervals
+        .iter()
+        .map(String::as_str)
+        .filter(|i| !is_supported(i))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_supported_intervals() {
+        assert!(is_supported("1m"));
+        assert!(is_supported("4h"));
+        assert!(is_supported("1M"));
+        assert!(!is_supported("7m"));
+        assert!(!is_supported("1H"));
+        assert!(!is_supported(""));
+    }
+
+    #[test]
+    fn test_seconds_covers_supported_intervals() {
+        assert!(SUPPORTED_INTERVALS.iter().all(|i| seconds(i).is_some()));
+        assert_eq!(seconds("4h"), Some(14400));
+        assert_eq!(seconds("7m"), None);
+        assert!((periods_per_year("1h") - 8760.0).abs() < 1e-9);
+        assert!((periods_per_year("7m") - 365.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_unsupported_lists_invalid_in_order() {
+        let intervals = vec!["1h".to_string(), "7m".to_string(), "2d".to_string()];
+        assert_eq!(unsupported(&intervals), vec!["7m", "2d"]);
+    }
+}
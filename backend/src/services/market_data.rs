@@ -1,14 +1,153 @@
 //! Market data fetching from Binance REST API.
+//!
+//! Latest-candle fetches are cached per `(ticker, interval, limit)` for a
+//! short TTL, so sessions evaluating the same bar share one REST call.  An
+//! entry also expires when the bar it was fetched in closes.
+//!
+//! Klines requests that Binance rate limits (HTTP 429/418, error -1003) or
+//! fails transiently are retried with exponential backoff and jitter.  The
+//! REST client does not expose response headers, so `Retry-After` cannot be
+//! honoured; the backoff stands in for it.
 
-use binance::{api::Binance, market::Market, rest_model::KlineSummaries};
+use binance::{
+    api::Binance,
+    general::General,
+    market::Market,
+    rest_model::{Filters, KlineSummaries},
+};
 use chrono::{DateTime, Utc};
 use polars::prelude::*;
+use rand::Rng;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::warn;
 
 use crate::error::AppError;
+use crate::services::diagnostics::{DiagnosticKind, Diagnostics};
+use crate::services::interval;
+use crate::services::metrics::Metrics;
+use crate::services::symbols::SymbolResolver;
+
+/// Most klines Binance returns per request.
+const KLINES_PAGE_LIMIT: u16 = 1000;
+
+/// Cached latest candles, keyed by `(ticker, interval, limit)`.
+type CandleCache = HashMap<(String, String, u16), (DateTime<Utc>, Arc<Vec<CandleBar>>)>;
+
+/// Retries of failed klines requests.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Attempts in total, including the first; 1 disables retries.
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled on each further attempt.
+    pub base_delay: Duration,
+}
+
+impl RetryPolicy {
+    pub const NONE: Self = Self {
+        max_attempts: 1,
+        base_delay: Duration::ZERO,
+    };
+
+    /// Delay before retry number `retry` (from 0), scaled by `jitter` in
+    /// `[0.5, 1.0]` so concurrent callers spread out.
+    fn delay(&self, retry: u32, jitter: f64) -> Duration {
+        self.base_delay
+            .saturating_mul(1 << retry.min(16))
+            .mul_f64(jitter.clamp(0.5, 1.0))
+    }
+}
+
+/// How a failed Binance call should be handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Failure {
+    /// Too many requests, or an IP ban for ignoring them.
+    RateLimited,
+    /// Server or network trouble that may clear on its own.
+    Transient,
+    /// Bad symbol, interval or similar; retrying cannot help.
+    Permanent,
+}
+
+/// Classify a Binance client error by its message, the only detail the
+/// client exposes for non-400 responses.
+fn classify_failure(message: &str) -> Failure {
+    let m = message.to_ascii_lowercase();
+    if m.contains("429")
+        || m.contains("418")
+        || m.contains("too many requests")
+        || m.contains("-1003")
+    {
+        Failure::RateLimited
+    } else if m.contains("internal server error")
+        || m.contains("service unavailable")
+        || m.contains("timed out")
+        || m.contains("timeout")
+        || m.contains("connection")
+        || m.contains("502")
+        || m.contains("503")
+        || m.contains("504")
+    {
+        Failure::Transient
+    } else {
+        Failure::Permanent
+    }
+}
+
+/// Candle cache effectiveness, for tuning `CANDLE_CACHE_TTL_MS`.
+#[derive(Debug, Serialize)]
+pub struct CandleCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+    pub ttl_ms: u128,
+}
 
 /// Thin wrapper around the Binance market REST client.
+///
+/// Symbols passed in may be aliases; they are resolved to exchange tickers
+/// before each request.
 pub struct MarketDataService {
     market: Market,
+    general: General,
+    symbols: SymbolResolver,
+    diagnostics: Arc<Diagnostics>,
+    candle_cache_ttl: Duration,
+    candle_cache: Mutex<CandleCache>,
+    candle_cache_hits: AtomicU64,
+    candle_cache_misses: AtomicU64,
+    retry: RetryPolicy,
+    lot_size_ttl: Duration,
+    /// `LOT_SIZE` filters by ticker with when they were read from exchange
+    /// info, reloaded once older than `lot_size_ttl`.
+    lot_sizes: Mutex<Option<(DateTime<Utc>, Arc<HashMap<String, LotSize>>)>>,
+    metrics: Arc<Metrics>,
+}
+
+/// A symbol's `LOT_SIZE` filter: order quantities must be a whole number of
+/// `step_size` and at least `min_qty`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LotSize {
+    pub step_size: f64,
+    pub min_qty: f64,
+}
+
+impl LotSize {
+    /// `quantity` rounded down to a whole number of steps, or `None` if that
+    /// is below the minimum quantity or zero.
+    pub fn round_down(&self, quantity: f64) -> Option<f64> {
+        let rounded = if self.step_size > 0.0 {
+            // The epsilon keeps a quantity that is already a whole number of
+            // steps from losing one to floating-point error.
+            (quantity / self.step_size + 1e-9).floor() * self.step_size
+        } else {
+            quantity
+        };
+        (rounded > 0.0 && rounded >= self.min_qty).then_some(rounded)
+    }
 }
 
 /// A single OHLCV candlestick bar.
@@ -20,13 +159,185 @@ pub struct CandleBar {
     pub high: f64,
     pub low: f64,
     pub close: f64,
+    pub volume: f64,
 }
 
 impl MarketDataService {
-    /// Create a new service.  API keys are optional for public market data.
-    pub fn new(api_key: Option<String>, secret_key: Option<String>) -> Self {
-        let market: Market = Binance::new(api_key, secret_key);
-        Self { market }
+    /// Create a new service.  API keys are optional for public market data;
+    /// failed calls are recorded in `diagnostics`.  Candles and lot sizes are
+    /// not cached until [`with_candle_cache_ttl`](Self::with_candle_cache_ttl)
+    /// and [`with_lot_size_ttl`](Self::with_lot_size_ttl) are set.
+    pub fn new(
+        api_key: Option<String>,
+        secret_key: Option<String>,
+        symbols: SymbolResolver,
+        diagnostics: Arc<Diagnostics>,
+    ) -> Self {
+        let market: Market = Binance::new(api_key.clone(), secret_key.clone());
+        let general: General = Binance::new(api_key, secret_key);
+        Self {
+            market,
+            general,
+            symbols,
+            diagnostics,
+            candle_cache_ttl: Duration::ZERO,
+            candle_cache: Mutex::new(HashMap::new()),
+            candle_cache_hits: AtomicU64::new(0),
+            candle_cache_misses: AtomicU64::new(0),
+            retry: RetryPolicy::NONE,
+            lot_size_ttl: Duration::ZERO,
+            lot_sizes: Mutex::new(None),
+            metrics: Arc::default(),
+        }
+    }
+
+    /// Count REST calls in `metrics`.
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    fn count_request(&self, call: &str) {
+        self.metrics
+            .binance_requests
+            .with_label_values(&[call])
+            .inc();
+    }
+
+    /// Retry rate-limited and transient klines failures per `retry`.
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Reuse latest-candle fetches for up to `ttl`; zero disables caching.
+    pub fn with_candle_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.candle_cache_ttl = ttl;
+        self
+    }
+
+    /// Reuse the lot sizes read from exchange info for up to `ttl`; zero
+    /// disables caching.
+    pub fn with_lot_size_ttl(mut self, ttl: Duration) -> Self {
+        self.lot_size_ttl = ttl;
+        self
+    }
+
+    pub fn candle_cache_stats(&self) -> CandleCacheStats {
+        CandleCacheStats {
+            hits: self.candle_cache_hits.load(Ordering::Relaxed),
+            misses: self.candle_cache_misses.load(Ordering::Relaxed),
+            entries: self
+                .candle_cache
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .len(),
+            ttl_ms: self.candle_cache_ttl.as_millis(),
+        }
+    }
+
+    /// Record a failed Binance call and convert it to an [`AppError`].
+    fn binance_error(&self, call: &str, e: impl std::fmt::Display) -> AppError {
+        let message = format!("{call}: {e}");
+        self.diagnostics.record(DiagnosticKind::Binance, &message);
+        AppError::Binance(message)
+    }
+
+    /// Exchange ticker for a symbol or alias.
+    pub fn resolve_symbol(&self, symbol: &str) -> String {
+        self.symbols.resolve(symbol)
+    }
+
+    /// Quote asset of a symbol or alias, if recognisable.
+    pub fn quote_asset(&self, symbol: &str) -> Option<&'static str> {
+        self.symbols.quote_asset(symbol)
+    }
+
+    /// Latest traded price of an exchange ticker (not resolved).
+    pub async fn latest_price(&self, ticker: &str) -> Result<f64, AppError> {
+        self.count_request("price");
+        let price = self
+            .market
+            .get_price(ticker)
+            .await
+            .map_err(|e| self.binance_error(&format!("price {ticker}"), e))?;
+        Ok(price.price)
+    }
+
+    /// `LOT_SIZE` filter of an exchange ticker (not resolved), or `None` if
+    /// the exchange has no such symbol or filter.  Exchange info is reused
+    /// for `lot_size_ttl`, so filter changes are picked up after that.
+    pub async fn lot_size(&self, ticker: &str) -> Result<Option<LotSize>, AppError> {
+        let now = Utc::now();
+        let cached = self
+            .lot_sizes
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+            .filter(|(fetched_at, _)| {
+                now.signed_duration_since(*fetched_at)
+                    .to_std()
+                    .is_ok_and(|age| age < self.lot_size_ttl)
+            });
+        let lot_sizes = match cached {
+            Some((_, lot_sizes)) => lot_sizes,
+            None => {
+                self.count_request("exchange_info");
+                let info = self
+                    .general
+                    .exchange_info()
+                    .await
+                    .map_err(|e| self.binance_error("exchange info", e))?;
+                let lot_sizes: HashMap<String, LotSize> = info
+                    .symbols
+                    .into_iter()
+                    .filter_map(|s| {
+                        s.filters.iter().find_map(|f| match f {
+                            Filters::LotSize {
+                                min_qty, step_size, ..
+                            } => Some((
+                                s.symbol.clone(),
+                                LotSize {
+                                    step_size: *step_size,
+                                    min_qty: *min_qty,
+                                },
+                            )),
+                            _ => None,
+                        })
+                    })
+                    .collect();
+                let lot_sizes = Arc::new(lot_sizes);
+                *self.lot_sizes.lock().unwrap_or_else(|e| e.into_inner()) =
+                    Some((now, lot_sizes.clone()));
+                lot_sizes
+            }
+        };
+        Ok(lot_sizes.get(ticker).copied())
+    }
+
+    /// Resolve each of `symbols` and check the exchange lists it, returning
+    /// the tickers.
+    pub async fn validate_symbols(&self, symbols: &[String]) -> Result<Vec<String>, AppError> {
+        self.count_request("exchange_info");
+        let info = self
+            .general
+            .exchange_info()
+            .await
+            .map_err(|e| self.binance_error("exchange info", e))?;
+
+        symbols
+            .iter()
+            .map(|symbol| {
+                let ticker = self.resolve_symbol(symbol);
+                if info.symbols.iter().any(|s| s.symbol == ticker) {
+                    Ok(ticker)
+                } else {
+                    Err(AppError::BadRequest(format!(
+                        "{symbol} resolves to {ticker}, which is not listed on the exchange"
+                    )))
+                }
+            })
+            .collect()
     }
 
     /// Fetch the most recent `limit` OHLCV candles as a Polars [`DataFrame`].
@@ -38,47 +349,81 @@ impl MarketDataService {
         interval: &str,
         limit: u16,
     ) -> Result<DataFrame, AppError> {
-        let klines = self
-            .market
-            .get_klines(symbol, interval, Some(limit), None, None)
-            .await
-            .map_err(|e| AppError::Binance(e.to_string()))?;
+        let bars = self.fetch_candles_vec(symbol, interval, limit).await?;
+        bars_to_frame(&bars)
+    }
 
-        let KlineSummaries::AllKlineSummaries(data) = klines;
+    /// [`fetch_candles`](Self::fetch_candles), bypassing the cache.  The
+    /// fresh candles replace any cached entry.
+    pub async fn refresh_candles(
+        &self,
+        symbol: &str,
+        interval: &str,
+        limit: u16,
+    ) -> Result<DataFrame, AppError> {
+        let bars = self.fetch_latest(symbol, interval, limit).await?;
+        bars_to_frame(&bars)
+    }
 
-        let mut times = Vec::with_capacity(data.len());
-        let mut opens = Vec::with_capacity(data.len());
-        let mut highs = Vec::with_capacity(data.len());
-        let mut lows = Vec::with_capacity(data.len());
-        let mut closes = Vec::with_capacity(data.len());
-        let mut volumes = Vec::with_capacity(data.len());
+    /// Fetch every candle opening within `[start_ms, end_ms]` (Unix millis)
+    /// as a Polars [`DataFrame`] with the same columns as
+    /// [`fetch_candles`](Self::fetch_candles), in chronological order.
+    ///
+    /// Windows longer than one request's 1000-candle cap are fetched page by
+    /// page; a candle returned by two pages is kept once.
+    pub async fn fetch_candles_range(
+        &self,
+        symbol: &str,
+        interval: &str,
+        start_ms: i64,
+        end_ms: i64,
+    ) -> Result<DataFrame, AppError> {
+        let bars = self
+            .fetch_candles_range_vec(symbol, interval, start_ms, end_ms)
+            .await?;
+        bars_to_frame(&bars)
+    }
 
-        for k in data {
-            let dt = DateTime::<Utc>::from_timestamp_millis(k.open_time)
-                .map(|d| d.naive_utc())
-                .unwrap_or_else(|| Utc::now().naive_utc());
-            times.push(dt);
-            opens.push(k.open);
-            highs.push(k.high);
-            lows.push(k.low);
-            closes.push(k.close);
-            volumes.push(k.volume);
+    /// [`fetch_candles_range`](Self::fetch_candles_range) as a
+    /// `Vec<CandleBar>`, oldest first.
+    pub async fn fetch_candles_range_vec(
+        &self,
+        symbol: &str,
+        interval: &str,
+        start_ms: i64,
+        end_ms: i64,
+    ) -> Result<Vec<CandleBar>, AppError> {
+        if start_ms < 0 || start_ms > end_ms {
+            return Err(AppError::BadRequest(format!(
+                "Invalid candle range: start {start_ms} must not be after end {end_ms}"
+            )));
         }
 
-        let df = df!(
-            "time" => times,
-            "open" => opens,
-            "high" => highs,
-            "low" => lows,
-            "close" => closes,
-            "volume" => volumes
-        )
-        .map_err(|e| AppError::Data(e.to_string()))?;
+        let mut bars: Vec<CandleBar> = Vec::new();
+        let mut cursor = start_ms;
 
-        Ok(df)
+        while cursor <= end_ms {
+            let page = self
+                .fetch_klines(
+                    symbol,
+                    interval,
+                    KLINES_PAGE_LIMIT,
+                    Some(cursor as u64),
+                    Some(end_ms as u64),
+                )
+                .await?;
+            let page_len = page.len();
+            if !append_new_bars(&mut bars, page) || page_len < KLINES_PAGE_LIMIT as usize {
+                break;
+            }
+            cursor = bars.last().map_or(end_ms, |b| b.time) + 1;
+        }
+
+        Ok(bars)
     }
 
-    /// Fetch the most recent `limit` candles as a `Vec<CandleBar>`.
+    /// Fetch the most recent `limit` candles as a `Vec<CandleBar>`, oldest
+    /// first.
     ///
     /// Lighter than [`fetch_candles`](Self::fetch_candles) when a full
     /// DataFrame is not required (e.g. live charting endpoint).
@@ -88,25 +433,493 @@ impl MarketDataService {
         interval: &str,
         limit: u16,
     ) -> Result<Vec<CandleBar>, AppError> {
-        let klines = self
-            .market
-            .get_klines(symbol, interval, Some(limit), None, None)
-            .await
-            .map_err(|e| AppError::Binance(e.to_string()))?;
+        let key = (self.resolve_symbol(symbol), interval.to_string(), limit);
+        let cached = {
+            let cache = self.candle_cache.lock().unwrap_or_else(|e| e.into_inner());
+            cache.get(&key).and_then(|(fetched_at, bars)| {
+                is_fresh(*fetched_at, Utc::now(), self.candle_cache_ttl, interval)
+                    .then(|| bars.clone())
+            })
+        };
+        if let Some(bars) = cached {
+            self.candle_cache_hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(bars.as_ref().clone());
+        }
 
-        let KlineSummaries::AllKlineSummaries(data) = klines;
-        let mut out = Vec::with_capacity(data.len());
+        self.candle_cache_misses.fetch_add(1, Ordering::Relaxed);
+        Ok(self
+            .fetch_latest(symbol, interval, limit)
+            .await?
+            .as_ref()
+            .clone())
+    }
+
+    /// Fetch the latest candles from Binance and cache them.
+    async fn fetch_latest(
+        &self,
+        symbol: &str,
+        interval: &str,
+        limit: u16,
+    ) -> Result<Arc<Vec<CandleBar>>, AppError> {
+        let bars = Arc::new(
+            self.fetch_klines(symbol, interval, limit, None, None)
+                .await?,
+        );
+        if !self.candle_cache_ttl.is_zero() {
+            let now = Utc::now();
+            let mut cache = self.candle_cache.lock().unwrap_or_else(|e| e.into_inner());
+            cache.retain(|(_, interval, _), (fetched_at, _)| {
+                is_fresh(*fetched_at, now, self.candle_cache_ttl, interval)
+            });
+            cache.insert(
+                (self.resolve_symbol(symbol), interval.to_string(), limit),
+                (now, bars.clone()),
+            );
+        }
+        Ok(bars)
+    }
 
-        for k in data {
-            out.push(CandleBar {
+    /// One klines request for `symbol` (resolved here), parsed into bars.
+    /// Every candle fetch goes through this, so the frame and vector forms
+    /// cannot disagree.  Rate-limited and transient failures are retried per
+    /// the [`RetryPolicy`]; a request still rate limited after the last
+    /// attempt fails with [`AppError::RateLimited`].
+    async fn fetch_klines(
+        &self,
+        symbol: &str,
+        interval: &str,
+        limit: u16,
+        start_ms: Option<u64>,
+        end_ms: Option<u64>,
+    ) -> Result<Vec<CandleBar>, AppError> {
+        let ticker = self.resolve_symbol(symbol);
+        let mut retry = 0;
+        let klines = loop {
+            self.count_request("klines");
+            let err = match self
+                .market
+                .get_klines(ticker.as_str(), interval, Some(limit), start_ms, end_ms)
+                .await
+            {
+                Ok(klines) => break klines,
+                Err(e) => e.to_string(),
+            };
+
+            let failure = classify_failure(&err);
+            if failure == Failure::Permanent || retry + 1 >= self.retry.max_attempts {
+                let call = format!("klines {symbol} {interval}");
+                let e = self.binance_error(&call, err);
+                return Err(match (failure, e) {
+                    (Failure::RateLimited, AppError::Binance(message)) => {
+                        AppError::RateLimited(message)
+                    }
+                    (_, e) => e,
+                });
+            }
+
+            let delay = self
+                .retry
+                .delay(retry, rand::thread_rng().gen_range(0.5..=1.0));
+            warn!(
+                "klines {} {} failed ({:?}: {}); retry {} in {} ms",
+                symbol,
+                interval,
+                failure,
+                err,
+                retry + 1,
+                delay.as_millis()
+            );
+            tokio::time::sleep(delay).await;
+            retry += 1;
+        };
+
+        let KlineSummaries::AllKlineSummaries(data) = klines;
+        Ok(data
+            .into_iter()
+            .map(|k| CandleBar {
                 time: k.open_time,
                 open: k.open,
                 high: k.high,
                 low: k.low,
                 close: k.close,
-            });
+                volume: k.volume,
+            })
+            .collect())
+    }
+
+    /// Fetch the last `limit` bars of each of `symbols`, in order, joined on
+    /// open time: only bars whose open time every constituent has are kept,
+    /// so the legs line up bar for bar.  A recently listed symbol therefore
+    /// shortens the whole window.
+    pub async fn fetch_basket_legs_vec(
+        &self,
+        symbols: &[String],
+        interval: &str,
+        limit: u16,
+    ) -> Result<Vec<Vec<CandleBar>>, AppError> {
+        if symbols.is_empty() {
+            return Err(AppError::Data("Basket has no constituents".into()));
+        }
+
+        let mut series = Vec::with_capacity(symbols.len());
+        for symbol in symbols {
+            series.push(self.fetch_candles_vec(symbol, interval, limit).await?);
+        }
+
+        Ok(join_legs(series))
+    }
+
+    /// Fetch an equal-weight composite of `symbols` as a `Vec<CandleBar>`.
+    ///
+    /// Composite OHLC is the geometric mean of the constituents' values, so
+    /// each leg contributes equally to returns and the index level does not
+    /// depend on the fetch window.  Volume is the sum of constituent quote
+    /// volumes.  Legs are joined as in
+    /// [`fetch_basket_legs_vec`](Self::fetch_basket_legs_vec).
+    pub async fn fetch_basket_candles_vec(
+        &self,
+        symbols: &[String],
+        interval: &str,
+        limit: u16,
+    ) -> Result<Vec<CandleBar>, AppError> {
+        let legs = self.fetch_basket_legs_vec(symbols, interval, limit).await?;
+        Ok(composite_bars(&legs))
+    }
+
+    /// Fetch an equal-weight basket composite as a Polars [`DataFrame`] with
+    /// the same columns as [`fetch_candles`](Self::fetch_candles).
+    pub async fn fetch_basket_candles(
+        &self,
+        symbols: &[String],
+        interval: &str,
+        limit: u16,
+    ) -> Result<DataFrame, AppError> {
+        let bars = self
+            .fetch_basket_candles_vec(symbols, interval, limit)
+            .await?;
+        bars_to_frame(&bars)
+    }
+}
+
+/// Whether candles fetched at `fetched_at` may still be served at `now`:
+/// younger than `ttl` and fetched during the `interval` bar that is still
+/// open, so a close is never masked by a cached in-progress bar.
+fn is_fresh(fetched_at: DateTime<Utc>, now: DateTime<Utc>, ttl: Duration, interval: &str) -> bool {
+    let age = now.signed_duration_since(fetched_at);
+    let within_ttl = age.to_std().is_ok_and(|age| age < ttl);
+    let same_bar = interval::seconds(interval)
+        .is_some_and(|secs| fetched_at.timestamp() / secs == now.timestamp() / secs);
+    within_ttl && same_bar
+}
+
+/// Append the bars of `page` that open after the last bar of `bars`,
+/// returning whether any were added.
+fn append_new_bars(bars: &mut Vec<CandleBar>, page: impl IntoIterator<Item = CandleBar>) -> bool {
+    let before = bars.len();
+    for bar in page {
+        if bars.last().is_none_or(|last| bar.time > last.time) {
+            bars.push(bar);
+        }
+    }
+    bars.len() > before
+}
+
+/// Keep the bars of each series whose open time every series has.
+fn join_legs(series: Vec<Vec<CandleBar>>) -> Vec<Vec<CandleBar>> {
+    let Some((first, rest)) = series.split_first() else {
+        return Vec::new();
+    };
+    let rest_times: Vec<HashSet<i64>> = rest
+        .iter()
+        .map(|s| s.iter().map(|bar| bar.time).collect())
+        .collect();
+    let common: HashSet<i64> = first
+        .iter()
+        .map(|bar| bar.time)
+        .filter(|time| rest_times.iter().all(|times| times.contains(time)))
+        .collect();
+    series
+        .into_iter()
+        .map(|s| {
+            s.into_iter()
+                .filter(|bar| common.contains(&bar.time))
+                .collect()
+        })
+        .collect()
+}
+
+/// Combine constituent series joined by [`join_legs`] into one equal-weight
+/// series.
+fn composite_bars(legs: &[Vec<CandleBar>]) -> Vec<CandleBar> {
+    let len = legs.iter().map(Vec::len).min().unwrap_or(0);
+    if len == 0 {
+        return Vec::new();
+    }
+
+    let n = legs.len() as f64;
+    let geo_mean = |f: fn(&CandleBar) -> f64, i: usize| {
+        (legs.iter().map(|s| f(&s[i]).ln()).sum::<f64>() / n).exp()
+    };
+
+    (0..len)
+        .map(|i| CandleBar {
+            time: legs[0][i].time,
+            open: geo_mean(|k| k.open, i),
+            high: geo_mean(|k| k.high, i),
+            low: geo_mean(|k| k.low, i),
+            close: geo_mean(|k| k.close, i),
+            volume: legs.iter().map(|s| s[i].volume * s[i].close).sum(),
+        })
+        .collect()
+}
+
+/// Bars of a basket position split equally across joined `legs` at entry,
+/// priced so that a move from `entry_price` is the position's return: each
+/// value is `entry_price` times the mean of the legs' values relative to
+/// their `leg_entries`.  Falls back to the [`composite_bars`] when there is
+/// no split to price, i.e. while flat or for a position opened before legs
+/// were recorded.
+pub(crate) fn split_position_bars(
+    legs: &[Vec<CandleBar>],
+    entry_price: Option<f64>,
+    leg_entries: Option<&[f64]>,
+) -> Vec<CandleBar> {
+    let (Some(entry_price), Some(leg_entries)) = (entry_price, leg_entries) else {
+        return composite_bars(legs);
+    };
+    if leg_entries.len() != legs.len() || leg_entries.iter().any(|e| *e <= 0.0) {
+        return composite_bars(legs);
+    }
+
+    let n = legs.len() as f64;
+    let split = |f: fn(&CandleBar) -> f64, i: usize| {
+        entry_price
+            * legs
+                .iter()
+                .zip(leg_entries)
+                .map(|(s, entry)| f(&s[i]) / entry)
+                .sum::<f64>()
+            / n
+    };
+    let composite = composite_bars(legs);
+    composite
+        .into_iter()
+        .enumerate()
+        .map(|(i, bar)| CandleBar {
+            open: split(|k| k.open, i),
+            high: split(|k| k.high, i),
+            low: split(|k| k.low, i),
+            close: split(|k| k.close, i),
+            ..bar
+        })
+        .collect()
+}
+
+/// Convert bars into the `time/open/high/low/close/volume` frame layout.
+pub(crate) fn bars_to_frame(bars: &[CandleBar]) -> Result<DataFrame, AppError> {
+    let times: Vec<_> = bars
+        .iter()
+        .map(|b| {
+            DateTime::<Utc>::from_timestamp_millis(b.time)
+                .map(|d| d.naive_utc())
+                .unwrap_or_else(|| Utc::now().naive_utc())
+        })
+        .collect();
+
+    df!(
+        "time" => times,
+        "open" => bars.iter().map(|b| b.open).collect::<Vec<_>>(),
+        "high" => bars.iter().map(|b| b.high).collect::<Vec<_>>(),
+        "low" => bars.iter().map(|b| b.low).collect::<Vec<_>>(),
+        "close" => bars.iter().map(|b| b.close).collect::<Vec<_>>(),
+        "volume" => bars.iter().map(|b| b.volume).collect::<Vec<_>>()
+    )
+    .map_err(|e| AppError::Data(e.to_string()))
+}
+
+/// Type a required candle column must have.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnKind {
+    Datetime,
+    Float,
+}
+
+impl ColumnKind {
+    fn accepts(self, dtype: &DataType) -> bool {
+        match self {
+            Self::Datetime => matches!(dtype, DataType::Datetime(_, _)),
+            Self::Float => matches!(dtype, DataType::Float32 | DataType::Float64),
         }
+    }
+}
+
+/// Columns `FeatureEngine::add_technicals` reads, in frame order.
+pub const CANDLE_COLUMNS: &[(&str, ColumnKind)] = &[
+    ("time", ColumnKind::Datetime),
+    ("open", ColumnKind::Float),
+    ("high", ColumnKind::Float),
+    ("low", ColumnKind::Float),
+    ("close", ColumnKind::Float),
+    ("volume", ColumnKind::Float),
+];
+
+/// Check that `df` has every column in `columns` with the expected type, so
+/// a malformed frame fails here with the column named instead of deep in the
+/// feature pass.
+pub fn validate_columns(df: &DataFrame, columns: &[(&str, ColumnKind)]) -> Result<(), AppError> {
+    for (name, kind) in columns {
+        let column = df
+            .column(name)
+            .map_err(|_| AppError::Data(format!("Candle frame is missing column `{name}`")))?;
+        if !kind.accepts(column.dtype()) {
+            return Err(AppError::Data(format!(
+                "Candle column `{name}` is {}, expected {kind:?}",
+                column.dtype()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// [`validate_columns`] against [`CANDLE_COLUMNS`].
+pub fn validate_candle_frame(df: &DataFrame) -> Result<(), AppError> {
+    validate_columns(df, CANDLE_COLUMNS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bar(time: i64) -> CandleBar {
+        CandleBar {
+            time,
+            open: 1.0,
+            high: 1.0,
+            low: 1.0,
+            close: 1.0,
+            volume: 1.0,
+        }
+    }
+
+    #[test]
+    fn test_cached_candles_expire_on_ttl_and_bar_close() {
+        let at = |s: i64| DateTime::<Utc>::from_timestamp(s, 0).unwrap();
+        let ttl = Duration::from_secs(30);
+
+        assert!(is_fresh(at(600), at(620), ttl, "5m"));
+        assert!(!is_fresh(at(600), at(631), ttl, "5m"));
+        // 890 s and 905 s straddle the 900 s bar boundary.
+        assert!(!is_fresh(at(890), at(905), ttl, "5m"));
+        assert!(!is_fresh(at(600), at(600), Duration::ZERO, "5m"));
+    }
+
+    #[test]
+    fn test_classify_failure() {
+        assert_eq!(
+            classify_failure("Received response: 429 Too Many Requests"),
+            Failure::RateLimited
+        );
+        assert_eq!(
+            classify_failure("Received response: 418 I'm a teapot"),
+            Failure::RateLimited
+        );
+        assert_eq!(
+            classify_failure("Internal Server Error"),
+            Failure::Transient
+        );
+        assert_eq!(
+            classify_failure("-1121: Invalid symbol."),
+            Failure::Permanent
+        );
+    }
+
+    #[test]
+    fn test_retry_delay_doubles_with_jitter() {
+        let policy = RetryPolicy {
+            max_attempts: 4,
+            base_delay: Duration::from_millis(500),
+        };
+        assert_eq!(policy.delay(0, 1.0), Duration::from_millis(500));
+        assert_eq!(policy.delay(2, 1.0), Duration::from_millis(2000));
+        assert_eq!(policy.delay(2, 0.5), Duration::from_millis(1000));
+        assert_eq!(policy.delay(1, 0.0), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_append_new_bars_skips_overlapping_boundary() {
+        let mut bars = vec![bar(0), bar(60_000)];
+        assert!(append_new_bars(
+            &mut bars,
+            [bar(60_000), bar(120_000), bar(180_000)]
+        ));
+        let times: Vec<i64> = bars.iter().map(|b| b.time).collect();
+        assert_eq!(times, vec![0, 60_000, 120_000, 180_000]);
+
+        assert!(!append_new_bars(&mut bars, [bar(180_000)]));
+    }
+
+    #[test]
+    fn test_join_legs_drops_bars_missing_from_a_leg() {
+        let legs = join_legs(vec![
+            vec![bar(0), bar(60_000), bar(120_000)],
+            vec![bar(0), bar(120_000), bar(180_000)],
+        ]);
+        for leg in &legs {
+            let times: Vec<i64> = leg.iter().map(|b| b.time).collect();
+            assert_eq!(times, vec![0, 120_000]);
+        }
+        assert_eq!(composite_bars(&legs).len(), 2);
+    }
+
+    #[test]
+    fn test_split_position_bars_average_leg_returns() {
+        let priced = |close| CandleBar { close, ..bar(0) };
+        let legs = vec![vec![priced(120.0)], vec![priced(40.0)]];
+        // Legs entered at 100 and 50: +20% and -20% average to flat.
+        let split = split_position_bars(&legs, Some(10.0), Some(&[100.0, 50.0]));
+        assert!((split[0].close - 10.0).abs() < 1e-9);
+        // Without recorded legs the position follows the composite.
+        let composite = split_position_bars(&legs, Some(10.0), None);
+        assert!((composite[0].close - (120.0_f64 * 40.0).sqrt()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_validate_candle_frame_names_bad_column() {
+        let df = bars_to_frame(&[bar(0), bar(60_000)]).unwrap();
+        assert!(validate_candle_frame(&df).is_ok());
+
+        let missing = df.drop("volume").unwrap();
+        match validate_candle_frame(&missing) {
+            Err(AppError::Data(msg)) => assert!(msg.contains("`volume`"), "{msg}"),
+            other => panic!("expected a data error, got {other:?}"),
+        }
+
+        let mut wrong = df.clone();
+        wrong
+            .replace("close", Series::new("close", &["a", "b"]))
+            .unwrap();
+        match validate_candle_frame(&wrong) {
+            Err(AppError::Data(msg)) => assert!(msg.contains("`close`"), "{msg}"),
+            other => panic!("expected a data error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_lot_size_rounds_down_to_step() {
+        let lot = LotSize {
+            step_size: 0.001,
+            min_qty: 0.01,
+        };
+
+        assert!((lot.round_down(0.123_456).unwrap() - 0.123).abs() < 1e-12);
+        assert!((lot.round_down(0.5).unwrap() - 0.5).abs() < 1e-12);
+        assert_eq!(lot.round_down(0.009_9), None);
+        assert_eq!(lot.round_down(0.0), None);
 
-        Ok(out)
+        let unstepped = LotSize {
+            step_size: 0.0,
+            min_qty: 0.0,
+        };
+        assert_eq!(unstepped.round_down(0.37), Some(0.37));
     }
 }
@@ -1,26 +1,98 @@
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
 use binance::config::Config;
 use binance::websockets::WebSockets;
 use binance::ws_model::{CombinedStreamEvent, WebsocketEventUntag};
 use tokio::sync::mpsc::UnboundedSender;
+use tokio_util::sync::CancellationToken;
 use tracing::{error, info};
 
+use crate::services::diagnostics::{DiagnosticKind, Diagnostics};
+use crate::services::metrics::Metrics;
+
+/// First reconnect delay, and the delay again after a healthy connection.
+pub const RECONNECT_FLOOR: Duration = Duration::from_secs(1);
+
+/// A connection that stayed up this long resets [`ReconnectBackoff`].
+pub const HEALTHY_CONNECTION: Duration = Duration::from_secs(60);
+
+/// Delays between stream reconnect attempts: doubling from
+/// [`RECONNECT_FLOOR`] up to a cap, so a flaky connection does not hammer
+/// Binance with reconnects.
+#[derive(Debug)]
+pub struct ReconnectBackoff {
+    cap: Duration,
+    next: Duration,
+    attempt: u32,
+}
+
+impl ReconnectBackoff {
+    pub fn new(cap: Duration) -> Self {
+        Self {
+            cap: cap.max(RECONNECT_FLOOR),
+            next: RECONNECT_FLOOR,
+            attempt: 0,
+        }
+    }
+
+    /// Delay before the next attempt, scaled by `jitter` in `[0.5, 1.0]` so
+    /// restarted engines spread out.  The attempt number is returned with it
+    /// for logging.
+    pub fn next_delay(&mut self, jitter: f64) -> (u32, Duration) {
+        let delay = self.next.mul_f64(jitter.clamp(0.5, 1.0));
+        self.next = self.next.saturating_mul(2).min(self.cap);
+        self.attempt += 1;
+        (self.attempt, delay)
+    }
+
+    /// Start again from the floor.
+    pub fn reset(&mut self) {
+        self.next = RECONNECT_FLOOR;
+        self.attempt = 0;
+    }
+}
+
 /// Thin wrapper around the binance-rs-async websocket client to stream kline data.
 pub struct MarketStream {
     keep_running: Arc<AtomicBool>,
+    /// Cancelled by [`stop`](Self::stop) or when the parent shutdown token is.
+    cancel: CancellationToken,
+    /// Cancelled when the connection task ends, for whatever reason.
+    closed: CancellationToken,
+    diagnostics: Arc<Diagnostics>,
+    metrics: Arc<Metrics>,
 }
 
 impl MarketStream {
-    pub fn new() -> Self {
+    /// A stream reporting connection failures to `diagnostics` and counting
+    /// lost connections in `metrics`, which disconnects once `shutdown` is
+    /// cancelled.
+    pub fn new(
+        diagnostics: Arc<Diagnostics>,
+        metrics: Arc<Metrics>,
+        shutdown: &CancellationToken,
+    ) -> Self {
         Self {
             keep_running: Arc::new(AtomicBool::new(true)),
+            cancel: shutdown.child_token(),
+            closed: CancellationToken::new(),
+            diagnostics,
+            metrics,
         }
     }
 
+    /// Disconnect the stream.  A stopped stream cannot be started again.
     pub fn stop(&self) {
         self.keep_running.store(false, Ordering::Relaxed);
+        self.cancel.cancel();
+    }
+
+    /// Resolves once the connection has ended: it failed, the server closed
+    /// it or it was stopped.
+    pub async fn closed(&self) {
+        self.closed.cancelled().await;
     }
 
     /// Start a combined websocket stream for the provided symbol-interval pairs.
@@ -29,8 +101,11 @@ impl MarketStream {
         subscriptions: Vec<(String, String)>,
         tx: UnboundedSender<CombinedStreamEvent<WebsocketEventUntag>>,
     ) {
-        self.keep_running.store(true, Ordering::Relaxed);
         let keep_running = self.keep_running.clone();
+        let cancel = self.cancel.clone();
+        let closed = self.closed.clone();
+        let diagnostics = self.diagnostics.clone();
+        let metrics = self.metrics.clone();
         let conf = websocket_config_from_env();
         let ws_base = conf.ws_endpoint.clone();
         let streams: Vec<String> = subscriptions
@@ -39,6 +114,7 @@ impl MarketStream {
             .collect();
 
         tokio::spawn(async move {
+            let _closed = closed.drop_guard();
             let mut web_socket: WebSockets<'static, CombinedStreamEvent<WebsocketEventUntag>> =
                 WebSockets::new_with_options(
                     move |event: CombinedStreamEvent<WebsocketEventUntag>| {
@@ -56,11 +132,31 @@ impl MarketStream {
             );
             if let Err(e) = web_socket.connect_multiple(streams).await {
                 error!("WebSocket connection error: {:?}", e);
+                diagnostics.record(
+                    DiagnosticKind::Stream,
+                    format!("WebSocket connection error: {e:?}"),
+                );
+                metrics.stream_reconnects.inc();
                 return;
             }
 
-            if let Err(e) = web_socket.event_loop(&keep_running).await {
-                error!("WebSocket event loop error: {:?}", e);
+            // The event loop only checks `keep_running` when a message
+            // arrives, so a quiet stream is also stopped by cancellation.
+            tokio::select! {
+                result = web_socket.event_loop(&keep_running) => {
+                    if let Err(e) = result {
+                        error!("WebSocket event loop error: {:?}", e);
+                        diagnostics.record(
+                            DiagnosticKind::Stream,
+                            format!("WebSocket event loop error: {e:?}"),
+                        );
+                    }
+                    // Ended by the connection rather than by `stop`.
+                    if keep_running.load(Ordering::Relaxed) {
+                        metrics.stream_reconnects.inc();
+                    }
+                }
+                _ = cancel.cancelled() => {}
             }
 
             if let Err(e) = web_socket.disconnect().await {
@@ -80,3 +176,31 @@ fn websocket_config_from_env() -> Config {
     }
     conf
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_to_cap_and_resets() {
+        let mut backoff = ReconnectBackoff::new(Duration::from_secs(5));
+        let delays: Vec<u64> = (0..5)
+            .map(|_| backoff.next_delay(1.0).1.as_secs())
+            .collect();
+        assert_eq!(delays, vec![1, 2, 4, 5, 5]);
+
+        let (attempt, jittered) = backoff.next_delay(0.5);
+        assert_eq!(attempt, 6);
+        assert_eq!(jittered, Duration::from_millis(2500));
+
+        backoff.reset();
+        assert_eq!(backoff.next_delay(1.0), (1, RECONNECT_FLOOR));
+    }
+
+    #[test]
+    fn test_backoff_cap_is_at_least_the_floor() {
+        let mut backoff = ReconnectBackoff::new(Duration::ZERO);
+        backoff.next_delay(1.0);
+        assert_eq!(backoff.next_delay(1.0).1, RECONNECT_FLOOR);
+    }
+}
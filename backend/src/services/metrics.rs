@@ -0,0 +1,124 @@
+//! Prometheus metrics, for `GET /metrics`.
+//!
+//! One [`Metrics`] registry is shared by the engine, the market stream, the
+//! Binance client and the portfolio manager, which update it as they work.
+//! Gauges that are cheaper to read than to track (active sessions, cache
+//! age) are set by the endpoint when it is scraped.
+
+use prometheus::{Encoder, Gauge, IntCounter, IntCounterVec, IntGauge, Opts, Registry};
+
+use crate::error::AppError;
+
+/// Every metric the app exports.  Cheap to share behind an `Arc`.
+pub struct Metrics {
+    registry: Registry,
+    pub active_sessions: IntGauge,
+    /// Klines handled by the engine, by exchange ticker.
+    pub klines_processed: IntCounterVec,
+    pub trades_executed: IntCounter,
+    /// Stream connections refused or lost, each followed by a reconnect.
+    pub stream_reconnects: IntCounter,
+    /// Minute points in `portfolio_cache` after the last update.
+    pub portfolio_cache_points: IntGauge,
+    /// Seconds since the last cache rebuild; NaN before the first.
+    pub portfolio_cache_age_seconds: Gauge,
+    /// Binance REST calls by kind (`klines`, `price`, `exchange_info`),
+    /// retries included.
+    pub binance_requests: IntCounterVec,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        let registry = Registry::new_custom(Some("krypto".into()), None)
+            .expect("static registry prefix is valid");
+        let metrics = Self {
+            active_sessions: IntGauge::new("active_sessions", "Sessions with status active")
+                .expect("valid metric"),
+            klines_processed: IntCounterVec::new(
+                Opts::new("klines_processed_total", "Klines handled by the engine"),
+                &["symbol"],
+            )
+            .expect("valid metric"),
+            trades_executed: IntCounter::new("trades_executed_total", "Trades written")
+                .expect("valid metric"),
+            stream_reconnects: IntCounter::new(
+                "stream_reconnects_total",
+                "Websocket connections refused or lost, each followed by a reconnect",
+            )
+            .expect("valid metric"),
+            portfolio_cache_points: IntGauge::new(
+                "portfolio_cache_points",
+                "Points in the portfolio cache after the last rebuild",
+            )
+            .expect("valid metric"),
+            portfolio_cache_age_seconds: Gauge::new(
+                "portfolio_cache_age_seconds",
+                "Seconds since the portfolio cache was last rebuilt",
+            )
+            .expect("valid metric"),
+            binance_requests: IntCounterVec::new(
+                Opts::new(
+                    "binance_requests_total",
+                    "Binance REST calls, retries included",
+                ),
+                &["call"],
+            )
+            .expect("valid metric"),
+            registry,
+        };
+        metrics.portfolio_cache_age_seconds.set(f64::NAN);
+        metrics.register_all();
+        metrics
+    }
+}
+
+impl Metrics {
+    fn register_all(&self) {
+        let collectors: [Box<dyn prometheus::core::Collector>; 7] = [
+            Box::new(self.active_sessions.clone()),
+            Box::new(self.klines_processed.clone()),
+            Box::new(self.trades_executed.clone()),
+            Box::new(self.stream_reconnects.clone()),
+            Box::new(self.portfolio_cache_points.clone()),
+            Box::new(self.portfolio_cache_age_seconds.clone()),
+            Box::new(self.binance_requests.clone()),
+        ];
+        for collector in collectors {
+            self.registry
+                .register(collector)
+                .expect("each metric is registered once");
+        }
+    }
+
+    /// Every metric in the Prometheus text exposition format.
+    pub fn encode(&self) -> Result<String, AppError> {
+        let mut buf = Vec::new();
+        prometheus::TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buf)
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+        String::from_utf8(buf).map_err(|e| AppError::Internal(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_exports_prefixed_metrics() {
+        let metrics = Metrics::default();
+        metrics
+            .klines_processed
+            .with_label_values(&["BTCUSDT"])
+            .inc_by(3);
+        metrics.trades_executed.inc();
+        metrics.active_sessions.set(2);
+
+        let text = metrics.encode().unwrap();
+        assert!(text.contains("krypto_klines_processed_total{symbol=\"BTCUSDT\"} 3"));
+        assert!(text.contains("krypto_trades_executed_total 1"));
+        assert!(text.contains("krypto_active_sessions 2"));
+        assert!(text.contains("# TYPE krypto_stream_reconnects_total counter"));
+        assert!(text.contains("krypto_portfolio_cache_age_seconds NaN"));
+    }
+}
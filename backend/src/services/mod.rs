@@ -1,5 +1,29 @@
+pub mod backfill;
+pub mod backtest;
+pub mod bar_explain;
+pub mod capital_flows;
+pub mod correlation;
+pub mod diagnostics;
+pub mod drawdown;
+pub mod engine_status;
+pub mod generation_estimate;
+pub mod generation_jobs;
+pub mod interval;
 pub mod market_data;
 pub mod market_stream;
+pub mod metrics;
+pub mod notifier;
+pub mod paper_broker;
 pub mod portfolio_manager;
+pub mod replay;
+pub mod risk_metrics;
+pub mod session_events;
+pub mod snapshot_budget;
+pub mod strategy_diff;
 pub mod strategy_generator;
+pub mod strategy_registry;
+pub mod strategy_schema;
+pub mod stream_capture;
+pub mod symbols;
+pub mod trade_import;
 pub mod trading_engine;
@@ -0,0 +1,126 @@
+//! Webhook notifications for alert-only sessions.
+//!
+//! With `ALERT_WEBHOOK_URL` set, each change of an alert session's signal
+//! direction is POSTed there as JSON.  Delivery runs in the background and is
+//! not retried; failures are logged and recorded in [`Diagnostics`].
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::services::diagnostics::{DiagnosticKind, Diagnostics};
+
+/// Longest a webhook delivery may take.
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Body of an alert webhook.
+#[derive(Debug, Clone, Serialize)]
+pub struct SignalAlert {
+    pub session_id: Uuid,
+    pub strategy_id: Uuid,
+    pub strategy_type: String,
+    pub symbol: String,
+    pub interval: String,
+    /// `long`, `short` or `flat`.
+    pub direction: &'static str,
+    pub signal: f64,
+    pub previous_signal: Option<f64>,
+    pub price: f64,
+    pub at: DateTime<Utc>,
+    /// Human-readable summary of the change.
+    pub explanation: String,
+}
+
+/// Direction of a signal value.
+pub fn direction(signal: f64) -> &'static str {
+    if signal > 0.0 {
+        "long"
+    } else if signal < 0.0 {
+        "short"
+    } else {
+        "flat"
+    }
+}
+
+pub struct Notifier {
+    /// Webhook URL and the client posting to it.
+    webhook: Option<(String, reqwest::Client)>,
+    diagnostics: Arc<Diagnostics>,
+}
+
+impl Notifier {
+    /// A notifier posting to `webhook_url`; without one, alerts are only
+    /// logged.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the HTTP client for the webhook cannot be built.
+    pub fn new(
+        webhook_url: Option<String>,
+        diagnostics: Arc<Diagnostics>,
+    ) -> Result<Self, reqwest::Error> {
+        let webhook = webhook_url
+            .map(|url| {
+                reqwest::Client::builder()
+                    .timeout(WEBHOOK_TIMEOUT)
+                    .build()
+                    .map(|client| (url, client))
+            })
+            .transpose()?;
+        Ok(Self {
+            webhook,
+            diagnostics,
+        })
+    }
+
+    /// Deliver `alert` in the background.
+    pub fn notify(&self, alert: SignalAlert) {
+        let Some((url, client)) = self.webhook.clone() else {
+            return;
+        };
+        let diagnostics = self.diagnostics.clone();
+        tokio::spawn(async move {
+            let result = client
+                .post(&url)
+                .json(&alert)
+                .send()
+                .await
+                .and_then(|r| r.error_for_status());
+            if let Err(e) = result {
+                warn!(
+                    "Alert webhook for session {} failed: {}",
+                    alert.session_id, e
+                );
+                diagnostics.record(
+                    DiagnosticKind::Engine,
+                    format!("Alert webhook for session {} failed: {e}", alert.session_id),
+                );
+            }
+        });
+    }
+}
+
+impl Default for Notifier {
+    fn default() -> Self {
+        Self {
+            webhook: None,
+            diagnostics: Arc::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_direction() {
+        assert_eq!(direction(0.4), "long");
+        assert_eq!(direction(-1.0), "short");
+        assert_eq!(direction(0.0), "flat");
+    }
+}
@@ -1,12 +1,34 @@
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
-use chrono::{DateTime, Duration as ChronoDuration, Timelike, Utc};
-use sqlx::{FromRow, PgPool, QueryBuilder};
-use tracing::{error, info};
+use chrono::{DateTime, Duration as ChronoDuration, DurationRound, Utc};
+use serde::Serialize;
+use sqlx::{FromRow, PgConnection, PgPool, QueryBuilder};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
+use crate::config::Config;
+use crate::models::strategy::{Session, SessionTrigger};
+use crate::services::capital_flows::{self, CapitalFlows};
+use crate::services::market_data::MarketDataService;
+use crate::services::metrics::Metrics;
+
+/// How long fetched FX rates are reused before being refreshed.
+const FX_REFRESH_SECS: i64 = 300;
+
+/// Minutes behind the newest cached point that each cache update
+/// recomputes, rounded up to whole cache steps.  Engine snapshots are
+/// stamped before their transaction commits, so one can become visible
+/// after its step was cached.
+const SETTLE_MINUTES: i64 = 5;
+
+/// `date_trunc` units snapshots over `max_snapshots_per_session` are thinned
+/// to, finest first.
+const SNAPSHOT_THINNING_BUCKETS: [&str; 2] = ["hour", "day"];
+
 #[derive(FromRow)]
 struct SnapshotRow {
     session_id: Uuid,
@@ -14,92 +36,1008 @@ struct SnapshotRow {
     timestamp: DateTime<Utc>,
 }
 
+/// State of the `portfolio_cache` rebuild, reported by `/portfolio/cache-status`.
+#[derive(Debug, Serialize)]
+pub struct CacheStatus {
+    pub last_rebuild_at: Option<DateTime<Utc>>,
+    pub in_progress: bool,
+    pub min_interval_secs: u64,
+}
+
+/// Latest rates converting each quote asset into the reporting currency.
+#[derive(Default)]
+struct FxRates {
+    rates: HashMap<String, f64>,
+    fetched_at: Option<DateTime<Utc>>,
+}
+
+impl FxRates {
+    fn is_stale(&self, now: DateTime<Utc>) -> bool {
+        self.fetched_at
+            .is_none_or(|at| now - at >= ChronoDuration::seconds(FX_REFRESH_SECS))
+    }
+
+    /// Rate of `quote`, if one has been fetched.
+    fn rate(&self, quote: &str) -> Option<f64> {
+        self.rates.get(quote).copied()
+    }
+}
+
 pub struct PortfolioManager {
     pool: PgPool,
+    config: Config,
+    market: Arc<MarketDataService>,
+    fx_rates: Mutex<FxRates>,
+    /// Held for the duration of a rebuild, so only one runs at a time.
+    rebuild_lock: tokio::sync::Mutex<()>,
+    last_rebuild: Mutex<Option<DateTime<Utc>>>,
+    /// Where the next update resumes; `None` until the first one, or after
+    /// one failed.
+    cache_state: Mutex<Option<CacheState>>,
+    /// Set when snapshots were written into already-cached minutes.
+    full_rebuild_requested: AtomicBool,
+    metrics: Arc<Metrics>,
 }
 
 impl PortfolioManager {
-    pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+    pub fn new(pool: PgPool, config: Config, market: Arc<MarketDataService>) -> Self {
+        Self {
+            pool,
+            config,
+            market,
+            fx_rates: Mutex::new(FxRates::default()),
+            rebuild_lock: tokio::sync::Mutex::new(()),
+            last_rebuild: Mutex::new(None),
+            cache_state: Mutex::new(None),
+            full_rebuild_requested: AtomicBool::new(false),
+            metrics: Arc::default(),
+        }
+    }
+
+    /// Report the cache size after each rebuild in `metrics`.
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Rebuild the portfolio cache unless one is already running or the last
+    /// finished less than `portfolio_rebuild_min_interval_secs` ago; such
+    /// requests are coalesced into the existing rebuild.  Returns whether a
+    /// rebuild ran.
+    pub async fn request_rebuild(&self) -> Result<bool, sqlx::Error> {
+        let Ok(_guard) = self.rebuild_lock.try_lock() else {
+            return Ok(false);
+        };
+        let min_interval =
+            ChronoDuration::seconds(self.config.portfolio_rebuild_min_interval_secs as i64);
+        if !rebuild_due(
+            *self.last_rebuild.lock().unwrap_or_else(|e| e.into_inner()),
+            Utc::now(),
+            min_interval,
+        ) {
+            return Ok(false);
+        }
+
+        self.update_cache().await?;
+        *self.last_rebuild.lock().unwrap_or_else(|e| e.into_inner()) = Some(Utc::now());
+        Ok(true)
+    }
+
+    /// Rebuild the cache now, after any rebuild already running and
+    /// regardless of the minimum interval.
+    async fn flush_cache(&self) -> Result<(), sqlx::Error> {
+        let _guard = self.rebuild_lock.lock().await;
+        self.update_cache().await?;
+        *self.last_rebuild.lock().unwrap_or_else(|e| e.into_inner()) = Some(Utc::now());
+        Ok(())
     }
 
-    pub async fn start_background_task(self: Arc<Self>) {
-        info!("Portfolio Manager started. Syncing cache every 60s.");
+    /// Spacing in seconds of `portfolio_cache` points.
+    pub fn resolution_secs(&self) -> i64 {
+        self.config.portfolio_cache_resolution_secs
+    }
 
-        if let Err(e) = self.update_cache().await {
+    fn step(&self) -> ChronoDuration {
+        ChronoDuration::seconds(self.resolution_secs())
+    }
+
+    /// Make the next update rebuild the whole cache.  Call after writing
+    /// snapshots dated before the newest cached point, which an incremental
+    /// update would not revisit.
+    pub fn invalidate_cache(&self) {
+        self.full_rebuild_requested.store(true, Ordering::Release);
+    }
+
+    pub fn cache_status(&self) -> CacheStatus {
+        CacheStatus {
+            last_rebuild_at: *self.last_rebuild.lock().unwrap_or_else(|e| e.into_inner()),
+            in_progress: self.rebuild_lock.try_lock().is_err(),
+            min_interval_secs: self.config.portfolio_rebuild_min_interval_secs,
+        }
+    }
+
+    /// Evaluate triggers, thin snapshots and update the cache every
+    /// `portfolio_sync_interval_secs`.  On `shutdown`, update the cache once
+    /// more to cover the engine's last writes.
+    pub async fn start_background_task(self: Arc<Self>, shutdown: CancellationToken) {
+        let period = self.config.portfolio_sync_interval_secs;
+        info!(
+            "Portfolio Manager started. Syncing cache every {}s.",
+            period
+        );
+
+        if let Err(e) = self.request_rebuild().await {
             error!("Initial portfolio cache update failed: {}", e);
         }
 
-        let mut interval = tokio::time::interval(Duration::from_secs(60));
+        let mut interval = tokio::time::interval(Duration::from_secs(period));
         loop {
-            interval.tick().await;
-            if let Err(e) = self.update_cache().await {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown.cancelled() => {
+                    info!("Portfolio Manager stopping; flushing the cache");
+                    if let Err(e) = self.flush_cache().await {
+                        error!("Final portfolio cache update failed: {}", e);
+                    }
+                    return;
+                }
+            }
+            if let Err(e) = self.evaluate_triggers().await {
+                error!("Session trigger evaluation failed: {}", e);
+            }
+            if let Err(e) = self.enforce_snapshot_cap().await {
+                error!("Snapshot cap enforcement failed: {}", e);
+            }
+            if let Err(e) = self.request_rebuild().await {
                 error!("Portfolio cache update failed: {}", e);
             }
         }
     }
 
-    async fn update_cache(&self) -> Result<(), sqlx::Error> {
-        let snapshots = sqlx::query_as::<_, SnapshotRow>(
-            "SELECT session_id, equity, timestamp FROM equity_snapshots ORDER BY timestamp ASC"
+    /// Activate pending sessions whose trigger condition is now met.
+    async fn evaluate_triggers(&self) -> Result<(), sqlx::Error> {
+        let pending = sqlx::query_as::<_, Session>(
+            "SELECT * FROM sessions WHERE status = 'pending' AND trigger IS NOT NULL",
         )
         .fetch_all(&self.pool)
         .await?;
 
-        if snapshots.is_empty() {
+        for session in pending {
+            let Some(raw) = session.trigger.clone() else {
+                continue;
+            };
+            let trigger: SessionTrigger = match serde_json::from_value(raw) {
+                Ok(t) => t,
+                Err(e) => {
+                    warn!("Session {} has an invalid trigger: {}", session.id, e);
+                    continue;
+                }
+            };
+
+            let Some(watched) =
+                sqlx::query_as::<_, Session>("SELECT * FROM sessions WHERE id = $1")
+                    .bind(trigger.watched_session())
+                    .fetch_optional(&self.pool)
+                    .await?
+            else {
+                continue;
+            };
+
+            let peak_equity: Option<f64> = sqlx::query_scalar(
+                "SELECT MAX(equity) FROM equity_snapshots WHERE session_id = $1",
+            )
+            .bind(watched.id)
+            .fetch_one(&self.pool)
+            .await?;
+
+            // Only stops after the pending session was created count.
+            let stopped_out: bool = sqlx::query_scalar(
+                "SELECT EXISTS (SELECT 1 FROM trades WHERE session_id = $1 AND order_type = 'STOP' AND timestamp >= $2)",
+            )
+            .bind(watched.id)
+            .bind(session.created_at)
+            .fetch_one(&self.pool)
+            .await?;
+
+            let peak = peak_equity
+                .unwrap_or(watched.current_equity)
+                .max(watched.current_equity);
+            if !trigger.is_met(&watched, peak, stopped_out) {
+                continue;
+            }
+
+            let mut tx = self.pool.begin().await?;
+            let activated = sqlx::query(
+                "UPDATE sessions SET status = 'active', last_update = NOW() WHERE id = $1 AND status = 'pending'",
+            )
+            .bind(session.id)
+            .execute(&mut *tx)
+            .await?;
+
+            if activated.rows_affected() > 0 {
+                sqlx::query(
+                    "INSERT INTO equity_snapshots (session_id, equity, timestamp) VALUES ($1, $2, NOW())",
+                )
+                .bind(session.id)
+                .bind(session.current_equity)
+                .execute(&mut *tx)
+                .await?;
+                info!(
+                    "Activated session {} (trigger on session {} met)",
+                    session.id, watched.id
+                );
+            }
+            tx.commit().await?;
+        }
+
+        Ok(())
+    }
+
+    /// Thin the equity snapshots of each session over the configured cap.
+    ///
+    /// The newest half of the cap is kept as is; older snapshots are reduced
+    /// to the last one in each hour, and then in each day if the session is
+    /// still over the cap.  A session's first snapshot is always kept.  The
+    /// last snapshot of a bucket is the one a cache step after it replays
+    /// from, so a rebuild still spans each session's whole history.
+    async fn enforce_snapshot_cap(&self) -> Result<(), sqlx::Error> {
+        let Some(cap) = self.config.max_snapshots_per_session else {
             return Ok(());
+        };
+
+        for bucket in SNAPSHOT_THINNING_BUCKETS {
+            let res = sqlx::query(
+                r#"
+                DELETE FROM equity_snapshots WHERE id IN (
+                    SELECT id FROM (
+                        SELECT
+                            id,
+                            COUNT(*) OVER (PARTITION BY session_id) AS total,
+                            ROW_NUMBER() OVER (PARTITION BY session_id ORDER BY timestamp DESC) AS newest_rn,
+                            ROW_NUMBER() OVER (PARTITION BY session_id ORDER BY timestamp ASC) AS oldest_rn,
+                            ROW_NUMBER() OVER (
+                                PARTITION BY session_id, date_trunc($2, timestamp)
+                                ORDER BY timestamp DESC
+                            ) AS bucket_rn
+                        FROM equity_snapshots
+                    ) ranked
+                    WHERE total > $1 AND newest_rn > $1 / 2 AND oldest_rn > 1 AND bucket_rn > 1
+                )
+                "#,
+            )
+            .bind(cap)
+            .bind(bucket)
+            .execute(&self.pool)
+            .await?;
+
+            if res.rows_affected() > 0 {
+                info!(
+                    "Thinned {} equity snapshots to one per {} over the per-session cap of {}",
+                    res.rows_affected(),
+                    bucket,
+                    cap
+                );
+            }
         }
+        Ok(())
+    }
 
-        let start_time = snapshots[0].timestamp
-            .with_second(0).unwrap()
-            .with_nanosecond(0).unwrap();
-        let end_time = Utc::now();
+    /// Rate converting one unit of `quote` into the reporting currency, from
+    /// the direct pair or else the inverse of the reverse pair.
+    async fn fetch_fx_rate(&self, quote: &str) -> Option<f64> {
+        let reporting = &self.config.reporting_currency;
+        if quote == reporting {
+            return Some(1.0);
+        }
+        if let Ok(price) = self
+            .market
+            .latest_price(&format!("{quote}{reporting}"))
+            .await
+        {
+            if price > 0.0 {
+                return Some(price);
+            }
+        }
+        match self
+            .market
+            .latest_price(&format!("{reporting}{quote}"))
+            .await
+        {
+            Ok(price) if price > 0.0 => Some(1.0 / price),
+            _ => None,
+        }
+    }
 
-        let mut current_equities: HashMap<Uuid, f64> = HashMap::new();
-        let mut cache_points: Vec<(DateTime<Utc>, f64)> = Vec::with_capacity(10000);
-        let mut snapshot_idx = 0;
-        let mut curr = start_time;
+    /// Refresh the cached rate of each of `quotes` once the cache is stale.
+    /// A quote whose rate cannot be fetched keeps its previous rate.
+    async fn refresh_fx_rates(&self, quotes: &[String]) {
+        let now = Utc::now();
+        {
+            let cached = self.fx_rates.lock().unwrap_or_else(|e| e.into_inner());
+            if !cached.is_stale(now) && quotes.iter().all(|q| cached.rates.contains_key(q)) {
+                return;
+            }
+        }
 
-        while curr <= end_time {
-            while snapshot_idx < snapshots.len() && snapshots[snapshot_idx].timestamp <= curr {
-                let snap = &snapshots[snapshot_idx];
-                current_equities.insert(snap.session_id, snap.equity);
-                snapshot_idx += 1;
+        let mut fetched = HashMap::new();
+        for quote in quotes {
+            match self.fetch_fx_rate(quote).await {
+                Some(rate) => {
+                    fetched.insert(quote.clone(), rate);
+                }
+                None => warn!(
+                    "No {} rate for quote asset {}",
+                    self.config.reporting_currency, quote
+                ),
             }
+        }
+
+        let mut cached = self.fx_rates.lock().unwrap_or_else(|e| e.into_inner());
+        cached.rates.extend(fetched);
+        cached.fetched_at = Some(now);
+    }
 
-            let total: f64 = current_equities.values().sum();
+    /// Per-session rate converting equity into the reporting currency.
+    /// Sessions whose quote asset has no rate are left out, so their equity
+    /// is not summed in the wrong currency.
+    async fn session_fx_rates(&self) -> Result<HashMap<Uuid, f64>, sqlx::Error> {
+        let sessions: Vec<(Uuid, String)> = sqlx::query_as("SELECT id, symbol FROM sessions")
+            .fetch_all(&self.pool)
+            .await?;
 
-            if total > 0.0 {
-                cache_points.push((curr, total));
+        let quotes: HashMap<Uuid, String> = sessions
+            .into_iter()
+            .map(|(id, symbol)| {
+                let quote = self
+                    .market
+                    .quote_asset(&symbol)
+                    .map(str::to_string)
+                    .unwrap_or_else(|| self.config.reporting_currency.clone());
+                (id, quote)
+            })
+            .collect();
+
+        let mut distinct: Vec<String> = quotes.values().cloned().collect();
+        distinct.sort();
+        distinct.dedup();
+        self.refresh_fx_rates(&distinct).await;
+
+        let cached = self.fx_rates.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(quotes
+            .into_iter()
+            .filter_map(|(id, quote)| {
+                let rate = cached.rate(&quote);
+                if rate.is_none() {
+                    warn!(
+                        "Leaving session {} out of the portfolio total: no {} rate for {}",
+                        id, self.config.reporting_currency, quote
+                    );
+                }
+                Some((id, rate?))
+            })
+            .collect())
+    }
+
+    /// Each session's allocation, with its capital adjustments.
+    async fn session_allocations(&self) -> Result<HashMap<Uuid, Allocation>, sqlx::Error> {
+        let mut flows = capital_flows::load_all(&self.pool).await?;
+        Ok(sqlx::query_as::<_, (Uuid, f64, f64)>(
+            "SELECT id, initial_capital, allocated_weight FROM sessions",
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|(id, initial_capital, weight)| {
+            let flows = flows.remove(&id).unwrap_or_default();
+            let allocation = Allocation {
+                capital: flows.opening_capital(initial_capital),
+                weight,
+                flows,
+            };
+            (id, allocation)
+        })
+        .collect())
+    }
+
+    /// Bring `portfolio_cache` up to date: the sum of session equities in the
+    /// reporting currency at each step, and `return_pct`, the sessions'
+    /// flow-neutral returns on opening capital averaged by `allocated_weight`.
+    ///
+    /// Normally only the steps after the last settled one are replayed,
+    /// from the snapshots since then and the sessions' holdings carried over
+    /// from the previous update; current FX rates apply to the new steps.
+    /// The whole cache is rebuilt (with current rates throughout) on start
+    /// with an empty cache or one at another resolution, after
+    /// [`invalidate_cache`](Self::invalidate_cache), when a held session has
+    /// been deleted, or when the cache no longer reaches the settled step.
+    async fn update_cache(&self) -> Result<(), sqlx::Error> {
+        let fx = self.session_fx_rates().await?;
+        let allocations = self.session_allocations().await?;
+
+        let carried = self
+            .cache_state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        let resume = match carried {
+            _ if self.full_rebuild_requested.load(Ordering::Acquire) => None,
+            Some(state) => Some(state),
+            None => self.load_cache_state(&fx, &allocations).await?,
+        }
+        .filter(|state| state.holdings.sessions_exist(&allocations));
+
+        let extended = match resume {
+            Some(state) => self.extend_cache(state, &fx, &allocations).await?,
+            None => None,
+        };
+        let next = match extended {
+            Some(state) => state,
+            None => {
+                self.full_rebuild_requested.store(false, Ordering::Release);
+                match self.rebuild_cache(&fx, &allocations).await {
+                    Ok(Some(state)) => state,
+                    Ok(None) => return Ok(()),
+                    Err(e) => {
+                        self.full_rebuild_requested.store(true, Ordering::Release);
+                        return Err(e);
+                    }
+                }
             }
+        };
+        *self.cache_state.lock().unwrap_or_else(|e| e.into_inner()) = Some(next);
+        Ok(())
+    }
+
+    /// Resume point for a cache written before this process started: the
+    /// step [`SETTLE_MINUTES`] before its newest point, holding each
+    /// session's latest snapshot at or before it.  `None` when the cache is
+    /// empty or was written at another resolution.
+    async fn load_cache_state(
+        &self,
+        fx: &HashMap<Uuid, f64>,
+        allocations: &HashMap<Uuid, Allocation>,
+    ) -> Result<Option<CacheState>, sqlx::Error> {
+        let (points, last): (i64, Option<DateTime<Utc>>) =
+            sqlx::query_as("SELECT COUNT(*), MAX(timestamp) FROM portfolio_cache")
+                .fetch_one(&self.pool)
+                .await?;
+        let Some(last) = last else {
+            return Ok(None);
+        };
+        self.metrics.portfolio_cache_points.set(points);
 
-            curr += ChronoDuration::minutes(1);
+        let step = self.step();
+        let newest: Vec<DateTime<Utc>> = sqlx::query_scalar(
+            "SELECT timestamp FROM portfolio_cache ORDER BY timestamp DESC LIMIT 2",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        if !on_grid(&newest, step) {
+            info!(
+                "Portfolio cache is not on a {}s grid; rebuilding it",
+                step.num_seconds()
+            );
+            return Ok(None);
         }
 
+        let settled = floor_to(last, step) - settle_span(step);
+        let latest = sqlx::query_as::<_, SnapshotRow>(
+            "SELECT DISTINCT ON (session_id) session_id, equity, timestamp FROM equity_snapshots WHERE timestamp <= $1 ORDER BY session_id, timestamp DESC",
+        )
+        .bind(settled)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut holdings = Holdings::default();
+        for snap in &latest {
+            holdings.apply(snap, fx, allocations);
+        }
+        Ok(Some(CacheState { settled, holdings }))
+    }
+
+    /// Replay the steps after `state.settled` and upsert them, rewriting
+    /// the unsettled tail.  `None` when the cache no longer reaches
+    /// `state.settled`, which calls for a full rebuild.
+    async fn extend_cache(
+        &self,
+        state: CacheState,
+        fx: &HashMap<Uuid, f64>,
+        allocations: &HashMap<Uuid, Allocation>,
+    ) -> Result<Option<CacheState>, sqlx::Error> {
+        let last: Option<DateTime<Utc>> =
+            sqlx::query_scalar("SELECT MAX(timestamp) FROM portfolio_cache")
+                .fetch_one(&self.pool)
+                .await?;
+        let Some(last) = last.filter(|last| *last >= state.settled) else {
+            warn!(
+                "Portfolio cache does not reach {}; rebuilding it",
+                state.settled
+            );
+            return Ok(None);
+        };
+
+        let snapshots = sqlx::query_as::<_, SnapshotRow>(
+            "SELECT session_id, equity, timestamp FROM equity_snapshots WHERE timestamp > $1 ORDER BY timestamp ASC",
+        )
+        .bind(state.settled)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let (points, next) = replay(state, &snapshots, Utc::now(), self.step(), fx, allocations);
+        if !points.is_empty() {
+            let mut tx = self.pool.begin().await?;
+            upsert_points(&mut tx, &points).await?;
+            tx.commit().await?;
+        }
+
+        let appended = points.iter().filter(|(ts, _, _)| *ts > last).count();
+        self.metrics.portfolio_cache_points.add(appended as i64);
+        debug!(
+            "Extended portfolio cache by {} points ({} rewritten)",
+            appended,
+            points.len() - appended
+        );
+        Ok(Some(next))
+    }
+
+    /// Replay every snapshot from the first and replace `portfolio_cache`
+    /// with the result.  `None` when there is nothing to cache yet.
+    ///
+    /// The table is replaced row by row rather than truncated: points are
+    /// upserted and steps the replay no longer covers are deleted, in one
+    /// transaction.  Readers are not blocked, and since each statement at
+    /// `READ COMMITTED` sees only committed rows, a read sees either the old
+    /// series or the new one, never a partial table.
+    async fn rebuild_cache(
+        &self,
+        fx: &HashMap<Uuid, f64>,
+        allocations: &HashMap<Uuid, Allocation>,
+    ) -> Result<Option<CacheState>, sqlx::Error> {
+        let snapshots = sqlx::query_as::<_, SnapshotRow>(
+            "SELECT session_id, equity, timestamp FROM equity_snapshots ORDER BY timestamp ASC",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let Some(first) = snapshots.first() else {
+            return Ok(None);
+        };
+        let step = self.step();
+        let start = CacheState {
+            settled: floor_to(first.timestamp, step) - step,
+            holdings: Holdings::default(),
+        };
+        let (cache_points, next) = replay(start, &snapshots, Utc::now(), step, fx, allocations);
+
         if cache_points.is_empty() {
-            return Ok(());
+            return Ok(None);
         }
 
         let mut tx = self.pool.begin().await?;
 
-        sqlx::query("TRUNCATE TABLE portfolio_cache").execute(&mut *tx).await?;
+        sqlx::query("DELETE FROM portfolio_cache WHERE timestamp < $1 OR timestamp > $2")
+            .bind(cache_points[0].0)
+            .bind(cache_points[cache_points.len() - 1].0)
+            .execute(&mut *tx)
+            .await?;
+        for (after, before) in gaps(&cache_points, step) {
+            sqlx::query("DELETE FROM portfolio_cache WHERE timestamp > $1 AND timestamp < $2")
+                .bind(after)
+                .bind(before)
+                .execute(&mut *tx)
+                .await?;
+        }
+        upsert_points(&mut tx, &cache_points).await?;
 
-        for chunk in cache_points.chunks(5000) {
-            let mut query_builder = QueryBuilder::new(
-                "INSERT INTO portfolio_cache (timestamp, total_equity) "
-            );
+        tx.commit().await?;
+        self.metrics
+            .portfolio_cache_points
+            .set(cache_points.len() as i64);
+
+        info!(
+            "Rebuilt portfolio cache with {} data points",
+            cache_points.len()
+        );
+        Ok(Some(next))
+    }
+}
+
+/// A session's share of `return_pct`.
+#[derive(Clone, Debug, Default)]
+struct Allocation {
+    /// Capital before any deposit or withdrawal.
+    capital: f64,
+    weight: f64,
+    flows: CapitalFlows,
+}
+
+/// Each session's latest snapshot as of some step, which is all the
+/// replay carries from one step to the next.
+#[derive(Clone, Default)]
+struct Holdings {
+    /// Equity in the reporting currency; sessions without an FX rate are
+    /// left out.
+    equities: HashMap<Uuid, f64>,
+    /// Flow-neutral return on opening capital and weight of each session
+    /// seen so far.
+    returns: HashMap<Uuid, (f64, f64)>,
+}
+
+impl Holdings {
+    fn apply(
+        &mut self,
+        snap: &SnapshotRow,
+        fx: &HashMap<Uuid, f64>,
+        allocations: &HashMap<Uuid, Allocation>,
+    ) {
+        if let Some(rate) = fx.get(&snap.session_id) {
+            self.equities.insert(snap.session_id, snap.equity * rate);
+        }
+        if let Some(allocation) = allocations.get(&snap.session_id) {
+            if allocation.capital > 0.0 {
+                let equity = allocation.flows.neutral_equity(snap.equity, snap.timestamp);
+                self.returns.insert(
+                    snap.session_id,
+                    (equity / allocation.capital - 1.0, allocation.weight),
+                );
+            }
+        }
+    }
+
+    /// Total equity and weighted return, when there is any equity.
+    fn point(&self) -> Option<(f64, Option<f64>)> {
+        let total: f64 = self.equities.values().sum();
+        (total > 0.0).then(|| (total, weighted_return_pct(self.returns.values().copied())))
+    }
+
+    /// Whether every held session still exists; a deleted one would keep
+    /// counting in new steps while its history is gone.
+    fn sessions_exist(&self, allocations: &HashMap<Uuid, Allocation>) -> bool {
+        self.equities.keys().all(|id| allocations.contains_key(id))
+    }
+}
+
+/// Where the last cache update left off: points up to `settled` are final,
+/// and `holdings` are the sessions' snapshots as of that step.
+struct CacheState {
+    settled: DateTime<Utc>,
+    holdings: Holdings,
+}
+
+/// `(timestamp, total_equity, return_pct)` row of `portfolio_cache`.
+type CachePoint = (DateTime<Utc>, f64, Option<f64>);
+
+/// Write `points` over any cached rows at the same timestamps.
+async fn upsert_points(conn: &mut PgConnection, points: &[CachePoint]) -> Result<(), sqlx::Error> {
+    for chunk in points.chunks(5000) {
+        let mut query_builder =
+            QueryBuilder::new("INSERT INTO portfolio_cache (timestamp, total_equity, return_pct) ");
+
+        query_builder.push_values(chunk, |mut b, (ts, eq, ret)| {
+            b.push_bind(ts).push_bind(eq).push_bind(ret);
+        });
+        query_builder.push(
+            " ON CONFLICT (timestamp) DO UPDATE SET total_equity = EXCLUDED.total_equity, return_pct = EXCLUDED.return_pct",
+        );
+
+        query_builder.build().execute(&mut *conn).await?;
+    }
+    Ok(())
+}
+
+/// Open intervals between consecutive `points` (oldest first) that skip
+/// steps, i.e. steps without equity to cache.
+fn gaps(points: &[CachePoint], step: ChronoDuration) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+    points
+        .windows(2)
+        .filter(|w| w[1].0 - w[0].0 > step)
+        .map(|w| (w[0].0, w[1].0))
+        .collect()
+}
+
+fn floor_to(t: DateTime<Utc>, step: ChronoDuration) -> DateTime<Utc> {
+    t.duration_trunc(step).unwrap_or(t)
+}
 
-            query_builder.push_values(chunk, |mut b, (ts, eq)| {
-                b.push_bind(ts)
-                 .push_bind(eq);
+/// Span behind the newest point that stays open to rewrites: [`SETTLE_MINUTES`]
+/// rounded up to whole steps.
+fn settle_span(step: ChronoDuration) -> ChronoDuration {
+    let secs = step.num_seconds().max(1);
+    let steps = (SETTLE_MINUTES * 60 + secs - 1) / secs;
+    ChronoDuration::seconds(steps.max(1) * secs)
+}
+
+/// Whether the newest cached timestamps (newest first) were written at
+/// `step`: each on the grid, and adjacent ones one step apart.
+fn on_grid(newest: &[DateTime<Utc>], step: ChronoDuration) -> bool {
+    newest.iter().all(|t| floor_to(*t, step) == *t)
+        && newest.windows(2).all(|w| w[0] - w[1] == step)
+}
+
+/// Points for each `step` after `from.settled` up to `end`, from
+/// `snapshots` (oldest first, all after `from.settled`) on top of
+/// `from.holdings`.  The returned state is settled [`settle_span`] before
+/// `end`, or stays where it was if that is not later.
+fn replay(
+    from: CacheState,
+    snapshots: &[SnapshotRow],
+    end: DateTime<Utc>,
+    step: ChronoDuration,
+    fx: &HashMap<Uuid, f64>,
+    allocations: &HashMap<Uuid, Allocation>,
+) -> (Vec<CachePoint>, CacheState) {
+    let settle_at = (floor_to(end, step) - settle_span(step)).max(from.settled);
+    let mut holdings = from.holdings;
+    let mut settled = (settle_at == from.settled).then(|| holdings.clone());
+    let mut points = Vec::new();
+    let mut snapshot_idx = 0;
+    let mut curr = from.settled + step;
+
+    while curr <= end {
+        while snapshot_idx < snapshots.len() && snapshots[snapshot_idx].timestamp <= curr {
+            holdings.apply(&snapshots[snapshot_idx], fx, allocations);
+            snapshot_idx += 1;
+        }
+        if let Some((total, return_pct)) = holdings.point() {
+            points.push((curr, total, return_pct));
+        }
+        if curr == settle_at {
+            settled = Some(holdings.clone());
+        }
+        curr += step;
+    }
+
+    let holdings = settled.unwrap_or(holdings);
+    (
+        points,
+        CacheState {
+            settled: settle_at,
+            holdings,
+        },
+    )
+}
+
+/// Mean of `(return, weight)` pairs weighted by allocation, in percent.
+/// Negative weights count as zero; `None` when no weight remains.
+fn weighted_return_pct(returns: impl IntoIterator<Item = (f64, f64)>) -> Option<f64> {
+    let (weighted, total_weight) =
+        returns
+            .into_iter()
+            .fold((0.0, 0.0), |(sum, total), (ret, weight)| {
+                let weight = weight.max(0.0);
+                (sum + ret * weight, total + weight)
             });
+    (total_weight > 0.0).then(|| weighted / total_weight * 100.0)
+}
+
+/// Whether a rebuild may start at `now` given when the last one finished.
+fn rebuild_due(
+    last_rebuild: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+    min_interval: ChronoDuration,
+) -> bool {
+    match last_rebuild {
+        Some(last) => now - last >= min_interval,
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-            query_builder.build().execute(&mut *tx).await?;
+    fn allocation(capital: f64) -> Allocation {
+        Allocation {
+            capital,
+            weight: 1.0,
+            flows: CapitalFlows::default(),
         }
+    }
 
-        tx.commit().await?;
+    #[test]
+    fn test_rebuild_due_debounces_within_window() {
+        let now = Utc::now();
+        let window = ChronoDuration::seconds(10);
 
-        info!("Updated portfolio cache with {} data points", cache_points.len());
-        Ok(())
+        assert!(rebuild_due(None, now, window));
+        assert!(!rebuild_due(
+            Some(now - ChronoDuration::seconds(3)),
+            now,
+            window
+        ));
+        assert!(rebuild_due(
+            Some(now - ChronoDuration::seconds(10)),
+            now,
+            window
+        ));
+    }
+
+    #[test]
+    fn test_weighted_return_ignores_session_size() {
+        // A 10% gain and a 10% loss at equal weight net to zero however
+        // different the sessions' capital.
+        let ret = weighted_return_pct([(0.10, 1.0), (-0.10, 1.0)]).unwrap();
+        assert!(ret.abs() < 1e-9);
+
+        let ret = weighted_return_pct([(0.10, 3.0), (-0.10, 1.0)]).unwrap();
+        assert!((ret - 5.0).abs() < 1e-9);
+
+        assert!(weighted_return_pct([(0.10, 0.0)]).is_none());
+        assert!(weighted_return_pct(std::iter::empty()).is_none());
+    }
+
+    #[test]
+    fn test_fx_rates_staleness_and_missing_rate() {
+        let now = Utc::now();
+        let mut fx = FxRates::default();
+        assert!(fx.is_stale(now));
+
+        fx.rates.insert("BTC".to_string(), 60000.0);
+        fx.fetched_at = Some(now - ChronoDuration::seconds(FX_REFRESH_SECS - 1));
+        assert!(!fx.is_stale(now));
+        assert_eq!(fx.rate("BTC"), Some(60000.0));
+        assert_eq!(fx.rate("BUSD"), None);
+
+        fx.fetched_at = Some(now - ChronoDuration::seconds(FX_REFRESH_SECS));
+        assert!(fx.is_stale(now));
+    }
+
+    #[test]
+    fn test_incremental_replay_matches_full_replay() {
+        let step = ChronoDuration::minutes(1);
+        let t0 = DateTime::from_timestamp(1_700_000_000, 0)
+            .map(|t| floor_to(t, step))
+            .unwrap();
+        let at = |mins: i64, secs: i64| {
+            t0 + ChronoDuration::minutes(mins) + ChronoDuration::seconds(secs)
+        };
+        let (a, b) = (Uuid::from_u128(1), Uuid::from_u128(2));
+        let snap = |session_id, equity, timestamp| SnapshotRow {
+            session_id,
+            equity,
+            timestamp,
+        };
+        let snapshots = vec![
+            snap(a, 100.0, at(0, 10)),
+            snap(b, 50.0, at(3, 0)),
+            snap(a, 110.0, at(8, 30)),
+            snap(b, 40.0, at(14, 5)),
+            snap(a, 120.0, at(19, 59)),
+        ];
+        let fx = HashMap::from([(a, 1.0), (b, 2.0)]);
+        let allocations = HashMap::from([(a, allocation(100.0)), (b, allocation(50.0))]);
+        let start = || CacheState {
+            settled: t0 - ChronoDuration::minutes(1),
+            holdings: Holdings::default(),
+        };
+
+        let end = at(25, 0);
+        let (full, _) = replay(start(), &snapshots, end, step, &fx, &allocations);
+        assert_eq!(full.len(), 25);
+        assert_eq!(full[3].1, 200.0);
+
+        let (first, state) = replay(start(), &snapshots, at(12, 0), step, &fx, &allocations);
+        assert_eq!(state.settled, at(7, 0));
+        assert_eq!(first.len(), 12);
+        let rest: Vec<SnapshotRow> = snapshots
+            .into_iter()
+            .filter(|s| s.timestamp > state.settled)
+            .collect();
+        let (tail, state) = replay(state, &rest, end, step, &fx, &allocations);
+        assert_eq!(state.settled, at(20, 0));
+        assert_eq!(tail.first().map(|p| p.0), Some(at(8, 0)));
+        assert_eq!(tail[..], full[7..]);
+    }
+
+    #[test]
+    fn test_replay_keeps_settled_point_when_no_minute_passed() {
+        let step = ChronoDuration::minutes(1);
+        let t0 = DateTime::from_timestamp(1_700_000_000, 0)
+            .map(|t| floor_to(t, step))
+            .unwrap();
+        let state = CacheState {
+            settled: t0,
+            holdings: Holdings::default(),
+        };
+        let (points, next) = replay(state, &[], t0, step, &HashMap::new(), &HashMap::new());
+        assert!(points.is_empty());
+        assert_eq!(next.settled, t0);
+    }
+
+    #[test]
+    fn test_gaps_between_points() {
+        let step = ChronoDuration::minutes(1);
+        let t0 = DateTime::from_timestamp(1_700_000_000, 0)
+            .map(|t| floor_to(t, step))
+            .unwrap();
+        let at = |mins| (t0 + ChronoDuration::minutes(mins), 1.0, None);
+        assert!(gaps(&[at(0), at(1), at(2)], step).is_empty());
+        assert_eq!(
+            gaps(&[at(0), at(1), at(4), at(5), at(7)], step),
+            vec![(at(1).0, at(4).0), (at(5).0, at(7).0)]
+        );
+        assert!(gaps(&[], step).is_empty());
+    }
+
+    #[test]
+    fn test_settle_span_covers_whole_steps() {
+        assert_eq!(
+            settle_span(ChronoDuration::minutes(1)),
+            ChronoDuration::minutes(5)
+        );
+        assert_eq!(
+            settle_span(ChronoDuration::minutes(3)),
+            ChronoDuration::minutes(6)
+        );
+        assert_eq!(
+            settle_span(ChronoDuration::minutes(15)),
+            ChronoDuration::minutes(15)
+        );
+    }
+
+    #[test]
+    fn test_on_grid_detects_resolution_change() {
+        let step = ChronoDuration::minutes(5);
+        let t0 = DateTime::from_timestamp(1_700_000_000, 0)
+            .map(|t| floor_to(t, step))
+            .unwrap();
+        let minute = ChronoDuration::minutes(1);
+
+        assert!(on_grid(&[t0, t0 - step], step));
+        assert!(on_grid(&[t0], step));
+        assert!(!on_grid(&[t0, t0 - minute], step));
+        assert!(!on_grid(&[t0 + minute], step));
+        // A 5m cache read at 1m has every point on the grid but too far apart.
+        assert!(!on_grid(&[t0, t0 - step], minute));
+    }
+
+    #[test]
+    fn test_holdings_notice_deleted_sessions() {
+        let id = Uuid::from_u128(1);
+        let mut holdings = Holdings::default();
+        let allocations = HashMap::from([(id, allocation(100.0))]);
+        let snap = SnapshotRow {
+            session_id: id,
+            equity: 110.0,
+            timestamp: Utc::now(),
+        };
+        // Without a rate the equity is not summed, though its return counts.
+        holdings.apply(&snap, &HashMap::new(), &allocations);
+        assert!(holdings.point().is_none());
+        holdings.apply(&snap, &HashMap::from([(id, 1.0)]), &allocations);
+        let (total, return_pct) = holdings.point().unwrap();
+        assert_eq!(total, 110.0);
+        assert!((return_pct.unwrap() - 10.0).abs() < 1e-9);
+        assert!(holdings.sessions_exist(&allocations));
+        assert!(!holdings.sessions_exist(&HashMap::new()));
+    }
+
+    #[test]
+    fn test_deposit_is_not_a_return() {
+        let id = Uuid::from_u128(1);
+        let t0 = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let mut flows = CapitalFlows::default();
+        flows.push(t0, 100.0, 110.0, 210.0);
+        // `initial_capital` is 200 after the deposit; the return stays on 100.
+        let allocations = HashMap::from([(
+            id,
+            Allocation {
+                capital: flows.opening_capital(200.0),
+                weight: 1.0,
+                flows,
+            },
+        )]);
+        let fx = HashMap::from([(id, 1.0)]);
+        let mut holdings = Holdings::default();
+        for (equity, timestamp) in [(110.0, t0 - ChronoDuration::minutes(1)), (210.0, t0)] {
+            let snap = SnapshotRow {
+                session_id: id,
+                equity,
+                timestamp,
+            };
+            holdings.apply(&snap, &fx, &allocations);
+            let (_, return_pct) = holdings.point().unwrap();
+            assert!((return_pct.unwrap() - 10.0).abs() < 1e-9);
+        }
     }
 }
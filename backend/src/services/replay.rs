@@ -0,0 +1,369 @@
+//! Offline replay of a session's market with alternative strategy parameters.
+//!
+//! The replay fetches the session's candles, recomputes features and runs the
+//! strategy with each parameter set, then simulates only the bars since the
+//! session was created, so both runs see exactly the market the live session
+//! traded.  Fills follow the live engine: full equity per position at the bar
+//! close with [`SLIPPAGE_RATE`] slippage and [`FEE_RATE`] per leg.  Trailing
+//! stops, leverage and signal confirmation are not modelled.
+
+use chrono::{DateTime, Utc};
+use krypto::features::indicators::FeatureEngine;
+use polars::prelude::*;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::error::AppError;
+use crate::models::strategy::Session;
+use crate::services::market_data::{self, MarketDataService};
+use crate::services::strategy_registry;
+use crate::services::trading_engine::{FEE_RATE, SLIPPAGE_RATE};
+
+/// Candles fetched for feature warm-up plus the replay window.
+const REPLAY_CANDLES: u16 = 1000;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayPoint {
+    pub time: DateTime<Utc>,
+    pub equity: f64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ReplayMetrics {
+    pub final_equity: f64,
+    pub total_return_pct: f64,
+    pub max_drawdown_pct: f64,
+    pub trades: usize,
+}
+
+/// One position the simulation held, entry to exit.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayTrade {
+    /// `LONG` or `SHORT`.
+    pub side: &'static str,
+    pub entry_time: DateTime<Utc>,
+    pub entry_price: f64,
+    /// `None` while the position is still open at the last bar.
+    pub exit_time: Option<DateTime<Utc>>,
+    pub exit_price: Option<f64>,
+    /// Equity change over the position, fees included.
+    pub pnl: Option<f64>,
+    pub pnl_pct: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReplayRun {
+    pub metrics: ReplayMetrics,
+    pub equity_curve: Vec<ReplayPoint>,
+    pub trades: Vec<ReplayTrade>,
+}
+
+/// `b` minus `a` for each metric.
+#[derive(Debug, Serialize)]
+pub struct MetricDelta {
+    pub final_equity: f64,
+    pub total_return_pct: f64,
+    pub max_drawdown_pct: f64,
+    pub trades: i64,
+}
+
+/// Two replays over the same bars.
+#[derive(Debug, Serialize)]
+pub struct ParamComparison {
+    pub bars: usize,
+    pub a: ReplayRun,
+    pub b: ReplayRun,
+    pub delta: MetricDelta,
+}
+
+/// Replay `params_a` and `params_b` for `strategy_type` over the bars
+/// `session` has seen.
+pub async fn compare_params(
+    market: &MarketDataService,
+    session: &Session,
+    strategy_type: &str,
+    params_a: &Value,
+    params_b: &Value,
+) -> Result<ParamComparison, AppError> {
+    let raw = if session.is_basket() {
+        market
+            .fetch_basket_candles(&session.basket_symbols(), &session.interval, REPLAY_CANDLES)
+            .await?
+    } else {
+        market
+            .fetch_candles(&session.symbol, &session.interval, REPLAY_CANDLES)
+            .await?
+    };
+    market_data::validate_candle_frame(&raw)?;
+    let df =
+        FeatureEngine::add_technicals(&raw, None).map_err(|e| AppError::Data(e.to_string()))?;
+
+    let times = frame_times(&df)?;
+    let closes = frame_closes(&df)?;
+
+    let start = times
+        .iter()
+        .position(|t| *t >= session.created_at)
+        .unwrap_or(times.len());
+    if start == times.len() {
+        return Err(AppError::BadRequest(
+            "Session has no closed bars to replay yet".into(),
+        ));
+    }
+
+    let run = |params: &Value| -> Result<ReplayRun, AppError> {
+        let signals = frame_signals(strategy_type, params, &df)?;
+        Ok(simulate(
+            &times[start..],
+            &closes[start..],
+            &signals[start..],
+            session.initial_capital,
+        ))
+    };
+
+    let a = run(params_a)?;
+    let b = run(params_b)?;
+    let delta = MetricDelta {
+        final_equity: b.metrics.final_equity - a.metrics.final_equity,
+        total_return_pct: b.metrics.total_return_pct - a.metrics.total_return_pct,
+        max_drawdown_pct: b.metrics.max_drawdown_pct - a.metrics.max_drawdown_pct,
+        trades: b.metrics.trades as i64 - a.metrics.trades as i64,
+    };
+
+    Ok(ParamComparison {
+        bars: times.len() - start,
+        a,
+        b,
+        delta,
+    })
+}
+
+/// Close of each row of a candle frame; missing closes are NaN.
+pub(crate) fn frame_closes(df: &DataFrame) -> Result<Vec<f64>, AppError> {
+    Ok(df
+        .column("close")
+        .and_then(|c| c.f64().cloned())
+        .map_err(|e| AppError::Data(e.to_string()))?
+        .into_iter()
+        .map(|v| v.unwrap_or(f64::NAN))
+        .collect())
+}
+
+/// `strategy_type`'s signal for each row of `df`.  Null and NaN signals are
+/// `None`, which the live engine treats as no signal rather than flat.
+pub(crate) fn frame_signals(
+    strategy_type: &str,
+    params: &Value,
+    df: &DataFrame,
+) -> Result<Vec<Option<f64>>, AppError> {
+    let series = strategy_registry::predict(strategy_type, params, df)?;
+    let signals: Vec<Option<f64>> = series
+        .f64()
+        .map_err(|e| AppError::Data(e.to_string()))?
+        .into_iter()
+        .map(|v| v.filter(|v| !v.is_nan()))
+        .collect();
+    if signals.len() != df.height() {
+        return Err(AppError::Strategy(format!(
+            "Signal series has {} rows but candle frame has {}",
+            signals.len(),
+            df.height()
+        )));
+    }
+    Ok(signals)
+}
+
+pub(crate) fn frame_times(df: &DataFrame) -> Result<Vec<DateTime<Utc>>, AppError> {
+    let column = df
+        .column("time")
+        .map_err(|e| AppError::Data(e.to_string()))?;
+    let DataType::Datetime(unit, _) = column.dtype() else {
+        return Err(AppError::Data(format!(
+            "time column has type {}",
+            column.dtype()
+        )));
+    };
+    let unit = *unit;
+    let raw = column
+        .datetime()
+        .map_err(|e| AppError::Data(e.to_string()))?;
+
+    Ok(raw
+        .into_iter()
+        .map(|v| {
+            let v = v.unwrap_or_default();
+            match unit {
+                TimeUnit::Nanoseconds => DateTime::from_timestamp_nanos(v),
+                TimeUnit::Microseconds => DateTime::from_timestamp_micros(v).unwrap_or_default(),
+                TimeUnit::Milliseconds => DateTime::from_timestamp_millis(v).unwrap_or_default(),
+            }
+        })
+        .collect())
+}
+
+/// Simulate a session acting on `signals` at each bar close.
+///
+/// Only the signal's direction matters, as in the unlevered live engine.  A
+/// bar without a signal holds the position, as the live engine does.
+pub(crate) fn simulate(
+    times: &[DateTime<Utc>],
+    closes: &[f64],
+    signals: &[Option<f64>],
+    initial_capital: f64,
+) -> ReplayRun {
+    let direction = |v: f64| {
+        if v > 0.0 {
+            1.0
+        } else if v < 0.0 {
+            -1.0
+        } else {
+            0.0
+        }
+    };
+
+    let mut equity = initial_capital;
+    let mut position = 0.0;
+    let mut entry_price = 0.0;
+    let mut basis = initial_capital;
+    let mut peak = initial_capital;
+    let mut max_drawdown_pct: f64 = 0.0;
+    let mut trades = 0;
+    let mut equity_curve = Vec::with_capacity(closes.len());
+    let mut round_trips: Vec<ReplayTrade> = Vec::new();
+    // Equity before the open position's entry fee.
+    let mut entry_equity = initial_capital;
+
+    for ((&time, &close), &signal) in times.iter().zip(closes).zip(signals) {
+        if !close.is_finite() {
+            continue;
+        }
+        if position != 0.0 {
+            equity = basis * (1.0 + position * (close - entry_price) / entry_price);
+        }
+
+        let target = signal.map_or(position, direction);
+        if target != position {
+            let exec_price = if target > position {
+                close * (1.0 + SLIPPAGE_RATE)
+            } else {
+                close * (1.0 - SLIPPAGE_RATE)
+            };
+            if position != 0.0 {
+                equity = basis * (1.0 + position * (exec_price - entry_price) / entry_price)
+                    - basis * FEE_RATE;
+                trades += 1;
+                if let Some(open) = round_trips.last_mut() {
+                    open.exit_time = Some(time);
+                    open.exit_price = Some(exec_price);
+                    open.pnl = Some(equity - entry_equity);
+                    open.pnl_pct = Some((equity / entry_equity - 1.0) * 100.0);
+                }
+            }
+            if target != 0.0 {
+                entry_equity = equity;
+                equity -= equity * FEE_RATE;
+                basis = equity;
+                entry_price = exec_price;
+                trades += 1;
+                round_trips.push(ReplayTrade {
+                    side: if target > 0.0 { "LONG" } else { "SHORT" },
+                    entry_time: time,
+                    entry_price: exec_price,
+                    exit_time: None,
+                    exit_price: None,
+                    pnl: None,
+                    pnl_pct: None,
+                });
+            }
+            position = target;
+        }
+
+        peak = peak.max(equity);
+        if peak > 0.0 {
+            max_drawdown_pct = max_drawdown_pct.max((peak - equity) / peak * 100.0);
+        }
+        equity_curve.push(ReplayPoint { time, equity });
+    }
+
+    ReplayRun {
+        metrics: ReplayMetrics {
+            final_equity: equity,
+            total_return_pct: (equity / initial_capital - 1.0) * 100.0,
+            max_drawdown_pct,
+            trades,
+        },
+        equity_curve,
+        trades: round_trips,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn times(n: usize) -> Vec<DateTime<Utc>> {
+        (0..n)
+            .map(|i| DateTime::from_timestamp(i as i64 * 3600, 0).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_simulate_flat_signal_keeps_capital() {
+        let run = simulate(&times(3), &[100.0, 110.0, 90.0], &[Some(0.0); 3], 1000.0);
+
+        assert_eq!(run.metrics.trades, 0);
+        assert_eq!(run.metrics.final_equity, 1000.0);
+        assert_eq!(run.equity_curve.len(), 3);
+    }
+
+    #[test]
+    fn test_simulate_long_round_trip() {
+        let run = simulate(
+            &times(3),
+            &[100.0, 110.0, 110.0],
+            &[Some(1.0), Some(1.0), Some(0.0)],
+            1000.0,
+        );
+
+        // Open at 100.1 (fee 1.0), close at 109.89 (fee on the 999 basis).
+        let basis = 1000.0 * (1.0 - FEE_RATE);
+        let entry = 100.0 * (1.0 + SLIPPAGE_RATE);
+        let exit = 110.0 * (1.0 - SLIPPAGE_RATE);
+        let expected = basis * (1.0 + (exit - entry) / entry) - basis * FEE_RATE;
+
+        assert_eq!(run.metrics.trades, 2);
+        assert!((run.metrics.final_equity - expected).abs() < 1e-9);
+        assert!(run.metrics.total_return_pct > 9.0);
+
+        assert_eq!(run.trades.len(), 1);
+        let trade = &run.trades[0];
+        assert_eq!(trade.side, "LONG");
+        assert_eq!(trade.entry_price, entry);
+        assert_eq!(trade.exit_price, Some(exit));
+        assert!((trade.pnl.unwrap() - (expected - 1000.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_simulate_tracks_drawdown() {
+        let run = simulate(&times(3), &[100.0, 80.0, 80.0], &[Some(1.0); 3], 1000.0);
+
+        assert!(run.metrics.max_drawdown_pct > 19.0);
+        assert_eq!(run.metrics.trades, 1);
+        assert!(run.trades[0].exit_time.is_none());
+    }
+
+    #[test]
+    fn test_simulate_holds_position_without_signal() {
+        let run = simulate(
+            &times(4),
+            &[100.0, 110.0, 120.0, 120.0],
+            &[Some(1.0), None, None, Some(0.0)],
+            1000.0,
+        );
+
+        // Missing signals keep the long open until the explicit flat.
+        assert_eq!(run.metrics.trades, 2);
+        assert_eq!(run.trades[0].exit_time, Some(times(4)[3]));
+        assert!(run.metrics.total_return_pct > 15.0);
+    }
+}
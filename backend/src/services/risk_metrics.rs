@@ -0,0 +1,133 @@
+//! Volatility and risk-adjusted ratios of an equity curve.
+//!
+//! Sharpe penalises upside and downside volatility alike, which misranks
+//! strategies with skewed returns.  Sortino divides by downside deviation
+//! only, and Calmar compares annualised growth with the worst drawdown.
+//! All are annualised from the bar count, so `periods_per_year` must match
+//! the curve's interval.
+
+/// Bar returns of `curve`, skipping bars that start from non-positive equity.
+fn bar_returns(curve: &[f64]) -> Vec<f64> {
+    curve
+        .windows(2)
+        .filter(|w| w[0] > 0.0)
+        .map(|w| w[1] / w[0] - 1.0)
+        .collect()
+}
+
+/// Annualised standard deviation of bar returns, as a fraction.  `None`
+/// with fewer than two points.
+pub fn annualized_volatility(curve: &[f64], periods_per_year: f64) -> Option<f64> {
+    let returns = bar_returns(curve);
+    if returns.is_empty() {
+        return None;
+    }
+
+    let n = returns.len() as f64;
+    let mean = returns.iter().sum::<f64>() / n;
+    let std = (returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / n).sqrt();
+    Some(std * periods_per_year.sqrt())
+}
+
+/// Annualised mean bar return over its standard deviation (risk-free rate
+/// 0).  `None` with fewer than two points or a constant curve.
+pub fn sharpe_ratio(curve: &[f64], periods_per_year: f64) -> Option<f64> {
+    let returns = bar_returns(curve);
+    if returns.is_empty() {
+        return None;
+    }
+
+    let n = returns.len() as f64;
+    let mean = returns.iter().sum::<f64>() / n;
+    let std = (returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / n).sqrt();
+    (std > 0.0).then(|| mean / std * periods_per_year.sqrt())
+}
+
+/// Annualised mean bar return over downside deviation (target 0).  `None`
+/// with fewer than two points or when no bar lost money.
+pub fn sortino_ratio(curve: &[f64], periods_per_year: f64) -> Option<f64> {
+    let returns = bar_returns(curve);
+    if returns.is_empty() {
+        return None;
+    }
+
+    let n = returns.len() as f64;
+    let mean = returns.iter().sum::<f64>() / n;
+    let downside = (returns.iter().map(|r| r.min(0.0).powi(2)).sum::<f64>() / n).sqrt();
+    (downside > 0.0).then(|| mean / downside * periods_per_year.sqrt())
+}
+
+/// Compound annual growth over the maximum drawdown, both as fractions.
+/// `None` for a curve without a drawdown or a non-positive start.
+pub fn calmar_ratio(curve: &[f64], periods_per_year: f64) -> Option<f64> {
+    let (&first, &last) = (curve.first()?, curve.last()?);
+    if first <= 0.0 || curve.len() < 2 {
+        return None;
+    }
+
+    let mut peak = first;
+    let mut max_drawdown = 0.0_f64;
+    for &equity in curve {
+        peak = peak.max(equity);
+        if peak > 0.0 {
+            max_drawdown = max_drawdown.max((peak - equity) / peak);
+        }
+    }
+    if max_drawdown <= 0.0 {
+        return None;
+    }
+
+    let years = (curve.len() - 1) as f64 / periods_per_year;
+    let cagr = (last.max(0.0) / first).powf(1.0 / years) - 1.0;
+    Some(cagr / max_drawdown)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sharpe_is_mean_over_volatility() {
+        // Returns of +10% and -5% alternate: mean 2.5%, deviation 7.5%.
+        let curve = [100.0, 110.0, 104.5, 114.95, 109.2025];
+        let sharpe = sharpe_ratio(&curve, 4.0).unwrap();
+        assert!((sharpe - 0.025 / 0.075 * 2.0).abs() < 1e-9);
+
+        assert!(sharpe_ratio(&[100.0, 100.0, 100.0], 1.0).is_none());
+        assert!(sharpe_ratio(&[100.0], 1.0).is_none());
+    }
+
+    #[test]
+    fn test_volatility_scales_with_sqrt_of_periods() {
+        let curve = [100.0, 110.0, 104.5, 114.95, 109.2025];
+        let vol = annualized_volatility(&curve, 4.0).unwrap();
+        assert!((vol - 0.075 * 2.0).abs() < 1e-9);
+
+        assert_eq!(annualized_volatility(&[100.0, 100.0], 1.0), Some(0.0));
+        assert!(annualized_volatility(&[100.0], 1.0).is_none());
+    }
+
+    #[test]
+    fn test_sortino_ignores_upside_volatility() {
+        // Same losses, but `b` has more volatile gains; Sortino ranks it higher.
+        let a = [100.0, 101.0, 100.0, 101.0, 100.0, 101.0];
+        let b = [100.0, 105.0, 104.0, 109.0, 108.0, 113.0];
+
+        let sa = sortino_ratio(&a, 1.0).unwrap();
+        let sb = sortino_ratio(&b, 1.0).unwrap();
+        assert!(sb > sa);
+        assert!(sortino_ratio(&[100.0, 101.0, 102.0], 1.0).is_none());
+        assert!(sortino_ratio(&[100.0], 1.0).is_none());
+    }
+
+    #[test]
+    fn test_calmar_annualises_growth() {
+        // One period per year: 100 -> 80 -> 120 grows 20% over two years
+        // with a 20% drawdown.
+        let calmar = calmar_ratio(&[100.0, 80.0, 120.0], 1.0).unwrap();
+        assert!((calmar - (1.2_f64.sqrt() - 1.0) / 0.2).abs() < 1e-9);
+
+        assert!(calmar_ratio(&[100.0, 110.0, 120.0], 1.0).is_none());
+        assert!(calmar_ratio(&[0.0, 10.0, 5.0], 1.0).is_none());
+    }
+}
@@ -0,0 +1,105 @@
+//! Live session updates, for `GET /ws/sessions/{id}`.
+//!
+//! The engine publishes a [`SessionEvent`] whenever it writes a session's
+//! equity or one of its trades.  Every socket subscribes to the one
+//! broadcast channel and keeps its own session's events, so publishing never
+//! waits on a client: a subscriber that falls more than [`EVENT_BUFFER`]
+//! events behind skips the ones it missed and carries on from the newest.
+//! Since every trade write is published here, this is also where trades are
+//! counted for [`Metrics`].
+
+use std::sync::Arc;
+
+use serde::Serialize;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use crate::models::strategy::Trade;
+use crate::services::metrics::Metrics;
+
+/// Events buffered per subscriber before it starts missing some.
+pub const EVENT_BUFFER: usize = 1024;
+
+/// A session's state after an engine write.
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionEvent {
+    pub session_id: Uuid,
+    pub equity: f64,
+    pub position: f64,
+    /// The trade written, when the update was a fill.
+    pub last_trade: Option<Trade>,
+}
+
+/// Broadcast hub for [`SessionEvent`]s.  Cheap to share behind an `Arc`.
+pub struct SessionEvents {
+    tx: broadcast::Sender<SessionEvent>,
+    metrics: Arc<Metrics>,
+}
+
+impl Default for SessionEvents {
+    fn default() -> Self {
+        Self {
+            tx: broadcast::channel(EVENT_BUFFER).0,
+            metrics: Arc::default(),
+        }
+    }
+}
+
+impl SessionEvents {
+    /// Count published trades in `metrics`.
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Send `event` to current subscribers, if any.
+    pub fn publish(&self, event: SessionEvent) {
+        if event.last_trade.is_some() {
+            self.metrics.trades_executed.inc();
+        }
+        let _ = self.tx.send(event);
+    }
+
+    /// Receiver for every session's events published from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<SessionEvent> {
+        self.tx.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use broadcast::error::TryRecvError;
+
+    fn event(equity: f64) -> SessionEvent {
+        SessionEvent {
+            session_id: Uuid::nil(),
+            equity,
+            position: 0.0,
+            last_trade: None,
+        }
+    }
+
+    #[test]
+    fn test_publish_without_subscribers_is_dropped() {
+        let events = SessionEvents::default();
+        events.publish(event(1.0));
+
+        let mut rx = events.subscribe();
+        assert!(matches!(rx.try_recv(), Err(TryRecvError::Empty)));
+        events.publish(event(2.0));
+        assert_eq!(rx.try_recv().unwrap().equity, 2.0);
+    }
+
+    #[test]
+    fn test_slow_subscriber_skips_to_newest() {
+        let events = SessionEvents::default();
+        let mut rx = events.subscribe();
+        for i in 0..(EVENT_BUFFER + 5) {
+            events.publish(event(i as f64));
+        }
+
+        assert!(matches!(rx.try_recv(), Err(TryRecvError::Lagged(5))));
+        assert_eq!(rx.try_recv().unwrap().equity, 5.0);
+    }
+}
@@ -0,0 +1,101 @@
+//! Global cap on the rate of routine equity snapshot writes.
+//!
+//! Mark-to-market ticks write a snapshot at most once per session cooldown,
+//! but with many sessions on fast intervals the inserts still add up.  All
+//! sessions draw from one token bucket refilled at the configured rate; a
+//! tick that finds it empty updates the session's equity without writing a
+//! snapshot.  Snapshots taken on trades do not draw from the bucket.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+pub struct SnapshotBudget {
+    /// Tokens added per second; `0` disables the cap.
+    rate: f64,
+    /// `(tokens available, last refill)`.
+    bucket: Mutex<(f64, Instant)>,
+    skipped: AtomicU64,
+}
+
+impl SnapshotBudget {
+    /// A bucket allowing `per_sec` writes per second, with bursts of up to
+    /// one second's worth.  `0` (or less) means unlimited.
+    pub fn new(per_sec: f64) -> Self {
+        let rate = per_sec.max(0.0);
+        Self {
+            rate,
+            bucket: Mutex::new((rate.max(1.0), Instant::now())),
+            skipped: AtomicU64::new(0),
+        }
+    }
+
+    /// Take a token for one snapshot write, or count a skip if none is left.
+    pub fn try_take(&self) -> bool {
+        self.try_take_at(Instant::now())
+    }
+
+    fn try_take_at(&self, now: Instant) -> bool {
+        if self.rate == 0.0 {
+            return true;
+        }
+        let mut bucket = self.bucket.lock().unwrap_or_else(|e| e.into_inner());
+        let (tokens, last) = &mut *bucket;
+        let elapsed = now.saturating_duration_since(*last).as_secs_f64();
+        *tokens = (*tokens + elapsed * self.rate).min(self.rate.max(1.0));
+        *last = now;
+
+        if *tokens >= 1.0 {
+            *tokens -= 1.0;
+            true
+        } else {
+            self.skipped.fetch_add(1, Ordering::Relaxed);
+            false
+        }
+    }
+
+    /// Snapshots dropped for lack of budget since startup.
+    pub fn skipped(&self) -> u64 {
+        self.skipped.load(Ordering::Relaxed)
+    }
+}
+
+impl Default for SnapshotBudget {
+    fn default() -> Self {
+        Self::new(0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_bucket_drains_and_refills() {
+        let budget = SnapshotBudget::new(2.0);
+        let start = Instant::now();
+
+        assert!(budget.try_take_at(start));
+        assert!(budget.try_take_at(start));
+        assert!(!budget.try_take_at(start));
+        assert_eq!(budget.skipped(), 1);
+
+        // Half a second refills one token at 2/s.
+        assert!(budget.try_take_at(start + Duration::from_millis(500)));
+        assert!(!budget.try_take_at(start + Duration::from_millis(500)));
+
+        // A long idle period refills only up to the burst size.
+        let later = start + Duration::from_secs(60);
+        assert!(budget.try_take_at(later));
+        assert!(budget.try_take_at(later));
+        assert!(!budget.try_take_at(later));
+    }
+
+    #[test]
+    fn test_zero_rate_is_unlimited() {
+        let budget = SnapshotBudget::new(0.0);
+        assert!((0..1000).all(|_| budget.try_take()));
+        assert_eq!(budget.skipped(), 0);
+    }
+}
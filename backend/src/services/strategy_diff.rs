@@ -0,0 +1,114 @@
+//! Structured comparison of two strategies' stored parameters and metrics.
+//!
+//! Both `parameters` and `performance_metrics` are flat JSON objects, so the
+//! diff is taken over their top-level keys.  A key present on only one side
+//! is reported with `null` on the other.
+
+use std::collections::BTreeSet;
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+/// A parameter whose value differs between the two strategies.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ParamChange {
+    pub key: String,
+    pub a: Value,
+    pub b: Value,
+}
+
+/// A performance metric of either strategy.  `delta` is `b - a` when both
+/// values are numeric.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct MetricChange {
+    pub key: String,
+    pub a: Value,
+    pub b: Value,
+    pub delta: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StrategyDiff {
+    pub a: Uuid,
+    pub b: Uuid,
+    pub parameters: Vec<ParamChange>,
+    pub metrics: Vec<MetricChange>,
+}
+
+/// Parameter keys whose values differ, in key order.
+pub fn diff_parameters(a: &Value, b: &Value) -> Vec<ParamChange> {
+    let (a, b) = (a.as_object(), b.as_object());
+    union_keys(a, b)
+        .into_iter()
+        .filter_map(|key| {
+            let va = a.and_then(|m| m.get(&key)).cloned().unwrap_or(Value::Null);
+            let vb = b.and_then(|m| m.get(&key)).cloned().unwrap_or(Value::Null);
+            (va != vb).then_some(ParamChange { key, a: va, b: vb })
+        })
+        .collect()
+}
+
+/// Every metric of either strategy, in key order, with its delta.
+pub fn diff_metrics(a: Option<&Value>, b: Option<&Value>) -> Vec<MetricChange> {
+    let (a, b) = (a.and_then(Value::as_object), b.and_then(Value::as_object));
+    union_keys(a, b)
+        .into_iter()
+        .map(|key| {
+            let va = a.and_then(|m| m.get(&key)).cloned().unwrap_or(Value::Null);
+            let vb = b.and_then(|m| m.get(&key)).cloned().unwrap_or(Value::Null);
+            let delta = match (va.as_f64(), vb.as_f64()) {
+                (Some(x), Some(y)) => Some(y - x),
+                _ => None,
+            };
+            MetricChange {
+                key,
+                a: va,
+                b: vb,
+                delta,
+            }
+        })
+        .collect()
+}
+
+fn union_keys(a: Option<&Map<String, Value>>, b: Option<&Map<String, Value>>) -> BTreeSet<String> {
+    a.into_iter()
+        .chain(b)
+        .flat_map(|m| m.keys().cloned())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_diff_parameters_reports_changed_and_one_sided_keys() {
+        let a = json!({"fast": 10, "slow": 50, "stop": 0.02});
+        let b = json!({"fast": 12, "slow": 50, "trail": true});
+
+        let diff = diff_parameters(&a, &b);
+        let keys: Vec<&str> = diff.iter().map(|c| c.key.as_str()).collect();
+        assert_eq!(keys, vec!["fast", "stop", "trail"]);
+        assert_eq!(diff[0].a, json!(10));
+        assert_eq!(diff[0].b, json!(12));
+        assert_eq!(diff[1].b, Value::Null);
+        assert_eq!(diff[2].a, Value::Null);
+    }
+
+    #[test]
+    fn test_diff_metrics_deltas_numeric_values_only() {
+        let a = json!({"sharpe": 1.5, "trades": 20, "note": "x"});
+        let b = json!({"sharpe": 2.0, "trades": 18, "note": "y"});
+
+        let diff = diff_metrics(Some(&a), Some(&b));
+        let by_key = |k: &str| diff.iter().find(|c| c.key == k).map(|c| c.delta);
+        assert_eq!(by_key("sharpe"), Some(Some(0.5)));
+        assert_eq!(by_key("trades"), Some(Some(-2.0)));
+        assert_eq!(by_key("note"), Some(None));
+
+        let one_sided = diff_metrics(Some(&a), None);
+        assert!(one_sided.iter().all(|c| c.b.is_null() && c.delta.is_none()));
+    }
+}
@@ -1,30 +1,144 @@
 //! Strategy generation via genetic algorithm optimisation.
 //!
-//! Fetches OHLCV data, adds technical features, then runs an [`Optimizer`]
-//! over each strategy type's parameter space.  Top-N candidates by Sharpe
-//! ratio are persisted to the `strategies` table.
+//! Fetches OHLCV data, adds technical features, then runs the krypto
+//! optimiser over each registered strategy type's parameter space.  Top-N
+//! candidates by the requested [`RankMetric`] are persisted to the
+//! `strategies` table.
+//!
+//! The optimiser only reports its final best, so each type's iteration budget
+//! is split into [`CONVERGENCE_CHECKPOINTS`] segments.  krypto searches each
+//! segment with the requested [`Objective`] as its fitness, and the best
+//! result's Sharpe after each segment is returned as a convergence trace.
+//!
+//! Markets (symbol × interval) are evaluated up to a configurable number at
+//! once: candles are fetched on the async runtime, then the feature pass and
+//! optimiser runs are moved to the blocking pool.
+//!
+//! A caller can pass a progress channel to receive each viable candidate as
+//! soon as its market has been evaluated, with its rank among the candidates
+//! found so far.
 
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use anyhow::Result;
-use krypto::algo::optimization::{OptimizableStrategy, Optimizer};
-use krypto::algo::strategies::{
-    AdaptiveMaCrossover, AtrBreakout, BollingerReversion, DynamicTrend, MacdTrend, ObvTrend,
-    PriceMomentum, RsiMeanReversion, VolatilitySqueeze,
-};
+use futures::stream::{self, StreamExt};
 use krypto::backtest::engine::BacktestResult;
 use krypto::features::indicators::FeatureEngine;
 use polars::prelude::*;
 use serde::Serialize;
 use sqlx::PgPool;
-use tracing::{error, info};
+use tokio::sync::mpsc::UnboundedSender;
+use tracing::{error, info, warn};
+
+use crate::error::AppError;
+use crate::models::strategy::{Objective, RankMetric};
+use crate::services::generation_estimate::{GenerationEstimate, GenerationTimings};
+use crate::services::interval;
+use crate::services::market_data::{self, MarketDataService};
+use crate::services::replay::{self, ReplayTrade};
+use crate::services::risk_metrics;
+use crate::services::strategy_registry::{self, StrategyEntry};
+
+/// Number of points in each convergence trace.
+const CONVERGENCE_CHECKPOINTS: usize = 10;
+
+/// Fewest bars allowed on either side of a train/test split.
+pub const MIN_SPLIT_BARS: usize = 100;
+
+/// Starting capital of the generator's replayed backtests (out-of-sample
+/// runs and stored trades); their figures are relative to it.
+const REPLAY_CAPITAL: f64 = 10_000.0;
+
+/// Wait after a market stays rate limited through its retries, before
+/// trying it once more and moving on.
+const RATE_LIMIT_PAUSE: Duration = Duration::from_secs(60);
+
+/// Best Sharpe found after `iteration` generations.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConvergencePoint {
+    pub iteration: usize,
+    pub best_sharpe: Option<f64>,
+}
+
+/// Optimiser progress for one strategy type on one market.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConvergenceTrace {
+    pub symbol: String,
+    pub interval: String,
+    pub strategy_type: String,
+    pub points: Vec<ConvergencePoint>,
+}
+
+/// Outcome of a generation run.
+#[derive(Debug, Clone, Serialize)]
+pub struct GenerationReport {
+    pub strategies_created: usize,
+    pub convergence: Vec<ConvergenceTrace>,
+}
+
+/// A viable candidate reported while generation is still running.
+#[derive(Debug, Clone, Serialize)]
+pub struct CandidateFound {
+    pub symbol: String,
+    pub interval: String,
+    pub strategy_type: String,
+    pub metrics: serde_json::Value,
+    /// 1-based rank by the requested metric among candidates found so far.
+    pub rank: usize,
+    /// Candidates found so far, including this one.
+    pub found: usize,
+}
 
-use crate::services::market_data::MarketDataService;
+/// Progress of a generation run, in the order it happens.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum GenerationEvent {
+    /// A market's candles are being fetched.
+    MarketStarted {
+        symbol: String,
+        interval: String,
+    },
+    Candidate(CandidateFound),
+    /// A market has been evaluated (or skipped); `completed` of `total`.
+    MarketDone {
+        symbol: String,
+        interval: String,
+        completed: usize,
+        total: usize,
+    },
+    Done(GenerationReport),
+    /// The run failed; candidates already reported were not saved.
+    Error {
+        message: String,
+    },
+}
+
+impl GenerationEvent {
+    /// Server-sent event name, matching the serialised `event` tag.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::MarketStarted { .. } => "market_started",
+            Self::Candidate(_) => "candidate",
+            Self::MarketDone { .. } => "market_done",
+            Self::Done(_) => "done",
+            Self::Error { .. } => "error",
+        }
+    }
+
+    /// Whether this is the last event of a run.
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, Self::Done(_) | Self::Error { .. })
+    }
+}
 
 /// Generates optimised strategies and persists the best ones to the database.
 pub struct StrategyGenerator {
     pool: PgPool,
     market: Arc<MarketDataService>,
+    timings: Arc<GenerationTimings>,
+    /// Markets fetched and optimised at once.
+    concurrency: usize,
 }
 
 /// Internal candidate produced by a single optimisation run.
@@ -35,16 +149,125 @@ struct Candidate {
     strategy_type: String,
     config_json: serde_json::Value,
     metrics: BacktestResult,
+    sortino: Option<f64>,
+    calmar: Option<f64>,
+    /// Backtest over the held-out rows, when the data was split.
+    out_of_sample: Option<OutOfSample>,
+    /// Returns of the in-sample closed positions, in percent.
+    trade_returns: Vec<f64>,
+    /// In-sample positions, when the run stores them.
+    trades: Option<Vec<ReplayTrade>>,
+}
+
+/// Metrics of a candidate backtested on data it was not optimised on.
+#[derive(Debug, Serialize)]
+struct OutOfSample {
+    bars: usize,
+    sharpe: Option<f64>,
+    sortino: Option<f64>,
+    calmar: Option<f64>,
+    total_return_pct: f64,
+    max_drawdown_pct: f64,
+    trades: usize,
+}
+
+/// Settings shared by every market of one generation run.
+#[derive(Debug, Clone, Copy)]
+struct RunSettings {
+    limit: u16,
+    iterations: usize,
+    objective: Objective,
+    train_ratio: Option<f64>,
+    store_trades: bool,
+}
+
+/// Viable candidates and convergence traces of one market.
+type MarketResult = (Vec<Candidate>, Vec<ConvergenceTrace>);
+
+impl Candidate {
+    /// Ranking score; a ratio that could not be computed ranks last.
+    fn score(&self, rank_by: RankMetric) -> f64 {
+        match rank_by {
+            RankMetric::Sharpe => Some(self.metrics.sharpe_ratio),
+            RankMetric::Sortino => self.sortino,
+            RankMetric::Calmar => self.calmar,
+            RankMetric::Expectancy => self.expectancy(),
+            RankMetric::ProfitFactor => strategy_registry::profit_factor_score(
+                self.metrics.profit_factor,
+                self.metrics.total_trades > 0,
+            ),
+            RankMetric::TotalReturn => Some(self.metrics.total_return_pct),
+        }
+        .filter(|v| v.is_finite())
+        .unwrap_or(f64::NEG_INFINITY)
+    }
+
+    /// [`expectancy`] of the in-sample closed trades' returns.
+    fn expectancy(&self) -> Option<f64> {
+        expectancy(&self.trade_returns)
+    }
+
+    /// The `performance_metrics` stored for this candidate.  A split
+    /// candidate's in-sample metrics are kept, with `oos_sharpe` and the
+    /// held-out backtest under `out_of_sample`.
+    fn metrics_json(&self) -> serde_json::Value {
+        let mut metrics = serde_json::json!({
+            "sharpe": self.metrics.sharpe_ratio,
+            "sortino": self.sortino,
+            "calmar": self.calmar,
+            "expectancy_pct": self.expectancy(),
+            "total_return_pct": self.metrics.total_return_pct,
+            "max_drawdown_pct": self.metrics.max_drawdown_pct,
+            "win_rate": self.metrics.win_rate,
+            "profit_factor": self.metrics.profit_factor,
+            "trades": self.metrics.total_trades
+        });
+        if let Some(oos) = &self.out_of_sample {
+            metrics["oos_sharpe"] = serde_json::json!(oos.sharpe);
+            metrics["out_of_sample"] = serde_json::json!(oos);
+        }
+        metrics
+    }
 }
 
 impl StrategyGenerator {
     /// Create a new generator backed by the given pool and market data service.
     pub fn new(pool: PgPool, market: Arc<MarketDataService>) -> Self {
-        Self { pool, market }
+        Self {
+            pool,
+            market,
+            timings: Arc::new(GenerationTimings::default()),
+            concurrency: 1,
+        }
+    }
+
+    /// Fetch and optimise up to `concurrency` markets at once (at least 1).
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Estimate the runtime and REST weight of a request from the timings of
+    /// previous runs, without running anything.
+    pub fn estimate(
+        &self,
+        symbols: usize,
+        intervals: usize,
+        strategy_types: usize,
+        limit: u16,
+        iterations: usize,
+    ) -> GenerationEstimate {
+        self.timings.estimate(
+            symbols * intervals,
+            strategy_types,
+            limit,
+            iterations,
+            self.concurrency,
+        )
     }
 
     /// Run the optimiser over all `symbols × intervals × strategy_types` and
-    /// save the best `top_n` strategies ranked by Sharpe ratio.
+    /// save the best `top_n` strategies ranked by `rank_by`.
     ///
     /// # Arguments
     ///
@@ -53,10 +276,22 @@ impl StrategyGenerator {
     /// * `top_n` - Maximum number of strategies to persist
     /// * `limit` - Number of candles to fetch per symbol/interval
     /// * `iterations` - Genetic algorithm generations per strategy type
+    /// * `strategy_types` - Strategy types to optimise; others are skipped
+    /// * `objective` - Fitness the optimiser searches each type by
+    /// * `rank_by` - Metric the candidates are ranked by
+    /// * `train_ratio` - Share of each market's bars to optimise on; the
+    ///   rest are held out for an out-of-sample backtest
+    /// * `store_trades` - Save each strategy's in-sample positions to
+    ///   `backtest_trades`
+    /// * `progress` - Receives each market's start and completion and each
+    ///   viable candidate as it is found; the final
+    ///   [`GenerationEvent::Done`] is left to the caller
     ///
     /// # Returns
     ///
-    /// Number of strategies saved to the database.
+    /// Number of strategies saved to the database, with the optimiser's
+    /// convergence trace for every strategy type evaluated.
+    #[allow(clippy::too_many_arguments)]
     pub async fn generate_and_save(
         &self,
         symbols: Vec<String>,
@@ -64,7 +299,13 @@ impl StrategyGenerator {
         top_n: usize,
         limit: u16,
         iterations: usize,
-    ) -> Result<usize> {
+        strategy_types: &[String],
+        objective: Objective,
+        rank_by: RankMetric,
+        train_ratio: Option<f64>,
+        store_trades: bool,
+        progress: Option<&UnboundedSender<GenerationEvent>>,
+    ) -> Result<GenerationReport> {
         info!(
             "Starting strategy generation: {} symbols, {} intervals, depth {}, iter {}",
             symbols.len(),
@@ -73,120 +314,58 @@ impl StrategyGenerator {
             iterations
         );
 
+        let entries: Vec<&'static StrategyEntry> = strategy_registry::REGISTRY
+            .iter()
+            .filter(|e| strategy_types.iter().any(|t| t == e.name))
+            .collect();
+        let markets: Vec<(String, String)> = symbols
+            .iter()
+            .flat_map(|s| intervals.iter().map(move |i| (s.clone(), i.clone())))
+            .collect();
+        let total = markets.len();
+        let settings = RunSettings {
+            limit,
+            iterations,
+            objective,
+            train_ratio,
+            store_trades,
+        };
+
         let mut candidates = Vec::new();
-        let optimizer = Optimizer::new(iterations, 0.7);
-
-        for symbol in &symbols {
-            for interval in &intervals {
-                let raw_df = match self.market.fetch_candles(symbol, interval, limit).await {
-                    Ok(df) => df,
-                    Err(e) => {
-                        error!("Failed to fetch data for {} {}: {}", symbol, interval, e);
-                        continue;
-                    }
-                };
+        let mut convergence = Vec::new();
 
-                let df = match FeatureEngine::add_technicals(&raw_df, None) {
-                    Ok(df) => df,
-                    Err(e) => {
-                        error!("Feature calc failed for {} {}: {}", symbol, interval, e);
-                        continue;
-                    }
-                };
+        let mut evaluated = stream::iter(markets)
+            .map(|(symbol, interval)| {
+                self.evaluate_market(symbol, interval, settings, entries.clone(), progress)
+            })
+            .buffer_unordered(self.concurrency);
+        let mut completed = 0;
+        while let Some(market) = evaluated.next().await {
+            let (symbol, interval, result) = market?;
+            completed += 1;
+            let found_before = candidates.len();
+            if let Some((found, traces)) = result {
+                candidates.extend(found);
+                convergence.extend(traces);
+            }
 
-                self.evaluate_type::<DynamicTrend>(
-                    &optimizer,
-                    &df,
-                    symbol,
-                    interval,
-                    "DynamicTrend",
-                    &mut candidates,
-                )?;
-                self.evaluate_type::<RsiMeanReversion>(
-                    &optimizer,
-                    &df,
-                    symbol,
-                    interval,
-                    "RsiMeanReversion",
-                    &mut candidates,
-                )?;
-                self.evaluate_type::<BollingerReversion>(
-                    &optimizer,
-                    &df,
-                    symbol,
-                    interval,
-                    "BollingerReversion",
-                    &mut candidates,
-                )?;
-                self.evaluate_type::<AtrBreakout>(
-                    &optimizer,
-                    &df,
-                    symbol,
-                    interval,
-                    "AtrBreakout",
-                    &mut candidates,
-                )?;
-                self.evaluate_type::<VolatilitySqueeze>(
-                    &optimizer,
-                    &df,
+            if let Some(tx) = progress {
+                report_found(tx, &candidates, found_before, rank_by);
+                let _ = tx.send(GenerationEvent::MarketDone {
                     symbol,
                     interval,
-                    "VolatilitySqueeze",
-                    &mut candidates,
-                )?;
-                self.evaluate_type::<MacdTrend>(
-                    &optimizer,
-                    &df,
-                    symbol,
-                    interval,
-                    "MacdTrend",
-                    &mut candidates,
-                )?;
-                self.evaluate_type::<ObvTrend>(
-                    &optimizer,
-                    &df,
-                    symbol,
-                    interval,
-                    "ObvTrend",
-                    &mut candidates,
-                )?;
-                self.evaluate_type::<PriceMomentum>(
-                    &optimizer,
-                    &df,
-                    symbol,
-                    interval,
-                    "PriceMomentum",
-                    &mut candidates,
-                )?;
-                self.evaluate_type::<AdaptiveMaCrossover>(
-                    &optimizer,
-                    &df,
-                    symbol,
-                    interval,
-                    "AdaptiveMaCrossover",
-                    &mut candidates,
-                )?;
+                    completed,
+                    total,
+                });
             }
         }
 
-        candidates.sort_by(|a, b| {
-            b.metrics
-                .sharpe_ratio
-                .partial_cmp(&a.metrics.sharpe_ratio)
-                .unwrap_or(std::cmp::Ordering::Equal)
-        });
+        candidates.sort_by(|a, b| b.score(rank_by).total_cmp(&a.score(rank_by)));
         let mut saved_count = 0usize;
         for cand in candidates.into_iter().take(top_n) {
             let kelly_fraction = cand.metrics.kelly_fraction;
 
-            let metrics_json = serde_json::json!({
-                "sharpe": cand.metrics.sharpe_ratio,
-                "total_return_pct": cand.metrics.total_return_pct,
-                "max_drawdown_pct": cand.metrics.max_drawdown_pct,
-                "win_rate": cand.metrics.win_rate,
-                "profit_factor": cand.metrics.profit_factor,
-                "trades": cand.metrics.total_trades
-            });
+            let metrics_json = cand.metrics_json();
 
             let curve = &cand.metrics.equity_curve;
             let step = (curve.len() / 50).max(1);
@@ -198,13 +377,14 @@ impl StrategyGenerator {
             }
             let curve_json = serde_json::to_value(&downsampled)?;
 
+            let trades_json = cand.trades.as_ref().map(serde_json::to_value).transpose()?;
             let name = format!("{} {} {}", cand.symbol, cand.interval, cand.strategy_name);
 
             sqlx::query(
                 r#"
                 INSERT INTO strategies
-                (name, strategy_type, symbol, interval, parameters, performance_metrics, backtest_curve, kelly_fraction)
-                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                (name, strategy_type, symbol, interval, parameters, performance_metrics, backtest_curve, kelly_fraction, backtest_trades)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
                 "#,
             )
             .bind(name)
@@ -215,6 +395,7 @@ impl StrategyGenerator {
             .bind(metrics_json)
             .bind(curve_json)
             .bind(kelly_fraction)
+            .bind(trades_json)
             .execute(&self.pool)
             .await?;
 
@@ -222,44 +403,323 @@ impl StrategyGenerator {
         }
 
         info!("Saved {} optimized strategies", saved_count);
-        Ok(saved_count)
+        Ok(GenerationReport {
+            strategies_created: saved_count,
+            convergence,
+        })
     }
 
-    /// Optimise a single strategy type against `df` and push any viable
-    /// candidates into `candidates`.
+    /// Fetch one market, then featurise it and optimise every entry on the
+    /// blocking pool so other markets' fetches carry on meanwhile.
     ///
-    /// A candidate is considered viable if it has more than 10 trades and a
-    /// positive total return.
-    fn evaluate_type<S>(
+    /// Returns the market with `None` when it was skipped because its
+    /// candles or features could not be computed, or were too few to split.
+    async fn evaluate_market(
         &self,
-        optimizer: &Optimizer,
+        symbol: String,
+        interval: String,
+        settings: RunSettings,
+        entries: Vec<&'static StrategyEntry>,
+        progress: Option<&UnboundedSender<GenerationEvent>>,
+    ) -> Result<(String, String, Option<MarketResult>)> {
+        if let Some(tx) = progress {
+            let _ = tx.send(GenerationEvent::MarketStarted {
+                symbol: symbol.clone(),
+                interval: interval.clone(),
+            });
+        }
+        let started = Instant::now();
+        let limit = settings.limit;
+        let mut fetched = self.market.refresh_candles(&symbol, &interval, limit).await;
+        if let Err(AppError::RateLimited(e)) = &fetched {
+            // Only this market's slot waits; the other slots keep fetching
+            // and optimising, each pausing on its own if it is limited too.
+            warn!(
+                "Rate limited fetching {} {} ({}); pausing it for {} s",
+                symbol,
+                interval,
+                e,
+                RATE_LIMIT_PAUSE.as_secs()
+            );
+            tokio::time::sleep(RATE_LIMIT_PAUSE).await;
+            fetched = self.market.refresh_candles(&symbol, &interval, limit).await;
+        }
+        let raw_df = match fetched {
+            Ok(df) => df,
+            Err(e) => {
+                error!("Failed to fetch data for {} {}: {}", symbol, interval, e);
+                return Ok((symbol, interval, None));
+            }
+        };
+
+        let timings = self.timings.clone();
+        // The blocking task takes the names; this copy labels the result.
+        let market = (symbol.clone(), interval.clone());
+        let result = tokio::task::spawn_blocking(move || -> Result<Option<MarketResult>> {
+            if let Err(e) = market_data::validate_candle_frame(&raw_df) {
+                error!("Invalid candles for {} {}: {}", symbol, interval, e);
+                return Ok(None);
+            }
+            let df = match FeatureEngine::add_technicals(&raw_df, None) {
+                Ok(df) => df,
+                Err(e) => {
+                    error!("Feature calc failed for {} {}: {}", symbol, interval, e);
+                    return Ok(None);
+                }
+            };
+            timings.record_market(started.elapsed());
+
+            let train_bars = match settings.train_ratio {
+                Some(ratio) => match split_point(df.height(), ratio) {
+                    Some(bars) => Some(bars),
+                    None => {
+                        error!(
+                            "{} {} has {} bars, too few to split at {}",
+                            symbol,
+                            interval,
+                            df.height(),
+                            ratio
+                        );
+                        return Ok(None);
+                    }
+                },
+                None => None,
+            };
+
+            let mut candidates = Vec::new();
+            let mut convergence = Vec::new();
+            for entry in entries {
+                let (candidate, trace) = Self::evaluate_type(
+                    &timings, entry, settings, &df, train_bars, &symbol, &interval,
+                )?;
+                candidates.extend(candidate);
+                convergence.push(trace);
+            }
+            Ok(Some((candidates, convergence)))
+        })
+        .await??;
+        Ok((market.0, market.1, result))
+    }
+
+    /// Optimise `entry` against `df`, or its first `train_bars` rows when
+    /// split, returning the candidate if viable and the convergence trace.
+    ///
+    /// A candidate is considered viable if it has more than 10 trades and a
+    /// positive total return in sample.  A split candidate is then backtested
+    /// once over the remaining rows.  A viable candidate's in-sample
+    /// positions are replayed for its expectancy, and kept when the run
+    /// stores trades.
+    fn evaluate_type(
+        timings: &GenerationTimings,
+        entry: &StrategyEntry,
+        settings: RunSettings,
         df: &DataFrame,
+        train_bars: Option<usize>,
         symbol: &str,
         interval: &str,
-        type_name: &str,
-        candidates: &mut Vec<Candidate>,
-    ) -> Result<()>
-    where
-        S: OptimizableStrategy + Clone + Default + Serialize,
-    {
-        let mut strat = S::default();
-        let (_, best_result) = optimizer.optimize(&mut strat, df);
-
-        if let Some(res) = best_result {
-            if res.total_trades > 10 && res.total_return_pct > 0.0 {
-                let config_json = serde_json::to_value(&strat)?;
-
-                let strategy_name = strat.name().to_string();
-                candidates.push(Candidate {
-                    symbol: symbol.to_string(),
-                    interval: interval.to_string(),
-                    strategy_name,
-                    strategy_type: type_name.to_string(),
-                    config_json,
-                    metrics: res,
-                });
-            }
+    ) -> Result<(Option<Candidate>, ConvergenceTrace)> {
+        let train = match train_bars {
+            Some(bars) => df.slice(0, bars),
+            None => df.clone(),
+        };
+        let periods_per_year = interval::periods_per_year(interval);
+        let started = Instant::now();
+        let optimisation = entry.optimize(
+            &train,
+            settings.iterations,
+            CONVERGENCE_CHECKPOINTS,
+            settings.objective,
+            periods_per_year,
+        )?;
+        timings.record_optimisation(started.elapsed(), settings.iterations, train.height());
+
+        let trace = ConvergenceTrace {
+            symbol: symbol.to_string(),
+            interval: interval.to_string(),
+            strategy_type: entry.name.to_string(),
+            points: optimisation
+                .trace
+                .into_iter()
+                .map(|(iteration, best_sharpe)| ConvergencePoint {
+                    iteration,
+                    best_sharpe,
+                })
+                .collect(),
+        };
+
+        let Some(best) = optimisation.best else {
+            return Ok((None, trace));
+        };
+        let res = best.result;
+        if res.total_trades <= 10 || res.total_return_pct <= 0.0 {
+            return Ok((None, trace));
+        }
+
+        let sortino = risk_metrics::sortino_ratio(&res.equity_curve, periods_per_year);
+        let calmar = risk_metrics::calmar_ratio(&res.equity_curve, periods_per_year);
+        let out_of_sample = match train_bars {
+            Some(bars) => Some(out_of_sample(entry, &best.parameters, df, bars, interval)?),
+            None => None,
+        };
+        let replayed = in_sample_trades(entry, &best.parameters, &train)?;
+        let trade_returns = replayed.iter().filter_map(|t| t.pnl_pct).collect();
+        let trades = settings.store_trades.then_some(replayed);
+        let candidate = Candidate {
+            symbol: symbol.to_string(),
+            interval: interval.to_string(),
+            strategy_name: best.name,
+            strategy_type: entry.name.to_string(),
+            config_json: best.parameters,
+            metrics: res,
+            sortino,
+            calmar,
+            out_of_sample,
+            trade_returns,
+            trades,
+        };
+        Ok((Some(candidate), trace))
+    }
+}
+
+/// Backtest `parameters` over the rows of `df` from `train_bars` on, with the
+/// replay fill model.  Signals are computed over the whole frame so the
+/// held-out rows' indicators are warmed up by the training rows.
+fn out_of_sample(
+    entry: &StrategyEntry,
+    parameters: &serde_json::Value,
+    df: &DataFrame,
+    train_bars: usize,
+    interval: &str,
+) -> Result<OutOfSample> {
+    let times = replay::frame_times(df)?;
+    let closes = replay::frame_closes(df)?;
+    let signals = replay::frame_signals(entry.name, parameters, df)?;
+    let run = replay::simulate(
+        &times[train_bars..],
+        &closes[train_bars..],
+        &signals[train_bars..],
+        REPLAY_CAPITAL,
+    );
+
+    let curve: Vec<f64> = run.equity_curve.iter().map(|p| p.equity).collect();
+    let periods_per_year = interval::periods_per_year(interval);
+    Ok(OutOfSample {
+        bars: times.len() - train_bars,
+        sharpe: risk_metrics::sharpe_ratio(&curve, periods_per_year),
+        sortino: risk_metrics::sortino_ratio(&curve, periods_per_year),
+        calmar: risk_metrics::calmar_ratio(&curve, periods_per_year),
+        total_return_pct: run.metrics.total_return_pct,
+        max_drawdown_pct: run.metrics.max_drawdown_pct,
+        trades: run.metrics.trades,
+    })
+}
+
+/// Positions `parameters` takes over `train`, simulated with the replay
+/// fill model.  krypto's result only summarises its trades, so entries and
+/// exits come from the same simulation `/backtest` uses.
+fn in_sample_trades(
+    entry: &StrategyEntry,
+    parameters: &serde_json::Value,
+    train: &DataFrame,
+) -> Result<Vec<ReplayTrade>> {
+    let times = replay::frame_times(train)?;
+    let closes = replay::frame_closes(train)?;
+    let signals = replay::frame_signals(entry.name, parameters, train)?;
+    Ok(replay::simulate(&times, &closes, &signals, REPLAY_CAPITAL).trades)
+}
+
+/// Rows of a `bars`-row frame that go to training at `train_ratio`, or
+/// `None` if either side would have fewer than [`MIN_SPLIT_BARS`].
+pub fn split_point(bars: usize, train_ratio: f64) -> Option<usize> {
+    if !(train_ratio > 0.0 && train_ratio < 1.0) {
+        return None;
+    }
+    let train = (bars as f64 * train_ratio).round() as usize;
+    (train >= MIN_SPLIT_BARS && bars.saturating_sub(train) >= MIN_SPLIT_BARS).then_some(train)
+}
+
+/// Send a [`GenerationEvent::Candidate`] for each of `candidates[from..]`.
+/// A closed channel (the client went away) is ignored so the run still
+/// completes and saves its results.
+fn report_found(
+    tx: &UnboundedSender<GenerationEvent>,
+    candidates: &[Candidate],
+    from: usize,
+    rank_by: RankMetric,
+) {
+    for (i, cand) in candidates.iter().enumerate().skip(from) {
+        let found = &candidates[..=i];
+        let score = cand.score(rank_by);
+        let _ = tx.send(GenerationEvent::Candidate(CandidateFound {
+            symbol: cand.symbol.clone(),
+            interval: cand.interval.clone(),
+            strategy_type: cand.strategy_type.clone(),
+            metrics: cand.metrics_json(),
+            rank: running_rank(found.iter().map(|c| c.score(rank_by)), score),
+            found: found.len(),
+        }));
+    }
+}
+
+/// Expected return of one trade, in percent of the equity it was entered
+/// with: win rate × average win − loss rate × average loss over per-trade
+/// `returns`.  `None` without trades.
+fn expectancy(returns: &[f64]) -> Option<f64> {
+    if returns.is_empty() {
+        return None;
+    }
+    let (wins, losses): (Vec<f64>, Vec<f64>) = returns.iter().partition(|r| **r > 0.0);
+    let mean = |xs: &[f64]| {
+        if xs.is_empty() {
+            0.0
+        } else {
+            xs.iter().sum::<f64>() / xs.len() as f64
         }
-        Ok(())
+    };
+    let win_rate = wins.len() as f64 / returns.len() as f64;
+    let loss_rate = losses.len() as f64 / returns.len() as f64;
+    Some(win_rate * mean(&wins) - loss_rate * -mean(&losses))
+}
+
+/// 1-based rank of `score` among `scores`: one more than the number that
+/// are strictly higher.
+fn running_rank(scores: impl Iterator<Item = f64>, score: f64) -> usize {
+    1 + scores.filter(|s| *s > score).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_running_rank_counts_strictly_better() {
+        let scores = [1.2, 0.4, 2.0, 0.4];
+
+        assert_eq!(running_rank(scores.iter().copied(), 2.0), 1);
+        assert_eq!(running_rank(scores.iter().copied(), 1.2), 2);
+        // Ties share a rank.
+        assert_eq!(running_rank(scores.iter().copied(), 0.4), 3);
+        assert_eq!(running_rank(std::iter::empty(), 0.1), 1);
+    }
+
+    #[test]
+    fn test_expectancy_weighs_average_win_and_loss() {
+        // 0.75 × 4 − 0.25 × 6.
+        let mixed = expectancy(&[2.0, 4.0, 6.0, -6.0]).unwrap();
+        assert!((mixed - 1.5).abs() < 1e-9);
+        assert_eq!(expectancy(&[-1.0, -3.0]), Some(-2.0));
+        assert_eq!(expectancy(&[]), None);
+    }
+
+    #[test]
+    fn test_split_point_keeps_both_sides_long_enough() {
+        assert_eq!(split_point(1000, 0.7), Some(700));
+        assert_eq!(split_point(250, 0.5), Some(125));
+        // 1000 × 0.95 leaves 50 held-out bars.
+        assert_eq!(split_point(1000, 0.95), None);
+        assert_eq!(split_point(150, 0.5), None);
+        assert_eq!(split_point(1000, 0.0), None);
+        assert_eq!(split_point(1000, 1.0), None);
+        assert_eq!(split_point(1000, f64::NAN), None);
     }
 }
@@ -0,0 +1,298 @@
+//! The strategy types the app can run, dispatched by name.
+//!
+//! Every caller (engine, generator, replay, schema) goes through
+//! [`REGISTRY`], whose entries are monomorphised for their krypto strategy.
+//! Parameters are deserialised here, so callers only handle type names and
+//! JSON, and adding a strategy is one line in the table.
+
+use krypto::algo::optimization::{OptimizableStrategy, Optimizer};
+use krypto::algo::strategies::{
+    AdaptiveMaCrossover, AtrBreakout, BollingerReversion, DynamicTrend, MacdTrend, ObvTrend,
+    PriceMomentum, RsiMeanReversion, VolatilitySqueeze,
+};
+use krypto::algo::SignalGenerator;
+use krypto::backtest::engine::BacktestResult;
+use polars::prelude::{DataFrame, Series};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::error::AppError;
+use crate::models::strategy::Objective;
+use crate::services::risk_metrics;
+use crate::services::strategy_schema::{self, StrategySchema};
+
+/// Best configuration found by [`StrategyEntry::optimize`].
+pub struct OptimisedStrategy {
+    pub name: String,
+    pub parameters: Value,
+    pub result: BacktestResult,
+}
+
+/// Outcome of an optimisation run.
+pub struct Optimisation {
+    /// Iterations completed and best Sharpe so far, after each checkpoint.
+    pub trace: Vec<(usize, Option<f64>)>,
+    pub best: Option<OptimisedStrategy>,
+}
+
+/// One runnable strategy type.
+pub struct StrategyEntry {
+    pub name: &'static str,
+    predict: fn(&Value, &DataFrame) -> Result<Series, AppError>,
+    parse: fn(&Value) -> Result<(), AppError>,
+    defaults: fn() -> Result<Value, serde_json::Error>,
+    optimize: fn(
+        &DataFrame,
+        usize,
+        usize,
+        Objective,
+        f64,
+        &StrategySchema,
+    ) -> Result<Optimisation, AppError>,
+}
+
+impl StrategyEntry {
+    /// Signal series of the strategy configured with `parameters` over `df`.
+    pub fn predict(&self, parameters: &Value, df: &DataFrame) -> Result<Series, AppError> {
+        (self.predict)(parameters, df)
+    }
+
+    /// Reject `parameters` the strategy cannot be configured from.
+    pub fn check_parameters(&self, parameters: &Value) -> Result<(), AppError> {
+        (self.parse)(parameters)
+    }
+
+    /// The strategy's default parameters.
+    pub fn default_parameters(&self) -> Result<Value, serde_json::Error> {
+        (self.defaults)()
+    }
+
+    /// Optimise from the defaults for `iterations`, split into `checkpoints`
+    /// segments so the best Sharpe can be traced as the search converges.
+    /// `objective` is the optimiser's fitness, annualised over
+    /// `periods_per_year` where it needs to be.  Only configurations within
+    /// the strategy's schema are kept.
+    pub fn optimize(
+        &self,
+        df: &DataFrame,
+        iterations: usize,
+        checkpoints: usize,
+        objective: Objective,
+        periods_per_year: f64,
+    ) -> Result<Optimisation, AppError> {
+        let schema =
+            strategy_schema::schema(self).map_err(|e| AppError::Strategy(e.to_string()))?;
+        (self.optimize)(
+            df,
+            iterations,
+            checkpoints,
+            objective,
+            periods_per_year,
+            &schema,
+        )
+    }
+}
+
+const fn entry<S>(name: &'static str) -> StrategyEntry
+where
+    S: SignalGenerator + OptimizableStrategy + DeserializeOwned + Serialize + Clone + Default,
+{
+    StrategyEntry {
+        name,
+        predict: predict_with::<S>,
+        parse: parse_with::<S>,
+        defaults: defaults_of::<S>,
+        optimize: optimize_with::<S>,
+    }
+}
+
+/// Every strategy type the engine can run.
+pub static REGISTRY: &[StrategyEntry] = &[
+    entry::<DynamicTrend>("DynamicTrend"),
+    entry::<RsiMeanReversion>("RsiMeanReversion"),
+    entry::<BollingerReversion>("BollingerReversion"),
+    entry::<AtrBreakout>("AtrBreakout"),
+    entry::<VolatilitySqueeze>("VolatilitySqueeze"),
+    entry::<MacdTrend>("MacdTrend"),
+    entry::<ObvTrend>("ObvTrend"),
+    entry::<PriceMomentum>("PriceMomentum"),
+    entry::<AdaptiveMaCrossover>("AdaptiveMaCrossover"),
+];
+
+/// Names of every registered strategy type, in registry order.
+pub fn names() -> Vec<&'static str> {
+    REGISTRY.iter().map(|e| e.name).collect()
+}
+
+pub fn lookup(strategy_type: &str) -> Option<&'static StrategyEntry> {
+    REGISTRY.iter().find(|e| e.name == strategy_type)
+}
+
+/// [`StrategyEntry::predict`] by name.
+pub fn predict(
+    strategy_type: &str,
+    parameters: &Value,
+    df: &DataFrame,
+) -> Result<Series, AppError> {
+    lookup(strategy_type)
+        .ok_or_else(|| AppError::Strategy(format!("Unknown strategy type: {strategy_type}")))?
+        .predict(parameters, df)
+}
+
+fn predict_with<S: SignalGenerator + DeserializeOwned>(
+    parameters: &Value,
+    df: &DataFrame,
+) -> Result<Series, AppError> {
+    let strat: S = serde_json::from_value(parameters.clone())
+        .map_err(|e| AppError::Strategy(format!("Config error: {e}")))?;
+    strat
+        .predict(df)
+        .map_err(|e| AppError::Strategy(e.to_string()))
+}
+
+fn parse_with<S: DeserializeOwned>(parameters: &Value) -> Result<(), AppError> {
+    serde_json::from_value::<S>(parameters.clone())
+        .map(|_| ())
+        .map_err(|e| AppError::BadRequest(format!("Invalid parameters: {e}")))
+}
+
+fn defaults_of<S: Serialize + Default>() -> Result<Value, serde_json::Error> {
+    serde_json::to_value(S::default())
+}
+
+/// Highest profit factor scored, given to runs without a losing trade.
+const MAX_PROFIT_FACTOR: f64 = 1_000.0;
+
+/// Profit factor for scoring, capped at [`MAX_PROFIT_FACTOR`].  A run that
+/// `traded` without a loss reports an infinite factor and scores the cap;
+/// one without trades has no factor.
+pub fn profit_factor_score(profit_factor: f64, traded: bool) -> Option<f64> {
+    if !traded {
+        return None;
+    }
+    Some(if profit_factor.is_nan() {
+        0.0
+    } else {
+        profit_factor.min(MAX_PROFIT_FACTOR)
+    })
+}
+
+/// Score of `res` under `objective`; higher is better, and a value that
+/// cannot be computed scores lowest.  Calmar is
+/// [`risk_metrics::calmar_ratio`] of the backtest equity curve, as used for
+/// ranking.
+pub fn fitness(objective: Objective, res: &BacktestResult, periods_per_year: f64) -> f64 {
+    let score = match objective {
+        Objective::Sharpe => Some(res.sharpe_ratio),
+        Objective::Calmar => risk_metrics::calmar_ratio(&res.equity_curve, periods_per_year),
+        Objective::ProfitFactor => profit_factor_score(res.profit_factor, res.total_trades > 0),
+        Objective::TotalReturn => Some(res.total_return_pct),
+    };
+    score.filter(|v| v.is_finite()).unwrap_or(f64::NEG_INFINITY)
+}
+
+fn optimize_with<S>(
+    df: &DataFrame,
+    iterations: usize,
+    checkpoints: usize,
+    objective: Objective,
+    periods_per_year: f64,
+    schema: &StrategySchema,
+) -> Result<Optimisation, AppError>
+where
+    S: OptimizableStrategy + DeserializeOwned + Clone + Default + Serialize,
+{
+    let segment = iterations.div_ceil(checkpoints.max(1)).max(1);
+    let score = move |res: &BacktestResult| fitness(objective, res, periods_per_year);
+    let mut strat = S::default();
+    let mut best: Option<(S, BacktestResult)> = None;
+    let mut trace = Vec::new();
+    let mut done = 0;
+
+    while done < iterations {
+        let step = segment.min(iterations - done);
+        let optimizer = Optimizer::new(step, 0.7).with_fitness(score);
+        let (_, result) = optimizer.optimize(&mut strat, df);
+        done += step;
+
+        // krypto mutates without the schema's bounds, so a segment's best
+        // may lie outside them.  Its result is then dropped and the search
+        // resumes from the values clamped into range, or from the best so
+        // far when a pair such as fast/slow is out of order.
+        let parameters =
+            serde_json::to_value(&strat).map_err(|e| AppError::Strategy(e.to_string()))?;
+        let clamped = strategy_schema::clamp_parameters(schema, &parameters);
+        if !strategy_schema::ordering_errors(&clamped).is_empty() {
+            strat = best.as_ref().map_or_else(S::default, |(b, _)| b.clone());
+        } else if clamped != parameters {
+            strat = serde_json::from_value(clamped)
+                .map_err(|e| AppError::Strategy(format!("Config error: {e}")))?;
+        } else if let Some(res) = result {
+            let improved = match &best {
+                Some((_, b)) => score(&res) > score(b),
+                None => true,
+            };
+            if improved {
+                best = Some((strat.clone(), res));
+            }
+        }
+        trace.push((done, best.as_ref().map(|(_, b)| b.sharpe_ratio)));
+    }
+
+    let best = match best {
+        Some((strat, result)) => Some(OptimisedStrategy {
+            name: strat.name().to_string(),
+            parameters: serde_json::to_value(&strat)
+                .map_err(|e| AppError::Strategy(e.to_string()))?,
+            result,
+        }),
+        None => None,
+    };
+    Ok(Optimisation { trace, best })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_names_are_unique_and_resolve() {
+        let names = names();
+        let mut unique = names.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), names.len());
+
+        assert!(names
+            .iter()
+            .all(|n| lookup(n).is_some_and(|e| e.name == *n)));
+        assert!(lookup("NoSuchStrategy").is_none());
+        assert!(REGISTRY.iter().all(|e| e.default_parameters().is_ok()));
+    }
+
+    #[test]
+    fn test_profit_factor_without_losses_scores_highest() {
+        assert_eq!(
+            profit_factor_score(f64::INFINITY, true),
+            Some(MAX_PROFIT_FACTOR)
+        );
+        assert_eq!(profit_factor_score(1.8, true), Some(1.8));
+        assert_eq!(profit_factor_score(0.4, true), Some(0.4));
+        // No gross profit or loss: every trade broke even.
+        assert_eq!(profit_factor_score(f64::NAN, true), Some(0.0));
+        assert_eq!(profit_factor_score(f64::INFINITY, false), None);
+    }
+
+    #[test]
+    fn test_defaults_pass_parameter_check() {
+        for entry in REGISTRY {
+            let defaults = entry.default_parameters().unwrap();
+            assert!(entry.check_parameters(&defaults).is_ok(), "{}", entry.name);
+            assert!(matches!(
+                entry.check_parameters(&serde_json::json!("not an object")),
+                Err(AppError::BadRequest(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,379 @@
+//! Parameter schemas for the supported strategy types.
+//!
+//! Fields and defaults are read from each strategy's `Default` impl, so the
+//! schema always matches what the engine deserialises.  The krypto optimiser
+//! does not expose its search ranges, so bounds are conservative: integer
+//! parameters (periods, lookbacks) must be at least 1 and RSI levels lie in
+//! 0-100; other float bounds are left open since thresholds may legitimately
+//! be negative.  Paired parameters such as fast and slow periods must also be
+//! in ascending order.
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::services::strategy_registry::{self, StrategyEntry};
+
+/// Which strategy types may be created or generated on this deployment.
+#[derive(Clone, Debug, Default)]
+pub struct StrategyTypePolicy {
+    /// When set, only these types are permitted.
+    allowed: Option<Vec<String>>,
+    /// Types refused even if allowed.
+    denied: Vec<String>,
+}
+
+impl StrategyTypePolicy {
+    pub fn new(allowed: Option<Vec<String>>, denied: Vec<String>) -> Self {
+        Self { allowed, denied }
+    }
+
+    pub fn permits(&self, strategy_type: &str) -> bool {
+        let allowed = self
+            .allowed
+            .as_ref()
+            .is_none_or(|list| list.iter().any(|t| t == strategy_type));
+        allowed && !self.denied.iter().any(|t| t == strategy_type)
+    }
+
+    /// The known strategy types this policy permits.
+    pub fn effective(&self) -> Vec<&'static str> {
+        strategy_registry::names()
+            .into_iter()
+            .filter(|t| self.permits(t))
+            .collect()
+    }
+}
+
+/// Schema for a single strategy parameter.
+#[derive(Debug, Serialize)]
+pub struct ParameterSchema {
+    pub name: String,
+    /// `int`, `float`, `bool`, `string` or `object`.
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub default: Value,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+/// Schema for one strategy type.
+#[derive(Debug, Serialize)]
+pub struct StrategySchema {
+    pub strategy_type: &'static str,
+    pub parameters: Vec<ParameterSchema>,
+}
+
+/// Schemas for every strategy type the engine can run.
+pub fn all_schemas() -> Result<Vec<StrategySchema>, serde_json::Error> {
+    strategy_registry::REGISTRY.iter().map(schema).collect()
+}
+
+/// Schema of one registered strategy type.
+pub fn schema(entry: &StrategyEntry) -> Result<StrategySchema, serde_json::Error> {
+    schema_for(entry.name, entry.default_parameters()?)
+}
+
+fn schema_for<S: Serialize>(
+    strategy_type: &'static str,
+    defaults: S,
+) -> Result<StrategySchema, serde_json::Error> {
+    let Value::Object(fields) = serde_json::to_value(defaults)? else {
+        return Ok(StrategySchema {
+            strategy_type,
+            parameters: Vec::new(),
+        });
+    };
+
+    let parameters = fields
+        .into_iter()
+        .map(|(name, default)| parameter_schema(name, default))
+        .collect();
+
+    Ok(StrategySchema {
+        strategy_type,
+        parameters,
+    })
+}
+
+fn parameter_schema(name: String, default: Value) -> ParameterSchema {
+    let kind = match &default {
+        Value::Bool(_) => "bool",
+        Value::Number(n) if n.is_f64() => "float",
+        Value::Number(_) => "int",
+        Value::String(_) => "string",
+        _ => "object",
+    };
+    let (min, max) = parameter_bounds(&name, kind);
+
+    ParameterSchema {
+        name,
+        kind,
+        default,
+        min,
+        max,
+    }
+}
+
+/// Bounds inferred from a parameter's name and type: integer periods and
+/// lookbacks are at least 1, and RSI levels lie on its 0-100 scale.
+fn parameter_bounds(name: &str, kind: &str) -> (Option<f64>, Option<f64>) {
+    let lower = name.to_ascii_lowercase();
+    let is_rsi_level = kind == "float"
+        && (lower.contains("overbought")
+            || lower.contains("oversold")
+            || (lower.contains("rsi") && !lower.contains("period")));
+    if is_rsi_level {
+        (Some(0.0), Some(100.0))
+    } else if kind == "int" {
+        (Some(1.0), None)
+    } else {
+        (None, None)
+    }
+}
+
+/// A parameter that fails its schema.
+#[derive(Debug, PartialEq)]
+pub struct ParameterError {
+    pub field: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for ParameterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+/// Check `parameters` against the schema of `schema`'s strategy type.
+/// Parameters the schema does not know are left to the strategy's
+/// deserialiser.
+pub fn validate_parameters(schema: &StrategySchema, parameters: &Value) -> Vec<ParameterError> {
+    let Some(values) = parameters.as_object() else {
+        return vec![ParameterError {
+            field: "parameters".into(),
+            message: "must be a JSON object".into(),
+        }];
+    };
+
+    let mut errors = Vec::new();
+    for param in &schema.parameters {
+        let Some(value) = values.get(&param.name) else {
+            continue;
+        };
+        let error = |message: String| ParameterError {
+            field: param.name.clone(),
+            message,
+        };
+
+        let number = match param.kind {
+            "int" => match value.as_i64() {
+                Some(n) => n as f64,
+                None => {
+                    errors.push(error("must be an integer".into()));
+                    continue;
+                }
+            },
+            "float" => match value.as_f64().filter(|n| n.is_finite()) {
+                Some(n) => n,
+                None => {
+                    errors.push(error("must be a number".into()));
+                    continue;
+                }
+            },
+            "bool" if !value.is_boolean() => {
+                errors.push(error("must be true or false".into()));
+                continue;
+            }
+            _ => continue,
+        };
+
+        if let Some(min) = param.min.filter(|min| number < *min) {
+            errors.push(error(format!("must be at least {min}, got {number}")));
+        }
+        if let Some(max) = param.max.filter(|max| number > *max) {
+            errors.push(error(format!("must be at most {max}, got {number}")));
+        }
+    }
+    errors.extend(ordering_errors(parameters));
+    errors
+}
+
+/// Name fragments of parameter pairs whose first value must lie below the
+/// second, e.g. `fast_period` and `slow_period`.
+const ORDERED_PAIRS: &[(&str, &str)] = &[
+    ("fast", "slow"),
+    ("short", "long"),
+    ("oversold", "overbought"),
+    ("lower", "upper"),
+];
+
+/// Pairs in `parameters` that break [`ORDERED_PAIRS`].  Only pairs where
+/// both values are numbers are checked.
+pub fn ordering_errors(parameters: &Value) -> Vec<ParameterError> {
+    let Some(values) = parameters.as_object() else {
+        return Vec::new();
+    };
+
+    let mut errors = Vec::new();
+    for (name, value) in values {
+        for (low, high) in ORDERED_PAIRS {
+            if !name.contains(low) {
+                continue;
+            }
+            let partner = name.replace(low, high);
+            let (Some(a), Some(b)) = (value.as_f64(), values.get(&partner).and_then(Value::as_f64))
+            else {
+                continue;
+            };
+            if a >= b {
+                errors.push(ParameterError {
+                    field: name.clone(),
+                    message: format!("must be below {partner} ({b}), got {a}"),
+                });
+            }
+        }
+    }
+    errors
+}
+
+/// `parameters` with every number the schema bounds moved to the nearest
+/// bound.  Integers stay integers; anything else is left as it is.
+pub fn clamp_parameters(schema: &StrategySchema, parameters: &Value) -> Value {
+    let mut clamped = parameters.clone();
+    let Some(values) = clamped.as_object_mut() else {
+        return clamped;
+    };
+
+    for param in &schema.parameters {
+        let Some(value) = values.get_mut(&param.name) else {
+            continue;
+        };
+        let min = param.min.unwrap_or(f64::NEG_INFINITY);
+        let max = param.max.unwrap_or(f64::INFINITY);
+        match param.kind {
+            "int" => {
+                if let Some(n) = value.as_i64() {
+                    *value = Value::from((n as f64).clamp(min, max).round() as i64);
+                }
+            }
+            "float" => {
+                if let Some(n) = value.as_f64().filter(|n| n.is_finite()) {
+                    *value = Value::from(n.clamp(min, max));
+                }
+            }
+            _ => {}
+        }
+    }
+    clamped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schema() -> StrategySchema {
+        schema_for(
+            "Test",
+            serde_json::json!({"period": 14, "rsi_upper": 70.0, "trail": true, "scale": 1.5}),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parameter_bounds_from_names() {
+        let schema = schema();
+        let bounds = |name: &str| {
+            let p = schema.parameters.iter().find(|p| p.name == name).unwrap();
+            (p.min, p.max)
+        };
+        assert_eq!(bounds("period"), (Some(1.0), None));
+        assert_eq!(bounds("rsi_upper"), (Some(0.0), Some(100.0)));
+        assert_eq!(bounds("scale"), (None, None));
+    }
+
+    #[test]
+    fn test_validate_parameters_reports_each_field() {
+        let schema = schema();
+        assert!(validate_parameters(
+            &schema,
+            &serde_json::json!({"period": 20, "rsi_upper": 80.0, "unknown": -1})
+        )
+        .is_empty());
+
+        let errors = validate_parameters(
+            &schema,
+            &serde_json::json!({"period": -3, "rsi_upper": 150.0, "trail": "yes", "scale": 2.5}),
+        );
+        let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, vec!["period", "rsi_upper", "trail"]);
+
+        let errors = validate_parameters(&schema, &serde_json::json!({"period": 1.5}));
+        assert_eq!(errors[0].message, "must be an integer");
+
+        assert_eq!(
+            validate_parameters(&schema, &serde_json::json!([1, 2]))[0].field,
+            "parameters"
+        );
+    }
+
+    #[test]
+    fn test_ordered_pairs_must_be_ascending() {
+        let parameters = serde_json::json!({
+            "fast_period": 30,
+            "slow_period": 20,
+            "rsi_oversold": 30.0,
+            "rsi_overbought": 70.0,
+            "allow_long": true,
+        });
+        let errors = ordering_errors(&parameters);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].field, "fast_period");
+
+        let schema = schema_for(
+            "Test",
+            serde_json::json!({"fast_period": 12, "slow_period": 26}),
+        )
+        .unwrap();
+        assert_eq!(
+            validate_parameters(
+                &schema,
+                &serde_json::json!({"fast_period": 26, "slow_period": 26})
+            )[0]
+            .field,
+            "fast_period"
+        );
+    }
+
+    #[test]
+    fn test_clamp_parameters_to_bounds() {
+        let schema = schema();
+        let clamped = clamp_parameters(
+            &schema,
+            &serde_json::json!({"period": -3, "rsi_upper": 104.5, "trail": true, "scale": -9.0}),
+        );
+        assert_eq!(
+            clamped,
+            serde_json::json!({"period": 1, "rsi_upper": 100.0, "trail": true, "scale": -9.0})
+        );
+        assert!(validate_parameters(&schema, &clamped).is_empty());
+    }
+
+    #[test]
+    fn test_policy_applies_allow_then_deny() {
+        assert_eq!(
+            StrategyTypePolicy::default().effective(),
+            strategy_registry::names()
+        );
+
+        let policy = StrategyTypePolicy::new(
+            Some(vec!["MacdTrend".to_string(), "ObvTrend".to_string()]),
+            vec!["ObvTrend".to_string()],
+        );
+        assert_eq!(policy.effective(), vec!["MacdTrend"]);
+        assert!(!policy.permits("DynamicTrend"));
+
+        let deny_only = StrategyTypePolicy::new(None, vec!["PriceMomentum".to_string()]);
+        assert!(deny_only.permits("DynamicTrend"));
+        assert!(!deny_only.permits("PriceMomentum"));
+    }
+}
@@ -0,0 +1,105 @@
+//! Friendly symbol names and their exchange tickers.
+//!
+//! Sessions and strategies may name a market by alias (`BTC`) rather than by
+//! its exchange ticker (`BTCUSDT` on Binance.com, `BTCUSD` on Binance.US).
+//! Names are resolved only where they cross into Binance: REST fetches and
+//! websocket subscriptions.
+
+use std::collections::HashMap;
+
+/// Quote assets recognised as the suffix of a full trading pair.
+const KNOWN_QUOTES: &[&str] = &[
+    "USDT", "USDC", "FDUSD", "BUSD", "TUSD", "USD", "BTC", "ETH", "BNB", "EUR", "TRY",
+];
+
+#[derive(Clone, Debug)]
+pub struct SymbolResolver {
+    aliases: HashMap<String, String>,
+    default_quote: String,
+}
+
+impl SymbolResolver {
+    /// Alias keys and values are upper-cased.  `default_quote` completes bare
+    /// base assets that have no alias.
+    pub fn new(aliases: HashMap<String, String>, default_quote: &str) -> Self {
+        Self {
+            aliases: aliases
+                .into_iter()
+                .map(|(k, v)| (k.to_uppercase(), v.to_uppercase()))
+                .collect(),
+            default_quote: default_quote.to_uppercase(),
+        }
+    }
+
+    /// Exchange ticker for `symbol`: its alias if one is configured, the
+    /// symbol itself if it already ends in a known quote asset, and otherwise
+    /// the symbol with the default quote appended.
+    pub fn resolve(&self, symbol: &str) -> String {
+        let symbol = symbol.trim().to_uppercase();
+        if let Some(ticker) = self.aliases.get(&symbol) {
+            return ticker.clone();
+        }
+        let is_pair = KNOWN_QUOTES
+            .iter()
+            .any(|quote| symbol.len() > quote.len() && symbol.ends_with(quote));
+        if is_pair {
+            symbol
+        } else {
+            format!("{symbol}{}", self.default_quote)
+        }
+    }
+
+    /// Quote asset of the ticker `symbol` resolves to, preferring the longest
+    /// match (`FDUSD` over `USD`).
+    pub fn quote_asset(&self, symbol: &str) -> Option<&'static str> {
+        let ticker = self.resolve(symbol);
+        KNOWN_QUOTES
+            .iter()
+            .filter(|quote| ticker.len() > quote.len() && ticker.ends_with(*quote))
+            .max_by_key(|quote| quote.len())
+            .copied()
+    }
+}
+
+impl Default for SymbolResolver {
+    fn default() -> Self {
+        Self::new(HashMap::new(), "USDT")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_full_pairs_unchanged() {
+        let resolver = SymbolResolver::default();
+        assert_eq!(resolver.resolve("BTCUSDT"), "BTCUSDT");
+        assert_eq!(resolver.resolve("ethbtc"), "ETHBTC");
+    }
+
+    #[test]
+    fn test_resolve_bare_assets_use_default_quote() {
+        assert_eq!(SymbolResolver::default().resolve("BTC"), "BTCUSDT");
+        assert_eq!(
+            SymbolResolver::new(HashMap::new(), "usd").resolve("sol"),
+            "SOLUSD"
+        );
+    }
+
+    #[test]
+    fn test_quote_asset_prefers_longest_suffix() {
+        let resolver = SymbolResolver::default();
+        assert_eq!(resolver.quote_asset("BTCUSDT"), Some("USDT"));
+        assert_eq!(resolver.quote_asset("ETHFDUSD"), Some("FDUSD"));
+        assert_eq!(resolver.quote_asset("ETHBTC"), Some("BTC"));
+        assert_eq!(resolver.quote_asset("SOL"), Some("USDT"));
+    }
+
+    #[test]
+    fn test_resolve_alias_takes_precedence() {
+        let aliases = HashMap::from([("wbtc".to_string(), "wbtcusdt".to_string())]);
+        let resolver = SymbolResolver::new(aliases, "USDT");
+        assert_eq!(resolver.resolve("WBTC"), "WBTCUSDT");
+    }
+}
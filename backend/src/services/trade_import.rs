@@ -0,0 +1,219 @@
+//! Validation of externally recorded trades imported into a session.
+//!
+//! Imported trades are history from before the session was created.  Each is
+//! an entry (no `pnl`) or a close (with `pnl`, net of its fee); they must
+//! alternate, close on the opposite side to their entry and end flat.  Entry
+//! fees are booked as negative PnL, as the engine does.  The equity curve is
+//! anchored so that it ends at the session's initial capital, and so runs
+//! continuously into the live snapshots.
+
+use chrono::{DateTime, Utc};
+
+use crate::models::strategy::ImportedTrade;
+
+/// A validated trade ready to insert.
+#[derive(Debug, PartialEq)]
+pub struct PlannedTrade {
+    pub timestamp: DateTime<Utc>,
+    pub side: &'static str,
+    pub price: f64,
+    pub fee: f64,
+    pub pnl: f64,
+    /// For closes, `pnl` as a percentage of equity after the entry.
+    pub pnl_pct: Option<f64>,
+    /// For closes, seconds since the matching entry.
+    pub holding_seconds: Option<i64>,
+    pub is_close: bool,
+}
+
+#[derive(Debug)]
+pub struct ImportPlan {
+    pub trades: Vec<PlannedTrade>,
+    /// Equity before the first trade and after each one.
+    pub snapshots: Vec<(DateTime<Utc>, f64)>,
+}
+
+/// Validate `trades` for a session created at `created_at` with
+/// `initial_capital`, and derive the rows to insert.
+pub fn plan_import(
+    trades: &[ImportedTrade],
+    created_at: DateTime<Utc>,
+    initial_capital: f64,
+) -> Result<ImportPlan, String> {
+    if trades.is_empty() {
+        return Err("No trades to import".into());
+    }
+
+    let mut planned: Vec<PlannedTrade> = Vec::with_capacity(trades.len());
+    let mut open: Option<(&'static str, DateTime<Utc>, usize)> = None;
+    let mut prev_ts: Option<DateTime<Utc>> = None;
+
+    for (i, t) in trades.iter().enumerate() {
+        let side = match t.side.trim().to_ascii_uppercase().as_str() {
+            "BUY" => "BUY",
+            "SELL" => "SELL",
+            other => return Err(format!("Trade {i}: unknown side {other:?}")),
+        };
+        if !(t.price.is_finite() && t.price > 0.0) {
+            return Err(format!("Trade {i}: price must be positive"));
+        }
+        if !(t.fee.is_finite() && t.fee >= 0.0) {
+            return Err(format!("Trade {i}: fee must be non-negative"));
+        }
+        if prev_ts.is_some_and(|prev| t.timestamp < prev) {
+            return Err(format!(
+                "Trade {i}: timestamps are not in chronological order"
+            ));
+        }
+        if t.timestamp >= created_at {
+            return Err(format!(
+                "Trade {i}: imported trades must predate the session ({created_at})"
+            ));
+        }
+        prev_ts = Some(t.timestamp);
+
+        match (t.pnl, open) {
+            (None, None) => {
+                open = Some((side, t.timestamp, i));
+                planned.push(PlannedTrade {
+                    timestamp: t.timestamp,
+                    side,
+                    price: t.price,
+                    fee: t.fee,
+                    pnl: -t.fee,
+                    pnl_pct: None,
+                    holding_seconds: None,
+                    is_close: false,
+                });
+            }
+            (Some(pnl), Some((entry_side, opened_at, _))) => {
+                if !pnl.is_finite() {
+                    return Err(format!("Trade {i}: pnl must be a number"));
+                }
+                if side == entry_side {
+                    return Err(format!(
+                        "Trade {i}: a close must be on the opposite side to its {entry_side} entry"
+                    ));
+                }
+                open = None;
+                planned.push(PlannedTrade {
+                    timestamp: t.timestamp,
+                    side,
+                    price: t.price,
+                    fee: t.fee,
+                    pnl,
+                    pnl_pct: None,
+                    holding_seconds: Some((t.timestamp - opened_at).num_seconds()),
+                    is_close: true,
+                });
+            }
+            (None, Some(_)) => {
+                return Err(format!("Trade {i}: entry while a position is already open"));
+            }
+            (Some(_), None) => return Err(format!("Trade {i}: close without an open position")),
+        }
+    }
+    if let Some((_, _, i)) = open {
+        return Err(format!("Trade {i}: position is never closed"));
+    }
+
+    let total_pnl: f64 = planned.iter().map(|t| t.pnl).sum();
+    let mut equity = initial_capital - total_pnl;
+    let mut snapshots = vec![(planned[0].timestamp, equity)];
+    let mut entry_equity = equity;
+    for t in planned.iter_mut() {
+        equity += t.pnl;
+        if t.is_close {
+            t.pnl_pct = (entry_equity > 0.0).then(|| t.pnl / entry_equity * 100.0);
+        } else {
+            entry_equity = equity;
+        }
+        snapshots.push((t.timestamp, equity));
+    }
+    if let Some((ts, equity)) = snapshots.iter().find(|(_, e)| *e <= 0.0) {
+        return Err(format!(
+            "Equity would be {equity:.2} at {ts} for the history to end at the initial capital"
+        ));
+    }
+
+    Ok(ImportPlan {
+        trades: planned,
+        snapshots,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn trade(minute: i64, side: &str, price: f64, fee: f64, pnl: Option<f64>) -> ImportedTrade {
+        ImportedTrade {
+            timestamp: DateTime::from_timestamp(minute * 60, 0).unwrap(),
+            side: side.to_string(),
+            price,
+            fee,
+            pnl,
+        }
+    }
+
+    fn created() -> DateTime<Utc> {
+        DateTime::from_timestamp(0, 0).unwrap() + Duration::days(1)
+    }
+
+    #[test]
+    fn test_plan_import_ends_at_initial_capital() {
+        let trades = vec![
+            trade(0, "BUY", 100.0, 1.0, None),
+            trade(60, "sell", 110.0, 1.0, Some(99.0)),
+            trade(120, "SELL", 110.0, 1.0, None),
+            trade(180, "BUY", 105.0, 1.0, Some(-21.0)),
+        ];
+
+        let plan = plan_import(&trades, created(), 1000.0).unwrap();
+
+        // Net PnL is -1 + 99 - 1 - 21 = 76.
+        let equities: Vec<f64> = plan.snapshots.iter().map(|s| s.1).collect();
+        assert_eq!(equities, vec![924.0, 923.0, 1022.0, 1021.0, 1000.0]);
+        assert_eq!(plan.trades[1].holding_seconds, Some(3600));
+        assert_eq!(plan.trades[1].side, "SELL");
+        let pnl_pct = plan.trades[1].pnl_pct.unwrap();
+        assert!((pnl_pct - 99.0 / 923.0 * 100.0).abs() < 1e-9);
+        assert!(plan.trades[0].pnl_pct.is_none());
+    }
+
+    #[test]
+    fn test_plan_import_rejects_inconsistent_history() {
+        let out_of_order = vec![
+            trade(10, "BUY", 100.0, 0.0, None),
+            trade(5, "SELL", 100.0, 0.0, Some(0.0)),
+        ];
+        assert!(plan_import(&out_of_order, created(), 1000.0).is_err());
+
+        let same_side_close = vec![
+            trade(0, "BUY", 100.0, 0.0, None),
+            trade(5, "BUY", 100.0, 0.0, Some(0.0)),
+        ];
+        assert!(plan_import(&same_side_close, created(), 1000.0).is_err());
+
+        let left_open = vec![trade(0, "BUY", 100.0, 0.0, None)];
+        assert!(plan_import(&left_open, created(), 1000.0).is_err());
+
+        let close_first = vec![trade(0, "SELL", 100.0, 0.0, Some(5.0))];
+        assert!(plan_import(&close_first, created(), 1000.0).is_err());
+
+        let after_creation = vec![
+            trade(0, "BUY", 100.0, 0.0, None),
+            trade(60 * 48, "SELL", 100.0, 0.0, Some(0.0)),
+        ];
+        assert!(plan_import(&after_creation, created(), 1000.0).is_err());
+
+        // A gain larger than the initial capital implies non-positive
+        // starting equity.
+        let too_profitable = vec![
+            trade(0, "BUY", 100.0, 0.0, None),
+            trade(5, "SELL", 300.0, 0.0, Some(2000.0)),
+        ];
+        assert!(plan_import(&too_profitable, created(), 1000.0).is_err());
+    }
+}
@@ -1,123 +1,777 @@
-use std::collections::HashMap;
-use std::sync::Arc;
-use std::time::Duration;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use binance::ws_model::{CombinedStreamEvent, Kline, WebsocketEvent, WebsocketEventUntag};
 use chrono::{DateTime, Utc};
-use krypto::algo::strategies::{
-    AdaptiveMaCrossover, AtrBreakout, BollingerReversion, DynamicTrend, MacdTrend, ObvTrend,
-    PriceMomentum, RsiMeanReversion, VolatilitySqueeze,
-};
-use krypto::algo::SignalGenerator;
+use futures::stream::{self, StreamExt};
 use krypto::features::indicators::FeatureEngine;
+use polars::prelude::DataFrame;
+use rand::Rng;
+use serde::Serialize;
 use serde_json::Value;
 use sqlx::{FromRow, PgPool};
-use tokio::sync::mpsc;
-use tracing::{error, info, warn};
+use tokio::sync::{mpsc, OnceCell};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, info_span, warn, Instrument};
 use uuid::Uuid;
 
+use crate::config::{Config, ExitKind, LengthMismatchPolicy, PositionSizing};
 use crate::error::AppError;
-use crate::models::strategy::Session;
-use crate::services::market_data::MarketDataService;
-use crate::services::market_stream::MarketStream;
+use crate::models::strategy::{OrderType, Session, Trade};
+use crate::services::diagnostics::{DiagnosticKind, Diagnostics};
+use crate::services::engine_status::{EngineStatus, Phase};
+use crate::services::interval;
+use crate::services::market_data::{self, CandleBar, LotSize, MarketDataService};
+use crate::services::market_stream::{MarketStream, ReconnectBackoff, HEALTHY_CONNECTION};
+use crate::services::notifier::{self, Notifier, SignalAlert};
+use crate::services::paper_broker::PaperBroker;
+use crate::services::replay::frame_times;
+use crate::services::session_events::{SessionEvent, SessionEvents};
+use crate::services::strategy_registry;
+use crate::services::stream_capture::{self, StreamRecorder};
 
 #[derive(FromRow)]
 struct StrategyRow {
     strategy_type: String,
     parameters: Value,
+    kelly_fraction: Option<f64>,
+}
+
+/// Feature frames computed during one kline event, keyed by
+/// `(symbol, interval)`, so sessions sharing a market reuse a single
+/// `add_technicals` pass.  A fresh cache is created per event, so entries
+/// never outlive the bar they were computed for.  Sessions evaluated
+/// concurrently wait on the same cell rather than each computing the frame.
+#[derive(Default)]
+struct FeatureCache {
+    frames: Mutex<HashMap<(String, String), Arc<OnceCell<Arc<DataFrame>>>>>,
+}
+
+impl FeatureCache {
+    fn cell(&self, symbol: &str, interval: &str) -> Arc<OnceCell<Arc<DataFrame>>> {
+        let mut frames = self.frames.lock().unwrap_or_else(|e| e.into_inner());
+        frames
+            .entry((symbol.to_string(), interval.to_string()))
+            .or_default()
+            .clone()
+    }
 }
 
 const SNAPSHOT_COOLDOWN_MS: i64 = 15_000;
-const FEE_RATE: f64 = 0.001;
-const SLIPPAGE_RATE: f64 = 0.001;
+pub(crate) const FEE_RATE: f64 = 0.001;
+pub(crate) const SLIPPAGE_RATE: f64 = 0.001;
 const TRAILING_SL_PCT: f64 = 0.05;
-
-pub async fn start_engine(pool: PgPool, market_service: Arc<MarketDataService>) {
+/// Share of margin that must remain before a levered position is liquidated.
+const MAINTENANCE_MARGIN: f64 = 0.005;
+/// Most missed bars refetched after a stream gap: the strategy window.
+const MAX_GAP_REFETCH_BARS: i64 = 1000;
+
+/// Run the engine until `shutdown` is cancelled.  The bar being processed
+/// is finished and the stream disconnected; open positions are left as they
+/// are, with active sessions' `last_update` stamped on the way out.
+pub async fn start_engine(
+    pool: PgPool,
+    market_service: Arc<MarketDataService>,
+    status: Arc<EngineStatus>,
+    config: Config,
+    shutdown: CancellationToken,
+) {
     info!("Trading Engine Starting (Bar Close Execution Mode)...");
 
-    loop {
-        if let Err(e) = run_engine_cycle(&pool, &market_service).await {
-            error!("Trading engine error: {:?}", e);
-            tokio::time::sleep(Duration::from_secs(3)).await;
+    if let Err(e) = restore_watchlist(
+        &pool,
+        &config.watchlist_strategy_ids,
+        config.watchlist_initial_capital,
+    )
+    .await
+    {
+        error!("Failed to restore watchlist sessions: {:?}", e);
+    }
+
+    let broker = PaperBroker::new(
+        config.broker_reject_rate,
+        config.broker_max_latency_ms,
+        config.broker_seed,
+    );
+
+    if let Some(path) = &config.replay_stream_path {
+        match replay_capture(&pool, &market_service, &status, &config, &broker, path).await {
+            Ok(()) => info!("Replay of {} finished; engine idle", path),
+            Err(e) => error!("Replay of {} failed: {:?}", path, e),
+        }
+        return;
+    }
+
+    let recorder =
+        config
+            .record_stream_path
+            .as_deref()
+            .and_then(|path| match StreamRecorder::open(path) {
+                Ok(recorder) => {
+                    info!("Recording stream klines to {}", path);
+                    Some(recorder)
+                }
+                Err(e) => {
+                    error!("Cannot record stream to {}: {}", path, e);
+                    None
+                }
+            });
+
+    let mut backoff = ReconnectBackoff::new(Duration::from_secs(config.stream_reconnect_max_secs));
+    // Kept across cycles, so bars missed while reconnecting count as a gap.
+    let mut last_final_bar: HashMap<String, i64> = HashMap::new();
+    while !shutdown.is_cancelled() {
+        let started = Instant::now();
+        let result = run_engine_cycle(
+            &pool,
+            &market_service,
+            &status,
+            &config,
+            &broker,
+            recorder.as_ref(),
+            &mut last_final_bar,
+            &shutdown,
+        )
+        .await;
+        status.record_stream_down();
+        let failure = match result {
+            Ok(CycleEnd::Idle | CycleEnd::Resubscribe) => {
+                backoff.reset();
+                continue;
+            }
+            Ok(CycleEnd::StreamLost) => "Websocket stream ended".to_string(),
+            Err(e) => {
+                error!("Trading engine error: {:?}", e);
+                format!("Engine cycle failed: {e}")
+            }
+        };
+        if shutdown.is_cancelled() {
+            break;
+        }
+        if started.elapsed() >= HEALTHY_CONNECTION {
+            backoff.reset();
+        }
+        let (attempt, delay) = backoff.next_delay(rand::thread_rng().gen_range(0.5..=1.0));
+        warn!(
+            "{}; reconnect attempt {} in {} ms",
+            failure,
+            attempt,
+            delay.as_millis()
+        );
+        status.diagnostics().record(
+            DiagnosticKind::Engine,
+            format!("{failure}; reconnecting in {} ms", delay.as_millis()),
+        );
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = shutdown.cancelled() => {}
         }
     }
+
+    match sqlx::query("UPDATE sessions SET last_update = NOW() WHERE status = 'active'")
+        .execute(&pool)
+        .await
+    {
+        Ok(_) => info!("Trading engine stopped"),
+        Err(e) => error!("Failed to stamp sessions on shutdown: {:?}", e),
+    }
+}
+
+/// Start a session for each watchlist strategy that has never had one.
+///
+/// A session active at shutdown is still `active` and simply resumes, so
+/// nothing is created for it; one that was stopped or liquidated stays
+/// that way rather than being replaced on the next boot.  Idempotent, so
+/// it is safe to run on every boot.
+async fn restore_watchlist(
+    pool: &PgPool,
+    strategy_ids: &[Uuid],
+    initial_capital: f64,
+) -> Result<(), AppError> {
+    for strategy_id in strategy_ids {
+        let mut tx = pool.begin().await?;
+
+        let session_id: Option<Uuid> = sqlx::query_scalar(
+            r#"
+            INSERT INTO sessions (strategy_id, symbol, interval, initial_capital, current_equity)
+            SELECT id, symbol, interval, $2, $2 FROM strategies
+            WHERE id = $1
+              AND NOT EXISTS (SELECT 1 FROM sessions WHERE strategy_id = $1)
+            RETURNING id
+            "#,
+        )
+        .bind(strategy_id)
+        .bind(initial_capital)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        if let Some(session_id) = session_id {
+            sqlx::query(
+                "INSERT INTO equity_snapshots (session_id, equity, timestamp) VALUES ($1, $2, NOW())",
+            )
+            .bind(session_id)
+            .bind(initial_capital)
+            .execute(&mut *tx)
+            .await?;
+            info!(
+                "Watchlist: started session {} for strategy {}",
+                session_id, strategy_id
+            );
+        }
+
+        tx.commit().await?;
+    }
+
+    Ok(())
+}
+
+/// Feed the klines of a capture file through the engine in recorded order.
+///
+/// Lag resyncing does not apply, since every recorded event is old; closed
+/// bars recorded twice (stream handovers) are still processed once.
+async fn replay_capture(
+    pool: &PgPool,
+    market_service: &MarketDataService,
+    status: &EngineStatus,
+    config: &Config,
+    broker: &PaperBroker,
+    path: &str,
+) -> Result<(), AppError> {
+    let updates: Vec<KlineUpdate> = stream_capture::read_capture(path)?;
+    info!("Replaying {} captured klines from {}", updates.len(), path);
+
+    let mut snapshot_tracker = load_snapshot_tracker(pool).await?;
+    let mut last_final_bar: HashMap<String, i64> = HashMap::new();
+    for update in updates {
+        handle_update(
+            pool,
+            market_service,
+            status,
+            config,
+            broker,
+            update,
+            &mut last_final_bar,
+            &mut snapshot_tracker,
+        )
+        .await;
+    }
+    Ok(())
+}
+
+/// Why [`run_engine_cycle`] returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CycleEnd {
+    /// No active sessions to stream, or shutdown was requested.
+    Idle,
+    /// The active markets changed and the stream was torn down.
+    Resubscribe,
+    /// The stream failed or was closed by the server.
+    StreamLost,
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn run_engine_cycle(
     pool: &PgPool,
     market_service: &Arc<MarketDataService>,
-) -> Result<(), AppError> {
-    let subscriptions = fetch_active_subscriptions(pool).await?;
+    status: &EngineStatus,
+    config: &Config,
+    broker: &PaperBroker,
+    recorder: Option<&StreamRecorder>,
+    last_final_bar: &mut HashMap<String, i64>,
+    shutdown: &CancellationToken,
+) -> Result<CycleEnd, AppError> {
+    let mut subscriptions = fetch_active_subscriptions(pool, market_service).await?;
 
     if subscriptions.is_empty() {
         info!("No active sessions. Waiting...");
-        tokio::time::sleep(Duration::from_secs(5)).await;
-        return Ok(());
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs(5)) => {}
+            _ = shutdown.cancelled() => {}
+        }
+        return Ok(CycleEnd::Idle);
     }
+    // A market that dropped out of the stream has no gap to recover.
+    last_final_bar.retain(|market, _| {
+        subscriptions
+            .iter()
+            .any(|(symbol, interval)| *market == format!("{symbol}@{interval}"))
+    });
 
     let (tx, mut rx) = mpsc::unbounded_channel();
-    let stream = MarketStream::new();
-    stream.start_stream(subscriptions.clone(), tx).await;
-
-    let mut snapshot_tracker: HashMap<Uuid, DateTime<Utc>> = HashMap::new();
+    let mut stream = MarketStream::new(
+        status.diagnostics().clone(),
+        status.metrics().clone(),
+        shutdown,
+    );
+    stream.start_stream(subscriptions.clone(), tx.clone()).await;
+
+    let mut snapshot_tracker = load_snapshot_tracker(pool).await?;
     let mut refresh = tokio::time::interval(Duration::from_secs(30));
 
-    loop {
+    let end = loop {
         tokio::select! {
+            _ = shutdown.cancelled() => {
+                info!("Shutdown requested, stopping the stream");
+                break CycleEnd::Idle;
+            }
+            _ = stream.closed() => {
+                status
+                    .diagnostics()
+                    .record(DiagnosticKind::Stream, "Websocket stream ended");
+                break CycleEnd::StreamLost;
+            }
             maybe_event = rx.recv() => {
                 let Some(event) = maybe_event else {
                     warn!("Websocket channel closed, restarting...");
-                    break;
+                    status
+                        .diagnostics()
+                        .record(DiagnosticKind::Stream, "Websocket channel closed, restarting");
+                    break CycleEnd::StreamLost;
+                };
+                status.record_stream_event();
+
+                let Some(update) = extract_kline_info(event) else {
+                    continue;
                 };
+                capture(recorder, &update);
+                let mut batch = vec![update];
+                let lag_ms = Utc::now().timestamp_millis() - batch[0].event_time;
+                if lag_ms > config.stream_max_lag_ms {
+                    while let Ok(event) = rx.try_recv() {
+                        if let Some(update) = extract_kline_info(event) {
+                            capture(recorder, &update);
+                            batch.push(update);
+                        }
+                    }
+                    let received = batch.len();
+                    batch = latest_per_market(
+                        batch,
+                        |u| format!("{}@{}", u.symbol, u.interval),
+                        |u| u.kline.is_final_bar,
+                    );
+                    warn!(
+                        "Stream lagging by {} ms; dropped {} stale klines, resyncing on {}",
+                        lag_ms,
+                        received - batch.len(),
+                        batch.len()
+                    );
+                }
 
-                if let Some((symbol, interval, kline)) = extract_kline_info(event) {
-                    if let Err(e) = process_candle_event(
+                for update in batch {
+                    handle_update(
                         pool,
                         market_service,
-                        &symbol,
-                        &interval,
-                        &kline,
+                        status,
+                        config,
+                        broker,
+                        update,
+                        last_final_bar,
                         &mut snapshot_tracker,
                     )
-                    .await
-                    {
-                        error!("Error processing {} {}: {:?}", symbol, interval, e);
-                    }
+                    .await;
                 }
             }
             _ = refresh.tick() => {
-                let current_subs = fetch_active_subscriptions(pool).await?;
-                if current_subs != subscriptions {
+                let current_subs = fetch_active_subscriptions(pool, market_service).await?;
+                if current_subs == subscriptions {
+                    continue;
+                }
+                if current_subs.is_empty() || config.stream_handover_ms == 0 {
                     info!("Subscription list changed, restarting stream...");
-                    break;
+                    status.diagnostics().record(
+                        DiagnosticKind::Stream,
+                        "Subscription list changed, restarting stream",
+                    );
+                    break CycleEnd::Resubscribe;
                 }
+
+                // Connect the new stream before closing the old one so no bar
+                // falls into a reconnect gap; bars both deliver are deduplicated.
+                info!("Subscription list changed, handing over to a new stream...");
+                status.diagnostics().record(
+                    DiagnosticKind::Stream,
+                    format!("Handing over to a new stream for {} markets", current_subs.len()),
+                );
+                let next = MarketStream::new(
+                    status.diagnostics().clone(),
+                    status.metrics().clone(),
+                    shutdown,
+                );
+                next.start_stream(current_subs.clone(), tx.clone()).await;
+                let previous = std::mem::replace(&mut stream, next);
+                let handover = Duration::from_millis(config.stream_handover_ms);
+                tokio::spawn(async move {
+                    tokio::time::sleep(handover).await;
+                    previous.stop();
+                });
+                subscriptions = current_subs;
             }
         }
-    }
+    };
 
     stream.stop();
-    tokio::time::sleep(Duration::from_secs(2)).await;
+    if end == CycleEnd::Resubscribe {
+        // Let the old connection close before the next one opens.
+        tokio::time::sleep(Duration::from_secs(2)).await;
+    }
+    Ok(end)
+}
+
+/// Append `update` to the stream capture, if recording.
+fn capture(recorder: Option<&StreamRecorder>, update: &KlineUpdate) {
+    if let Some(recorder) = recorder {
+        if let Err(e) = recorder.record(update) {
+            warn!("{}", e);
+        }
+    }
+}
+
+/// Process one kline, skipping closed bars already seen.  Errors are logged
+/// and recorded rather than returned, so one market cannot stall the stream.
+#[allow(clippy::too_many_arguments)]
+async fn handle_update(
+    pool: &PgPool,
+    market_service: &MarketDataService,
+    status: &EngineStatus,
+    config: &Config,
+    broker: &PaperBroker,
+    KlineUpdate {
+        symbol,
+        interval,
+        kline,
+        ..
+    }: KlineUpdate,
+    last_final_bar: &mut HashMap<String, i64>,
+    snapshot_tracker: &mut HashMap<Uuid, DateTime<Utc>>,
+) {
+    if kline.is_final_bar {
+        let market = format!("{symbol}@{interval}");
+        let previous = last_final_bar.get(&market).copied();
+        if !first_final_bar(last_final_bar, market, kline.start_time) {
+            return;
+        }
+        if let Some(previous) = previous {
+            let missed = recover_gap(
+                market_service,
+                status,
+                &symbol,
+                &interval,
+                previous,
+                kline.start_time,
+            )
+            .await;
+            if !missed.is_empty() {
+                if let Err(e) = process_missed_bars(
+                    pool,
+                    market_service,
+                    status,
+                    config,
+                    &symbol,
+                    &interval,
+                    &missed,
+                    snapshot_tracker,
+                )
+                .await
+                {
+                    error!(
+                        "Error processing missed {} {} bars: {:?}",
+                        symbol, interval, e
+                    );
+                    status.diagnostics().record(
+                        DiagnosticKind::Engine,
+                        format!("Processing missed {symbol} {interval} bars failed: {e}"),
+                    );
+                }
+            }
+        }
+    }
+    status.record_kline();
+    status
+        .metrics()
+        .klines_processed
+        .with_label_values(&[symbol.as_str()])
+        .inc();
+    let started = Instant::now();
+    if let Err(e) = process_candle_event(
+        pool,
+        market_service,
+        status,
+        config,
+        broker,
+        &symbol,
+        &interval,
+        &kline,
+        last_final_bar,
+        snapshot_tracker,
+    )
+    .await
+    {
+        error!("Error processing {} {}: {:?}", symbol, interval, e);
+        status.diagnostics().record(
+            DiagnosticKind::Engine,
+            format!("Processing {symbol} {interval} failed: {e}"),
+        );
+    }
+    status.record(Phase::Tick, started.elapsed());
+}
+
+/// Closed bars the stream skipped between bars opening at `previous` and
+/// `start_time` (Unix ms), `step_ms` apart.
+fn missed_bars(previous: i64, start_time: i64, step_ms: i64) -> i64 {
+    if step_ms <= 0 {
+        return 0;
+    }
+    ((start_time - previous) / step_ms - 1).max(0)
+}
+
+/// Warn about closed bars the stream skipped before `start_time` and refetch
+/// them, oldest first, for [`process_missed_bars`] before the strategy runs
+/// on the new bar.  Empty when nothing was missed or the refetch failed.
+async fn recover_gap(
+    market: &MarketDataService,
+    status: &EngineStatus,
+    symbol: &str,
+    interval: &str,
+    previous: i64,
+    start_time: i64,
+) -> Vec<CandleBar> {
+    let Some(step_ms) = interval::seconds(interval).map(|s| s * 1000) else {
+        return Vec::new();
+    };
+    let missed = missed_bars(previous, start_time, step_ms);
+    if missed == 0 {
+        return Vec::new();
+    }
+    warn!(
+        "Stream skipped {} closed {} {} bars before {}; refetching",
+        missed, symbol, interval, start_time
+    );
+    status.diagnostics().record(
+        DiagnosticKind::Stream,
+        format!("Gap of {missed} {interval} bars on {symbol}"),
+    );
+
+    let wanted = missed.min(MAX_GAP_REFETCH_BARS);
+    match market
+        .fetch_candles_range_vec(
+            symbol,
+            interval,
+            start_time - wanted * step_ms,
+            start_time - 1,
+        )
+        .await
+    {
+        Ok(bars) => {
+            if bars.len() as i64 >= wanted {
+                info!(
+                    "Refetched {} missed {} {} bars",
+                    bars.len(),
+                    symbol,
+                    interval
+                );
+            } else {
+                warn!(
+                    "Binance returned {} of {} missed {} {} bars",
+                    bars.len(),
+                    wanted,
+                    symbol,
+                    interval
+                );
+            }
+            bars
+        }
+        Err(e) => {
+            warn!(
+                "Refetching missed {} {} bars failed: {}",
+                symbol, interval, e
+            );
+            Vec::new()
+        }
+    }
+}
+
+/// Mark the market's single-symbol sessions to each of the `bars` the
+/// stream missed, in order, and apply their protective exits and the
+/// bar-range exits of [`Config::exit_precedence`].
+///
+/// The strategy is not run on the missed bars: its candle fetch for the
+/// next closed bar already spans the gap.  Baskets are left to their next
+/// close, which reads the composite afresh.
+#[allow(clippy::too_many_arguments)]
+async fn process_missed_bars(
+    pool: &PgPool,
+    market: &MarketDataService,
+    status: &EngineStatus,
+    config: &Config,
+    symbol: &str,
+    interval: &str,
+    bars: &[CandleBar],
+    snapshot_tracker: &mut HashMap<Uuid, DateTime<Utc>>,
+) -> Result<(), AppError> {
+    let exits: Vec<ExitKind> = config
+        .exit_precedence
+        .iter()
+        .copied()
+        .filter(|k| *k != ExitKind::Signal)
+        .collect();
+    let sessions = market_sessions(pool, market, symbol, interval).await?;
+
+    for id in sessions.iter().filter(|s| !s.is_basket()).map(|s| s.id) {
+        for bar in bars {
+            let Some(session) = sqlx::query_as::<_, Session>(
+                "SELECT * FROM sessions WHERE id = $1 AND status = 'active'",
+            )
+            .bind(id)
+            .fetch_optional(pool)
+            .await?
+            else {
+                break;
+            };
+
+            update_equity_mtm(pool, status, &session, bar.close, snapshot_tracker, false).await?;
+            if enforce_min_equity(
+                pool,
+                status.session_events(),
+                config,
+                &session,
+                bar.close,
+                snapshot_tracker,
+            )
+            .await?
+                || enforce_protective_exits(
+                    pool,
+                    status.session_events(),
+                    &session,
+                    bar.close,
+                    snapshot_tracker,
+                )
+                .await?
+            {
+                continue;
+            }
+            check_exit_conditions(
+                pool,
+                status.session_events(),
+                &session,
+                (bar.high, bar.low, bar.close),
+                &exits,
+                snapshot_tracker,
+            )
+            .await?;
+        }
+    }
     Ok(())
 }
 
-async fn fetch_active_subscriptions(pool: &PgPool) -> Result<Vec<(String, String)>, AppError> {
+/// Active sessions trading `symbol` on `interval`, including baskets with
+/// it as a constituent.
+async fn market_sessions(
+    pool: &PgPool,
+    market: &MarketDataService,
+    symbol: &str,
+    interval: &str,
+) -> Result<Vec<Session>, AppError> {
+    // `symbol` is an exchange ticker; sessions may name it by alias, so they
+    // are matched after resolution rather than in SQL.
+    Ok(sqlx::query_as::<_, Session>(
+        "SELECT * FROM sessions WHERE status = 'active' AND interval = $1",
+    )
+    .bind(interval)
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .filter(|s| {
+        follows_kline(&s.basket_symbols(), &s.interval, symbol, interval, |c| {
+            market.resolve_symbol(c)
+        })
+    })
+    .collect())
+}
+
+/// Latest snapshot time of each active session, so the snapshot cooldown
+/// carries over engine restarts instead of starting empty.
+async fn load_snapshot_tracker(pool: &PgPool) -> Result<HashMap<Uuid, DateTime<Utc>>, AppError> {
+    let rows: Vec<(Uuid, DateTime<Utc>)> = sqlx::query_as(
+        r#"
+        SELECT es.session_id, MAX(es.timestamp)
+        FROM equity_snapshots es
+        JOIN sessions s ON s.id = es.session_id
+        WHERE s.status = 'active'
+        GROUP BY es.session_id
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().collect())
+}
+
+/// Exchange `(ticker, interval)` pairs to stream, with session symbol aliases
+/// resolved.
+async fn fetch_active_subscriptions(
+    pool: &PgPool,
+    market: &MarketDataService,
+) -> Result<Vec<(String, String)>, AppError> {
     let sessions = sqlx::query_as::<_, Session>("SELECT * FROM sessions WHERE status = 'active'")
         .fetch_all(pool)
         .await?;
 
+    Ok(subscription_pairs(
+        sessions
+            .iter()
+            .map(|s| (s.basket_symbols(), s.interval.as_str())),
+        |symbol| market.resolve_symbol(symbol),
+    ))
+}
+
+/// Distinct, sorted `(ticker, interval)` pairs streamed for sessions given as
+/// their symbols and interval.  A symbol traded at several intervals gets a
+/// pair, and so a `@kline_<interval>` stream, for each.
+fn subscription_pairs<'a>(
+    sessions: impl IntoIterator<Item = (Vec<String>, &'a str)>,
+    resolve: impl Fn(&str) -> String,
+) -> Vec<(String, String)> {
     let mut subs: Vec<(String, String)> = sessions
         .into_iter()
-        .map(|s| (s.symbol, s.interval))
+        .flat_map(|(symbols, interval)| {
+            symbols
+                .into_iter()
+                .map(|symbol| (resolve(&symbol), interval.to_string()))
+                .collect::<Vec<_>>()
+        })
         .collect();
 
     subs.sort();
     subs.dedup();
-    Ok(subs)
+    subs
+}
+
+/// Whether a session on `symbols` at `session_interval` runs on a kline of
+/// the exchange ticker `symbol` at `interval`.  A kline of another interval
+/// never drives the session, even for the same ticker.
+fn follows_kline(
+    symbols: &[String],
+    session_interval: &str,
+    symbol: &str,
+    interval: &str,
+    resolve: impl Fn(&str) -> String,
+) -> bool {
+    session_interval == interval && symbols.iter().any(|c| resolve(c) == symbol)
 }
 
-fn extract_kline_info(
-    event: CombinedStreamEvent<WebsocketEventUntag>,
-) -> Option<(String, String, Kline)> {
+/// A kline from the stream with the exchange ticker and interval it was
+/// published for.  Serialized as one line of a stream capture.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct KlineUpdate {
+    symbol: String,
+    interval: String,
+    kline: Kline,
+    /// Exchange event time in Unix milliseconds.
+    event_time: i64,
+}
+
+fn extract_kline_info(event: CombinedStreamEvent<WebsocketEventUntag>) -> Option<KlineUpdate> {
     let (stream_name, _) = event.parse_stream();
 
     if let WebsocketEventUntag::WebsocketEvent(WebsocketEvent::Kline(kline_event)) = event.data {
@@ -127,95 +781,806 @@ fn extract_kline_info(
         }
         let interval = parts[1].replace("kline_", "");
         let symbol = kline_event.kline.symbol.to_uppercase();
-        return Some((symbol, interval, kline_event.kline));
+        return Some(KlineUpdate {
+            symbol,
+            interval,
+            kline: kline_event.kline,
+            event_time: kline_event.event_time as i64,
+        });
     }
     None
 }
 
+/// Record the closed bar of `market` opening at `start_time`.  Returns
+/// `false` if that bar, or a later one, was already seen, as happens while
+/// two streams overlap during a handover.
+fn first_final_bar(seen: &mut HashMap<String, i64>, market: String, start_time: i64) -> bool {
+    match seen.get(&market) {
+        Some(&last) if last >= start_time => false,
+        _ => {
+            seen.insert(market, start_time);
+            true
+        }
+    }
+}
+
+/// Reduce a stale backlog to what still matters per market: its newest
+/// closed bar, which drives the strategy, and any newer in-progress update.
+/// Everything else would only replay outdated prices.  Order is preserved.
+fn latest_per_market<T>(
+    events: Vec<T>,
+    key: impl Fn(&T) -> String,
+    is_final: impl Fn(&T) -> bool,
+) -> Vec<T> {
+    let mut last_final: HashMap<String, usize> = HashMap::new();
+    let mut last_any: HashMap<String, usize> = HashMap::new();
+    for (i, event) in events.iter().enumerate() {
+        let k = key(event);
+        if is_final(event) {
+            last_final.insert(k.clone(), i);
+        }
+        last_any.insert(k, i);
+    }
+
+    let keep: HashSet<usize> = last_final
+        .into_values()
+        .chain(last_any.into_values())
+        .collect();
+    events
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| keep.contains(i))
+        .map(|(_, event)| event)
+        .collect()
+}
+
 async fn process_candle_event(
     pool: &PgPool,
     market: &MarketDataService,
+    status: &EngineStatus,
+    config: &Config,
+    broker: &PaperBroker,
     symbol: &str,
     interval: &str,
     kline: &Kline,
+    last_final_bar: &HashMap<String, i64>,
+    snapshot_tracker: &mut HashMap<Uuid, DateTime<Utc>>,
+) -> Result<(), AppError> {
+    let sessions = market_sessions(pool, market, symbol, interval).await?;
+
+    let feature_cache = FeatureCache::default();
+
+    // Sessions are independent, so they are evaluated concurrently.  Each
+    // session appears once per event and events are handled one at a time,
+    // so a session's own read-modify-write stays serialized.  Each task gets
+    // its own copy of the session's snapshot-tracker entry, merged back after.
+    let results: Vec<(HashMap<Uuid, DateTime<Utc>>, Result<(), AppError>)> = stream::iter(sessions)
+        .map(|session| {
+            let mut tracker: HashMap<Uuid, DateTime<Utc>> = snapshot_tracker
+                .get(&session.id)
+                .map(|ts| (session.id, *ts))
+                .into_iter()
+                .collect();
+            let feature_cache = &feature_cache;
+            async move {
+                let res = process_session_event(
+                    pool,
+                    market,
+                    status,
+                    config,
+                    broker,
+                    feature_cache,
+                    (symbol, interval, kline),
+                    last_final_bar,
+                    &session,
+                    &mut tracker,
+                )
+                .await;
+                (tracker, res)
+            }
+        })
+        .buffer_unordered(config.engine_session_concurrency.max(1))
+        .collect()
+        .await;
+
+    let mut first_err = None;
+    for (tracker, res) in results {
+        snapshot_tracker.extend(tracker);
+        if let Err(e) = res {
+            first_err.get_or_insert(e);
+        }
+    }
+    first_err.map_or(Ok(()), Err)
+}
+
+/// Apply one kline event to a single session.
+///
+/// `last_final_bar` holds the open time of each market's latest closed bar,
+/// this event's included.
+#[allow(clippy::too_many_arguments)]
+async fn process_session_event(
+    pool: &PgPool,
+    market: &MarketDataService,
+    status: &EngineStatus,
+    config: &Config,
+    broker: &PaperBroker,
+    feature_cache: &FeatureCache,
+    (symbol, interval, kline): (&str, &str, &Kline),
+    last_final_bar: &HashMap<String, i64>,
+    session: &Session,
     snapshot_tracker: &mut HashMap<Uuid, DateTime<Utc>>,
 ) -> Result<(), AppError> {
-    let current_price = kline.close;
     let is_closed = kline.is_final_bar;
 
-    let sessions = sqlx::query_as::<_, Session>(
-        "SELECT * FROM sessions WHERE status = 'active' AND symbol = $1 AND interval = $2",
+    if session.is_basket() {
+        // A basket bar is evaluated once, on the last of its constituents'
+        // closes; any other update is a tick of the open position.
+        let bar_complete = session.basket_symbols().iter().all(|leg| {
+            last_final_bar
+                .get(&format!("{}@{}", market.resolve_symbol(leg), interval))
+                .is_some_and(|&seen| seen >= kline.start_time)
+        });
+        if is_closed && bar_complete {
+            return process_basket_close(
+                pool,
+                market,
+                status,
+                config,
+                broker,
+                feature_cache,
+                session,
+                kline.start_time,
+                snapshot_tracker,
+            )
+            .await;
+        }
+        let price = current_price(market, session).await?;
+        enforce_tick_exits(pool, status, config, session, price, true, snapshot_tracker).await?;
+        return Ok(());
+    }
+
+    let current_price = kline.close;
+    if enforce_tick_exits(
+        pool,
+        status,
+        config,
+        session,
+        current_price,
+        !is_closed,
+        snapshot_tracker,
+    )
+    .await?
+    {
+        return Ok(());
+    }
+
+    if is_closed {
+        info!(
+            "Candle Closed: {} {} @ ${}",
+            symbol, interval, current_price
+        );
+
+        evaluate_closed_bar(
+            pool,
+            market,
+            status,
+            config,
+            broker,
+            feature_cache,
+            session,
+            (kline.high, kline.low, kline.close),
+            snapshot_tracker,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Mark `session` to `price` and apply the exits that watch every tick: the
+/// equity floor, stop-loss and take-profit, and, when `trail` is set, the
+/// trailing stop.  Returns whether the position or session was closed.
+#[allow(clippy::too_many_arguments)]
+async fn enforce_tick_exits(
+    pool: &PgPool,
+    status: &EngineStatus,
+    config: &Config,
+    session: &Session,
+    price: f64,
+    trail: bool,
+    snapshot_tracker: &mut HashMap<Uuid, DateTime<Utc>>,
+) -> Result<bool, AppError> {
+    update_equity_mtm(pool, status, session, price, snapshot_tracker, false).await?;
+    if enforce_min_equity(
+        pool,
+        status.session_events(),
+        config,
+        session,
+        price,
+        snapshot_tracker,
+    )
+    .await?
+    {
+        return Ok(true);
+    }
+    if enforce_protective_exits(
+        pool,
+        status.session_events(),
+        session,
+        price,
+        snapshot_tracker,
+    )
+    .await?
+    {
+        return Ok(true);
+    }
+    Ok(trail
+        && enforce_trailing_stop(
+            pool,
+            status.session_events(),
+            session,
+            price,
+            snapshot_tracker,
+        )
+        .await?)
+}
+
+/// Evaluate the basket bar opening at `open_time` (Unix ms), once every
+/// constituent has closed it, against the composite index.
+///
+/// The strategy trades the composite, but a position is split equally across
+/// the constituents at entry, with their prices recorded in
+/// `leg_entry_prices`; it is marked and stopped out at
+/// [`market_data::split_position_bars`].  Each leg's entry carries the same
+/// slippage as the session's `entry_price`, so the position opens marked at
+/// the composite close.  The bar is skipped when any constituent lacks it.
+#[allow(clippy::too_many_arguments)]
+async fn process_basket_close(
+    pool: &PgPool,
+    market: &MarketDataService,
+    status: &EngineStatus,
+    config: &Config,
+    broker: &PaperBroker,
+    feature_cache: &FeatureCache,
+    session: &Session,
+    open_time: i64,
+    snapshot_tracker: &mut HashMap<Uuid, DateTime<Utc>>,
+) -> Result<(), AppError> {
+    let symbols = session.basket_symbols();
+    let legs = market
+        .fetch_basket_legs_vec(&symbols, &session.interval, 3)
+        .await?;
+    let bars = market_data::split_position_bars(
+        &legs,
+        session.entry_price,
+        session.leg_entry_prices.as_deref(),
+    );
+    let Some(index) = bars.iter().position(|bar| bar.time == open_time) else {
+        warn!(
+            "Basket {} has no {} bar opening at {} in every constituent, skipping",
+            session.symbol, session.interval, open_time
+        );
+        return Ok(());
+    };
+    let bar = &bars[index];
+
+    info!(
+        "Basket Candle Closed: {} {} @ {:.4} ({} legs)",
+        session.symbol,
+        session.interval,
+        bar.close,
+        symbols.len()
+    );
+
+    if enforce_tick_exits(
+        pool,
+        status,
+        config,
+        session,
+        bar.close,
+        false,
+        snapshot_tracker,
+    )
+    .await?
+    {
+        return Ok(());
+    }
+
+    evaluate_closed_bar(
+        pool,
+        market,
+        status,
+        config,
+        broker,
+        feature_cache,
+        session,
+        (bar.high, bar.low, bar.close),
+        snapshot_tracker,
     )
-    .bind(symbol)
-    .bind(interval)
-    .fetch_all(pool)
     .await?;
 
-    for session in sessions {
-        update_equity_mtm(pool, &session, current_price, snapshot_tracker, false).await?;
+    // A position opened on this bar takes the constituents' closes, scaled by
+    // its fill's slippage over the composite close, as its leg entries.
+    let Some(entry_price) = sqlx::query_scalar::<_, Option<f64>>(
+        "SELECT entry_price FROM sessions WHERE id = $1 AND current_position <> 0 AND open_trade_id IS DISTINCT FROM $2",
+    )
+    .bind(session.id)
+    .bind(session.open_trade_id)
+    .fetch_optional(pool)
+    .await?
+    .flatten() else {
+        return Ok(());
+    };
+    let composite_close = market_data::split_position_bars(&legs, None, None)[index].close;
+    let fill = entry_price / composite_close;
+    let leg_entries: Vec<f64> = legs.iter().map(|leg| leg[index].close * fill).collect();
+    sqlx::query(
+        "UPDATE sessions SET leg_entry_prices = $1 WHERE id = $2 AND current_position <> 0 AND open_trade_id IS DISTINCT FROM $3",
+    )
+    .bind(&leg_entries)
+    .bind(session.id)
+    .bind(session.open_trade_id)
+    .execute(pool)
+    .await?;
 
-        if is_closed {
-            info!("Candle Closed: {} {} @ ${}", symbol, interval, current_price);
+    Ok(())
+}
 
-            let position_closed = check_exit_conditions(
-                pool,
-                &session,
-                kline,
-                snapshot_tracker
-            ).await?;
+/// Bars of `session`'s basket as its open position is valued, the last
+/// `limit` of them; see [`market_data::split_position_bars`].
+pub(crate) async fn basket_position_bars(
+    market: &MarketDataService,
+    session: &Session,
+    limit: u16,
+) -> Result<Vec<CandleBar>, AppError> {
+    let legs = market
+        .fetch_basket_legs_vec(&session.basket_symbols(), &session.interval, limit)
+        .await?;
+    Ok(market_data::split_position_bars(
+        &legs,
+        session.entry_price,
+        session.leg_entry_prices.as_deref(),
+    ))
+}
 
-            if !position_closed {
-                run_strategy_logic(pool, market, &session, current_price, snapshot_tracker).await?;
-            }
+/// Close any position and retire `session` as `liquidated` once its equity
+/// marked at `price` falls below `min_session_equity`, rather than letting it
+/// trade meaningless notionals.  Returns whether the session was liquidated.
+async fn enforce_min_equity(
+    pool: &PgPool,
+    events: &SessionEvents,
+    config: &Config,
+    session: &Session,
+    price: f64,
+    snapshot_tracker: &mut HashMap<Uuid, DateTime<Utc>>,
+) -> Result<bool, AppError> {
+    let equity = mark_to_market(session, price).unwrap_or(session.current_equity);
+    if equity >= config.min_session_equity {
+        return Ok(false);
+    }
+
+    let reason = format!(
+        "Equity {:.2} fell below the minimum of {:.2}",
+        equity, config.min_session_equity
+    );
+    warn!("Session {} liquidated: {}", session.id, reason);
+    if session.current_position != 0.0 {
+        close_position(
+            pool,
+            events,
+            session,
+            price,
+            "Minimum Equity".to_string(),
+            OrderType::Stop,
+            snapshot_tracker,
+        )
+        .await?;
+    }
+    sqlx::query("UPDATE sessions SET status = 'liquidated', last_error = $1 WHERE id = $2")
+        .bind(reason)
+        .bind(session.id)
+        .execute(pool)
+        .await?;
+    Ok(true)
+}
+
+/// A session's stop-loss or take-profit, hit by the price move since entry.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ProtectiveExit {
+    StopLoss,
+    TakeProfit,
+}
+
+impl ProtectiveExit {
+    fn reason(&self) -> &'static str {
+        match self {
+            ProtectiveExit::StopLoss => "stop_loss",
+            ProtectiveExit::TakeProfit => "take_profit",
         }
     }
 
-    Ok(())
+    fn order_type(&self) -> OrderType {
+        match self {
+            ProtectiveExit::StopLoss => OrderType::Stop,
+            ProtectiveExit::TakeProfit => OrderType::Limit,
+        }
+    }
 }
 
-async fn check_exit_conditions(
+/// Which protective exit, if any, `price` triggers for a `position` entered
+/// at `entry_price`.  The move is measured on price, in the position's
+/// favour, so a short is stopped out by a rise.  A threshold that is `None`
+/// or not positive is disabled.
+fn protective_exit(
+    position: f64,
+    entry_price: f64,
+    price: f64,
+    stop_loss_pct: Option<f64>,
+    take_profit_pct: Option<f64>,
+) -> Option<ProtectiveExit> {
+    if position == 0.0 || entry_price <= 0.0 {
+        return None;
+    }
+    let direction = if position > 0.0 { 1.0 } else { -1.0 };
+    let favourable_move = direction * (price - entry_price) / entry_price;
+    let enabled = |pct: Option<f64>| pct.filter(|p| *p > 0.0);
+
+    if enabled(stop_loss_pct).is_some_and(|stop| favourable_move <= -stop) {
+        Some(ProtectiveExit::StopLoss)
+    } else if enabled(take_profit_pct).is_some_and(|target| favourable_move >= target) {
+        Some(ProtectiveExit::TakeProfit)
+    } else {
+        None
+    }
+}
+
+/// Close `session`'s position at `price` if it has hit its stop-loss or
+/// take-profit.  Runs on every tick rather than waiting for the bar to
+/// close.  Returns whether the position was closed.
+async fn enforce_protective_exits(
     pool: &PgPool,
+    events: &SessionEvents,
     session: &Session,
-    kline: &Kline,
+    price: f64,
     snapshot_tracker: &mut HashMap<Uuid, DateTime<Utc>>,
 ) -> Result<bool, AppError> {
+    let Some(entry_price) = session.entry_price else {
+        return Ok(false);
+    };
+    let Some(exit) = protective_exit(
+        session.current_position,
+        entry_price,
+        price,
+        session.stop_loss_pct,
+        session.take_profit_pct,
+    ) else {
+        return Ok(false);
+    };
+
+    info!(
+        "{} hit for session {}: {} entry ${} now ${}",
+        exit.reason(),
+        session.id,
+        session.symbol,
+        entry_price,
+        price
+    );
+    close_position(
+        pool,
+        events,
+        session,
+        price,
+        exit.reason().to_string(),
+        exit.order_type(),
+        snapshot_tracker,
+    )
+    .await?;
+    Ok(true)
+}
+
+/// The session's trailing-stop distance, or `None` when disabled.
+fn trail_distance(session: &Session) -> Option<f64> {
+    match session.trail_pct {
+        None => Some(TRAILING_SL_PCT),
+        Some(pct) => (pct > 0.0).then_some(pct),
+    }
+}
+
+/// Move the position's best price `extreme` to `price` if it improves on it,
+/// and report whether `price` has retraced `trail` from the result.
+fn trail_tick(is_long: bool, extreme: f64, price: f64, trail: f64) -> (f64, bool) {
+    if is_long {
+        let best = extreme.max(price);
+        (best, price <= best * (1.0 - trail))
+    } else {
+        let best = extreme.min(price);
+        (best, price >= best * (1.0 + trail))
+    }
+}
+
+/// Track the trailing stop on an in-progress tick: persist a new best price
+/// so it survives a restart, and close at `price` once it retraces the
+/// trail.  Closed bars are left to [`check_exit_conditions`], which applies
+/// the configured exit precedence.  Returns whether the position was closed.
+async fn enforce_trailing_stop(
+    pool: &PgPool,
+    events: &SessionEvents,
+    session: &Session,
+    price: f64,
+    snapshot_tracker: &mut HashMap<Uuid, DateTime<Utc>>,
+) -> Result<bool, AppError> {
+    let (Some(trail), Some(entry_price)) = (trail_distance(session), session.entry_price) else {
+        return Ok(false);
+    };
     if session.current_position == 0.0 {
         return Ok(false);
     }
 
-    let mut highest = session.highest_high.unwrap_or(session.entry_price.unwrap_or(kline.close));
-    let mut lowest = session.lowest_low.unwrap_or(session.entry_price.unwrap_or(kline.close));
-    let mut db_update_needed = false;
+    let is_long = session.current_position > 0.0;
+    let extreme = if is_long {
+        session.highest_high
+    } else {
+        session.lowest_low
+    }
+    .unwrap_or(entry_price);
+    let (best, hit) = trail_tick(is_long, extreme, price, trail);
+
+    if hit {
+        info!(
+            "Trailing Stop Triggered (Tick): {} ${} retraced {:.2}% from ${}",
+            session.symbol,
+            price,
+            trail * 100.0,
+            best
+        );
+        close_position(
+            pool,
+            events,
+            session,
+            price,
+            "Trailing Stop".to_string(),
+            OrderType::Stop,
+            snapshot_tracker,
+        )
+        .await?;
+        return Ok(true);
+    }
 
-    if session.current_position > 0.0 {
-        if kline.high > highest {
-            highest = kline.high;
-            db_update_needed = true;
-        }
+    if best != extreme {
+        let column = if is_long {
+            "highest_high"
+        } else {
+            "lowest_low"
+        };
+        sqlx::query(&format!("UPDATE sessions SET {column} = $1 WHERE id = $2"))
+            .bind(best)
+            .bind(session.id)
+            .execute(pool)
+            .await?;
+    }
+    Ok(false)
+}
 
-        let stop_price = highest * (1.0 - TRAILING_SL_PCT);
+/// Apply the exits and strategy signal to a closed bar in the configured
+/// [`ExitKind`] precedence.  Stops ranked ahead of the signal are checked
+/// first and pre-empt it; stops ranked behind it are checked afterwards, but
+/// only if the signal left the same position open, since the bar's range
+/// predates any new entry.
+#[allow(clippy::too_many_arguments)]
+async fn evaluate_closed_bar(
+    pool: &PgPool,
+    market: &MarketDataService,
+    status: &EngineStatus,
+    config: &Config,
+    broker: &PaperBroker,
+    feature_cache: &FeatureCache,
+    session: &Session,
+    bar: (f64, f64, f64),
+    snapshot_tracker: &mut HashMap<Uuid, DateTime<Utc>>,
+) -> Result<(), AppError> {
+    let signal_at = config
+        .exit_precedence
+        .iter()
+        .position(|k| *k == ExitKind::Signal)
+        .unwrap_or(config.exit_precedence.len());
+    let (before_signal, after_signal) = config.exit_precedence.split_at(signal_at);
+
+    if check_exit_conditions(
+        pool,
+        status.session_events(),
+        session,
+        bar,
+        before_signal,
+        snapshot_tracker,
+    )
+    .await?
+    {
+        return Ok(());
+    }
 
-        if kline.low <= stop_price {
-            info!("LONG Trailing Stop Triggered (Bar Close): {} Low ${} <= Stop ${}", session.symbol, kline.low, stop_price);
-            close_position(pool, session, kline.close, "Trailing Stop (Bar Close)".to_string(), snapshot_tracker).await?;
-            return Ok(true);
-        }
+    run_strategy_logic(
+        pool,
+        market,
+        status,
+        config,
+        broker,
+        feature_cache,
+        session,
+        bar.2,
+        snapshot_tracker,
+    )
+    .await?;
 
+    let after_signal: Vec<ExitKind> = after_signal
+        .iter()
+        .copied()
+        .filter(|k| *k != ExitKind::Signal)
+        .collect();
+    if after_signal.is_empty() || session.current_position == 0.0 {
+        return Ok(());
+    }
+    let Some(current) =
+        sqlx::query_as::<_, Session>("SELECT * FROM sessions WHERE id = $1 AND status = 'active'")
+            .bind(session.id)
+            .fetch_optional(pool)
+            .await?
+    else {
+        return Ok(());
+    };
+    if current.open_trade_id != session.open_trade_id
+        || current.current_position != session.current_position
+    {
+        return Ok(());
+    }
+    check_exit_conditions(
+        pool,
+        status.session_events(),
+        &current,
+        bar,
+        &after_signal,
+        snapshot_tracker,
+    )
+    .await?;
+    Ok(())
+}
+
+/// A stop-type exit triggered within one bar.
+#[derive(Debug, Clone, PartialEq)]
+struct TriggeredExit {
+    kind: ExitKind,
+    price: f64,
+}
+
+/// Choose among the exits triggered in one bar.  The kind ranked first in
+/// `precedence` names the exit, but since the path within the bar is unknown
+/// the fill is the worst-case triggered price for the position: the lowest
+/// for a long, the highest for a short.
+fn select_exit(
+    triggered: &[TriggeredExit],
+    precedence: &[ExitKind],
+    is_long: bool,
+) -> Option<TriggeredExit> {
+    let kind = *precedence
+        .iter()
+        .find(|k| triggered.iter().any(|t| t.kind == **k))?;
+    let prices = triggered.iter().map(|t| t.price);
+    let price = if is_long {
+        prices.fold(f64::INFINITY, f64::min)
     } else {
-        if kline.low < lowest {
-            lowest = kline.low;
-            db_update_needed = true;
+        prices.fold(f64::NEG_INFINITY, f64::max)
+    };
+    Some(TriggeredExit { kind, price })
+}
+
+/// Check the stop-type exits in `kinds` against a closed `(high, low, close)`
+/// bar and close the position on the one chosen by [`select_exit`].  Returns
+/// whether the position was closed.
+async fn check_exit_conditions(
+    pool: &PgPool,
+    events: &SessionEvents,
+    session: &Session,
+    (bar_high, bar_low, bar_close): (f64, f64, f64),
+    kinds: &[ExitKind],
+    snapshot_tracker: &mut HashMap<Uuid, DateTime<Utc>>,
+) -> Result<bool, AppError> {
+    if session.current_position == 0.0 || kinds.is_empty() {
+        return Ok(false);
+    }
+
+    let is_long = session.current_position > 0.0;
+    let mut triggered = Vec::new();
+
+    let leverage = open_exposure(session);
+    if kinds.contains(&ExitKind::Liquidation) {
+        if let Some(liq_price) = session
+            .entry_price
+            .and_then(|entry| liquidation_price(entry, session.current_position, leverage))
+        {
+            let breached = if is_long {
+                bar_low <= liq_price
+            } else {
+                bar_high >= liq_price
+            };
+            if breached {
+                warn!(
+                    "Liquidation: {} {:.1}x position breached ${:.4}",
+                    session.symbol, leverage, liq_price
+                );
+                triggered.push(TriggeredExit {
+                    kind: ExitKind::Liquidation,
+                    price: liq_price,
+                });
+            }
         }
+    }
+
+    let mut highest = session
+        .highest_high
+        .unwrap_or(session.entry_price.unwrap_or(bar_close));
+    let mut lowest = session
+        .lowest_low
+        .unwrap_or(session.entry_price.unwrap_or(bar_close));
+    let mut db_update_needed = false;
 
-        let stop_price = lowest * (1.0 + TRAILING_SL_PCT);
+    let trail = trail_distance(session).filter(|_| kinds.contains(&ExitKind::TrailingStop));
+    if let Some(trail) = trail {
+        if is_long {
+            if bar_high > highest {
+                highest = bar_high;
+                db_update_needed = true;
+            }
+
+            let stop_price = highest * (1.0 - trail);
+
+            if bar_low <= stop_price {
+                info!(
+                    "LONG Trailing Stop Triggered (Bar Close): {} Low ${} <= Stop ${}",
+                    session.symbol, bar_low, stop_price
+                );
+                triggered.push(TriggeredExit {
+                    kind: ExitKind::TrailingStop,
+                    price: bar_close,
+                });
+            }
+        } else {
+            if bar_low < lowest {
+                lowest = bar_low;
+                db_update_needed = true;
+            }
 
-        if kline.high >= stop_price {
-            info!("SHORT Trailing Stop Triggered (Bar Close): {} High ${} >= Stop ${}", session.symbol, kline.high, stop_price);
-            close_position(pool, session, kline.close, "Trailing Stop (Bar Close)".to_string(), snapshot_tracker).await?;
-            return Ok(true);
+            let stop_price = lowest * (1.0 + trail);
+
+            if bar_high >= stop_price {
+                info!(
+                    "SHORT Trailing Stop Triggered (Bar Close): {} High ${} >= Stop ${}",
+                    session.symbol, bar_high, stop_price
+                );
+                triggered.push(TriggeredExit {
+                    kind: ExitKind::TrailingStop,
+                    price: bar_close,
+                });
+            }
         }
     }
 
+    if let Some(exit) = select_exit(&triggered, kinds, is_long) {
+        let reason = match exit.kind {
+            ExitKind::Liquidation => "Liquidation",
+            _ => "Trailing Stop (Bar Close)",
+        };
+        close_position(
+            pool,
+            events,
+            session,
+            exit.price,
+            reason.to_string(),
+            OrderType::Stop,
+            snapshot_tracker,
+        )
+        .await?;
+        return Ok(true);
+    }
+
     if db_update_needed {
         sqlx::query("UPDATE sessions SET highest_high = $1, lowest_low = $2 WHERE id = $3")
             .bind(highest)
@@ -228,23 +1593,39 @@ async fn check_exit_conditions(
     Ok(false)
 }
 
+/// Equity of `session`'s open position marked at `price`, or `None` when it
+/// is flat.
+pub(crate) fn mark_to_market(session: &Session, price: f64) -> Option<f64> {
+    if session.current_position.abs() < f64::EPSILON {
+        return None;
+    }
+    let entry_price = session.entry_price?;
+    let basis_equity = session.entry_equity.unwrap_or(session.current_equity);
+
+    let direction = if session.current_position > 0.0 {
+        1.0
+    } else {
+        -1.0
+    };
+    let leverage = open_exposure(session);
+    let raw_pnl_pct = direction * (price - entry_price) / entry_price;
+    Some((basis_equity * (1.0 + raw_pnl_pct * leverage)).max(0.0))
+}
+
+/// Mark `session` to `current_price`, writing an equity snapshot when forced
+/// or when the session's cooldown has passed and the snapshot budget has
+/// room, and publish the new equity.
 async fn update_equity_mtm(
     pool: &PgPool,
+    status: &EngineStatus,
     session: &Session,
     current_price: f64,
     snapshot_tracker: &mut HashMap<Uuid, DateTime<Utc>>,
-    force_snapshot: bool,
-) -> Result<(), AppError> {
-    if session.current_position.abs() < f64::EPSILON || session.entry_price.is_none() {
-        return Ok(());
-    }
-
-    let entry_price = session.entry_price.unwrap_or(current_price);
-    let basis_equity = session.entry_equity.unwrap_or(session.current_equity);
-
-    let direction = if session.current_position > 0.0 { 1.0 } else { -1.0 };
-    let raw_pnl_pct = direction * (current_price - entry_price) / entry_price;
-    let mtm_equity = basis_equity * (1.0 + raw_pnl_pct);
+    force_snapshot: bool,
+) -> Result<(), AppError> {
+    let Some(mtm_equity) = mark_to_market(session, current_price) else {
+        return Ok(());
+    };
 
     let now = Utc::now();
     let time_since_update = now
@@ -270,7 +1651,8 @@ async fn update_equity_mtm(
         || snapshot_tracker
             .get(&session.id)
             .map(|ts| now.signed_duration_since(*ts).num_milliseconds() >= SNAPSHOT_COOLDOWN_MS)
-            .unwrap_or(true);
+            .unwrap_or(true)
+            && status.snapshot_budget().try_take();
 
     if allow_snapshot {
         sqlx::query(
@@ -285,106 +1667,76 @@ async fn update_equity_mtm(
     }
 
     tx.commit().await?;
+    status.session_events().publish(SessionEvent {
+        session_id: session.id,
+        equity: mtm_equity,
+        position: session.current_position,
+        last_trade: None,
+    });
     Ok(())
 }
 
 async fn run_strategy_logic(
     pool: &PgPool,
     market: &MarketDataService,
+    status: &EngineStatus,
+    config: &Config,
+    broker: &PaperBroker,
+    feature_cache: &FeatureCache,
     session: &Session,
     current_price: f64,
     snapshot_tracker: &mut HashMap<Uuid, DateTime<Utc>>,
 ) -> Result<(), AppError> {
     let strategy_record = sqlx::query_as::<_, StrategyRow>(
-        "SELECT strategy_type, parameters FROM strategies WHERE id = $1",
+        "SELECT strategy_type, parameters, kelly_fraction FROM strategies WHERE id = $1",
     )
     .bind(session.strategy_id)
-    .fetch_one(pool)
+    .fetch_optional(pool)
     .await?;
+    let Some(strategy_record) = strategy_record else {
+        warn!(
+            "Session {} references missing strategy {}; marking orphaned",
+            session.id, session.strategy_id
+        );
+        orphan_session(pool, status.diagnostics(), session).await?;
+        return Ok(());
+    };
     let strategy_type = strategy_record.strategy_type;
 
-    let raw_df = market
-        .fetch_candles(&session.symbol, &session.interval, 1000)
-        .await?;
+    let df = load_features(market, status, feature_cache, session).await?;
 
-    let df =
-        FeatureEngine::add_technicals(&raw_df, None).map_err(|e| AppError::Data(e.to_string()))?;
-
-    let signal_series = match strategy_type.as_str() {
-        "DynamicTrend" => {
-            let strat: DynamicTrend = serde_json::from_value(strategy_record.parameters.clone())
-                .map_err(|e| AppError::Strategy(format!("Config error: {e}")))?;
-            strat
-                .predict(&df)
-                .map_err(|e| AppError::Strategy(e.to_string()))?
-        }
-        "RsiMeanReversion" => {
-            let strat: RsiMeanReversion =
-                serde_json::from_value(strategy_record.parameters.clone())
-                    .map_err(|e| AppError::Strategy(format!("Config error: {e}")))?;
-            strat
-                .predict(&df)
-                .map_err(|e| AppError::Strategy(e.to_string()))?
-        }
-        "BollingerReversion" => {
-            let strat: BollingerReversion =
-                serde_json::from_value(strategy_record.parameters.clone())
-                    .map_err(|e| AppError::Strategy(format!("Config error: {e}")))?;
-            strat
-                .predict(&df)
-                .map_err(|e| AppError::Strategy(e.to_string()))?
-        }
-        "AtrBreakout" => {
-            let strat: AtrBreakout = serde_json::from_value(strategy_record.parameters.clone())
-                .map_err(|e| AppError::Strategy(format!("Config error: {e}")))?;
-            strat
-                .predict(&df)
-                .map_err(|e| AppError::Strategy(e.to_string()))?
-        }
-        "VolatilitySqueeze" => {
-            let strat: VolatilitySqueeze =
-                serde_json::from_value(strategy_record.parameters.clone())
-                    .map_err(|e| AppError::Strategy(format!("Config error: {e}")))?;
-            strat
-                .predict(&df)
-                .map_err(|e| AppError::Strategy(e.to_string()))?
-        }
-        "MacdTrend" => {
-            let strat: MacdTrend = serde_json::from_value(strategy_record.parameters.clone())
-                .map_err(|e| AppError::Strategy(format!("Config error: {e}")))?;
-            strat
-                .predict(&df)
-                .map_err(|e| AppError::Strategy(e.to_string()))?
-        }
-        "ObvTrend" => {
-            let strat: ObvTrend = serde_json::from_value(strategy_record.parameters.clone())
-                .map_err(|e| AppError::Strategy(format!("Config error: {e}")))?;
-            strat
-                .predict(&df)
-                .map_err(|e| AppError::Strategy(e.to_string()))?
-        }
-        "PriceMomentum" => {
-            let strat: PriceMomentum =
-                serde_json::from_value(strategy_record.parameters.clone())
-                    .map_err(|e| AppError::Strategy(format!("Config error: {e}")))?;
-            strat
-                .predict(&df)
-                .map_err(|e| AppError::Strategy(e.to_string()))?
-        }
-        "AdaptiveMaCrossover" => {
-            let strat: AdaptiveMaCrossover =
-                serde_json::from_value(strategy_record.parameters.clone())
-                    .map_err(|e| AppError::Strategy(format!("Config error: {e}")))?;
-            strat
-                .predict(&df)
-                .map_err(|e| AppError::Strategy(e.to_string()))?
-        }
-        _ => {
-            warn!("Unknown strategy type: {}", strategy_type);
-            return Ok(());
-        }
+    if let Some(msg) = stale_data_message(
+        &df,
+        &session.interval,
+        config.max_candle_age_intervals,
+        Utc::now(),
+    )? {
+        warn!("Session {}: {}; skipping tick", session.id, msg);
+        record_session_error(pool, status.diagnostics(), session, &msg).await?;
+        return Ok(());
+    }
+
+    let Some(entry) = strategy_registry::lookup(&strategy_type) else {
+        warn!(
+            "Unknown strategy type {} for session {}, pausing",
+            strategy_type, session.id
+        );
+        pause_session_with_error(
+            pool,
+            status.diagnostics(),
+            session,
+            &format!("Unknown strategy type: {strategy_type}"),
+        )
+        .await?;
+        return Ok(());
     };
 
+    let started = Instant::now();
+    let predict_span = info_span!("predict", session = %session.id, strategy = %strategy_type);
+    let signal_series =
+        predict_span.in_scope(|| entry.predict(&strategy_record.parameters, &df))?;
+    status.record(Phase::Predict, started.elapsed());
+
     let signals = signal_series
         .f64()
         .map_err(|e| AppError::Data(e.to_string()))?;
@@ -392,60 +1744,522 @@ async fn run_strategy_logic(
         return Ok(());
     }
 
-    let idx = signals.len() - 1;
-    let signal = signals.get(idx).unwrap_or(0.0);
+    if let Err(msg) = check_signal_length(signals.len(), df.height(), config.signal_length_mismatch)
+    {
+        record_session_error(pool, status.diagnostics(), session, &msg).await?;
+        return Err(AppError::Strategy(msg));
+    }
+
+    sqlx::query(
+        "UPDATE sessions SET last_signal_at = NOW(), last_error = CASE WHEN last_error LIKE 'STALE_DATA:%' THEN NULL ELSE last_error END WHERE id = $1",
+    )
+        .bind(session.id)
+        .execute(pool)
+        .await?;
+
+    let signals: Vec<Option<f64>> = signals.into_iter().collect();
+    let signal = match decide_bar(session, &signals).decision {
+        SignalDecision::Act(signal) => signal,
+        SignalDecision::HoldUnconfirmed | SignalDecision::NoEdge(_) => return Ok(()),
+        SignalDecision::HoldNoSignal => {
+            info!(
+                "No valid {} signal for session {} on latest bar, holding position {}",
+                strategy_type, session.id, session.current_position
+            );
+            return Ok(());
+        }
+    };
+    if session.execution_mode == "alert" {
+        // No capital is at risk, so alerts keep flowing while trading is paused.
+        return report_alert(
+            pool,
+            status.notifier(),
+            session,
+            &strategy_type,
+            signal,
+            current_price,
+        )
+        .await;
+    }
+    if !status.trading_enabled() {
+        info!(
+            "Trading paused, not acting on {} signal {} for session {}",
+            strategy_type, signal, session.id
+        );
+        return Ok(());
+    }
     let reason = format!("{strategy_type} Signal");
+    // Baskets have no exchange symbol to take a lot size from.
+    let lot_size = if config.enforce_lot_size && !session.is_basket() {
+        market
+            .lot_size(&market.resolve_symbol(&session.symbol))
+            .await?
+    } else {
+        None
+    };
 
+    let started = Instant::now();
     execute_strategy_signal(
         pool,
+        market,
+        status.session_events(),
+        broker,
+        config,
+        strategy_record.kelly_fraction,
+        lot_size,
         session,
         signal,
         current_price,
         reason,
         snapshot_tracker,
     )
+    .instrument(info_span!("execute", session = %session.id))
     .await?;
+    status.record(Phase::Execute, started.elapsed());
+
+    Ok(())
+}
+
+/// Record the latest signal of an alert session and notify on a change of
+/// direction, including the first evaluation.  Alert sessions never trade.
+async fn report_alert(
+    pool: &PgPool,
+    notifier: &Notifier,
+    session: &Session,
+    strategy_type: &str,
+    signal: f64,
+    price: f64,
+) -> Result<(), AppError> {
+    sqlx::query("UPDATE sessions SET alert_signal = $1 WHERE id = $2")
+        .bind(signal)
+        .bind(session.id)
+        .execute(pool)
+        .await?;
+
+    let direction = notifier::direction(signal);
+    if session.alert_signal.map(notifier::direction) == Some(direction) {
+        return Ok(());
+    }
+
+    let explanation = match session.alert_signal {
+        Some(previous) => format!(
+            "{strategy_type} on {} {} turned {direction} (signal {previous:.3} -> {signal:.3}) at {price}",
+            session.symbol,
+            session.interval,
+        ),
+        None => format!(
+            "{strategy_type} on {} {} is {direction} (signal {signal:.3}) at {price}",
+            session.symbol, session.interval,
+        ),
+    };
+    info!("Alert for session {}: {}", session.id, explanation);
+    notifier.notify(SignalAlert {
+        session_id: session.id,
+        strategy_id: session.strategy_id,
+        strategy_type: strategy_type.to_string(),
+        symbol: session.symbol.clone(),
+        interval: session.interval.clone(),
+        direction,
+        signal,
+        previous_signal: session.alert_signal,
+        price,
+        at: Utc::now(),
+        explanation,
+    });
+    Ok(())
+}
+
+/// Fetch candles and compute technical features for the session's market,
+/// reusing the frame from `feature_cache` when another session on the same
+/// market has already computed it this event.
+async fn load_features(
+    market: &MarketDataService,
+    status: &EngineStatus,
+    feature_cache: &FeatureCache,
+    session: &Session,
+) -> Result<Arc<DataFrame>, AppError> {
+    let cell = feature_cache.cell(&session.symbol, &session.interval);
+    let computed = AtomicBool::new(false);
+    let df = cell
+        .get_or_try_init(|| async {
+            computed.store(true, Ordering::Relaxed);
+
+            let started = Instant::now();
+            let raw_df = async {
+                if session.is_basket() {
+                    market
+                        .fetch_basket_candles(&session.basket_symbols(), &session.interval, 1000)
+                        .await
+                } else {
+                    market
+                        .fetch_candles(&session.symbol, &session.interval, 1000)
+                        .await
+                }
+            }
+            .instrument(info_span!("fetch_candles", session = %session.id))
+            .await?;
+            status.record(Phase::FetchCandles, started.elapsed());
+            market_data::validate_candle_frame(&raw_df)?;
+
+            let started = Instant::now();
+            let df = info_span!("features", session = %session.id)
+                .in_scope(|| FeatureEngine::add_technicals(&raw_df, None))
+                .map_err(|e| AppError::Data(e.to_string()))?;
+            status.record(Phase::Features, started.elapsed());
+
+            Ok::<_, AppError>(Arc::new(df))
+        })
+        .await?
+        .clone();
+    status.record_feature_cache(!computed.load(Ordering::Relaxed));
+    Ok(df)
+}
+
+/// A `STALE_DATA` warning if the newest candle in `df` opened more than
+/// `max_age_intervals` of `bar_interval` before `now`, as when REST is down
+/// and only old data is available.
+fn stale_data_message(
+    df: &DataFrame,
+    bar_interval: &str,
+    max_age_intervals: f64,
+    now: DateTime<Utc>,
+) -> Result<Option<String>, AppError> {
+    let Some(bar_secs) = interval::seconds(bar_interval) else {
+        return Ok(None);
+    };
+    let Some(latest) = frame_times(df)?.last().copied() else {
+        return Ok(None);
+    };
+    let age_secs = (now - latest).num_seconds();
+    let stale = age_secs as f64 > max_age_intervals * bar_secs as f64;
+    Ok(stale.then(|| {
+        format!("STALE_DATA: latest {bar_interval} candle opened at {latest}, {age_secs} s ago")
+    }))
+}
 
+/// Ensure a signal series lines up with the frame it was computed from.
+///
+/// A length mismatch means the latest signal may not belong to the latest
+/// bar; under [`LengthMismatchPolicy::Align`] it is only logged.
+fn check_signal_length(
+    signal_len: usize,
+    frame_len: usize,
+    policy: LengthMismatchPolicy,
+) -> Result<(), String> {
+    if signal_len == frame_len {
+        return Ok(());
+    }
+
+    let msg = format!("Signal series has {signal_len} rows but candle frame has {frame_len}");
+    match policy {
+        LengthMismatchPolicy::Error => Err(msg),
+        LengthMismatchPolicy::Align => {
+            warn!("{msg}; aligning on latest bar");
+            Ok(())
+        }
+    }
+}
+
+/// What to do with the latest bar's signal.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(tag = "kind", content = "signal", rename_all = "snake_case")]
+pub(crate) enum SignalDecision {
+    /// Move the position to this signal.
+    Act(f64),
+    /// The direction has not persisted for the confirmation window.
+    HoldUnconfirmed,
+    /// The strategy produced no usable value (None or NaN) for the latest bar,
+    /// typically from insufficient data.  Distinct from a genuine flat signal.
+    HoldNoSignal,
+    /// Confirmed, but in edge mode this signal does not start a new regime.
+    NoEdge(f64),
+}
+
+/// The signals a session's decision on a bar weighed, and the decision.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct BarDecision {
+    /// Edge-mode lookback signals before the confirmation window.
+    pub prior: Vec<Option<f64>>,
+    /// The confirmation window, ending with the decided bar.
+    pub recent: Vec<Option<f64>>,
+    pub decision: SignalDecision,
+}
+
+/// Apply `session`'s confirmation window and edge lookback to `signals`, the
+/// strategy's output up to and including the bar being decided.
+pub(crate) fn decide_bar(session: &Session, signals: &[Option<f64>]) -> BarDecision {
+    let confirm_bars = (session.signal_confirm_bars.max(1) as usize).min(signals.len());
+    let edge_lookback = session
+        .edge_lookback
+        .or((session.execution_mode == "edge").then_some(1))
+        .map(|n| n.max(1) as usize);
+    let window = (confirm_bars + edge_lookback.unwrap_or(0)).min(signals.len());
+    let (prior, recent) = signals[signals.len() - window..].split_at(window - confirm_bars);
+
+    let decision = match decide_signal(recent) {
+        SignalDecision::Act(signal) => match edge_lookback {
+            Some(lookback) if !is_edge(prior, lookback, signal) => SignalDecision::NoEdge(signal),
+            _ => SignalDecision::Act(signal),
+        },
+        other => other,
+    };
+    BarDecision {
+        prior: prior.to_vec(),
+        recent: recent.to_vec(),
+        decision,
+    }
+}
+
+/// Decide on the latest value of `recent`, which holds the last
+/// `signal_confirm_bars` signals.  The signal is acted on only when every
+/// value is valid and points the same way (long, short or flat).
+fn decide_signal(recent: &[Option<f64>]) -> SignalDecision {
+    let Some(latest) = recent.last().and_then(valid_signal) else {
+        return SignalDecision::HoldNoSignal;
+    };
+    let confirmed = recent[..recent.len() - 1]
+        .iter()
+        .all(|v| valid_signal(v).map(direction) == Some(direction(latest)));
+    if confirmed {
+        SignalDecision::Act(latest)
+    } else {
+        SignalDecision::HoldUnconfirmed
+    }
+}
+
+/// Whether `latest` starts a new regime in edge mode: the `lookback` signals
+/// in `prior`, which precede the confirmation window, must all be valid and
+/// point one way, and that way must differ from `latest`.  A one-bar blip in
+/// `prior` is not a sustained regime, so crossing back from it is no edge.
+fn is_edge(prior: &[Option<f64>], lookback: usize, latest: f64) -> bool {
+    if prior.len() < lookback {
+        return false;
+    }
+    let mut directions = prior.iter().map(|v| valid_signal(v).map(direction));
+    let Some(Some(held)) = directions.next() else {
+        return false;
+    };
+    held != direction(latest) && directions.all(|d| d == Some(held))
+}
+
+fn valid_signal(v: &Option<f64>) -> Option<f64> {
+    v.filter(|x| x.is_finite())
+}
+
+/// Long (1), short (-1) or flat (0).
+fn direction(v: f64) -> i8 {
+    if v > 0.0 {
+        1
+    } else if v < 0.0 {
+        -1
+    } else {
+        0
+    }
+}
+
+/// Record a runtime error on the session without changing its status.
+async fn record_session_error(
+    pool: &PgPool,
+    diagnostics: &Diagnostics,
+    session: &Session,
+    message: &str,
+) -> Result<(), AppError> {
+    diagnostics.record_session(session.id, message);
+    sqlx::query("UPDATE sessions SET last_error = $1 WHERE id = $2")
+        .bind(message)
+        .bind(session.id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Take a misconfigured session out of the active set and record why, so the
+/// problem is visible to the user instead of the session silently idling.
+async fn pause_session_with_error(
+    pool: &PgPool,
+    diagnostics: &Diagnostics,
+    session: &Session,
+    message: &str,
+) -> Result<(), AppError> {
+    diagnostics.record_session(session.id, message);
+    sqlx::query("UPDATE sessions SET status = 'paused', last_error = $1 WHERE id = $2")
+        .bind(message)
+        .bind(session.id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Retire a session whose strategy row no longer exists.  Without this the
+/// strategy lookup would fail on every tick for as long as the session lives.
+async fn orphan_session(
+    pool: &PgPool,
+    diagnostics: &Diagnostics,
+    session: &Session,
+) -> Result<(), AppError> {
+    let message = format!("Strategy {} no longer exists", session.strategy_id);
+    diagnostics.record_session(session.id, &message);
+    sqlx::query("UPDATE sessions SET status = 'orphaned', last_error = $1 WHERE id = $2")
+        .bind(message)
+        .bind(session.id)
+        .execute(pool)
+        .await?;
     Ok(())
 }
 
+/// Halt a session on request.  It leaves the active set first, so the engine
+/// cannot reopen a position while this one is being closed at the latest
+/// price.  Trades and snapshots are kept, and the session can be resumed.
+pub(crate) async fn stop_session(
+    pool: &PgPool,
+    events: &SessionEvents,
+    market: &MarketDataService,
+    id: Uuid,
+) -> Result<Session, AppError> {
+    let session = sqlx::query_as::<_, Session>(
+        "UPDATE sessions SET status = 'stopped', last_update = NOW() WHERE id = $1 AND status IN ('active', 'pending', 'paused') RETURNING *",
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await?;
+    let Some(session) = session else {
+        let status: Option<String> =
+            sqlx::query_scalar("SELECT status FROM sessions WHERE id = $1")
+                .bind(id)
+                .fetch_optional(pool)
+                .await?;
+        return Err(match status {
+            Some(status) => AppError::BadRequest(format!("Session is already {status}")),
+            None => AppError::NotFound("Session not found".into()),
+        });
+    };
+
+    if session.current_position != 0.0 {
+        let price = current_price(market, &session).await?;
+        close_position(
+            pool,
+            events,
+            &session,
+            price,
+            "Manual Stop".to_string(),
+            OrderType::Market,
+            &mut HashMap::new(),
+        )
+        .await?;
+    }
+
+    let session = sqlx::query_as::<_, Session>("SELECT * FROM sessions WHERE id = $1")
+        .bind(id)
+        .fetch_one(pool)
+        .await?;
+    Ok(session)
+}
+
+/// Latest price of `session`'s market; for a basket, the close of its
+/// current bar as the open position is valued.
+async fn current_price(market: &MarketDataService, session: &Session) -> Result<f64, AppError> {
+    if session.is_basket() {
+        basket_position_bars(market, session, 1)
+            .await?
+            .last()
+            .map(|bar| bar.close)
+            .ok_or_else(|| AppError::Data("No composite price for basket".into()))
+    } else {
+        market
+            .latest_price(&market.resolve_symbol(&session.symbol))
+            .await
+    }
+}
+
+/// Return a stopped or paused session to the active set.
+pub(crate) async fn resume_session(pool: &PgPool, id: Uuid) -> Result<Session, AppError> {
+    let session = sqlx::query_as::<_, Session>(
+        "UPDATE sessions SET status = 'active', last_error = NULL, last_update = NOW() WHERE id = $1 AND status IN ('stopped', 'paused') RETURNING *",
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await?;
+    if let Some(session) = session {
+        return Ok(session);
+    }
+
+    let status: Option<String> = sqlx::query_scalar("SELECT status FROM sessions WHERE id = $1")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+    Err(match status {
+        Some(status) => AppError::BadRequest(format!("Session is {status} and cannot be resumed")),
+        None => AppError::NotFound("Session not found".into()),
+    })
+}
+
 async fn close_position(
     pool: &PgPool,
+    events: &SessionEvents,
     session: &Session,
     exec_price: f64,
     reason: String,
+    order_type: OrderType,
     snapshot_tracker: &mut HashMap<Uuid, DateTime<Utc>>,
-) -> Result<(), AppError> {
+) -> Result<Uuid, AppError> {
     let mut tx = pool.begin().await?;
     let now = Utc::now();
 
     let entry_price = session.entry_price.unwrap_or(exec_price);
     let basis_equity = session.entry_equity.unwrap_or(session.current_equity);
 
-    let direction = if session.current_position > 0.0 { 1.0 } else { -1.0 };
+    let direction = if session.current_position > 0.0 {
+        1.0
+    } else {
+        -1.0
+    };
+    let leverage = open_exposure(session);
     let pnl_pct = direction * (exec_price - entry_price) / entry_price;
+    let quantity = basis_equity * leverage / entry_price;
 
-    let fee = basis_equity * FEE_RATE;
-    let settled_equity = (basis_equity * (1.0 + pnl_pct)) - fee;
+    let fee = basis_equity * leverage * FEE_RATE;
+    let settled_equity = ((basis_equity * (1.0 + pnl_pct * leverage)) - fee).max(0.0);
     let pnl_amt = settled_equity - basis_equity;
+    let pnl_pct = (basis_equity > 0.0).then(|| pnl_amt / basis_equity * 100.0);
+
+    let side = if session.current_position > 0.0 {
+        "SELL"
+    } else {
+        "BUY"
+    };
 
-    let side = if session.current_position > 0.0 { "SELL" } else { "BUY" };
+    let opened_at: Option<DateTime<Utc>> = match session.open_trade_id {
+        Some(entry_id) => {
+            sqlx::query_scalar("SELECT timestamp FROM trades WHERE id = $1")
+                .bind(entry_id)
+                .fetch_optional(&mut *tx)
+                .await?
+        }
+        None => None,
+    };
+    let holding_seconds = opened_at.map(|ts| now.signed_duration_since(ts).num_seconds());
 
-    sqlx::query(
-        "INSERT INTO trades (session_id, symbol, side, price, quantity, pnl, reason) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+    let close_trade = sqlx::query_as::<_, Trade>(
+        "INSERT INTO trades (session_id, symbol, side, price, quantity, pnl, pnl_pct, fee_paid, reason, order_type, entry_trade_id, holding_seconds, timestamp) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13) RETURNING *",
     )
     .bind(session.id)
     .bind(&session.symbol)
     .bind(side)
     .bind(exec_price)
-    .bind(0.0_f64)
+    .bind(quantity)
     .bind(pnl_amt)
+    .bind(pnl_pct)
+    .bind(fee)
     .bind(&reason)
-    .execute(&mut *tx)
+    .bind(order_type.as_str())
+    .bind(session.open_trade_id)
+    .bind(holding_seconds)
+    .bind(now)
+    .fetch_one(&mut *tx)
     .await?;
 
     sqlx::query(
-        "UPDATE sessions SET current_equity = $1, current_position = 0, entry_price = NULL, entry_equity = NULL, highest_high = NULL, lowest_low = NULL, last_update = $2 WHERE id = $3",
+        "UPDATE sessions SET current_equity = $1, current_position = 0, entry_price = NULL, entry_equity = NULL, position_exposure = NULL, highest_high = NULL, lowest_low = NULL, open_trade_id = NULL, leg_entry_prices = NULL, last_update = $2 WHERE id = $3",
     )
     .bind(settled_equity)
     .bind(now)
@@ -462,33 +2276,224 @@ async fn close_position(
 
     tx.commit().await?;
     snapshot_tracker.insert(session.id, now);
-    Ok(())
+    let close_trade_id = close_trade.id;
+    events.publish(SessionEvent {
+        session_id: session.id,
+        equity: settled_equity,
+        position: 0.0,
+        last_trade: Some(close_trade),
+    });
+    Ok(close_trade_id)
+}
+
+/// Signal leverage of `position`, before any weighting.
+///
+/// Signal strength (capped at 1) scales up to `max_leverage`, but the
+/// leverage is never below 1x so unlevered sessions behave as before.
+fn position_leverage(position: f64, max_leverage: f64) -> f64 {
+    (position.abs().min(1.0) * max_leverage).max(1.0)
+}
+
+/// Notional multiple of equity for a new position on `signal`.
+///
+/// The signal leverage is scaled by the session's `allocated_weight`, clamped
+/// to `0..=1`, and under [`PositionSizing::Kelly`] by the strategy's
+/// `kelly_fraction`, clamped to `0..=kelly_cap` and 1 when unset so older
+/// strategies keep trading.  `initial_capital` only seeds the equity the
+/// multiple applies to; the rest of equity stays flat.
+fn position_exposure(
+    signal: f64,
+    max_leverage: f64,
+    allocated_weight: f64,
+    kelly_fraction: Option<f64>,
+    sizing: PositionSizing,
+    kelly_cap: f64,
+) -> f64 {
+    let leverage = position_leverage(signal, max_leverage);
+    let weight = allocated_weight.clamp(0.0, 1.0);
+    let kelly = kelly_fraction.unwrap_or(1.0).clamp(0.0, kelly_cap);
+    match sizing {
+        PositionSizing::Unweighted => leverage,
+        PositionSizing::Weighted => leverage * weight,
+        PositionSizing::Kelly => leverage * weight * kelly,
+    }
+}
+
+/// Exposure of `session`'s open position as fixed at entry.  Positions opened
+/// before exposure was recorded fall back to their signal leverage.
+fn open_exposure(session: &Session) -> f64 {
+    session
+        .position_exposure
+        .unwrap_or_else(|| position_leverage(session.current_position, session.max_leverage))
 }
 
+/// Price at which a levered position has lost all but [`MAINTENANCE_MARGIN`]
+/// of its margin.  Unlevered positions are never liquidated.
+fn liquidation_price(entry_price: f64, position: f64, leverage: f64) -> Option<f64> {
+    if leverage <= 1.0 || position == 0.0 {
+        return None;
+    }
+    let adverse_move = (1.0 - MAINTENANCE_MARGIN) / leverage;
+    Some(if position > 0.0 {
+        entry_price * (1.0 - adverse_move)
+    } else {
+        entry_price * (1.0 + adverse_move)
+    })
+}
+
+/// How a new signal changes the session's position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Transition {
+    /// Signal matches the current position; nothing to do.
+    Hold,
+    /// Flat to long/short.
+    Open,
+    /// Long/short to flat.
+    Close,
+    /// Long to short or short to long.
+    Flip,
+    /// Same direction, different size.
+    Resize,
+}
+
+impl Transition {
+    pub(crate) fn classify(current_position: f64, signal: f64) -> Self {
+        if (signal - current_position).abs() < 0.1 {
+            return Transition::Hold;
+        }
+        match (current_position == 0.0, signal == 0.0) {
+            (true, _) => Transition::Open,
+            (false, true) => Transition::Close,
+            _ if current_position.signum() != signal.signum() => Transition::Flip,
+            _ => Transition::Resize,
+        }
+    }
+}
+
+/// A trade row a transition will write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub(crate) struct PlannedTrade {
+    pub side: &'static str,
+    /// Prefix for the trade's `reason`.
+    pub label: &'static str,
+    pub closes: bool,
+}
+
+/// The trade rows a signal change produces, in write order.
+///
+/// Every transition writes exactly one row per position change, so the
+/// position history can be rebuilt from the trade log alone.
+pub(crate) fn plan_trades(current_position: f64, signal: f64) -> Vec<PlannedTrade> {
+    let close_side = if current_position > 0.0 {
+        "SELL"
+    } else {
+        "BUY"
+    };
+    let open_side = if signal > 0.0 { "BUY" } else { "SELL" };
+    let close = |label| PlannedTrade {
+        side: close_side,
+        label,
+        closes: true,
+    };
+    let open = |label| PlannedTrade {
+        side: open_side,
+        label,
+        closes: false,
+    };
+
+    match Transition::classify(current_position, signal) {
+        Transition::Hold => vec![],
+        Transition::Open => vec![open("Open")],
+        Transition::Close => vec![close("Close to Flat")],
+        Transition::Flip => vec![close("Signal Flip"), open("Open (Flip)")],
+        Transition::Resize => vec![close("Resize"), open("Open (Resize)")],
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn execute_strategy_signal(
     pool: &PgPool,
+    market: &MarketDataService,
+    events: &SessionEvents,
+    broker: &PaperBroker,
+    config: &Config,
+    kelly_fraction: Option<f64>,
+    lot_size: Option<LotSize>,
     session: &Session,
     signal: f64,
     raw_price: f64,
     reason: String,
     snapshot_tracker: &mut HashMap<Uuid, DateTime<Utc>>,
 ) -> Result<(), AppError> {
-    if (signal - session.current_position).abs() < 0.1 {
+    let plan = plan_trades(session.current_position, signal);
+    if plan.is_empty() {
         return Ok(());
     }
 
     let is_buying = signal > session.current_position;
-    let exec_price = if is_buying {
-        raw_price * (1.0 + SLIPPAGE_RATE)
-    } else {
-        raw_price * (1.0 - SLIPPAGE_RATE)
+    let slipped = |price: f64| {
+        if is_buying {
+            price * (1.0 + SLIPPAGE_RATE)
+        } else {
+            price * (1.0 - SLIPPAGE_RATE)
+        }
     };
+    let mut exec_price = slipped(raw_price);
+
+    if session.paper_broker {
+        let decision = broker.submit(session.id);
+        tokio::time::sleep(decision.latency).await;
+
+        if decision.rejected {
+            let side = if is_buying { "BUY" } else { "SELL" };
+            warn!(
+                "Paper broker rejected {} order for session {}",
+                side, session.id
+            );
+            sqlx::query(
+                "INSERT INTO order_events (session_id, symbol, side, price, event, reason) VALUES ($1, $2, $3, $4, 'REJECTED', $5)",
+            )
+            .bind(session.id)
+            .bind(&session.symbol)
+            .bind(side)
+            .bind(exec_price)
+            .bind(&reason)
+            .execute(pool)
+            .await?;
+            return Ok(());
+        }
 
-    if session.current_position != 0.0 {
-        close_position(pool, session, exec_price, format!("Signal Flip: {reason}"), snapshot_tracker).await?;
+        // The order fills at the price once the latency has passed.
+        if !decision.latency.is_zero() {
+            match current_price(market, session).await {
+                Ok(price) => exec_price = slipped(price),
+                Err(e) => warn!(
+                    "Re-reading {} after broker latency failed, filling at the signal price: {}",
+                    session.symbol, e
+                ),
+            }
+        }
     }
 
-    if signal.abs() > 0.0 {
+    let mut linked_trade_id = None;
+    for planned in plan {
+        if planned.closes {
+            linked_trade_id = Some(
+                close_position(
+                    pool,
+                    events,
+                    session,
+                    exec_price,
+                    format!("{}: {reason}", planned.label),
+                    OrderType::Market,
+                    snapshot_tracker,
+                )
+                .await?,
+            );
+            continue;
+        }
+
         let mut tx = pool.begin().await?;
         let now = Utc::now();
 
@@ -497,39 +2502,465 @@ async fn execute_strategy_signal(
             .fetch_one(&mut *tx)
             .await?;
 
-        let entry_fee = fresh_session.current_equity * FEE_RATE;
+        let mut exposure = position_exposure(
+            signal,
+            fresh_session.max_leverage,
+            fresh_session.allocated_weight,
+            kelly_fraction,
+            config.position_sizing,
+            config.kelly_cap,
+        );
+        let mut entry_fee = fresh_session.current_equity * exposure * FEE_RATE;
+        let unrounded = (fresh_session.current_equity - entry_fee) * exposure / exec_price;
+        if exposure <= 0.0 || unrounded <= 0.0 {
+            // A zero allocated weight or Kelly fraction, or exhausted equity,
+            // sizes the open to nothing; stay flat rather than record an
+            // empty position.
+            tx.rollback().await?;
+            warn!(
+                "Session {} {} sizes to exposure {} and quantity {}, skipping",
+                session.id, planned.side, exposure, unrounded
+            );
+            sqlx::query(
+                "INSERT INTO order_events (session_id, symbol, side, price, event, reason) VALUES ($1, $2, $3, $4, 'ZERO_SIZE', $5)",
+            )
+            .bind(session.id)
+            .bind(&session.symbol)
+            .bind(planned.side)
+            .bind(exec_price)
+            .bind(format!(
+                "{}: {reason} (exposure {exposure}, quantity {unrounded})",
+                planned.label
+            ))
+            .execute(pool)
+            .await?;
+            continue;
+        }
+        let quantity = match lot_size {
+            Some(lot) => match lot.round_down(unrounded) {
+                Some(quantity) => quantity,
+                None => {
+                    tx.rollback().await?;
+                    warn!(
+                        "Session {} {} quantity {} is below the minimum lot, skipping",
+                        session.id, planned.side, unrounded
+                    );
+                    sqlx::query(
+                        "INSERT INTO order_events (session_id, symbol, side, price, event, reason) VALUES ($1, $2, $3, $4, 'BELOW_MIN_LOT', $5)",
+                    )
+                    .bind(session.id)
+                    .bind(&session.symbol)
+                    .bind(planned.side)
+                    .bind(exec_price)
+                    .bind(format!(
+                        "{}: {reason} (quantity {unrounded} below minimum {})",
+                        planned.label, lot.min_qty
+                    ))
+                    .execute(pool)
+                    .await?;
+                    continue;
+                }
+            },
+            None => unrounded,
+        };
+        if quantity < unrounded {
+            // Rounding down shrinks the position, and its fee, to what was
+            // traded.
+            let traded = quantity / unrounded;
+            exposure *= traded;
+            entry_fee *= traded;
+        }
         let start_equity = fresh_session.current_equity - entry_fee;
-        let side = if signal > 0.0 { "BUY" } else { "SELL" };
 
-        sqlx::query(
-            "INSERT INTO trades (session_id, symbol, side, price, quantity, pnl, reason) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        let entry_trade = sqlx::query_as::<_, Trade>(
+            "INSERT INTO trades (session_id, symbol, side, price, quantity, pnl, fee_paid, reason, order_type, linked_trade_id, timestamp) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11) RETURNING *",
         )
         .bind(session.id)
         .bind(&session.symbol)
-        .bind(side)
+        .bind(planned.side)
         .bind(exec_price)
-        .bind(0.0_f64)
+        .bind(quantity)
         .bind(-entry_fee)
-        .bind(format!("Open: {reason}"))
-        .execute(&mut *tx)
+        .bind(entry_fee)
+        .bind(format!("{}: {reason}", planned.label))
+        .bind(OrderType::Market.as_str())
+        .bind(linked_trade_id)
+        .bind(now)
+        .fetch_one(&mut *tx)
         .await?;
 
         sqlx::query(
-            "UPDATE sessions SET current_equity = $1, current_position = $2, entry_price = $3, entry_equity = $4, highest_high = $5, lowest_low = $6, last_update = $7 WHERE id = $8",
+            "UPDATE sessions SET current_equity = $1, current_position = $2, entry_price = $3, entry_equity = $4, position_exposure = $5, highest_high = $6, lowest_low = $7, open_trade_id = $8, last_update = $9 WHERE id = $10",
         )
         .bind(start_equity)
         .bind(signal)
         .bind(exec_price)
         .bind(start_equity)
+        .bind(exposure)
         .bind(exec_price)
         .bind(exec_price)
+        .bind(entry_trade.id)
         .bind(now)
         .bind(session.id)
         .execute(&mut *tx)
         .await?;
 
         tx.commit().await?;
+        events.publish(SessionEvent {
+            session_id: session.id,
+            equity: start_equity,
+            position: signal,
+            last_trade: Some(entry_trade),
+        });
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use polars::prelude::{Float64Chunked, NewChunkedArray};
+
+    use super::*;
+
+    #[test]
+    fn test_decide_signal_requires_persistence() {
+        use SignalDecision::*;
+
+        assert_eq!(decide_signal(&[Some(0.7)]), Act(0.7));
+        assert_eq!(decide_signal(&[Some(1.0), Some(0.5), Some(0.8)]), Act(0.8));
+        assert_eq!(
+            decide_signal(&[Some(1.0), Some(-1.0), Some(1.0)]),
+            HoldUnconfirmed
+        );
+        assert_eq!(decide_signal(&[Some(0.0), Some(0.0)]), Act(0.0));
+        assert_eq!(decide_signal(&[None, Some(1.0)]), HoldUnconfirmed);
+        assert_eq!(decide_signal(&[]), HoldNoSignal);
+    }
+
+    #[test]
+    fn test_trail_tick_tracks_best_price() {
+        // Long: a new high moves the extreme; a 5% retrace from it triggers.
+        assert_eq!(trail_tick(true, 100.0, 110.0, 0.05), (110.0, false));
+        assert_eq!(trail_tick(true, 110.0, 105.0, 0.05), (110.0, false));
+        assert_eq!(trail_tick(true, 110.0, 104.5, 0.05), (110.0, true));
+        // Short mirrors it around the lowest low.
+        assert_eq!(trail_tick(false, 100.0, 90.0, 0.05), (90.0, false));
+        assert_eq!(trail_tick(false, 90.0, 94.0, 0.05), (90.0, false));
+        assert_eq!(trail_tick(false, 90.0, 94.5, 0.05), (90.0, true));
+    }
+
+    #[test]
+    fn test_protective_exit_is_direction_aware() {
+        use ProtectiveExit::*;
+
+        let sl = Some(0.05);
+        let tp = Some(0.10);
+        // Long from 100.
+        assert_eq!(protective_exit(1.0, 100.0, 95.0, sl, tp), Some(StopLoss));
+        assert_eq!(protective_exit(1.0, 100.0, 110.0, sl, tp), Some(TakeProfit));
+        assert_eq!(protective_exit(1.0, 100.0, 97.0, sl, tp), None);
+        // Short from 100: a rise stops it out, a fall takes profit.
+        assert_eq!(protective_exit(-1.0, 100.0, 105.0, sl, tp), Some(StopLoss));
+        assert_eq!(protective_exit(-1.0, 100.0, 90.0, sl, tp), Some(TakeProfit));
+        assert_eq!(protective_exit(-1.0, 100.0, 95.0, sl, tp), None);
+    }
+
+    #[test]
+    fn test_protective_exit_disabled_by_none_or_zero() {
+        assert_eq!(protective_exit(1.0, 100.0, 1.0, None, None), None);
+        assert_eq!(protective_exit(1.0, 100.0, 1.0, Some(0.0), None), None);
+        assert_eq!(protective_exit(-1.0, 100.0, 1.0, None, Some(0.0)), None);
+        assert_eq!(protective_exit(0.0, 100.0, 1.0, Some(0.05), None), None);
+    }
+
+    #[test]
+    fn test_stale_data_message_after_max_age() {
+        use polars::prelude::{df, NamedFrom};
+
+        let opened = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let frame = df!("time" => [opened.naive_utc()], "close" => [1.0]).unwrap();
+        let at = |hours: i64| opened + chrono::Duration::hours(hours);
+
+        assert!(stale_data_message(&frame, "1h", 3.0, at(3))
+            .unwrap()
+            .is_none());
+        let msg = stale_data_message(&frame, "1h", 3.0, at(4))
+            .unwrap()
+            .unwrap();
+        assert!(msg.starts_with("STALE_DATA:"));
+        assert!(stale_data_message(&frame, "unknown", 3.0, at(100))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_edge_requires_sustained_prior_regime() {
+        // A flip after three bars short is an edge.
+        assert!(is_edge(&[Some(-1.0), Some(-0.5), Some(-1.0)], 3, 1.0));
+        // Returning long after a one-bar short spike is not.
+        assert!(!is_edge(&[Some(1.0), Some(1.0), Some(-1.0)], 3, 1.0));
+        // Nor is a spike into the opposite direction from a mixed history.
+        assert!(!is_edge(&[Some(-1.0), Some(1.0), Some(-1.0)], 3, 1.0));
+        // No change of direction, too little history, or a gap.
+        assert!(!is_edge(&[Some(1.0), Some(1.0), Some(1.0)], 3, 0.5));
+        assert!(!is_edge(&[Some(-1.0)], 3, 1.0));
+        assert!(!is_edge(&[Some(-1.0), None, Some(-1.0)], 3, 1.0));
+
+        // A lookback of 1 treats any change from the previous bar as an edge.
+        assert!(is_edge(&[Some(-1.0)], 1, 1.0));
+        assert!(is_edge(&[Some(1.0)], 1, 0.0));
+        assert!(!is_edge(&[Some(1.0)], 1, 1.0));
+    }
+
+    #[test]
+    fn test_trailing_nan_signal_holds_position() {
+        let signals = Float64Chunked::from_slice("signal", &[1.0, 1.0, f64::NAN]);
+        let recent: Vec<Option<f64>> = signals.into_iter().skip(2).collect();
+
+        assert_eq!(decide_signal(&recent), SignalDecision::HoldNoSignal);
+        assert_eq!(
+            decide_signal(&[Some(1.0), None]),
+            SignalDecision::HoldNoSignal
+        );
+
+        // A genuine flat signal, by contrast, closes the position.
+        let SignalDecision::Act(flat) = decide_signal(&[Some(0.0)]) else {
+            panic!("flat signal should be acted on");
+        };
+        assert_eq!(
+            rows(&plan_trades(1.0, flat)),
+            vec![("SELL", "Close to Flat")]
+        );
+    }
+
+    #[test]
+    fn test_check_signal_length_matching() {
+        assert!(check_signal_length(1000, 1000, LengthMismatchPolicy::Error).is_ok());
+    }
+
+    #[test]
+    fn test_check_signal_length_mismatch_errors() {
+        let err = check_signal_length(998, 1000, LengthMismatchPolicy::Error).unwrap_err();
+        assert_eq!(err, "Signal series has 998 rows but candle frame has 1000");
+
+        assert!(check_signal_length(1001, 1000, LengthMismatchPolicy::Error).is_err());
+    }
+
+    #[test]
+    fn test_check_signal_length_mismatch_aligns() {
+        assert!(check_signal_length(998, 1000, LengthMismatchPolicy::Align).is_ok());
+    }
+
+    #[test]
+    fn test_select_exit_uses_precedence_and_worst_case_price() {
+        let liquidation = TriggeredExit {
+            kind: ExitKind::Liquidation,
+            price: 90.0,
+        };
+        let trailing = TriggeredExit {
+            kind: ExitKind::TrailingStop,
+            price: 94.0,
+        };
+        let both = [trailing.clone(), liquidation];
+
+        // Both levels inside one long bar: the ranked kind names the exit and
+        // the fill is the lower of the two prices.
+        let exit = select_exit(&both, &ExitKind::DEFAULT_PRECEDENCE, true).unwrap();
+        assert_eq!(exit.kind, ExitKind::Liquidation);
+        assert_eq!(exit.price, 90.0);
+
+        let trailing_first = [ExitKind::TrailingStop, ExitKind::Liquidation];
+        let exit = select_exit(&both, &trailing_first, true).unwrap();
+        assert_eq!(exit.kind, ExitKind::TrailingStop);
+        assert_eq!(exit.price, 90.0);
+
+        // Shorts fill at the higher price.
+        let exit = select_exit(&both, &trailing_first, false).unwrap();
+        assert_eq!(exit.price, 94.0);
+
+        assert!(select_exit(&[trailing], &[ExitKind::Liquidation], true).is_none());
+        assert!(select_exit(&[], &ExitKind::DEFAULT_PRECEDENCE, true).is_none());
+    }
+
+    #[test]
+    fn test_latest_per_market_keeps_last_close_and_newer_update() {
+        // (market, is_final, id)
+        let backlog = vec![
+            ("BTC", false, 1),
+            ("ETH", false, 2),
+            ("BTC", true, 3),
+            ("BTC", false, 4),
+            ("ETH", false, 5),
+            ("BTC", false, 6),
+            ("ETH", true, 7),
+        ];
+
+        let kept: Vec<i32> = latest_per_market(backlog, |e| e.0.to_string(), |e| e.1)
+            .into_iter()
+            .map(|e| e.2)
+            .collect();
+
+        assert_eq!(kept, vec![3, 6, 7]);
+    }
+
+    #[test]
+    fn test_first_final_bar_skips_repeats() {
+        let mut seen = HashMap::new();
+
+        assert!(first_final_bar(&mut seen, "BTCUSDT@1m".into(), 60_000));
+        assert!(!first_final_bar(&mut seen, "BTCUSDT@1m".into(), 60_000));
+        assert!(first_final_bar(&mut seen, "ETHUSDT@1m".into(), 60_000));
+        assert!(first_final_bar(&mut seen, "BTCUSDT@1m".into(), 120_000));
+        assert!(!first_final_bar(&mut seen, "BTCUSDT@1m".into(), 60_000));
+    }
+
+    #[test]
+    fn test_subscription_pairs_keep_each_interval() {
+        let sessions = vec![
+            (vec!["BTCUSDT".to_string()], "1m"),
+            (vec!["btc".to_string()], "1h"),
+            (vec!["BTCUSDT".to_string()], "1m"),
+            (vec!["ETHUSDT".to_string(), "BTCUSDT".to_string()], "1h"),
+        ];
+        let resolve = |s: &str| match s {
+            "btc" => "BTCUSDT".to_string(),
+            _ => s.to_string(),
+        };
+
+        assert_eq!(
+            subscription_pairs(sessions, resolve),
+            vec![
+                ("BTCUSDT".to_string(), "1h".to_string()),
+                ("BTCUSDT".to_string(), "1m".to_string()),
+                ("ETHUSDT".to_string(), "1h".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_minute_kline_does_not_drive_hourly_session() {
+        let symbols = vec!["btc".to_string()];
+        let resolve = |s: &str| match s {
+            "btc" => "BTCUSDT".to_string(),
+            _ => s.to_string(),
+        };
+
+        assert!(!follows_kline(&symbols, "1h", "BTCUSDT", "1m", resolve));
+        assert!(follows_kline(&symbols, "1h", "BTCUSDT", "1h", resolve));
+        assert!(!follows_kline(&symbols, "1h", "ETHUSDT", "1h", resolve));
+    }
+
+    #[test]
+    fn test_missed_bars_counts_skipped_closes() {
+        assert_eq!(missed_bars(60_000, 120_000, 60_000), 0);
+        assert_eq!(missed_bars(60_000, 240_000, 60_000), 2);
+        // Misaligned or out-of-order opens are not gaps.
+        assert_eq!(missed_bars(60_000, 150_000, 60_000), 0);
+        assert_eq!(missed_bars(120_000, 60_000, 60_000), 0);
+        assert_eq!(missed_bars(0, 600_000, 0), 0);
+    }
+
+    #[test]
+    fn test_position_leverage() {
+        assert_eq!(position_leverage(1.0, 1.0), 1.0);
+        assert_eq!(position_leverage(-0.3, 1.0), 1.0);
+        assert_eq!(position_leverage(0.5, 4.0), 2.0);
+        assert_eq!(position_leverage(-2.0, 3.0), 3.0);
+    }
+
+    #[test]
+    fn test_position_exposure_matrix() {
+        use PositionSizing::*;
+
+        // (signal, max_leverage, allocated_weight, kelly_fraction, sizing, exposure)
+        let cases = [
+            (1.0, 1.0, 1.0, Some(0.1), Weighted, 1.0),
+            (1.0, 1.0, 0.5, Some(0.1), Weighted, 0.5),
+            (0.5, 4.0, 0.5, None, Weighted, 1.0),
+            (1.0, 3.0, 0.25, Some(0.5), Kelly, 0.375),
+            (1.0, 1.0, 1.0, None, Kelly, 1.0),
+            (1.0, 1.0, 0.5, Some(0.1), Unweighted, 1.0),
+            (-1.0, 2.0, 0.5, Some(0.2), Unweighted, 2.0),
+            // Out-of-range weights and fractions are clamped to 0..=1.
+            (1.0, 1.0, 2.0, Some(1.5), Kelly, 1.0),
+            (1.0, 1.0, -1.0, None, Weighted, 0.0),
+        ];
+        for (signal, max_lev, weight, kelly, sizing, expected) in cases {
+            let got = position_exposure(signal, max_lev, weight, kelly, sizing, 1.0);
+            assert!(
+                (got - expected).abs() < 1e-12,
+                "{signal} {max_lev} {weight} {kelly:?} {sizing:?}: {got} != {expected}"
+            );
+        }
+
+        // A cap below 1 bounds the Kelly fraction, including the NULL fallback.
+        let capped = |kelly| position_exposure(1.0, 1.0, 1.0, kelly, Kelly, 0.25);
+        assert_eq!(capped(Some(0.1)), 0.1);
+        assert_eq!(capped(Some(0.6)), 0.25);
+        assert_eq!(capped(None), 0.25);
+        // The cap does not apply outside Kelly sizing.
+        assert_eq!(position_exposure(1.0, 1.0, 1.0, None, Weighted, 0.25), 1.0);
+    }
+
+    #[test]
+    fn test_liquidation_price() {
+        assert_eq!(liquidation_price(100.0, 1.0, 1.0), None);
+
+        let long = liquidation_price(100.0, 1.0, 10.0).unwrap();
+        assert!((long - 90.05).abs() < 1e-9);
+        let short = liquidation_price(100.0, -1.0, 10.0).unwrap();
+        assert!((short - 109.95).abs() < 1e-9);
+    }
+
+    fn rows(plan: &[PlannedTrade]) -> Vec<(&'static str, &'static str)> {
+        plan.iter().map(|t| (t.side, t.label)).collect()
+    }
+
+    #[test]
+    fn test_plan_trades_transitions() {
+        // flat -> long
+        assert_eq!(rows(&plan_trades(0.0, 1.0)), vec![("BUY", "Open")]);
+        // flat -> short
+        assert_eq!(rows(&plan_trades(0.0, -1.0)), vec![("SELL", "Open")]);
+        // long -> flat
+        assert_eq!(
+            rows(&plan_trades(1.0, 0.0)),
+            vec![("SELL", "Close to Flat")]
+        );
+        // short -> flat
+        assert_eq!(
+            rows(&plan_trades(-1.0, 0.0)),
+            vec![("BUY", "Close to Flat")]
+        );
+        // long -> short
+        assert_eq!(
+            rows(&plan_trades(1.0, -1.0)),
+            vec![("SELL", "Signal Flip"), ("SELL", "Open (Flip)")]
+        );
+        // short -> long
+        assert_eq!(
+            rows(&plan_trades(-1.0, 1.0)),
+            vec![("BUY", "Signal Flip"), ("BUY", "Open (Flip)")]
+        );
+        // long -> long
+        assert!(plan_trades(1.0, 1.0).is_empty());
+        assert!(plan_trades(0.0, 0.05).is_empty());
+    }
+
+    #[test]
+    fn test_plan_trades_flip_closes_before_opening() {
+        let plan = plan_trades(1.0, -1.0);
+        assert_eq!(plan.len(), 2);
+        assert!(plan[0].closes);
+        assert!(!plan[1].closes);
+    }
+
+    #[test]
+    fn test_plan_trades_resize() {
+        assert_eq!(
+            rows(&plan_trades(1.0, 0.5)),
+            vec![("SELL", "Resize"), ("BUY", "Open (Resize)")]
+        );
+    }
+}
@@ -8,6 +8,7 @@ import { Badge } from "@/components/ui/badge";
 import { Slider } from "@/components/ui/slider";
 import { toast } from "sonner";
 import { api } from "@/lib/api";
+import { GenerationDone, GenerationMarketDone } from "@/lib/types";
 import { useMutation, useQueryClient } from "@tanstack/react-query";
 import { Loader2, Zap, Settings2 } from "lucide-react";
 import { useState } from "react";
@@ -22,9 +23,10 @@ export function Generator() {
   const [historyLimit, setHistoryLimit] = useState([1000]);
   const [iterations, setIterations] = useState([50]);
   const [topN, setTopN] = useState(10);
+  const [progress, setProgress] = useState<{ completed: number; total: number } | null>(null);
 
   const mutation = useMutation({
-    mutationFn: () => {
+    mutationFn: async (): Promise<GenerationDone> => {
       const symbols = symbolsInput
         .split(",")
         .map(s => s.trim().toUpperCase())
@@ -33,14 +35,34 @@ export function Generator() {
       if (symbols.length === 0) throw new Error("Please enter at least one symbol");
       if (selectedIntervals.length === 0) throw new Error("Please select at least one interval");
 
-      return api.generateStrategies(
+      const { job_id, markets } = await api.startGenerationJob(
         symbols,
         selectedIntervals,
         topN,
         historyLimit[0],
         iterations[0]
       );
+      setProgress({ completed: 0, total: markets });
+
+      // The job keeps running server-side even if this page goes away.
+      return new Promise((resolve, reject) => {
+        const source = new EventSource(api.generationProgressUrl(job_id));
+        source.addEventListener("market_done", (e) => {
+          const done: GenerationMarketDone = JSON.parse((e as MessageEvent).data);
+          setProgress({ completed: done.completed, total: done.total });
+        });
+        source.addEventListener("done", (e) => {
+          source.close();
+          resolve(JSON.parse((e as MessageEvent).data));
+        });
+        source.addEventListener("error", (e) => {
+          source.close();
+          const data = (e as MessageEvent).data;
+          reject(new Error(data ? JSON.parse(data).message : "Lost connection to the optimizer"));
+        });
+      });
     },
+    onSettled: () => setProgress(null),
     onSuccess: (data) => {
       toast.success("Optimization Complete", {
         description: `Generated ${data.strategies_created} strategies.`,
@@ -165,7 +187,7 @@ export function Generator() {
           {mutation.isPending ? (
             <>
               <Loader2 className="mr-2 h-4 w-4 animate-spin" />
-              Running Genetic Algo...
+              {progress ? `Optimizing ${progress.completed}/${progress.total} markets...` : "Running Genetic Algo..."}
             </>
           ) : (
             <>
@@ -174,6 +196,14 @@ export function Generator() {
             </>
           )}
         </Button>
+        {progress && progress.total > 0 && (
+          <div className="h-1.5 w-full rounded bg-slate-800 overflow-hidden">
+            <div
+              className="h-full bg-indigo-500 transition-all"
+              style={{ width: `${(100 * progress.completed) / progress.total}%` }}
+            />
+          </div>
+        )}
       </CardContent>
     </Card>
   );
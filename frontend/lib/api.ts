@@ -1,5 +1,5 @@
 import axios from "axios";
-import { Strategy, Session, Trade, EquitySnapshot, PortfolioPoint, Candle } from "./types";
+import { Strategy, Session, Trade, EquitySnapshot, PortfolioPoint, PortfolioReturnPoint, Candle, BacktestReport, Page, SessionBeta, BarExplanation, BacktestTrade, SessionStats, PortfolioStats, CorrelationMatrix } from "./types";
 
 const API_URL = process.env.NEXT_PUBLIC_API_URL || "http://localhost:8080";
 
@@ -16,9 +16,56 @@ export const api = {
     return res.data;
   },
 
+  startGenerationJob: async (symbols: string[], intervals: string[], top_n: number, limit: number, iterations: number): Promise<{ job_id: string; markets: number }> => {
+    const res = await axios.post(`${API_URL}/strategies/generate/jobs`, {
+      symbols,
+      intervals,
+      top_n,
+      limit,
+      iterations
+    });
+    return res.data;
+  },
+
+  /** Server-sent events of a generation job, for `EventSource`. */
+  generationProgressUrl: (jobId: string) => `${API_URL}/strategies/generate/progress?job_id=${jobId}`,
+
   // Strategies
-  getStrategies: async (): Promise<Strategy[]> => {
-    const res = await axios.get(`${API_URL}/strategies`);
+  getStrategies: async (page?: Page): Promise<Strategy[]> => {
+    const res = await axios.get(`${API_URL}/strategies`, { params: page });
+    return res.data;
+  },
+
+  getStrategy: async (id: string): Promise<Strategy> => {
+    const res = await axios.get(`${API_URL}/strategies/${id}`);
+    return res.data;
+  },
+
+  /** `null` unless the strategy was generated with `store_trades`. */
+  getStrategyBacktestTrades: async (id: string): Promise<BacktestTrade[] | null> => {
+    const res = await axios.get(`${API_URL}/strategies/${id}/backtest-trades`);
+    return res.data;
+  },
+
+  runBacktest: async (params: {
+    strategy_type: string;
+    parameters: Strategy["parameters"];
+    symbol: string;
+    interval: string;
+    limit?: number;
+    start_ms?: number;
+    end_ms?: number;
+    initial_capital?: number;
+  }): Promise<BacktestReport> => {
+    const res = await axios.post(`${API_URL}/backtest`, params);
+    return res.data;
+  },
+
+  updateStrategy: async (
+    id: string,
+    changes: { name?: string; parameters?: Strategy["parameters"] },
+  ): Promise<Strategy> => {
+    const res = await axios.patch(`${API_URL}/strategies/${id}`, changes);
     return res.data;
   },
 
@@ -43,8 +90,13 @@ export const api = {
     return res.data;
   },
 
-  getSessions: async (): Promise<Session[]> => {
-    const res = await axios.get(`${API_URL}/sessions`);
+  getSessions: async (page?: Page): Promise<Session[]> => {
+    const res = await axios.get(`${API_URL}/sessions`, { params: page });
+    return res.data;
+  },
+
+  getSession: async (id: string): Promise<Session> => {
+    const res = await axios.get(`${API_URL}/sessions/${id}`);
     return res.data;
   },
 
@@ -53,11 +105,27 @@ export const api = {
     return res.data;
   },
 
-  getSessionTrades: async (id: string): Promise<Trade[]> => {
-    const res = await axios.get(`${API_URL}/sessions/${id}/trades`);
+  stopSession: async (id: string): Promise<Session> => {
+    const res = await axios.post(`${API_URL}/sessions/${id}/stop`);
     return res.data;
   },
 
+  resumeSession: async (id: string): Promise<Session> => {
+    const res = await axios.post(`${API_URL}/sessions/${id}/resume`);
+    return res.data;
+  },
+
+  getSessionTrades: async (id: string, page?: Page): Promise<Trade[]> => {
+    const res = await axios.get(`${API_URL}/sessions/${id}/trades`, { params: page });
+    return res.data;
+  },
+
+  /** Live `SessionUpdate`s of a session, for `WebSocket`. */
+  sessionSocketUrl: (id: string) => `${API_URL.replace(/^http/, "ws")}/ws/sessions/${id}`,
+
+  /** CSV download of a session's trades, for a link's `href`. */
+  sessionTradesCsvUrl: (id: string) => `${API_URL}/sessions/${id}/trades.csv`,
+
   getEquityCurve: async (id: string): Promise<EquitySnapshot[]> => {
     const res = await axios.get(`${API_URL}/sessions/${id}/equity`);
     return res.data;
@@ -68,19 +136,50 @@ export const api = {
     return res.data;
   },
 
+  getSessionStats: async (id: string): Promise<SessionStats> => {
+    const res = await axios.get(`${API_URL}/sessions/${id}/stats`);
+    return res.data;
+  },
+
+  getSessionBeta: async (id: string, params?: { benchmark?: string; window?: string }): Promise<SessionBeta> => {
+    const res = await axios.get(`${API_URL}/sessions/${id}/beta`, { params });
+    return res.data;
+  },
+
+  explainSessionBar: async (id: string, time: string): Promise<BarExplanation> => {
+    const res = await axios.get(`${API_URL}/sessions/${id}/explain-bar`, { params: { time } });
+    return res.data;
+  },
+
   // Portfolio
   getPortfolioHistory: async (params?: {
     rangeDays?: number;
     interval?: string;
-    style?: "line" | "candle"
-  }): Promise<PortfolioPoint[] | Candle[]> => {
+    style?: "line" | "candle";
+    basis?: "equity" | "return";
+  }): Promise<PortfolioPoint[] | PortfolioReturnPoint[] | Candle[]> => {
     const res = await axios.get(`${API_URL}/portfolio/history`, {
       params: {
         range_days: params?.rangeDays,
         interval: params?.interval,
-        style: params?.style || "line"
+        style: params?.style || "line",
+        basis: params?.basis
       }
     });
     return res.data;
   },
+
+  getPortfolioStats: async (params?: { rangeDays?: number; interval?: string }): Promise<PortfolioStats> => {
+    const res = await axios.get(`${API_URL}/portfolio/stats`, {
+      params: { range_days: params?.rangeDays, interval: params?.interval }
+    });
+    return res.data;
+  },
+
+  getPortfolioCorrelations: async (rangeDays?: number): Promise<CorrelationMatrix> => {
+    const res = await axios.get(`${API_URL}/portfolio/correlation`, {
+      params: { range_days: rangeDays, resolution: '1m' }
+    });
+    return res.data.by_session;
+  },
 };
@@ -8,6 +8,10 @@ export interface Strategy {
   parameters: Record<string, number | string | boolean>;
   performance_metrics: {
     sharpe: number;
+    // Absent on strategies generated before these were recorded
+    sortino?: number | null;
+    calmar?: number | null;
+    expectancy_pct?: number | null;
     win_rate: number;
     total_return_pct: number;
     max_drawdown_pct: number;
@@ -30,8 +34,23 @@ export interface Session {
   entry_equity?: number | null;
   current_position: number;
   entry_price: number | null;
-  status: "active" | "stopped";
+  status: "pending" | "active" | "stopped" | "paused" | "orphaned" | "liquidated";
   allocated_weight?: number;
+  session_type: "single" | "basket";
+  last_error?: string | null;
+  paper_broker: boolean;
+  signal_confirm_bars: number;
+  edge_lookback: number | null;
+  max_leverage: number;
+  position_exposure: number | null;
+  trail_pct: number | null;
+  stop_loss_pct: number | null;
+  take_profit_pct: number | null;
+  last_signal_at: string | null;
+  /** Latest signal of an `alert` session, which never trades. */
+  alert_signal: number | null;
+  /** Constituent prices at the entry of a basket's open position. */
+  leg_entry_prices: number[] | null;
   created_at: string;
   last_update: string;
 }
@@ -44,10 +63,24 @@ export interface Trade {
   price: number;
   quantity: number;
   pnl: number | null;
+  pnl_pct: number | null;
+  fee_paid: number;
   reason: string | null;
+  order_type: "MARKET" | "LIMIT" | "STOP";
+  entry_trade_id: string | null;
+  holding_seconds: number | null;
+  linked_trade_id: string | null;
   timestamp: string;
 }
 
+/** Message of `/ws/sessions/:id`; `last_trade` is set for fills. */
+export interface SessionUpdate {
+  session_id: string;
+  equity: number;
+  position: number;
+  last_trade: Trade | null;
+}
+
 export interface EquitySnapshot {
   equity: number;
   timestamp: string;
@@ -58,6 +91,26 @@ export interface PortfolioPoint {
   total_equity: number;
 }
 
+/** A point of `/portfolio/history?basis=return`: allocation-weighted return in percent. */
+export interface PortfolioReturnPoint {
+  timestamp: string;
+  return_pct: number;
+}
+
+/** `by_session` of `/portfolio/correlation`: pairwise return correlation by session id; `null` without enough overlap. */
+export type CorrelationMatrix = Record<string, Record<string, number | null>>;
+
+/** `/portfolio/stats`: drawdown and risk figures of the portfolio curve. */
+export interface PortfolioStats {
+  current_equity: number | null;
+  all_time_high: number | null;
+  current_drawdown_pct: number | null;
+  max_drawdown_pct: number;
+  volatility_pct: number | null;
+  sharpe_ratio: number | null;
+  points: number;
+}
+
 export interface Candle {
   time: string;
   open: number;
@@ -65,3 +118,113 @@ export interface Candle {
   low: number;
   close: number;
 }
+
+/** `/sessions/:id/beta`: beta is `null` with too little overlap or a flat benchmark. */
+export interface SessionBeta {
+  session_id: string;
+  benchmark: string;
+  interval: string;
+  start: string;
+  observations: number;
+  beta: number | null;
+  correlation: number | null;
+  rolling: { time: string; beta: number | null }[];
+}
+
+/** `/sessions/:id/stats`: realised results, trade statistics and the open position. */
+export interface SessionStats {
+  session_id: string;
+  current_equity: number;
+  total_trades: number;
+  closed_trades: number;
+  win_rate_pct: number | null;
+  gross_pnl: number;
+  total_fees: number;
+  net_pnl: number;
+  /** Closing trades only; entry fees count in `net_pnl`. */
+  realized_pnl: number;
+  fee_to_pnl_ratio: number | null;
+  avg_holding_seconds: number | null;
+  min_holding_seconds: number | null;
+  max_holding_seconds: number | null;
+  avg_trade_return_pct: number | null;
+  median_trade_return_pct: number | null;
+  expectancy: number | null;
+  max_drawdown_pct: number;
+  current_position: number;
+  entry_price: number | null;
+  unrealized_pnl: number | null;
+  lifetime_seconds: number;
+  exposed_seconds: number;
+  time_in_market_pct: number | null;
+}
+
+/** `/sessions/:id/explain-bar`: the engine's decision on one bar. */
+export interface BarExplanation {
+  session_id: string;
+  strategy_type: string;
+  parameters: Record<string, unknown>;
+  interval: string;
+  execution_mode: string;
+  bar_time: string;
+  bars_used: number;
+  candles: (Candle & { volume: number; signal: number | null })[];
+  signal: number | null;
+  edge_window: (number | null)[];
+  confirm_window: (number | null)[];
+  decision:
+    | { kind: 'act' | 'no_edge'; signal: number }
+    | { kind: 'hold_unconfirmed' | 'hold_no_signal' };
+  prior_position: number;
+  transition: 'hold' | 'open' | 'close' | 'flip' | 'resize' | null;
+  planned_trades: { side: 'BUY' | 'SELL'; label: string; closes: boolean }[];
+  explanation: string;
+}
+
+/** `/strategies/:id/backtest-trades`: exit fields are `null` for a position open at the last bar. */
+export interface BacktestTrade {
+  side: 'LONG' | 'SHORT';
+  entry_time: string;
+  entry_price: number;
+  exit_time: string | null;
+  exit_price: number | null;
+  pnl: number | null;
+  pnl_pct: number | null;
+}
+
+export interface BacktestReport {
+  strategy_type: string;
+  symbol: string;
+  interval: string;
+  bars: number;
+  metrics: {
+    final_equity: number;
+    total_return_pct: number;
+    max_drawdown_pct: number;
+    trades: number;
+  };
+  sharpe: number | null;
+  sortino: number | null;
+  calmar: number | null;
+  equity_curve: { time: string; equity: number }[];
+  trades: BacktestTrade[];
+}
+
+/** `limit` (default 50, max 500) and `offset` of a paginated list; the total is in `X-Total-Count`. */
+export interface Page {
+  limit?: number;
+  offset?: number;
+}
+
+/** `market_done` event of a generation job: `completed` of `total` markets evaluated. */
+export interface GenerationMarketDone {
+  symbol: string;
+  interval: string;
+  completed: number;
+  total: number;
+}
+
+/** `done` event of a generation job. */
+export interface GenerationDone {
+  strategies_created: number;
+}